@@ -0,0 +1,78 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// first and last character of the System Library default font, tile 0 is the space character
+const FONT_FIRST_CHAR: u8 = 0x20;
+const FONT_LAST_CHAR: u8 = 0x7F;
+
+// The values that .ascii and .asciz text is stored as, set with .charmap. Characters without a
+// value cannot be used.
+#[derive(Debug, Clone)]
+pub struct CharacterMap {
+    values: [Option<u8>; 256],
+}
+
+impl CharacterMap {
+    // every character is stored as its own value
+    pub fn ascii() -> Self {
+        let mut values = [None; 256];
+        for (c, value) in values.iter_mut().enumerate() {
+            *value = Some(c as u8);
+        }
+        Self { values }
+    }
+
+    // characters are stored as their tile index in the System Library default font
+    pub fn font() -> Self {
+        let mut values = [None; 256];
+        for c in FONT_FIRST_CHAR..=FONT_LAST_CHAR {
+            values[c as usize] = Some(c - FONT_FIRST_CHAR);
+        }
+        Self { values }
+    }
+
+    pub fn insert(&mut self, c: u8, value: u8) {
+        self.values[c as usize] = Some(value);
+    }
+
+    // translate text, returns the first character that has no value on failure
+    pub fn translate(&self, text: &[u8]) -> Result<Vec<u8>, u8> {
+        text.iter()
+            .map(|c| self.values[*c as usize].ok_or(*c))
+            .collect()
+    }
+}
+
+impl Default for CharacterMap {
+    fn default() -> Self {
+        Self::ascii()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_charmap() {
+        let mut map = CharacterMap::font();
+        assert_eq!(map.translate(b" AB~"), Ok(vec![0x00, 0x21, 0x22, 0x5E]));
+        assert_eq!(map.translate(b"A\n"), Err(b'\n'));
+
+        map.insert(b'\n', 0xFF);
+        assert_eq!(map.translate(b"A\n"), Ok(vec![0x21, 0xFF]));
+    }
+}
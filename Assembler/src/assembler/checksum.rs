@@ -0,0 +1,171 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::errors::AssemblyError;
+
+// offsets of the checksum fields, relative to the start of the cartridge header
+pub const HEADER_CHECKSUM_OFFSET: usize = 0x29;
+pub const GLOBAL_CHECKSUM_OFFSET: usize = 0x2A;
+pub const HEADER_SIZE: usize = 0x60;
+
+/// 8 bit wrapping sum of header bytes 0x00-0x28.
+pub fn calculate_header_checksum(rom: &[u8], header_addr: usize) -> u8 {
+    rom[header_addr..header_addr + HEADER_CHECKSUM_OFFSET]
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// 16 bit wrapping sum of every byte in the rom, excluding the two global checksum bytes.
+pub fn calculate_global_checksum(rom: &[u8], header_addr: usize) -> u16 {
+    let checksum_start = header_addr + GLOBAL_CHECKSUM_OFFSET;
+    let checksum_end = checksum_start + 2;
+
+    rom.iter()
+        .enumerate()
+        .filter(|(addr, _)| *addr < checksum_start || *addr >= checksum_end)
+        .fold(0u16, |sum, (_, byte)| sum.wrapping_add(*byte as u16))
+}
+
+/// Write the header checksum and global rom checksum into the rom image.
+pub fn patch_checksums(rom: &mut [u8], header_addr: usize) -> Result<(), AssemblyError> {
+    check_header_fits(rom, header_addr)?;
+
+    // the header checksum must be written first, the global checksum covers it
+    rom[header_addr + HEADER_CHECKSUM_OFFSET] = calculate_header_checksum(rom, header_addr);
+
+    let [low, high] = calculate_global_checksum(rom, header_addr).to_le_bytes();
+    rom[header_addr + GLOBAL_CHECKSUM_OFFSET] = low;
+    rom[header_addr + GLOBAL_CHECKSUM_OFFSET + 1] = high;
+
+    Ok(())
+}
+
+/// Check the header checksum and global rom checksum stored in a rom image.
+pub fn verify_checksums(rom: &[u8], header_addr: usize) -> Result<(), AssemblyError> {
+    check_header_fits(rom, header_addr)?;
+
+    let stored_header = rom[header_addr + HEADER_CHECKSUM_OFFSET];
+    let expected_header = calculate_header_checksum(rom, header_addr);
+
+    if stored_header != expected_header {
+        return Err(AssemblyError::HeaderInfoError {
+            reason: format!(
+                "Header checksum mismatch (stored: 0x{:02x}, calculated: 0x{:02x})",
+                stored_header, expected_header
+            ),
+        });
+    }
+
+    let stored_global = u16::from_le_bytes([
+        rom[header_addr + GLOBAL_CHECKSUM_OFFSET],
+        rom[header_addr + GLOBAL_CHECKSUM_OFFSET + 1],
+    ]);
+    let expected_global = calculate_global_checksum(rom, header_addr);
+
+    if stored_global != expected_global {
+        return Err(AssemblyError::HeaderInfoError {
+            reason: format!(
+                "Global ROM checksum mismatch (stored: 0x{:04x}, calculated: 0x{:04x})",
+                stored_global, expected_global
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+fn check_header_fits(rom: &[u8], header_addr: usize) -> Result<(), AssemblyError> {
+    if rom.len() < header_addr + HEADER_SIZE {
+        return Err(AssemblyError::HeaderInfoError {
+            reason: format!(
+                "ROM image is too small to contain a cartridge header at 0x{:04x} ({} bytes)",
+                header_addr,
+                rom.len()
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_checksum_wraps() {
+        let mut rom = vec![0u8; HEADER_SIZE];
+        rom[0x00] = 0xFF;
+        rom[0x01] = 0x02;
+        rom[0x28] = 0x10;
+        // bytes past 0x28 are not part of the header checksum
+        rom[0x29] = 0x55;
+        rom[0x30] = 0x55;
+
+        assert_eq!(calculate_header_checksum(&rom, 0), 0x11);
+    }
+
+    #[test]
+    fn test_global_checksum_skips_checksum_bytes() {
+        let mut rom = vec![0u8; HEADER_SIZE * 2];
+        rom[0x2A] = 0xFF;
+        rom[0x2B] = 0xFF;
+        rom[0x00] = 0x01;
+        rom[0x70] = 0x02;
+
+        assert_eq!(calculate_global_checksum(&rom, 0), 0x0003);
+    }
+
+    #[test]
+    fn test_patch_then_verify() {
+        let mut rom: Vec<u8> = (0..0x200).map(|i| i as u8).collect();
+
+        assert!(verify_checksums(&rom, 0).is_err());
+        patch_checksums(&mut rom, 0).unwrap();
+        assert!(verify_checksums(&rom, 0).is_ok());
+
+        // the global checksum must not depend on its own previous value
+        rom[0x2A] = 0x00;
+        rom[0x2B] = 0x00;
+        patch_checksums(&mut rom, 0).unwrap();
+        assert!(verify_checksums(&rom, 0).is_ok());
+    }
+
+    #[test]
+    fn test_verify_detects_corruption() {
+        let mut rom = vec![0xFFu8; 0x400];
+        patch_checksums(&mut rom, 0).unwrap();
+
+        rom[0x300] = 0x00;
+        assert_eq!(
+            verify_checksums(&rom, 0),
+            Err(AssemblyError::HeaderInfoError {
+                reason: format!(
+                    "Global ROM checksum mismatch (stored: 0x{:04x}, calculated: 0x{:04x})",
+                    calculate_global_checksum(&rom, 0).wrapping_add(0xFF),
+                    calculate_global_checksum(&rom, 0)
+                ),
+            })
+        );
+    }
+
+    #[test]
+    fn test_rom_too_small() {
+        let mut rom = vec![0u8; 0x20];
+        assert!(patch_checksums(&mut rom, 0).is_err());
+        assert!(verify_checksums(&rom, 0).is_err());
+    }
+}
@@ -14,6 +14,48 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::ast::Expr;
 use std::collections::HashMap;
 
-pub type ConstantTable = HashMap<String, i32>;
+// Constants defined with .define. Values that can be calculated from other constants are stored
+// directly, definitions that depend on label addresses are kept as expressions and are resolved
+// once the symbol table has been built.
+#[derive(Debug, Default)]
+pub struct ConstantTable {
+    values: HashMap<String, i32>,
+    deferred: HashMap<String, Expr>,
+}
+
+impl ConstantTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: String, value: i32) {
+        self.values.insert(name, value);
+    }
+
+    pub fn insert_deferred(&mut self, name: String, expr: Expr) {
+        self.deferred.insert(name, expr);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&i32> {
+        self.values.get(name)
+    }
+
+    pub fn get_deferred(&self, name: &str) -> Option<&Expr> {
+        self.deferred.get(name)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.values.contains_key(name) || self.deferred.contains_key(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &i32)> {
+        self.values.iter()
+    }
+
+    pub fn iter_deferred(&self) -> impl Iterator<Item = (&String, &Expr)> {
+        self.deferred.iter()
+    }
+}
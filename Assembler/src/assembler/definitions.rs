@@ -0,0 +1,163 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::assembler::AssemblyError;
+use crate::ast::{BinaryOp, Directive, EnumMember, Expr, Operand, StructField};
+
+// The constants defined by a directive, with the expressions for their values. A .define defines
+// one constant, a .struct defines the offset of every field and its size, and an .enum defines
+// one constant per member.
+pub fn defined_constants(
+    directive: &Directive,
+    line_number: &usize,
+) -> Result<Vec<(String, Expr)>, AssemblyError> {
+    match directive {
+        Directive::Define(name, op) => {
+            let expr = match op {
+                Operand::Immediate(value) => Expr::Number(*value),
+                Operand::Label(label) => Expr::Symbol(label.clone()),
+                Operand::Expr(expr) => expr.clone(),
+                _ => {
+                    return Err(AssemblyError::SemanticError {
+                        line: *line_number,
+                        reason: "Invalid value for .define statement.".to_string(),
+                    });
+                }
+            };
+            Ok(vec![(name.clone(), expr)])
+        }
+        Directive::Struct(name, fields) => Ok(struct_constants(name, fields)),
+        Directive::Enum(name, members) => Ok(enum_constants(name.as_deref(), members)),
+        _ => Ok(Vec::new()),
+    }
+}
+
+// "Name.field" for the offset of every field, in order, and "Name.SIZE" for the total size
+fn struct_constants(name: &str, fields: &[StructField]) -> Vec<(String, Expr)> {
+    let mut constants = Vec::new();
+    let mut offset = Expr::Number(0);
+
+    for field in fields {
+        constants.push((format!("{}.{}", name, field.name), offset.clone()));
+
+        let size = match &field.length {
+            Some(length) => fold(
+                BinaryOp::Mul,
+                Expr::Number(field.size as i32),
+                length.clone(),
+            ),
+            None => Expr::Number(field.size as i32),
+        };
+        offset = fold(BinaryOp::Add, offset, size);
+    }
+
+    constants.push((format!("{}.SIZE", name), offset));
+    constants
+}
+
+// "Name.MEMBER" for every member of a named enum, or "MEMBER" if the enum has no name. Members
+// start at zero
+fn enum_constants(name: Option<&str>, members: &[EnumMember]) -> Vec<(String, Expr)> {
+    let mut constants = Vec::new();
+    let mut next = Expr::Number(0);
+
+    for member in members {
+        let value = member.value.clone().unwrap_or(next);
+        let full_name = match name {
+            Some(name) => format!("{}.{}", name, member.name),
+            None => member.name.clone(),
+        };
+
+        next = fold(BinaryOp::Add, value.clone(), Expr::Number(1));
+        constants.push((full_name, value));
+    }
+
+    constants
+}
+
+// combine two expressions, calculating the result straight away if both are numbers
+fn fold(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    match (&op, &lhs, &rhs) {
+        (BinaryOp::Add, Expr::Number(a), Expr::Number(b)) => Expr::Number(a.wrapping_add(*b)),
+        (BinaryOp::Mul, Expr::Number(a), Expr::Number(b)) => Expr::Number(a.wrapping_mul(*b)),
+        _ => Expr::Binary(op, Box::new(lhs), Box::new(rhs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, size: u32, length: Option<Expr>) -> StructField {
+        StructField {
+            name: name.to_string(),
+            size,
+            length,
+        }
+    }
+
+    #[test]
+    fn test_struct_and_enum_constants() {
+        let fields = vec![
+            field("y", 1, None),
+            field("x", 1, None),
+            field("pos", 2, None),
+            field("name", 1, Some(Expr::Number(8))),
+            field("list", 2, Some(Expr::Symbol("COUNT".to_string()))),
+        ];
+        let constants =
+            defined_constants(&Directive::Struct("Obj".to_string(), fields), &1).unwrap();
+
+        let size = Expr::Binary(
+            BinaryOp::Add,
+            Box::new(Expr::Number(12)),
+            Box::new(Expr::Binary(
+                BinaryOp::Mul,
+                Box::new(Expr::Number(2)),
+                Box::new(Expr::Symbol("COUNT".to_string())),
+            )),
+        );
+        assert_eq!(
+            constants,
+            [
+                ("Obj.y".to_string(), Expr::Number(0)),
+                ("Obj.x".to_string(), Expr::Number(1)),
+                ("Obj.pos".to_string(), Expr::Number(2)),
+                ("Obj.name".to_string(), Expr::Number(4)),
+                ("Obj.list".to_string(), Expr::Number(12)),
+                ("Obj.SIZE".to_string(), size),
+            ]
+        );
+
+        let members = ["IDLE", "RUN", "JUMP", "FALL"]
+            .iter()
+            .map(|name| EnumMember {
+                name: name.to_string(),
+                value: (*name == "JUMP").then_some(Expr::Number(8)),
+            })
+            .collect();
+        let constants = defined_constants(&Directive::Enum(None, members), &1).unwrap();
+        assert_eq!(
+            constants,
+            [
+                ("IDLE".to_string(), Expr::Number(0)),
+                ("RUN".to_string(), Expr::Number(1)),
+                ("JUMP".to_string(), Expr::Number(8)),
+                ("FALL".to_string(), Expr::Number(9)),
+            ]
+        );
+    }
+}
@@ -53,7 +53,7 @@ impl<'a> Encoder<'a> {
     }
 
     pub fn encode_add_sp(self, offset: &Operand) -> Result<Vec<u8>, AssemblyError> {
-        let imm = self.expect_immediate(offset)?;
+        let imm = self.expect_signed_byte(offset)?;
         Ok(vec![ADD_SP_OPCODE, imm as u8])
     }
 
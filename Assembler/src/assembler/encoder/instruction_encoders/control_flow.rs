@@ -18,8 +18,8 @@ use crate::assembler::encoder::Encoder;
 use crate::assembler::encoder::constants::*;
 use crate::assembler::encoder::instruction_encoders::load_store::encode_ldi_data;
 use crate::assembler::encoder::utility_functions::*;
-use crate::assembler::symbol_table::{Symbol, get_and_check_symbol, get_symbol};
-use crate::ast::{ConditionCode, Operand, Register};
+use crate::assembler::symbol_table::{Symbol, get_symbol};
+use crate::ast::{BranchForm, ConditionCode, Operand, Register};
 use crate::errors::AssemblyError;
 
 impl<'a> Encoder<'a> {
@@ -34,30 +34,7 @@ impl<'a> Encoder<'a> {
     }
 
     pub fn encode_jr(self, op: &Operand) -> Result<Vec<u8>, AssemblyError> {
-        let rel = match op {
-            Operand::Immediate(imm) => *imm as i8,
-            Operand::Label(label_name) => {
-                let target_symbol = get_and_check_symbol(
-                    self.symbol_table,
-                    label_name,
-                    self.line_num,
-                    self.current_bank,
-                )?;
-                let rel: i32 = target_symbol.logical_address as i32 - *self.logical_address as i32;
-                if rel > i8::MAX as i32 || rel < i8::MIN as i32 {
-                    return Err(AssemblyError::SemanticError {
-                        line: *self.line_num,
-                        reason: format!(
-                            "Label \"{}\" too far away for relative jump, must be within {} bytes of JR instruction",
-                            label_name,
-                            i8::MAX
-                        ),
-                    });
-                }
-                rel as i8
-            }
-            _ => unreachable!(),
-        };
+        let rel = self.expect_relative_offset(op, "JR")?;
         Ok(vec![JR_OPCODE, rel as u8])
     }
 
@@ -69,62 +46,65 @@ impl<'a> Encoder<'a> {
     }
 
     pub fn encode_jrcc(self, cc: &ConditionCode, op: &Operand) -> Result<Vec<u8>, AssemblyError> {
-        let rel = match op {
-            Operand::Immediate(imm) => *imm as i8,
-            Operand::Label(label_name) => {
-                let target_symbol = get_and_check_symbol(
-                    self.symbol_table,
-                    label_name,
-                    self.line_num,
-                    self.current_bank,
-                )?;
-                let rel: i32 = target_symbol.logical_address as i32 - *self.logical_address as i32;
-                if rel > i8::MAX as i32 || rel < i8::MIN as i32 {
-                    return Err(AssemblyError::SemanticError {
-                        line: *self.line_num,
-                        reason: format!(
-                            "Label \"{}\" too far away for relative jump, must be within {} bytes of JRcc instruction",
-                            label_name,
-                            i8::MAX
-                        ),
-                    });
-                }
-                rel as i8
-            }
-            _ => unreachable!(),
-        };
+        let rel = self.expect_relative_offset(op, "JRcc")?;
         let opcode = encode_condition_code_opcode(JRCC_BASE_OPCODE, cc);
         Ok(vec![opcode, rel as u8])
     }
 
     pub fn encode_djnz(self, op: &Operand) -> Result<Vec<u8>, AssemblyError> {
-        let rel = match op {
-            Operand::Immediate(imm) => *imm as i8,
-            Operand::Label(label_name) => {
-                let target_symbol = get_and_check_symbol(
-                    self.symbol_table,
-                    label_name,
-                    self.line_num,
-                    self.current_bank,
-                )?;
-                let rel: i32 = target_symbol.logical_address as i32 - *self.logical_address as i32;
-                if rel > i8::MAX as i32 || rel < i8::MIN as i32 {
-                    return Err(AssemblyError::SemanticError {
-                        line: *self.line_num,
-                        reason: format!(
-                            "Label \"{}\" too far away for relative jump, must be within {} bytes of DJNZ instruction",
-                            label_name,
-                            i8::MAX
-                        ),
-                    });
-                }
-                rel as i8
-            }
-            _ => unreachable!(),
-        };
+        let rel = self.expect_relative_offset(op, "DJNZ")?;
         Ok(vec![DJNZ_OPCODE, rel as u8])
     }
 
+    pub fn encode_jmp_auto(
+        self,
+        op: &Operand,
+        form: &BranchForm,
+    ) -> Result<Vec<u8>, AssemblyError> {
+        match form {
+            BranchForm::Short => {
+                let rel = self.expect_relative_target(op, "JMP.auto")?;
+                Ok(vec![JR_OPCODE, rel as u8])
+            }
+            BranchForm::Long => self.encode_jmp_imm(op),
+        }
+    }
+
+    pub fn encode_jcc_auto(
+        self,
+        cc: &ConditionCode,
+        op: &Operand,
+        form: &BranchForm,
+    ) -> Result<Vec<u8>, AssemblyError> {
+        match form {
+            BranchForm::Short => {
+                let rel = self.expect_relative_target(op, "Jcc.auto")?;
+                let opcode = encode_condition_code_opcode(JRCC_BASE_OPCODE, cc);
+                Ok(vec![opcode, rel as u8])
+            }
+            BranchForm::Long => self.encode_jcc(cc, op),
+        }
+    }
+
+    // the long form of DJNZ is DEC R5 followed by JNZ, since DJNZ decrements R5
+    pub fn encode_djnz_auto(
+        self,
+        op: &Operand,
+        form: &BranchForm,
+    ) -> Result<Vec<u8>, AssemblyError> {
+        match form {
+            BranchForm::Short => {
+                let rel = self.expect_relative_target(op, "DJNZ.auto")?;
+                Ok(vec![DJNZ_OPCODE, rel as u8])
+            }
+            BranchForm::Long => {
+                let mut bytes = self.encode_dec(&Register::R5)?;
+                bytes.extend(self.encode_jcc(&ConditionCode::Nz, op)?);
+                Ok(bytes)
+            }
+        }
+    }
+
     pub fn encode_call_imm(self, op: &Operand) -> Result<Vec<u8>, AssemblyError> {
         let addr = resolve_label_or_immediate(op, self.symbol_table, self.line_num)?;
         Ok(encode_call_immediate_data(addr))
@@ -142,8 +122,8 @@ impl<'a> Encoder<'a> {
     }
 
     pub fn encode_syscall_imm(self, imm: &Operand) -> Result<Vec<u8>, AssemblyError> {
-        let val = self.expect_immediate(imm)?;
-        Ok(encode_syscall_data(val as u8))
+        let val = self.expect_unsigned_byte(imm)?;
+        Ok(encode_syscall_data(val))
     }
 
     pub fn encode_call_far(self, call_label: &String) -> Result<Vec<u8>, AssemblyError> {
@@ -176,7 +156,8 @@ impl<'a> Encoder<'a> {
         via_label: &String,
     ) -> Result<Vec<u8>, AssemblyError> {
         let call_symbol = get_symbol(self.symbol_table, call_label, self.line_num)?;
-        let via_symbol = get_symbol(self.symbol_table, via_label, self.line_num)?;
+        let via_symbol =
+            get_symbol(self.symbol_table, via_label, self.line_num).map_err(|e| e.at_operand(1))?;
 
         if call_symbol.bank == 0 {
             return Err(AssemblyError::SemanticError {
@@ -203,7 +184,8 @@ impl<'a> Encoder<'a> {
                     "Custom CALL.far via trampoline label must exist in bank 0, \"{}\" found in bank {}",
                     via_label, via_symbol.bank
                 ),
-            });
+            }
+            .at_operand(1));
         }
 
         Ok(encode_far_via_data(call_symbol, via_symbol))
@@ -239,7 +221,8 @@ impl<'a> Encoder<'a> {
         via_label: &String,
     ) -> Result<Vec<u8>, AssemblyError> {
         let call_symbol = get_symbol(self.symbol_table, call_label, self.line_num)?;
-        let via_symbol = get_symbol(self.symbol_table, via_label, self.line_num)?;
+        let via_symbol =
+            get_symbol(self.symbol_table, via_label, self.line_num).map_err(|e| e.at_operand(1))?;
 
         if call_symbol.bank == 0 {
             return Err(AssemblyError::SemanticError {
@@ -266,7 +249,8 @@ impl<'a> Encoder<'a> {
                     "Custom JMP.far via trampoline label must exist in bank 0, \"{}\" found in bank {}",
                     via_label, via_symbol.bank
                 ),
-            });
+            }
+            .at_operand(1));
         }
 
         Ok(encode_far_via_data(call_symbol, via_symbol))
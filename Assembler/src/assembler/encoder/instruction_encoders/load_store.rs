@@ -55,7 +55,7 @@ impl<'a> Encoder<'a> {
         offset: &Operand,
     ) -> Result<Vec<u8>, AssemblyError> {
         let sub_opcode = encode_rd_rs_byte(LD_INDEX_BASE_SUB_OPCODE, rd, rs);
-        let imm = self.expect_immediate(offset)?;
+        let imm = self.expect_signed_byte(offset)?;
         Ok(vec![FF_PREFIX, sub_opcode, imm as u8])
     }
 
@@ -98,7 +98,7 @@ impl<'a> Encoder<'a> {
         rs: &Register,
     ) -> Result<Vec<u8>, AssemblyError> {
         let sub_opcode = encode_rd_rs_byte(ST_INDEX_BASE_SUB_OPCODE, rd, rs);
-        let imm = self.expect_immediate(offset)?;
+        let imm = self.expect_signed_byte(offset)?;
         Ok(vec![FF_PREFIX, sub_opcode, imm as u8])
     }
 
@@ -120,8 +120,8 @@ impl<'a> Encoder<'a> {
 
     pub fn encode_ldib(self, rd: &Register, imm8: &Operand) -> Result<Vec<u8>, AssemblyError> {
         let sub_opcode = encode_reg_opcode(LDIB_BASE_SUB_OPCODE, rd);
-        let imm = self.expect_immediate(imm8)?;
-        Ok(vec![FD_PREFIX, sub_opcode, imm as u8])
+        let imm = self.expect_unsigned_byte(imm8)?;
+        Ok(vec![FD_PREFIX, sub_opcode, imm])
     }
 
     pub fn encode_ldb_indirect(
@@ -203,7 +203,7 @@ impl<'a> Encoder<'a> {
         offset: &Operand,
     ) -> Result<Vec<u8>, AssemblyError> {
         let sub_opcode = encode_rd_rs_byte(LEA_BASE_SUB_OPCODE, rd, rs);
-        let imm = self.expect_immediate(offset)?;
+        let imm = self.expect_signed_byte(offset)?;
         Ok(vec![FF_PREFIX, sub_opcode, imm as u8])
     }
 }
@@ -19,4 +19,4 @@ pub mod bitwise;
 pub mod control_flow;
 pub mod load_store;
 pub mod misc;
-pub mod stack;
\ No newline at end of file
+pub mod stack;
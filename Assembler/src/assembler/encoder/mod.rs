@@ -14,13 +14,13 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-mod constants;
+pub(crate) mod constants;
 mod instruction_encoders;
 mod operand_validators;
 pub mod utility_functions;
 
 use crate::assembler::symbol_table::*;
-use crate::ast::Instruction;
+use crate::ast::{BranchForm, Instruction};
 use crate::errors::AssemblyError;
 use constants::*;
 
@@ -136,6 +136,12 @@ pub fn calculate_instruction_size(instruction: &Instruction) -> u32 {
         Instruction::JccI(_, _) => 3,
         Instruction::JrccI(_, _) => 2,
         Instruction::Djnz(_) => 2,
+        Instruction::JmpAuto(_, BranchForm::Short) => 2,
+        Instruction::JmpAuto(_, BranchForm::Long) => 3,
+        Instruction::JccAuto(_, _, BranchForm::Short) => 2,
+        Instruction::JccAuto(_, _, BranchForm::Long) => 3,
+        Instruction::DjnzAuto(_, BranchForm::Short) => 2,
+        Instruction::DjnzAuto(_, BranchForm::Long) => 5,
         Instruction::CallI(_) => 3,
         Instruction::CallIndirect(_) => 1,
         Instruction::CallccI(_, _) => 3,
@@ -193,6 +199,14 @@ impl<'a> Encoder<'a> {
 
     /// Helper function to translate a single instruction into bytes during Pass 2.
     pub fn encode_instruction(self) -> Result<Vec<u8>, AssemblyError> {
+        self.encode()
+            .map_err(|e| match value_operand(self.instruction) {
+                Some(index) => e.at_operand(index),
+                None => e,
+            })
+    }
+
+    fn encode(self) -> Result<Vec<u8>, AssemblyError> {
         match self.instruction {
             Instruction::Nop => self.encode_nop(),
             Instruction::Halt => self.encode_halt(),
@@ -314,10 +328,70 @@ impl<'a> Encoder<'a> {
             Instruction::JmpFarVia(call_label, via_label) => {
                 self.encode_jmp_far_via(call_label, via_label)
             }
+            Instruction::JmpAuto(op, form) => self.encode_jmp_auto(op, form),
+            Instruction::JccAuto(cc, op, form) => self.encode_jcc_auto(cc, op, form),
+            Instruction::DjnzAuto(op, form) => self.encode_djnz_auto(op, form),
         }
     }
 }
 
+// the index of the operand whose value is resolved when encoding, errors are reported on it.
+// Instructions with a second resolved operand report errors in it themselves
+fn value_operand(instruction: &Instruction) -> Option<usize> {
+    match instruction {
+        Instruction::Ldi(_, _)
+        | Instruction::LdAbs(_, _)
+        | Instruction::LdIndexed(_, _, _)
+        | Instruction::LdiB(_, _)
+        | Instruction::LdBAbs(_, _)
+        | Instruction::Lea(_, _, _)
+        | Instruction::AddIReg(_, _)
+        | Instruction::SubIReg(_, _)
+        | Instruction::AndIReg(_, _)
+        | Instruction::OrIReg(_, _)
+        | Instruction::XorIReg(_, _)
+        | Instruction::CmpIReg(_, _)
+        | Instruction::BitReg(_, _)
+        | Instruction::SetReg(_, _)
+        | Instruction::ResReg(_, _)
+        | Instruction::BitIndirect(_, _)
+        | Instruction::SetIndirect(_, _)
+        | Instruction::ResIndirect(_, _) => Some(1),
+        Instruction::StAbs(_, _)
+        | Instruction::StIndexed(_, _, _)
+        | Instruction::StBAbs(_, _)
+        | Instruction::PushI(_)
+        | Instruction::AddAccI(_)
+        | Instruction::SubAccI(_)
+        | Instruction::AndAccI(_)
+        | Instruction::OrAccI(_)
+        | Instruction::XorAccI(_)
+        | Instruction::CmpAccI(_)
+        | Instruction::AdcAccI(_)
+        | Instruction::SbcAccI(_)
+        | Instruction::AddSp(_)
+        | Instruction::BitAbs(_, _)
+        | Instruction::SetAbs(_, _)
+        | Instruction::ResAbs(_, _)
+        | Instruction::JmpI(_)
+        | Instruction::JrI(_)
+        | Instruction::JccI(_, _)
+        | Instruction::JrccI(_, _)
+        | Instruction::Djnz(_)
+        | Instruction::CallI(_)
+        | Instruction::CallccI(_, _)
+        | Instruction::Syscall(_)
+        | Instruction::CallFar(_)
+        | Instruction::CallFarVia(_, _)
+        | Instruction::JmpFar(_)
+        | Instruction::JmpFarVia(_, _)
+        | Instruction::JmpAuto(_, _)
+        | Instruction::JccAuto(_, _, _)
+        | Instruction::DjnzAuto(_, _) => Some(0),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -379,7 +453,7 @@ mod tests {
         let symbol_table = SymbolTable::new();
         assert_eq!(
             encode_instruction(&instruction, &symbol_table, &0, &0, &0).unwrap(),
-            vec![0x15, (0 << 3) | 1]
+            vec![0x15, 1]
         );
     }
 
@@ -822,6 +896,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_encode_instruction_djnz_auto() {
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.insert(
+            "test_label".to_string(),
+            Symbol {
+                logical_address: 0x0100,
+                bank: 0,
+            },
+        );
+        let target = Operand::Label("test_label".to_string());
+
+        let short = Instruction::DjnzAuto(target.clone(), BranchForm::Short);
+        assert_eq!(calculate_instruction_size(&short), 2);
+        assert_eq!(
+            encode_instruction(&short, &symbol_table, &0x0110, &0, &0).unwrap(),
+            vec![0x6B, 0xF0]
+        );
+        assert!(encode_instruction(&short, &symbol_table, &0x0200, &0, &0).is_err());
+
+        // DEC R5, JNZ test_label
+        let long = Instruction::DjnzAuto(target, BranchForm::Long);
+        assert_eq!(calculate_instruction_size(&long), 5);
+        assert_eq!(
+            encode_instruction(&long, &symbol_table, &0x0200, &0, &0).unwrap(),
+            vec![0xFD, 0xAD, 0x62, 0x00, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_encode_instruction_jr_offset_out_of_range_fail() {
+        let symbol_table = SymbolTable::new();
+        assert_eq!(
+            encode_instruction(
+                &Instruction::JrI(Operand::Immediate(-128)),
+                &symbol_table,
+                &0,
+                &0,
+                &0
+            )
+            .unwrap(),
+            vec![0x5A, 0x80]
+        );
+
+        // the offset is not wrapped to a byte, e.g. 200 is not a jump back of 56 bytes
+        let instruction = Instruction::JrI(Operand::Immediate(200));
+        let result = encode_instruction(&instruction, &symbol_table, &0, &0, &1);
+        assert!(result.is_err_and(|e| {
+            e == AssemblyError::SemanticError {
+                line: 1,
+                reason: "Value 200 does not fit in a signed 8 bit operand (max: 127, min: -128)"
+                    .to_string(),
+            }
+            .at_operand(0)
+        }));
+    }
+
     #[test]
     fn test_encode_instruction_syscall() {
         let instruction = Instruction::Syscall(Operand::Immediate(0x1A));
@@ -1215,6 +1346,7 @@ mod tests {
                     "Label \"test_label\" exists in bank 0, use a normal CALL instruction instead."
                         .to_string(),
             }
+            .at_operand(0)
         }));
     }
 
@@ -1237,6 +1369,7 @@ mod tests {
                     "Label \"test_label\" exists in the same bank as the CALL.far instruction, use a normal CALL instruction instead."
                         .to_string(),
             }
+            .at_operand(0)
         }));
     }
 
@@ -1290,6 +1423,7 @@ mod tests {
                     "Label \"test_label\" exists in bank 0, use a normal CALL instruction instead."
                         .to_string(),
             }
+            .at_operand(0)
         }));
     }
 
@@ -1319,6 +1453,7 @@ mod tests {
                     "Label \"test_label\" exists in the same bank as the CALL.far instruction, use a normal CALL instruction instead."
                         .to_string(),
             }
+            .at_operand(0)
         }));
     }
 
@@ -1348,6 +1483,7 @@ mod tests {
                     "Custom CALL.far via trampoline label must exist in bank 0, \"tramp\" found in bank 1"
                         .to_string(),
             }
+            .at_operand(1)
         }));
     }
 }
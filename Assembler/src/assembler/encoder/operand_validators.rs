@@ -15,18 +15,100 @@ limitations under the License.
 */
 
 use crate::assembler::encoder::Encoder;
+use crate::assembler::encoder::utility_functions::{check_value_range, resolve_operand_value};
+use crate::assembler::symbol_table::get_and_check_symbol;
 use crate::ast::Operand;
 use crate::errors::AssemblyError;
+use crate::expression;
 
 impl<'a> Encoder<'a> {
-    pub fn expect_immediate(self, op: &Operand) -> Result<i32, AssemblyError> {
-        if let Operand::Immediate(val) = op {
-            Ok(*val)
-        } else {
-            Err(AssemblyError::StructuralError {
+    pub fn expect_unsigned_byte(self, op: &Operand) -> Result<u8, AssemblyError> {
+        let val = resolve_operand_value(op, self.symbol_table, self.line_num)?;
+        check_value_range(val, 0, u8::MAX as i32, "an unsigned 8 bit", self.line_num)?;
+        Ok(val as u8)
+    }
+
+    pub fn expect_signed_byte(self, op: &Operand) -> Result<i8, AssemblyError> {
+        let val = resolve_operand_value(op, self.symbol_table, self.line_num)?;
+        check_value_range(
+            val,
+            i8::MIN as i32,
+            i8::MAX as i32,
+            "a signed 8 bit",
+            self.line_num,
+        )?;
+        Ok(val as i8)
+    }
+
+    pub fn expect_bit_id(self, op: &Operand) -> Result<u8, AssemblyError> {
+        let val = resolve_operand_value(op, self.symbol_table, self.line_num)?;
+        check_value_range(val, 0, 7, "a bit ID", self.line_num)?;
+        Ok(val as u8)
+    }
+
+    // resolve the operand of a relative jump, immediates are the raw offset while labels and
+    // expressions are the target address
+    pub fn expect_relative_offset(self, op: &Operand, mnemonic: &str) -> Result<i8, AssemblyError> {
+        match op {
+            Operand::Immediate(imm) => {
+                check_value_range(
+                    *imm,
+                    i8::MIN as i32,
+                    i8::MAX as i32,
+                    "a signed 8 bit",
+                    self.line_num,
+                )?;
+                Ok(*imm as i8)
+            }
+            _ => self.expect_relative_target(op, mnemonic),
+        }
+    }
+
+    // resolve the offset of a relative jump to the target address given by the operand
+    pub fn expect_relative_target(self, op: &Operand, mnemonic: &str) -> Result<i8, AssemblyError> {
+        let (target_name, target_addr) = match op {
+            Operand::Immediate(addr) => (format!("Address 0x{:04x}", addr), *addr),
+            Operand::Label(label_name) => {
+                let target_symbol = get_and_check_symbol(
+                    self.symbol_table,
+                    label_name,
+                    self.line_num,
+                    self.current_bank,
+                )?;
+                (
+                    format!("Label \"{}\"", label_name),
+                    target_symbol.logical_address as i32,
+                )
+            }
+            Operand::Expr(expr) => {
+                for label_name in expression::referenced_symbols(expr) {
+                    get_and_check_symbol(
+                        self.symbol_table,
+                        label_name,
+                        self.line_num,
+                        self.current_bank,
+                    )?;
+                }
+                (
+                    "Target address".to_string(),
+                    resolve_operand_value(op, self.symbol_table, self.line_num)?,
+                )
+            }
+            _ => unreachable!(),
+        };
+
+        let rel: i32 = target_addr - *self.logical_address as i32;
+        if rel > i8::MAX as i32 || rel < i8::MIN as i32 {
+            return Err(AssemblyError::SemanticError {
                 line: *self.line_num,
-                reason: "Expected an immediate.".to_string(),
-            })
+                reason: format!(
+                    "{} too far away for relative jump, must be within {} bytes of {} instruction",
+                    target_name,
+                    i8::MAX,
+                    mnemonic
+                ),
+            });
         }
+        Ok(rel as i8)
     }
 }
@@ -17,6 +17,7 @@ limitations under the License.
 use crate::assembler::symbol_table::{SymbolTable, get_symbol};
 use crate::ast::{ConditionCode, Operand, Register};
 use crate::errors::AssemblyError;
+use crate::expression;
 
 // helper function to encode a register operand
 pub fn encode_register_operand(reg: &Register) -> u8 {
@@ -70,6 +71,11 @@ pub fn resolve_label_or_immediate(
             let target_symbol = get_symbol(symbol_table, label_name, line_num)?;
             Ok(target_symbol.logical_address as u16)
         }
+        Operand::Expr(_) => {
+            let value = resolve_operand_value(op, symbol_table, line_num)?;
+            check_value_range(value, 0, u16::MAX as i32, "an unsigned 16 bit", line_num)?;
+            Ok(value as u16)
+        }
         _ => Err(AssemblyError::SemanticError {
             line: *line_num,
             reason: "Expected an immediate value or a label.".to_string(),
@@ -77,6 +83,51 @@ pub fn resolve_label_or_immediate(
     }
 }
 
+/// Resolves an immediate, label or expression operand into its full value.
+pub fn resolve_operand_value(
+    op: &Operand,
+    symbol_table: &SymbolTable,
+    line_num: &usize,
+) -> Result<i32, AssemblyError> {
+    match op {
+        Operand::Immediate(value) => Ok(*value),
+        Operand::Label(label_name) => {
+            let target_symbol = get_symbol(symbol_table, label_name, line_num)?;
+            Ok(target_symbol.logical_address as i32)
+        }
+        Operand::Expr(expr) => expression::evaluate(
+            expr,
+            &|name| symbol_table.get(name).map(|s| s.logical_address as i32),
+            &|name| symbol_table.get(name).map(|s| s.bank as i32),
+            line_num,
+        ),
+        _ => Err(AssemblyError::SemanticError {
+            line: *line_num,
+            reason: "Expected an immediate value, label or expression.".to_string(),
+        }),
+    }
+}
+
+// check that a resolved value fits into the operand it is being encoded into
+pub fn check_value_range(
+    value: i32,
+    min: i32,
+    max: i32,
+    width_name: &str,
+    line_num: &usize,
+) -> Result<(), AssemblyError> {
+    if value < min || value > max {
+        return Err(AssemblyError::SemanticError {
+            line: *line_num,
+            reason: format!(
+                "Value {} does not fit in {} operand (max: {}, min: {})",
+                value, width_name, max, min
+            ),
+        });
+    }
+    Ok(())
+}
+
 // pub fn resolve_absolute(
 //     op: &Operand,
 //     symbol_table: &SymbolTable,
@@ -0,0 +1,220 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use super::BANK_SIZE;
+use super::object::{data_size, lcm};
+use super::section_stack::AddrCounter;
+use crate::ast::{AssemblyLine, Directive};
+use crate::file_reader::FileReader;
+use std::collections::HashMap;
+
+// start of the switchable bank in the cpu address space, bank 0 is below it
+pub(crate) const SWITCHABLE_BANK_START: u32 = 0x4000;
+pub(crate) const SWITCHABLE_BANK_END: u32 = 0x8000;
+
+// The unused address ranges of every ROM bank, banks are added as sections are placed in them.
+// Used by the linker and by floating sections when assembling directly into a ROM.
+pub(crate) struct FreeSpace {
+    pub banks: Vec<Vec<(u32, u32)>>, // free [start, end) logical address ranges, by bank
+}
+
+impl FreeSpace {
+    pub fn new() -> FreeSpace {
+        FreeSpace {
+            banks: vec![vec![(0, SWITCHABLE_BANK_START)]],
+        }
+    }
+
+    pub fn bank(&mut self, bank: u32) -> &mut Vec<(u32, u32)> {
+        while self.banks.len() <= bank as usize {
+            self.banks
+                .push(vec![(SWITCHABLE_BANK_START, SWITCHABLE_BANK_END)]);
+        }
+        &mut self.banks[bank as usize]
+    }
+
+    // mark the addresses [start, end) of a bank as used
+    pub fn reserve(&mut self, bank: u32, start: u32, end: u32) {
+        let ranges = self.bank(bank);
+        *ranges = ranges
+            .iter()
+            .flat_map(|&(s, e)| [(s, e.min(start)), (s.max(end), e)])
+            .filter(|(s, e)| s < e)
+            .collect();
+    }
+
+    // mark the physical ROM addresses [start, end) as used, in every bank they cross
+    pub fn reserve_physical(&mut self, start: u32, end: u32) {
+        let mut addr = start;
+        while addr < end {
+            let bank = addr / BANK_SIZE;
+            let bank_end = end.min((bank + 1) * BANK_SIZE);
+            let logical = |physical: u32| physical - bank * BANK_SIZE + bank_start(bank);
+            self.reserve(bank, logical(addr), logical(bank_end));
+            addr = bank_end;
+        }
+    }
+
+    // the first address of a bank where `size` bytes aligned to `align` are free
+    pub fn find(&mut self, bank: u32, size: u32, align: u32) -> Option<u32> {
+        self.bank(bank)
+            .iter()
+            .map(|&(start, end)| (start.next_multiple_of(align), end))
+            .find(|&(start, end)| start + size <= end)
+            .map(|(start, _)| start)
+    }
+
+    pub fn free_bytes(&mut self, bank: u32) -> u32 {
+        self.bank(bank).iter().map(|(start, end)| end - start).sum()
+    }
+}
+
+// the logical address of the first byte of a bank
+pub(crate) fn bank_start(bank: u32) -> u32 {
+    if bank == 0 { 0 } else { SWITCHABLE_BANK_START }
+}
+
+// a floating section of the program, measured before it is placed
+struct FloatingSection {
+    line_index: usize, // the .section line
+    name: String,
+    size: u32,
+    align: u32,
+    bank0: bool,
+    measured: bool, // the size of every line is known before the section is placed
+}
+
+/// Place every floating section of a program in the free space left by the rest of it, largest
+/// first. Sections go into the switchable banks 1 and up, adding banks as needed, and `bank0`
+/// sections (or every section when `switchable` is false) into bank 0. Returns where each section
+/// starts or why it could not be placed, by the line index of its `.section`.
+pub(crate) fn place_floating_sections<F: FileReader>(
+    lines: &[AssemblyLine],
+    free: &mut FreeSpace,
+    switchable: bool,
+    reader: &F,
+) -> HashMap<usize, Result<AddrCounter, String>> {
+    let mut floating = measure_floating_sections(lines, reader);
+
+    // placing the largest sections first leaves the smaller ones to fill the gaps
+    floating.sort_by_key(|section| std::cmp::Reverse(section.size));
+
+    let mut placements = HashMap::new();
+    for section in floating {
+        if !section.measured {
+            let reason = format!(
+                "Section \"{}\" cannot be placed, the size of its contents is not known.",
+                section.name
+            );
+            placements.insert(section.line_index, Err(reason));
+            continue;
+        }
+
+        let banks = if section.bank0 || !switchable {
+            0..1
+        } else {
+            1..free.banks.len() as u32 + 1
+        };
+
+        let space = if banks.start == 0 {
+            "ROM bank 0"
+        } else {
+            "any ROM bank"
+        };
+        let placement = banks
+            .into_iter()
+            .find_map(|bank| {
+                free.find(bank, section.size, section.align)
+                    .map(|logical_addr| (bank, logical_addr))
+            })
+            .map(|(bank, logical_addr)| {
+                free.reserve(bank, logical_addr, logical_addr + section.size);
+                AddrCounter {
+                    physical_addr: bank * BANK_SIZE + logical_addr - bank_start(bank),
+                    logical_addr,
+                    num_bytes: 0,
+                    bank,
+                }
+            })
+            .ok_or_else(|| {
+                format!(
+                    "Section \"{}\" ({} bytes) does not fit in the free space of {}.",
+                    section.name, section.size, space
+                )
+            });
+        placements.insert(section.line_index, placement);
+    }
+
+    placements
+}
+
+// the size and alignment of every floating section, including the .align padding inside it
+fn measure_floating_sections<F: FileReader>(
+    lines: &[AssemblyLine],
+    reader: &F,
+) -> Vec<FloatingSection> {
+    let mut floating = Vec::new();
+    let mut open: Option<(FloatingSection, Option<u32>)> = None; // with its size option
+
+    for (line_index, line) in lines.iter().enumerate() {
+        match &line.directive {
+            Some(Directive::SectionStart(options)) if options.floating() => {
+                // a nested section is an error of pass 1, the open one is still placed
+                floating.extend(open.take().map(|(section, _)| section));
+                open = Some((
+                    FloatingSection {
+                        line_index,
+                        name: options
+                            .name
+                            .clone()
+                            .unwrap_or_else(|| "UNNAMED".to_string()),
+                        size: 0,
+                        align: options.align.unwrap_or(1),
+                        bank0: options.bank0,
+                        measured: true,
+                    },
+                    options.size,
+                ));
+                continue;
+            }
+            Some(Directive::SectionEnd) => {
+                if let Some((mut section, size)) = open.take() {
+                    section.size = section.size.max(size.unwrap_or(0));
+                    floating.push(section);
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let Some((section, _)) = &mut open else {
+            continue;
+        };
+
+        match data_size(line, section.size, reader) {
+            Ok(size) => section.size += size,
+            Err(_) => section.measured = false,
+        }
+
+        // .align only holds when the section starts at a multiple of it
+        if let Some(Directive::Align(alignment)) = &line.directive {
+            section.align = lcm(section.align, *alignment);
+        }
+    }
+
+    floating.extend(open.map(|(section, _)| section));
+    floating
+}
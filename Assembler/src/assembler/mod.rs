@@ -14,83 +14,578 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+mod charmap;
+mod checksum;
 mod constant_table;
-mod encoder;
+mod definitions;
+pub(crate) mod encoder;
+mod free_space;
+pub mod object;
 mod preprocessor;
+pub mod relaxation;
 mod section_stack;
 mod symbol_table;
 
-use crate::ast::{AssemblyLine, Directive, Operand};
-use crate::errors::AssemblyError;
+use crate::ast::{
+    AssemblyLine, Charmap, Directive, Expr, HeaderInfo, Instruction, Operand, SectionOptions,
+};
+use crate::errors::{AssemblyError, ErrorCollector};
+use crate::expression;
+use crate::listing::{Listing, ListingLine};
+use crate::symbol_file::{ConstantEntry, LabelEntry, SectionEntry, SymbolFile};
+use crate::warnings::{Warning, WarningCollector, WarningKind};
+use charmap::CharacterMap;
 use constant_table::*;
-use encoder::utility_functions::resolve_label_or_immediate;
+use definitions::defined_constants;
+use encoder::utility_functions::{
+    check_value_range, resolve_label_or_immediate, resolve_operand_value,
+};
+use preprocessor::label::LabelScope;
+use relaxation::BranchSite;
 use section_stack::*;
-use symbol_table::*;
-
-const BANK_SIZE: u32 = 16384;
+use std::collections::{HashMap, HashSet};
+
+// the RAM address spaces are also used by the linker to place nobits sections
+pub use section_stack::SectionLayout;
+pub(crate) use section_stack::{RAM_REGIONS, ram_region};
+// the free space of the ROM banks is shared with the linker, which packs sections into it
+pub(crate) use free_space::{FreeSpace, SWITCHABLE_BANK_END, SWITCHABLE_BANK_START};
+pub use symbol_table::{Symbol, SymbolTable};
+
+/// Size of a ROM bank, the assembled ROM is padded to a whole number of banks.
+pub const BANK_SIZE: u32 = 16384;
+// end of the cartridge rom in the cpu address space, which cannot be written to
+const ROM_END: i32 = 0x8000;
+
+// an .if/.ifdef/.ifndef ... .endif block that is open during pass 0
+struct ConditionalBlock {
+    line: AssemblyLine,  // the .if/.ifdef/.ifndef that opened the block
+    parent_active: bool, // the enclosing block is being assembled
+    active: bool,        // the current branch is being assembled
+    taken: bool,         // a branch of this block has been selected
+    seen_else: bool,
+}
 
-/// Pass 0: build the constant table
-pub fn build_constant_table(lines: &[AssemblyLine]) -> Result<ConstantTable, AssemblyError> {
+/// Pass 0: evaluate conditional assembly and build the constant table. Lines in conditional
+/// branches that are not taken are removed. `defines` are constants given on the command line,
+/// they are inserted before any line is processed.
+pub fn build_constant_table(
+    lines: &mut Vec<AssemblyLine>,
+    defines: &[(String, i32)],
+    max_errors: usize,
+) -> Result<ConstantTable, AssemblyError> {
+    let mut errors = ErrorCollector::new(max_errors);
     let mut constant_table = ConstantTable::new();
+    for (name, value) in defines {
+        constant_table.insert(name.clone(), *value);
+    }
+
+    // constants that could not be calculated yet, with the index of their line in active_lines
+    let mut pending: Vec<(String, Expr, usize)> = Vec::new();
+    let mut conditions: Vec<ConditionalBlock> = Vec::new();
+    let mut active_lines: Vec<AssemblyLine> = Vec::new();
+
+    for line in lines.drain(..) {
+        let active = conditions.last().is_none_or(|block| block.active);
+
+        if let Some(directive) = &line.directive
+            && is_conditional(directive)
+        {
+            if let Err(e) = update_conditions(
+                directive,
+                &line,
+                active,
+                &mut conditions,
+                &mut constant_table,
+                &mut pending,
+                &active_lines,
+            ) {
+                errors.push(e.at_line(&line))?;
+            }
+            continue;
+        }
+
+        if !active {
+            continue;
+        }
 
-    for line in lines {
         // handle directives
         if let Some(directive) = &line.directive {
-            match directive {
-                Directive::Define(label, op) => {
-                    if constant_table.contains_key(label) {
-                        return Err(AssemblyError::SemanticError {
+            let constants = match defined_constants(directive, &line.line_number) {
+                Ok(constants) => constants,
+                Err(e) => {
+                    errors.push(e.at_line(&line))?;
+                    continue;
+                }
+            };
+
+            let mut failed = false;
+            for (label, expr) in constants {
+                if constant_table.contains_key(&label)
+                    || pending.iter().any(|(name, _, _)| *name == label)
+                {
+                    errors.push(
+                        AssemblyError::SemanticError {
                             line: line.line_number,
                             reason: format!("Duplicate constant definition: {}", label),
-                        });
-                    }
-
-                    match op {
-                        Operand::Immediate(value) => {
-                            constant_table.insert(label.clone(), value.clone());
-                        }
-                        _ => {
-                            return Err(AssemblyError::SemanticError {
-                                line: line.line_number,
-                                reason: "Invalid value for .define statement.".to_string(),
-                            });
                         }
-                    }
+                        .at_line(&line),
+                    )?;
+                    failed = true;
+                    continue;
                 }
-                _ => {}
+
+                match expr {
+                    Expr::Number(value) => constant_table.insert(label, value),
+                    expr => pending.push((label, expr, active_lines.len())),
+                }
+            }
+
+            if failed {
+                continue;
             }
         }
+
+        active_lines.push(line);
     }
 
+    if let Some(block) = conditions.last() {
+        errors.push(
+            AssemblyError::StructuralError {
+                line: block.line.line_number,
+                reason: "Conditional block has no matching .endif statement.".to_string(),
+            }
+            .at_line(&block.line),
+        )?;
+    }
+
+    *lines = active_lines;
+
+    errors.check(resolve_pending_constants(
+        &mut pending,
+        &mut constant_table,
+        lines,
+    ))?;
+
+    // whatever is left depends on label addresses and is resolved in the later passes
+    for (label, expr, _) in &pending {
+        constant_table.insert_deferred(label.clone(), expr.clone());
+    }
+
+    for (label, _, index) in &pending {
+        let line = &lines[*index];
+        if let Err(e) =
+            check_circular_constant(label, &constant_table, &mut Vec::new(), &line.line_number)
+        {
+            errors.push(e.at_line(line))?;
+        }
+    }
+
+    errors.finish()?;
     Ok(constant_table)
 }
 
+fn is_conditional(directive: &Directive) -> bool {
+    matches!(
+        directive,
+        Directive::If(_)
+            | Directive::Elif(_)
+            | Directive::Else
+            | Directive::Endif
+            | Directive::Ifdef(_)
+            | Directive::Ifndef(_)
+    )
+}
+
+// apply a conditional directive to the stack of open conditional blocks
+fn update_conditions(
+    directive: &Directive,
+    line: &AssemblyLine,
+    active: bool,
+    conditions: &mut Vec<ConditionalBlock>,
+    constant_table: &mut ConstantTable,
+    pending: &mut Vec<(String, Expr, usize)>,
+    active_lines: &[AssemblyLine],
+) -> Result<(), AssemblyError> {
+    let mut open_block = |taken: bool| {
+        conditions.push(ConditionalBlock {
+            line: line.clone(),
+            parent_active: active,
+            active: active && taken,
+            taken: taken || !active, // no branch is selected inside a block that is not assembled
+            seen_else: false,
+        })
+    };
+
+    match directive {
+        Directive::If(expr) => {
+            // the block is opened even if the condition is invalid, so that its .endif matches
+            let taken = if active {
+                resolve_pending_constants(pending, constant_table, active_lines)
+                    .and_then(|_| evaluate_condition(expr, constant_table, &line.line_number))
+            } else {
+                Ok(false)
+            };
+            open_block(*taken.as_ref().unwrap_or(&false));
+            taken?;
+        }
+        Directive::Ifdef(name) | Directive::Ifndef(name) => {
+            let defined = constant_table.contains_key(name)
+                || pending.iter().any(|(label, _, _)| label == name);
+            open_block(defined == matches!(directive, Directive::Ifdef(_)));
+        }
+        Directive::Elif(expr) => {
+            let block = expect_open_block(conditions, ".elif", &line.line_number)?;
+            if block.taken {
+                block.active = false;
+            } else {
+                resolve_pending_constants(pending, constant_table, active_lines)?;
+                let taken = evaluate_condition(expr, constant_table, &line.line_number)?;
+                block.active = taken;
+                block.taken = taken;
+            }
+        }
+        Directive::Else => {
+            let block = expect_open_block(conditions, ".else", &line.line_number)?;
+            block.active = block.parent_active && !block.taken;
+            block.taken = true;
+            block.seen_else = true;
+        }
+        Directive::Endif => {
+            conditions
+                .pop()
+                .ok_or_else(|| AssemblyError::StructuralError {
+                    line: line.line_number,
+                    reason: ".endif has no matching .if statement.".to_string(),
+                })?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+// get the innermost open conditional block for an .elif or .else
+fn expect_open_block<'a>(
+    conditions: &'a mut [ConditionalBlock],
+    directive_name: &str,
+    line_number: &usize,
+) -> Result<&'a mut ConditionalBlock, AssemblyError> {
+    match conditions.last_mut() {
+        Some(block) if !block.seen_else => Ok(block),
+        Some(_) => Err(AssemblyError::StructuralError {
+            line: *line_number,
+            reason: format!(
+                "{} cannot follow the .else of a conditional block.",
+                directive_name
+            ),
+        }),
+        None => Err(AssemblyError::StructuralError {
+            line: *line_number,
+            reason: format!("{} has no matching .if statement.", directive_name),
+        }),
+    }
+}
+
+// evaluate an .if or .elif condition, it may only use constants defined before it
+fn evaluate_condition(
+    expr: &Expr,
+    constant_table: &ConstantTable,
+    line_number: &usize,
+) -> Result<bool, AssemblyError> {
+    if let Some(name) = expression::referenced_symbols(expr)
+        .into_iter()
+        .find(|name| constant_table.get(name).is_none())
+    {
+        return Err(AssemblyError::SemanticError {
+            line: *line_number,
+            reason: format!(
+                "Condition must only use constants defined before it, found: {}",
+                name
+            ),
+        });
+    }
+
+    let value = expression::evaluate(
+        expr,
+        &|name| constant_table.get(name).copied(),
+        &|_| None,
+        line_number,
+    )?;
+
+    Ok(value != 0)
+}
+
+// constants may be defined in terms of other constants in any order, keep evaluating until
+// no more progress is made
+fn resolve_pending_constants(
+    pending: &mut Vec<(String, Expr, usize)>,
+    constant_table: &mut ConstantTable,
+    lines: &[AssemblyLine],
+) -> Result<(), AssemblyError> {
+    loop {
+        let mut progress = false;
+        let mut still_pending = Vec::new();
+
+        for (label, expr, index) in pending.drain(..) {
+            let resolvable = expression::referenced_symbols(&expr)
+                .iter()
+                .all(|name| constant_table.get(name).is_some())
+                && !contains_bank_expr(&expr);
+
+            if resolvable {
+                let line = &lines[index];
+                let value = expression::evaluate(
+                    &expr,
+                    &|name| constant_table.get(name).copied(),
+                    &|_| None,
+                    &line.line_number,
+                )
+                .map_err(|e| e.at_line(line))?;
+                constant_table.insert(label, value);
+                progress = true;
+            } else {
+                still_pending.push((label, expr, index));
+            }
+        }
+
+        *pending = still_pending;
+
+        if !progress {
+            return Ok(());
+        }
+    }
+}
+
+// returns true if an expression contains a bank() lookup, which always needs the symbol table
+fn contains_bank_expr(expr: &Expr) -> bool {
+    match expr {
+        Expr::Bank(_) => true,
+        Expr::Number(_) | Expr::Symbol(_) => false,
+        Expr::Unary(_, inner) => contains_bank_expr(inner),
+        Expr::Binary(_, lhs, rhs) => contains_bank_expr(lhs) || contains_bank_expr(rhs),
+    }
+}
+
+// walk the deferred constants referenced by a constant, erroring if it refers back to itself
+fn check_circular_constant(
+    label: &String,
+    constant_table: &ConstantTable,
+    visiting: &mut Vec<String>,
+    line_number: &usize,
+) -> Result<(), AssemblyError> {
+    let Some(expr) = constant_table.get_deferred(label) else {
+        return Ok(());
+    };
+
+    if visiting.contains(label) {
+        return Err(AssemblyError::SemanticError {
+            line: *line_number,
+            reason: format!("Circular constant definition: {}", label),
+        });
+    }
+
+    visiting.push(label.clone());
+    for name in expression::referenced_symbols(expr) {
+        check_circular_constant(name, constant_table, visiting, line_number)?;
+    }
+    visiting.pop();
+
+    Ok(())
+}
+
+/// Pass 0.25: Give local and anonymous labels the unique names they are stored under in the
+/// symbol table, e.g. ".loop" after "draw:" becomes "draw.loop". Returns the full names of every
+/// referenced label.
+pub fn resolve_label_scopes(
+    lines: &mut [AssemblyLine],
+    constant_table: &mut ConstantTable,
+    max_errors: usize,
+) -> Result<HashSet<String>, AssemblyError> {
+    let mut errors = ErrorCollector::new(max_errors);
+    let mut scope = LabelScope::new(lines);
+
+    for (line_index, line) in lines.iter_mut().enumerate() {
+        if let Err(e) = scope.enter_line(line_index, line) {
+            errors.push(e.at_line(line))?;
+        }
+
+        let mut result = Ok(());
+
+        if let Some(instruction) = &mut line.instruction {
+            result = preprocessor::label::process_instruction_labels(
+                instruction,
+                &mut scope,
+                &line.line_number,
+            );
+        }
+
+        if let Some(directive) = &mut line.directive {
+            result = preprocessor::label::process_directive_labels(
+                directive,
+                &mut scope,
+                &line.line_number,
+            );
+
+            // constants that depend on labels were stored before the labels had their full names
+            for (name, expr) in defined_constants(directive, &line.line_number).unwrap_or_default()
+            {
+                if constant_table.get_deferred(&name).is_some() {
+                    constant_table.insert_deferred(name, expr);
+                }
+            }
+        }
+
+        if let Err(e) = result {
+            errors.push(e.at_line(line))?;
+        }
+    }
+
+    errors.finish()?;
+
+    Ok(scope.into_referenced())
+}
+
 /// Pass 0.5: Replace constant values
 pub fn process_constants(
     lines: &mut [AssemblyLine],
     constant_table: &ConstantTable,
+    max_errors: usize,
 ) -> Result<(), AssemblyError> {
+    let mut errors = ErrorCollector::new(max_errors);
+
     for line in lines {
+        let mut result = Ok(());
+
         if let Some(instruction) = &mut line.instruction {
-            preprocessor::constant::process_instruction_constants(
+            result = preprocessor::constant::process_instruction_constants(
                 instruction,
                 constant_table,
                 &line.line_number,
-            )?;
+            );
         }
 
         if let Some(directive) = &mut line.directive {
-            preprocessor::constant::process_directive_constants(
+            result = preprocessor::constant::process_directive_constants(
                 directive,
                 constant_table,
                 &line.line_number,
-            )?;
+            );
+        }
+
+        if let Err(e) = result {
+            errors.push(e.at_line(line))?;
         }
     }
+
+    errors.finish()
+}
+
+/// Pass 0.75: Translate the text of .ascii and .asciz directives through the character map set by
+/// the .charmap directives before them.
+pub fn apply_charmaps(lines: &mut [AssemblyLine], max_errors: usize) -> Result<(), AssemblyError> {
+    let mut errors = ErrorCollector::new(max_errors);
+    let mut charmap = CharacterMap::default();
+
+    for line in lines {
+        let result = match &mut line.directive {
+            Some(Directive::Charmap(change)) => {
+                update_charmap(&mut charmap, change, line.line_number)
+            }
+            Some(Directive::Ascii(text) | Directive::Asciz(text)) => {
+                match charmap.translate(text) {
+                    Ok(translated) => {
+                        *text = translated;
+                        Ok(())
+                    }
+                    Err(c) => Err(AssemblyError::SemanticError {
+                        line: line.line_number,
+                        reason: format!(
+                            "Character {:?} has no value in the current .charmap",
+                            c as char
+                        ),
+                    }),
+                }
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(e) = result {
+            errors.push(e.at_line(line))?;
+        }
+    }
+
+    errors.finish()
+}
+
+fn update_charmap(
+    charmap: &mut CharacterMap,
+    change: &Charmap,
+    line_number: usize,
+) -> Result<(), AssemblyError> {
+    match change {
+        Charmap::Ascii => *charmap = CharacterMap::ascii(),
+        Charmap::Font => *charmap = CharacterMap::font(),
+        Charmap::Map(chars, value) => {
+            let Operand::Immediate(value) = value else {
+                return Err(AssemblyError::SemanticError {
+                    line: line_number,
+                    reason: ".charmap value must be a constant.".to_string(),
+                });
+            };
+
+            let chars = match chars {
+                Operand::String(text) => text.chars().map(|c| c as u32).collect(),
+                Operand::Immediate(c) => vec![*c as u32],
+                _ => Vec::new(),
+            };
+
+            // the characters of a string are mapped to consecutive values
+            for (offset, c) in chars.into_iter().enumerate() {
+                let mapped = value + offset as i32;
+                if c > u8::MAX as u32 || !(0..=u8::MAX as i32).contains(&mapped) {
+                    return Err(AssemblyError::SemanticError {
+                        line: line_number,
+                        reason: ".charmap characters and values must fit in a byte.".to_string(),
+                    });
+                }
+                charmap.insert(c as u8, mapped as u8);
+            }
+        }
+    }
+
     Ok(())
 }
 
-/// Pass 1: Build the symbol table.
+/// The result of pass 1: the symbol table, the layout of every section, the short `.auto` branches
+/// and the free bytes left in every ROM bank.
+pub type ProgramLayout = (SymbolTable, Vec<SectionLayout>, Vec<BranchSite>, Vec<u32>);
+
+/// Move every floating section after the other lines of the program, keeping their order. Pass 1
+/// can then place them in the ROM bank space the rest of the program leaves free.
+pub fn move_floating_sections(lines: &mut Vec<AssemblyLine>) {
+    let mut in_float = false;
+    let (floating, inline): (Vec<_>, Vec<_>) =
+        std::mem::take(lines).into_iter().partition(|line| {
+            match &line.directive {
+                Some(Directive::SectionStart(options)) => in_float = options.floating(),
+                Some(Directive::SectionEnd) if in_float => {
+                    in_float = false;
+                    return true;
+                }
+                _ => {}
+            }
+            in_float
+        });
+
+    *lines = inline;
+    lines.extend(floating);
+}
+
+/// Pass 1: Build the symbol table, also records the final layout of every section, the address
+/// of every short `.auto` branch (see `relaxation::relax_branches`) and the free bytes left in
+/// every ROM bank. Floating sections must come after every other line, see
+/// `move_floating_sections`, they are placed in the space the rest of the program leaves free.
 pub fn build_symbol_table<F: crate::file_reader::FileReader>(
     lines: &[AssemblyLine],
     final_logical_addr: &u16,
@@ -98,293 +593,449 @@ pub fn build_symbol_table<F: crate::file_reader::FileReader>(
     expected_header_addr: Option<u16>,
     constant_table: &ConstantTable,
     reader: &F,
-) -> Result<SymbolTable, AssemblyError> {
+    max_errors: usize,
+) -> Result<ProgramLayout, AssemblyError> {
+    let mut errors = ErrorCollector::new(max_errors);
     let mut symbol_table = SymbolTable::new();
+    let mut sections: Vec<SectionLayout> = Vec::new();
+    let mut branches: Vec<BranchSite> = Vec::new();
     let mut addr_counter: AddrCounter = AddrCounter::new();
     let mut found_interrupt_table_addr: Option<u32> = None;
     let mut found_header_addr: Option<u32> = None;
     let mut context_stack: ContextStack = vec![];
+    let mut ram_end: Option<u32> = None; // address after the last nobits section
+    let mut free = FreeSpace::new();
+    let mut placements: Option<HashMap<usize, Result<AddrCounter, String>>> = None;
+
+    for (line_index, line) in lines.iter().enumerate() {
+        let line_start = addr_counter;
+
+        // errors are annotated with the macro expansion chain of the line
+        let mut process_line = || -> Result<(), AssemblyError> {
+            // If a label exists on this line, record its current address.
+            if let Some(label) = &line.label {
+                if symbol_table.contains_key(label) {
+                    return Err(AssemblyError::SemanticError {
+                        line: line.line_number,
+                        reason: format!("Duplicate label definition: {}", label),
+                    });
+                }
 
-    for line in lines {
-        // If a label exists on this line, record its current address.
-        if let Some(label) = &line.label {
-            if symbol_table.contains_key(label) {
-                return Err(AssemblyError::SemanticError {
-                    line: line.line_number,
-                    reason: format!("Duplicate label definition: {}", label),
-                });
+                // check to see if there is already a constant defined with the same name
+                if constant_table.contains_key(label) {
+                    return Err(AssemblyError::SemanticError {
+                        line: line.line_number,
+                        reason: format!("Label already defined as a constant: {}", label),
+                    });
+                }
+
+                // let logical_address = match current_bank {
+                //     0 => physical_address,
+                //     _ => BANK_SIZE + (physical_address % BANK_SIZE),
+                // };
+
+                symbol_table.insert(
+                    label.clone(),
+                    Symbol {
+                        logical_address: addr_counter.logical_addr,
+                        bank: addr_counter.bank,
+                    },
+                );
             }
 
-            // check to see if there is already a constant defined with the same name
-            if constant_table.contains_key(label) {
-                return Err(AssemblyError::SemanticError {
-                    line: line.line_number,
-                    reason: format!("Label already defined as a constant: {}", label),
-                });
+            // nobits sections only reserve RAM, nothing is placed in the rom
+            if context_stack.last().is_some_and(|context| context.nobits)
+                && !matches!(
+                    line.directive,
+                    Some(Directive::SectionStart(_) | Directive::SectionEnd)
+                )
+            {
+                return reserve_nobits_space(line, &mut addr_counter, &symbol_table);
             }
 
-            // let logical_address = match current_bank {
-            //     0 => physical_address,
-            //     _ => BANK_SIZE + (physical_address % BANK_SIZE),
-            // };
-
-            symbol_table.insert(
-                label.clone(),
-                Symbol {
-                    logical_address: addr_counter.logical_addr,
-                    bank: addr_counter.bank,
-                },
-            );
-        }
+            // Increment physical_address by the size of the instruction.
+            if let Some(instruction) = &line.instruction {
+                if relaxation::is_short_branch(instruction) {
+                    branches.push(BranchSite {
+                        line_index,
+                        logical_address: addr_counter.logical_addr,
+                        bank: addr_counter.bank,
+                    });
+                }
 
-        // Increment physical_address by the size of the instruction.
-        if let Some(instruction) = &line.instruction {
-            let instruction_size = encoder::calculate_instruction_size(instruction);
-            addr_counter.increment_by(instruction_size);
-        }
+                let instruction_size = encoder::calculate_instruction_size(instruction);
+                addr_counter.increment_by(instruction_size);
+            }
 
-        // handle directives
-        if let Some(directive) = &line.directive {
-            match directive {
-                Directive::Org(Operand::Immediate(addr)) => {
-                    addr_counter.logical_addr = *addr as u32;
+            // handle directives
+            if let Some(directive) = &line.directive {
+                if context_stack.last().is_some_and(|context| context.float)
+                    && matches!(directive, Directive::Org(_) | Directive::Bank(_))
+                {
+                    return Err(AssemblyError::StructuralError {
+                        line: line.line_number,
+                        reason: ".org and .bank cannot be used in a floating section, it is placed in free ROM bank space."
+                            .to_string(),
+                    });
+                }
 
-                    if addr_counter.logical_addr > *final_logical_addr as u32 {
-                        return Err(AssemblyError::SemanticError {
-                            line: line.line_number,
-                            reason: format!(
-                                ".org directive cannot move beyond the final logical address for a rom (final addr: 0x{:04x}).",
-                                final_logical_addr
-                            ),
-                        });
-                    }
+                match directive {
+                    Directive::Org(op) => {
+                        let addr = resolve_location_operand(op, &symbol_table, &line.line_number)?;
+                        addr_counter.logical_addr = addr as u32;
 
-                    if addr_counter.bank == 0 && addr_counter.logical_addr > 0x3FFF {
-                        return Err(AssemblyError::SemanticError {
-                            line: line.line_number,
-                            reason: format!(
-                                "Currently selected bank is bank 0, the given .org address (0x{:04x}) is outside of the bank 0 fixed address space 0x0000-0x3FFF.",
-                                addr_counter.logical_addr
-                            ),
-                        });
-                    }
+                        if addr_counter.logical_addr > *final_logical_addr as u32 {
+                            return Err(AssemblyError::SemanticError {
+                                line: line.line_number,
+                                reason: format!(
+                                    ".org directive cannot move beyond the final logical address for a rom (final addr: 0x{:04x}).",
+                                    final_logical_addr
+                                ),
+                            });
+                        }
 
-                    if addr_counter.bank != 0 && addr_counter.logical_addr < 0x4000 {
-                        return Err(AssemblyError::SemanticError {
-                            line: line.line_number,
-                            reason: format!(
-                                "Currently selected bank is bank {} (a switchable bank), the given .org address (0x{:04x}) is outside of the switchable bank address space 0x4000-0x7FFF.",
-                                addr_counter.bank, addr_counter.logical_addr
-                            ),
-                        });
-                    }
+                        if addr_counter.bank == 0 && addr_counter.logical_addr > 0x3FFF {
+                            return Err(AssemblyError::SemanticError {
+                                line: line.line_number,
+                                reason: format!(
+                                    "Currently selected bank is bank 0, the given .org address (0x{:04x}) is outside of the bank 0 fixed address space 0x0000-0x3FFF.",
+                                    addr_counter.logical_addr
+                                ),
+                            });
+                        }
 
-                    // calculate the new physical address
-                    let new_physical_addr = calculate_physical_addr(
-                        &(addr_counter.logical_addr as u16),
-                        &addr_counter.bank,
-                    );
+                        if addr_counter.bank != 0 && addr_counter.logical_addr < 0x4000 {
+                            return Err(AssemblyError::SemanticError {
+                                line: line.line_number,
+                                reason: format!(
+                                    "Currently selected bank is bank {} (a switchable bank), the given .org address (0x{:04x}) is outside of the switchable bank address space 0x4000-0x7FFF.",
+                                    addr_counter.bank, addr_counter.logical_addr
+                                ),
+                            });
+                        }
 
-                    // It's good practice to ensure .org doesn't move backwards,
-                    // as it can overwrite previous label definitions.
-                    if new_physical_addr < addr_counter.physical_addr {
-                        return Err(AssemblyError::SemanticError {
-                            line: line.line_number,
-                            reason: ".org directive cannot move the address backwards.".to_string(),
-                        });
-                    }
+                        // calculate the new physical address
+                        let new_physical_addr = calculate_physical_addr(
+                            &(addr_counter.logical_addr as u16),
+                            &addr_counter.bank,
+                        );
 
-                    addr_counter.num_bytes += new_physical_addr - addr_counter.physical_addr;
-                    addr_counter.physical_addr = new_physical_addr;
-                }
-                Directive::Bank(Operand::Immediate(num)) => {
-                    if (*num as u32) < addr_counter.bank {
-                        return Err(AssemblyError::SemanticError {
-                            line: line.line_number,
-                            reason: ".bank directive cannot move to a previous bank.".to_string(),
-                        });
-                    }
+                        // It's good practice to ensure .org doesn't move backwards,
+                        // as it can overwrite previous label definitions.
+                        if new_physical_addr < addr_counter.physical_addr {
+                            return Err(AssemblyError::SemanticError {
+                                line: line.line_number,
+                                reason: ".org directive cannot move the address backwards."
+                                    .to_string(),
+                            });
+                        }
 
-                    // if trying to select the already currently selected bank, do nothing
-                    if *num as u32 != addr_counter.bank {
-                        addr_counter.bank = *num as u32;
-                        let new_physical_addr = addr_counter.bank * BANK_SIZE;
                         addr_counter.num_bytes += new_physical_addr - addr_counter.physical_addr;
                         addr_counter.physical_addr = new_physical_addr;
-                        addr_counter.logical_addr = 0;
                     }
-                }
-                Directive::Byte(bytes) => {
-                    let num_bytes = bytes.len() as u32;
-                    addr_counter.increment_by(num_bytes);
-                }
-                Directive::Word(words) => {
-                    let num_bytes = (words.len() as u32) * 2;
-                    addr_counter.increment_by(num_bytes);
-                }
-                Directive::Incbin(path) => {
-                    // Get the file size to determine how many bytes to allocate
-                    let binary_data = reader
-                        .read_binary(std::path::Path::new(path))
-                        .map_err(|e| AssemblyError::StructuralError {
-                            line: line.line_number,
-                            reason: format!("Failed to read binary file '{}': {}", path, e),
-                        })?;
-                    addr_counter.increment_by(binary_data.len() as u32);
-                }
-                Directive::Header(_) => {
-                    if let None = expected_header_addr {
-                        return Err(AssemblyError::StructuralError {
-                            line: line.line_number,
-                            reason: "Cartridge rom header not allowed in boot roms.".to_string(),
-                        });
+                    Directive::Bank(op) => {
+                        let num = resolve_bank_operand(op, &symbol_table, &line.line_number)?;
+                        if num < addr_counter.bank {
+                            return Err(AssemblyError::SemanticError {
+                                line: line.line_number,
+                                reason: ".bank directive cannot move to a previous bank."
+                                    .to_string(),
+                            });
+                        }
+
+                        // if trying to select the already currently selected bank, do nothing
+                        if num != addr_counter.bank {
+                            addr_counter.bank = num;
+                            let new_physical_addr = addr_counter.bank * BANK_SIZE;
+                            addr_counter.num_bytes +=
+                                new_physical_addr - addr_counter.physical_addr;
+                            addr_counter.physical_addr = new_physical_addr;
+                            addr_counter.logical_addr = 0;
+                        }
                     }
-                    found_header_addr = Some(addr_counter.physical_addr);
-                    addr_counter.increment_by(96);
-                }
-                Directive::Interrupt(_) => {
-                    if let None = expected_interrupt_table_addr {
-                        return Err(AssemblyError::StructuralError {
-                            line: line.line_number,
-                            reason: "Interrupt vector table not expected.".to_string(),
-                        });
+                    Directive::Byte(bytes) => {
+                        let num_bytes = bytes.len() as u32;
+                        addr_counter.increment_by(num_bytes);
                     }
-                    found_interrupt_table_addr = Some(addr_counter.physical_addr);
-                    addr_counter.increment_by(32);
-                }
-                Directive::SectionStart(section_options) => {
-                    // disallow nested sections for now
-                    if !context_stack.is_empty() {
+                    Directive::Ascii(text) => {
+                        addr_counter.increment_by(text.len() as u32);
+                    }
+                    Directive::Fill(count, _) => {
+                        let count =
+                            resolve_location_operand(count, &symbol_table, &line.line_number)?;
+                        addr_counter.increment_by(count as u32);
+                    }
+                    Directive::Asciz(text) => {
+                        // text followed by a zero byte
+                        addr_counter.increment_by(text.len() as u32 + 1);
+                    }
+                    Directive::Word(words) => {
+                        let num_bytes = (words.len() as u32) * 2;
+                        addr_counter.increment_by(num_bytes);
+                    }
+                    Directive::Incbin(path) => {
+                        // Get the file size to determine how many bytes to allocate
+                        let binary_data =
+                            reader
+                                .read_binary(std::path::Path::new(path))
+                                .map_err(|e| AssemblyError::StructuralError {
+                                    line: line.line_number,
+                                    reason: format!("Failed to read binary file '{}': {}", path, e),
+                                })?;
+                        addr_counter.increment_by(binary_data.len() as u32);
+                    }
+                    Directive::Header(_) => {
+                        if expected_header_addr.is_none() {
+                            return Err(AssemblyError::StructuralError {
+                                line: line.line_number,
+                                reason: "Cartridge rom header not allowed in boot roms."
+                                    .to_string(),
+                            });
+                        }
+                        found_header_addr = Some(addr_counter.physical_addr);
+                        addr_counter.increment_by(96);
+                    }
+                    Directive::Interrupt(_) => {
+                        if expected_interrupt_table_addr.is_none() {
+                            return Err(AssemblyError::StructuralError {
+                                line: line.line_number,
+                                reason: "Interrupt vector table not expected.".to_string(),
+                            });
+                        }
+                        found_interrupt_table_addr = Some(addr_counter.physical_addr);
+                        addr_counter.increment_by(32);
+                    }
+                    Directive::SectionStart(section_options) => {
+                        // disallow nested sections for now
+                        if !context_stack.is_empty() {
+                            let mut name: String = "UNNAMED".to_string();
+
+                            let old_context: Context = context_stack.pop().unwrap();
+
+                            if let Some(n) = old_context.name {
+                                name = n;
+                            }
+
+                            return Err(AssemblyError::StructuralError {
+                                line: line.line_number,
+                                reason: format!(
+                                    "Cannot nest a section within a section, already within the \"{}\" section.",
+                                    name
+                                ),
+                            });
+                        }
+
+                        let mut new_context: Context = Context {
+                            name: section_options.name.clone(),
+                            size: section_options.size,
+                            vaddr: section_options.vaddr,
+                            paddr: section_options.paddr,
+                            align: section_options.align,
+                            nobits: section_options.nobits,
+                            bank0: section_options.bank0,
+                            float: section_options.floating(),
+                            address: addr_counter,
+                            start: addr_counter,
+                        };
+
+                        if new_context.nobits {
+                            // the section is opened even if its address is invalid, so that its
+                            // .section_end matches
+                            let start =
+                                start_nobits_section(section_options, ram_end, &line.line_number);
+                            new_context.start = *start.as_ref().unwrap_or(&AddrCounter::new());
+                            addr_counter = new_context.start;
+                            context_stack.push(new_context);
+                            return start.map(|_| ());
+                        }
+
+                        // floating sections are placed once the rest of the program is laid out
+                        if new_context.float {
+                            let placements = placements.get_or_insert_with(|| {
+                                let switchable =
+                                    *final_logical_addr as u32 >= SWITCHABLE_BANK_START;
+                                free_space::place_floating_sections(
+                                    lines, &mut free, switchable, reader,
+                                )
+                            });
+
+                            if let Some(placement) = placements.get(&line_index) {
+                                // the section is opened even if it was not placed, so that its
+                                // .section_end matches
+                                new_context.start = *placement.as_ref().unwrap_or(&addr_counter);
+                                addr_counter = new_context.start;
+                                context_stack.push(new_context);
+                                return placement.as_ref().map(|_| ()).map_err(|reason| {
+                                    AssemblyError::StructuralError {
+                                        line: line.line_number,
+                                        reason: reason.clone(),
+                                    }
+                                });
+                            }
+                        }
+
+                        // reset section size counter
+                        addr_counter.num_bytes = 0;
+
+                        // set logical address
+                        if let Some(vaddr) = new_context.vaddr {
+                            addr_counter.logical_addr = vaddr;
+                        }
+
+                        // set physical address
+                        if let Some(paddr) = new_context.paddr {
+                            addr_counter.physical_addr = paddr;
+                        }
+
+                        // set alignment
+                        if let Some(alignment) = new_context.align {
+                            if alignment == BANK_SIZE {
+                                return Err(AssemblyError::StructuralError {
+                                    line: line.line_number,
+                                    reason: format!(
+                                        ".section align value cannot be larger than the size of a ROM bank ({})",
+                                        BANK_SIZE
+                                    ),
+                                });
+                            }
+
+                            let extra_bytes = addr_counter.physical_addr % alignment;
+                            let pad_size = alignment - extra_bytes;
+
+                            if extra_bytes != 0 {
+                                addr_counter.increment_by(pad_size);
+                            }
+                        }
+
+                        new_context.start = addr_counter;
+                        context_stack.push(new_context);
+                    }
+                    Directive::SectionEnd => {
+                        if context_stack.is_empty() {
+                            return Err(AssemblyError::StructuralError {
+                                line: line.line_number,
+                                reason:
+                                    ".section_end found without a preceding .section statement."
+                                        .to_string(),
+                            });
+                        }
+
+                        let old_context: Context = context_stack.pop().unwrap();
+
+                        if old_context.nobits {
+                            let ram_counter =
+                                std::mem::replace(&mut addr_counter, old_context.address);
+                            let end =
+                                end_nobits_section(&old_context, ram_counter, &line.line_number)?;
+                            ram_end = Some(end);
+                            sections.push(SectionLayout {
+                                name: old_context.name,
+                                bank: old_context.start.bank,
+                                logical_addr: old_context.start.logical_addr,
+                                physical_addr: old_context.start.physical_addr,
+                                size: end - old_context.start.logical_addr,
+                            });
+                            return Ok(());
+                        }
+
                         let mut name: String = "UNNAMED".to_string();
 
-                        let old_context: Context = context_stack.pop().unwrap();
+                        if let Some(n) = &old_context.name {
+                            name = n.clone();
+                        }
 
-                        if let Some(n) = old_context.name {
-                            name = n;
+                        if old_context.vaddr.is_some() {
+                            addr_counter.logical_addr =
+                                calculate_logical_addr(&addr_counter.physical_addr);
                         }
 
-                        return Err(AssemblyError::StructuralError {
-                            line: line.line_number,
-                            reason: format!(
-                                "Cannot nest a section within a section, already within the \"{}\" section.",
-                                name
-                            ),
-                        });
-                    }
+                        if let Some(size) = old_context.size {
+                            if addr_counter.num_bytes > size {
+                                return Err(AssemblyError::StructuralError {
+                                    line: line.line_number,
+                                    reason: format!(
+                                        "Section \"{}\" larger than the allotted section size of {} bytes, ({} bytes)",
+                                        name, size, addr_counter.num_bytes
+                                    ),
+                                });
+                            }
 
-                    let new_context: Context = Context {
-                        name: section_options.name.clone(),
-                        size: section_options.size,
-                        vaddr: section_options.vaddr,
-                        paddr: section_options.paddr,
-                        align: section_options.align,
-                        address: addr_counter.clone(),
-                    };
-
-                    // reset section size counter
-                    addr_counter.num_bytes = 0;
-
-                    // set logical address
-                    if let Some(vaddr) = new_context.vaddr {
-                        addr_counter.logical_addr = vaddr;
-                    }
+                            let pad_size = size - addr_counter.num_bytes;
 
-                    // set physical address
-                    if let Some(paddr) = new_context.paddr {
-                        addr_counter.physical_addr = paddr;
-                    }
+                            addr_counter.increment_by(pad_size);
+                        }
 
-                    // set alignment
-                    if let Some(alignment) = new_context.align {
-                        if alignment == BANK_SIZE {
+                        // bank0 sections must not be moved into a switchable bank
+                        if old_context.bank0 && addr_counter.physical_addr > BANK_SIZE {
                             return Err(AssemblyError::StructuralError {
                                 line: line.line_number,
                                 reason: format!(
-                                    ".section align value cannot be larger than the size of a ROM bank ({})",
-                                    BANK_SIZE
+                                    "Section \"{}\" must be in ROM bank 0, but ends at physical address 0x{:04x}.",
+                                    name, addr_counter.physical_addr
                                 ),
                             });
                         }
 
-                        let extra_bytes = addr_counter.physical_addr % alignment;
-                        let pad_size = alignment - extra_bytes;
-
-                        if extra_bytes != 0 {
-                            addr_counter.increment_by(pad_size);
-                        }
-                    }
-
-                    context_stack.push(new_context);
-                }
-                Directive::SectionEnd => {
-                    if context_stack.is_empty() {
-                        return Err(AssemblyError::StructuralError {
-                            line: line.line_number,
-                            reason: ".section_end found without a preceding .section statement."
-                                .to_string(),
+                        sections.push(SectionLayout {
+                            name: old_context.name,
+                            bank: old_context.start.bank,
+                            logical_addr: old_context.start.logical_addr,
+                            physical_addr: old_context.start.physical_addr,
+                            size: addr_counter.physical_addr - old_context.start.physical_addr,
                         });
-                    }
 
-                    let old_context: Context = context_stack.pop().unwrap();
-                    let mut name: String = "UNNAMED".to_string();
-
-                    if let Some(n) = old_context.name {
-                        name = n;
-                    }
+                        // a floating section does not move the address of the lines after it
+                        if old_context.float {
+                            addr_counter = old_context.address;
+                            return Ok(());
+                        }
 
-                    if old_context.vaddr.is_some() {
-                        addr_counter.logical_addr =
-                            calculate_logical_addr(&addr_counter.physical_addr);
+                        // restore the old num_bytes value
+                        addr_counter.num_bytes += old_context.address.num_bytes;
                     }
-
-                    if let Some(size) = old_context.size {
-                        if addr_counter.num_bytes > size {
+                    Directive::Align(alignment) => {
+                        if *alignment == BANK_SIZE {
                             return Err(AssemblyError::StructuralError {
                                 line: line.line_number,
                                 reason: format!(
-                                    "Section \"{}\" larger than the allotted section size of {} bytes, ({} bytes)",
-                                    name, size, addr_counter.num_bytes
+                                    ".align value cannot be larger than the size of a ROM bank ({})",
+                                    BANK_SIZE
                                 ),
                             });
                         }
 
-                        let pad_size = size - addr_counter.num_bytes;
+                        let extra_bytes = addr_counter.physical_addr % alignment;
+                        let pad_size = alignment - extra_bytes;
 
-                        addr_counter.increment_by(pad_size);
+                        if extra_bytes != 0 {
+                            addr_counter.increment_by(pad_size);
+                        }
                     }
-
-                    // restore the old num_bytes value
-                    addr_counter.num_bytes += old_context.address.num_bytes;
+                    _ => {}
                 }
-                Directive::Align(alignment) => {
-                    if *alignment == BANK_SIZE {
-                        return Err(AssemblyError::StructuralError {
-                            line: line.line_number,
-                            reason: format!(
-                                ".align value cannot be larger than the size of a ROM bank ({})",
-                                BANK_SIZE
-                            ),
-                        });
-                    }
+            }
 
-                    let extra_bytes = addr_counter.physical_addr % alignment;
-                    let pad_size = alignment - extra_bytes;
+            // check for overflow of current bank
+            let cur_bank_end = (addr_counter.bank + 1) * BANK_SIZE;
+            if addr_counter.physical_addr > cur_bank_end {
+                return Err(AssemblyError::StructuralError {
+                    line: line.line_number,
+                    reason: format!("ROM bank {} overflow.", addr_counter.bank),
+                });
+            }
 
-                    if extra_bytes != 0 {
-                        addr_counter.increment_by(pad_size);
-                    }
-                }
-                _ => {}
+            // the padding of .org, .bank and section starts is left free for floating sections
+            if !matches!(
+                line.directive,
+                Some(Directive::Org(_) | Directive::Bank(_) | Directive::SectionStart(_))
+            ) {
+                free.reserve_physical(line_start.physical_addr, addr_counter.physical_addr);
             }
-        }
+            Ok(())
+        };
 
-        // check for overflow of current bank
-        let cur_bank_end = (addr_counter.bank as u32 + 1) * BANK_SIZE;
-        if addr_counter.physical_addr > cur_bank_end {
-            return Err(AssemblyError::StructuralError {
-                line: line.line_number,
-                reason: format!("ROM bank {} overflow.", addr_counter.bank),
-            });
+        if let Err(e) = process_line() {
+            errors.push(e.at_line(line))?;
         }
     }
 
@@ -398,310 +1049,827 @@ pub fn build_symbol_table<F: crate::file_reader::FileReader>(
             name = n;
         }
 
-        return Err(AssemblyError::StructuralErrorNoLine {
+        errors.push(AssemblyError::StructuralErrorNoLine {
             reason: format!(
                 "section \"{}\" has no matching .section_end statement.",
                 name
             ),
-        });
+        })?;
     }
 
     // check for correct header placement
     match (expected_header_addr, found_header_addr) {
-        (Some(ex_addr), Some(found_addr)) => {
-            if ex_addr as u32 != found_addr {
-                return Err(AssemblyError::StructuralErrorNoLine {
-                    reason: format!(
-                        "Expected cartridge rom header at 0x{:04x}, found at 0x{:04x}",
-                        ex_addr, found_addr
-                    ),
-                });
-            }
+        (Some(ex_addr), Some(found_addr)) if ex_addr as u32 != found_addr => {
+            errors.push(AssemblyError::StructuralErrorNoLine {
+                reason: format!(
+                    "Expected cartridge rom header at 0x{:04x}, found at 0x{:04x}",
+                    ex_addr, found_addr
+                ),
+            })?;
         }
         (Some(ex_addr), None) => {
-            return Err(AssemblyError::StructuralErrorNoLine {
+            errors.push(AssemblyError::StructuralErrorNoLine {
                 reason: format!("Expected cartridge rom header at 0x{:04x}", ex_addr),
-            });
+            })?;
         }
         _ => {}
     }
 
     // check for correct interrupt vector table placement
     match (expected_interrupt_table_addr, found_interrupt_table_addr) {
-        (Some(ex_addr), Some(found_addr)) => {
-            if ex_addr as u32 != found_addr {
-                return Err(AssemblyError::StructuralErrorNoLine {
-                    reason: format!(
-                        "Expected interrupt vector table at 0x{:04x}, found at 0x{:04x}",
-                        ex_addr, found_addr
-                    ),
-                });
-            }
+        (Some(ex_addr), Some(found_addr)) if ex_addr as u32 != found_addr => {
+            errors.push(AssemblyError::StructuralErrorNoLine {
+                reason: format!(
+                    "Expected interrupt vector table at 0x{:04x}, found at 0x{:04x}",
+                    ex_addr, found_addr
+                ),
+            })?;
         }
         (Some(ex_addr), None) => {
-            return Err(AssemblyError::StructuralErrorNoLine {
+            errors.push(AssemblyError::StructuralErrorNoLine {
                 reason: format!("Expected interrupt vector table at 0x{:04x}", ex_addr),
-            });
+            })?;
         }
         _ => {}
     }
 
-    Ok(symbol_table)
+    errors.finish()?;
+    let free_bytes = (0..free.banks.len() as u32)
+        .map(|bank| free.free_bytes(bank))
+        .collect();
+    Ok((symbol_table, sections, branches, free_bytes))
 }
 
-/// Pass 2: Generate machine code.
+/// Pass 2: Generate machine code. Also returns the listing, the address and emitted bytes
+/// (including padding) of every line. Padding, data and memory access warnings are collected
+/// into `warnings`. Padding is filled with `pad_byte` until a .padbyte directive changes it, by
+/// default padding inside the program is 0x00 and the unused space of the last bank is 0xFF. The
+/// ROM is padded to a whole number of banks, and to at least `min_banks` banks. Floating sections
+/// are written at the addresses pass 1 gave them in `sections`.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_bytecode<F: crate::file_reader::FileReader>(
     lines: &[AssemblyLine],
     symbol_table: &SymbolTable,
+    sections: &[SectionLayout],
     reader: &F,
-) -> Result<Vec<u8>, AssemblyError> {
+    max_errors: usize,
+    warnings: &mut WarningCollector,
+    pad_byte: Option<u8>,
+    min_banks: u32,
+) -> Result<(Vec<u8>, Listing), AssemblyError> {
+    let mut errors = ErrorCollector::new(max_errors);
+    let mut pad_byte = pad_byte;
     let mut bytecode = Vec::new();
     let mut addr_counter = AddrCounter::new();
     let mut context_stack: ContextStack = vec![];
+    let mut ram_end: Option<u32> = None;
+    let mut listing = Listing::default();
+    let mut section_index = 0; // index in `sections` of the next section
+    let mut displaced: Option<(usize, Vec<u8>)> = None; // the ROM after the open floating section
 
     for line in lines {
-        if let Some(directive) = &line.directive {
-            match directive {
-                Directive::Org(Operand::Immediate(addr)) => {
-                    let new_addr = *addr as u16;
-                    let new_physical_addr = calculate_physical_addr(&new_addr, &addr_counter.bank);
-                    if new_physical_addr > addr_counter.physical_addr {
-                        let padding_size =
-                            (new_physical_addr - addr_counter.physical_addr) as usize;
-                        bytecode.resize(bytecode.len() + padding_size, 0x00);
-                        addr_counter.logical_addr += padding_size as u32;
-                        addr_counter.num_bytes += padding_size as u32;
-                        addr_counter.physical_addr = new_physical_addr;
-                    }
-                }
-                Directive::Bank(Operand::Immediate(num)) => {
-                    let new_addr = *num as u32 * BANK_SIZE;
-                    if new_addr > addr_counter.physical_addr {
-                        let padding_size = (new_addr - addr_counter.physical_addr) as usize;
-                        bytecode.resize(bytecode.len() + padding_size, 0x00);
-                        addr_counter.num_bytes += padding_size as u32;
+        let line_start = addr_counter;
+        let rom_offset = bytecode.len();
+        // the .section line of a nobits section is still placed in the ROM, its other lines are not
+        let nobits = context_stack.last().is_some_and(|context| context.nobits);
+
+        // errors are annotated with the macro expansion chain of the line
+        let mut process_line = || -> Result<(), AssemblyError> {
+            let padding = pad_byte.unwrap_or(0x00);
+
+            // nobits sections only reserve RAM, nothing is placed in the rom
+            if context_stack.last().is_some_and(|context| context.nobits)
+                && !matches!(
+                    line.directive,
+                    Some(Directive::SectionStart(_) | Directive::SectionEnd)
+                )
+            {
+                return reserve_nobits_space(line, &mut addr_counter, symbol_table);
+            }
+
+            if let Some(directive) = &line.directive {
+                match directive {
+                    Directive::Org(op) => {
+                        let new_addr =
+                            resolve_location_operand(op, symbol_table, &line.line_number)?;
+                        let new_physical_addr =
+                            calculate_physical_addr(&new_addr, &addr_counter.bank);
+                        if new_physical_addr > addr_counter.physical_addr {
+                            let padding_size =
+                                (new_physical_addr - addr_counter.physical_addr) as usize;
+                            let limit = warnings.options().org_padding_limit;
+                            if padding_size as u32 > limit {
+                                warnings.push(Warning::new(
+                                    WarningKind::OrgPadding,
+                                    line,
+                                    format!(
+                                        ".org pads {} bytes with zeros (limit: {})",
+                                        padding_size, limit
+                                    ),
+                                ));
+                            }
+                            bytecode.resize(bytecode.len() + padding_size, padding);
+                            addr_counter.num_bytes += padding_size as u32;
+                            addr_counter.physical_addr = new_physical_addr;
+                        }
+                        // also needed without padding, e.g. .org 0x4000 right after .bank
+                        addr_counter.logical_addr = new_addr as u32;
                     }
+                    Directive::Bank(op) => {
+                        let num = resolve_bank_operand(op, symbol_table, &line.line_number)?;
+                        let new_addr = num * BANK_SIZE;
+                        if new_addr > addr_counter.physical_addr {
+                            let padding_size = (new_addr - addr_counter.physical_addr) as usize;
+                            bytecode.resize(bytecode.len() + padding_size, padding);
+                            addr_counter.num_bytes += padding_size as u32;
+                        }
 
-                    addr_counter.bank = *num as u32;
-                    addr_counter.physical_addr = new_addr;
-                    addr_counter.logical_addr = 0;
-                }
-                Directive::Byte(bytes) => {
-                    let byte_vec: Vec<u8> = bytes
-                        .iter()
-                        .flat_map(|byte| -> Vec<u8> {
-                            match byte {
-                                Operand::Immediate(byte_data) => {
-                                    vec![*byte_data as u8]
-                                }
-                                _ => vec![], // Should be unreachable
+                        addr_counter.bank = num;
+                        addr_counter.physical_addr = new_addr;
+                        addr_counter.logical_addr = 0;
+                    }
+                    Directive::Byte(bytes) => {
+                        let mut byte_vec: Vec<u8> = Vec::new();
+                        for (index, byte) in bytes.iter().enumerate() {
+                            let value =
+                                resolve_operand_value(byte, symbol_table, &line.line_number)
+                                    .map_err(|e| e.at_operand(index))?;
+                            // negative values are stored as their two's complement byte
+                            if !(i8::MIN as i32..=u8::MAX as i32).contains(&value) {
+                                let warning = Warning::new(
+                                    WarningKind::ByteTruncation,
+                                    line,
+                                    format!(
+                                        "Value {} does not fit in a byte, truncated to 0x{:02x}",
+                                        value, value as u8
+                                    ),
+                                );
+                                warnings.push(warning.at_operand(index));
                             }
-                        })
-                        .collect();
-                    addr_counter.increment_by(byte_vec.len() as u32);
-                    bytecode.extend(byte_vec);
-                }
-                Directive::Word(words) => {
-                    let word_bytes: Vec<u8> = words
-                        .iter()
-                        .flat_map(|word| -> Vec<u8> {
-                            match word {
-                                Operand::Immediate(word_data) => {
-                                    (*word_data as u16).to_le_bytes().to_vec()
-                                }
-                                Operand::Label(label_name) => {
-                                    // This unwrap is safe because symbols are validated in pass 1
-                                    let sym =
-                                        get_symbol(symbol_table, label_name, &line.line_number)
-                                            .unwrap();
-                                    (sym.logical_address as u16).to_le_bytes().to_vec()
-                                }
-                                _ => vec![], // Should be unreachable
+                            byte_vec.push(value as u8);
+                        }
+                        addr_counter.increment_by(byte_vec.len() as u32);
+                        bytecode.extend(byte_vec);
+                    }
+                    Directive::Fill(count, value) => {
+                        let count =
+                            resolve_location_operand(count, symbol_table, &line.line_number)?;
+                        let value = match value {
+                            Some(value) => {
+                                resolve_byte_operand(value, symbol_table, &line.line_number)?
                             }
-                        })
-                        .collect();
-                    addr_counter.increment_by(word_bytes.len() as u32);
-                    bytecode.extend(word_bytes);
-                }
-                Directive::Incbin(path) => {
-                    // Read the binary file and include its contents
-                    let binary_data = reader
-                        .read_binary(std::path::Path::new(path))
-                        .map_err(|e| AssemblyError::StructuralError {
-                            line: line.line_number,
-                            reason: format!("Failed to read binary file '{}': {}", path, e),
-                        })?;
-                    addr_counter.increment_by(binary_data.len() as u32);
-                    bytecode.extend(binary_data);
-                }
-                Directive::Header(info) => {
-                    let mut header: Vec<u8> = Vec::new();
-
-                    header.extend(info.boot_anim.as_bytes());
-
-                    header.extend(info.title.as_bytes());
-                    if header.len() < 0x14 {
-                        header.resize(0x14, 0x00);
+                            None => padding,
+                        };
+                        addr_counter.increment_by(count as u32);
+                        bytecode.resize(bytecode.len() + count as usize, value);
                     }
-
-                    header.extend(info.developer.as_bytes());
-                    if header.len() < 0x24 {
-                        header.resize(0x24, 0x00);
+                    Directive::PadByte(value) => {
+                        pad_byte = Some(resolve_byte_operand(
+                            value,
+                            symbol_table,
+                            &line.line_number,
+                        )?);
                     }
+                    Directive::Ascii(text) => {
+                        addr_counter.increment_by(text.len() as u32);
+                        bytecode.extend(text);
+                    }
+                    Directive::Asciz(text) => {
+                        addr_counter.increment_by(text.len() as u32 + 1);
+                        bytecode.extend(text);
+                        bytecode.push(0x00);
+                    }
+                    Directive::Word(words) => {
+                        let mut word_bytes: Vec<u8> = Vec::new();
+                        for (index, word) in words.iter().enumerate() {
+                            let value =
+                                resolve_label_or_immediate(word, symbol_table, &line.line_number)
+                                    .map_err(|e| e.at_operand(index))?;
+                            word_bytes.extend(value.to_le_bytes());
+                        }
+                        addr_counter.increment_by(word_bytes.len() as u32);
+                        bytecode.extend(word_bytes);
+                    }
+                    Directive::Incbin(path) => {
+                        // Read the binary file and include its contents
+                        let binary_data =
+                            reader
+                                .read_binary(std::path::Path::new(path))
+                                .map_err(|e| AssemblyError::StructuralError {
+                                    line: line.line_number,
+                                    reason: format!("Failed to read binary file '{}': {}", path, e),
+                                })?;
+                        addr_counter.increment_by(binary_data.len() as u32);
+                        bytecode.extend(binary_data);
+                    }
+                    Directive::Header(info) => {
+                        let header = encode_header(info);
+                        addr_counter.increment_by(header.len() as u32);
+                        bytecode.extend(header);
+                    }
+                    Directive::Interrupt(words) => {
+                        let mut word_bytes: Vec<u8> = Vec::new();
+                        for word in words {
+                            let addr =
+                                resolve_label_or_immediate(word, symbol_table, &line.line_number)?;
+                            word_bytes.extend(addr.to_le_bytes());
+                        }
 
-                    header.push(info.version);
-
-                    header.push(info.rom_size);
-
-                    header.push(info.ram_size);
-
-                    let mut cart_info: u8 = info.hardware_rev & 0x3;
-                    cart_info = (cart_info << 3) | (info.region & 0x7);
-                    cart_info = cart_info << 3;
-                    header.push(cart_info);
+                        if word_bytes.len() < 32 {
+                            word_bytes.resize(32, 0x00);
+                        }
+                        addr_counter.increment_by(word_bytes.len() as u32);
+                        bytecode.extend(word_bytes);
+                    }
+                    Directive::SectionStart(section_options) => {
+                        let mut new_context: Context = Context {
+                            name: section_options.name.clone(),
+                            size: section_options.size,
+                            vaddr: section_options.vaddr,
+                            paddr: section_options.paddr,
+                            align: section_options.align,
+                            nobits: section_options.nobits,
+                            bank0: section_options.bank0,
+                            float: section_options.floating(),
+                            address: addr_counter,
+                            start: addr_counter,
+                        };
+                        let layout = &sections[section_index];
+                        section_index += 1;
+
+                        if new_context.nobits {
+                            new_context.start =
+                                start_nobits_section(section_options, ram_end, &line.line_number)?;
+                            addr_counter = new_context.start;
+                            context_stack.push(new_context);
+                            return Ok(());
+                        }
 
-                    let mut features: u8 = info.interrupt_mode & 0x1;
-                    features = (features << 2) | (info.mapper & 0x3);
-                    features = features << 5;
-                    header.push(features);
+                        // floating sections are written over the free space they were placed in,
+                        // the rest of the ROM is put back after them
+                        if new_context.float {
+                            let start = layout.physical_addr as usize;
+                            if start > bytecode.len() {
+                                bytecode.resize(start, padding);
+                            } else {
+                                displaced = Some((start, bytecode.split_off(start)));
+                            }
 
-                    header.resize(0x60, 0x00);
-                    // checksums will be caclculated and added later
+                            addr_counter = AddrCounter {
+                                physical_addr: layout.physical_addr,
+                                logical_addr: layout.logical_addr,
+                                num_bytes: 0,
+                                bank: layout.bank,
+                            };
+                            new_context.start = addr_counter;
+                            context_stack.push(new_context);
+                            return Ok(());
+                        }
 
-                    addr_counter.increment_by(header.len() as u32);
-                    bytecode.extend(header);
-                }
-                Directive::Interrupt(words) => {
-                    let mut word_bytes: Vec<u8> = words
-                        .iter()
-                        .flat_map(|word| -> Vec<u8> {
-                            let addr =
-                                resolve_label_or_immediate(word, symbol_table, &line.line_number)
-                                    .unwrap();
-                            addr.to_le_bytes().to_vec()
-                        })
-                        .collect();
+                        // reset section size counter
+                        addr_counter.num_bytes = 0;
 
-                    if word_bytes.len() < 32 {
-                        word_bytes.resize(32, 0x00);
-                    }
-                    addr_counter.increment_by(word_bytes.len() as u32);
-                    bytecode.extend(word_bytes);
-                }
-                Directive::SectionStart(section_options) => {
-                    let new_context: Context = Context {
-                        name: section_options.name.clone(),
-                        size: section_options.size,
-                        vaddr: section_options.vaddr,
-                        paddr: section_options.paddr,
-                        align: section_options.align,
-                        address: addr_counter.clone(),
-                    };
-
-                    // reset section size counter
-                    addr_counter.num_bytes = 0;
-
-                    // set logical address
-                    if let Some(vaddr) = new_context.vaddr {
-                        addr_counter.logical_addr = vaddr;
-                    }
+                        // set logical address
+                        if let Some(vaddr) = new_context.vaddr {
+                            addr_counter.logical_addr = vaddr;
+                        }
 
-                    // set physical address
-                    if let Some(paddr) = new_context.paddr {
-                        if paddr > addr_counter.physical_addr {
-                            let padding_size = (paddr - addr_counter.physical_addr) as usize;
+                        // set physical address
+                        if let Some(paddr) = new_context.paddr {
+                            if paddr > addr_counter.physical_addr {
+                                let padding_size = (paddr - addr_counter.physical_addr) as usize;
 
-                            bytecode.resize(bytecode.len() + padding_size, 0x00);
+                                bytecode.resize(bytecode.len() + padding_size, padding);
+                            }
+                            addr_counter.physical_addr = paddr;
                         }
-                        addr_counter.physical_addr = paddr;
-                    }
 
-                    // set alignment
-                    if let Some(alignment) = new_context.align {
-                        let extra_bytes = addr_counter.physical_addr % alignment;
-                        let pad_size = alignment - extra_bytes;
+                        // set alignment
+                        if let Some(alignment) = new_context.align {
+                            let extra_bytes = addr_counter.physical_addr % alignment;
+                            let pad_size = alignment - extra_bytes;
 
-                        if extra_bytes != 0 {
-                            bytecode.resize(bytecode.len() + pad_size as usize, 0x00);
-                            addr_counter.increment_by(pad_size);
+                            if extra_bytes != 0 {
+                                bytecode.resize(bytecode.len() + pad_size as usize, padding);
+                                addr_counter.increment_by(pad_size);
+                            }
                         }
-                    }
 
-                    context_stack.push(new_context);
-                }
-                Directive::SectionEnd => {
-                    if context_stack.is_empty() {
-                        return Err(AssemblyError::StructuralError {
-                            line: line.line_number,
-                            reason: ".section_end found without a preceding .section statement."
-                                .to_string(),
-                        });
+                        new_context.start = addr_counter;
+                        context_stack.push(new_context);
                     }
+                    Directive::SectionEnd => {
+                        if context_stack.is_empty() {
+                            return Err(AssemblyError::StructuralError {
+                                line: line.line_number,
+                                reason:
+                                    ".section_end found without a preceding .section statement."
+                                        .to_string(),
+                            });
+                        }
 
-                    let old_context: Context = context_stack.pop().unwrap();
+                        let old_context: Context = context_stack.pop().unwrap();
 
-                    if old_context.vaddr.is_some() {
-                        addr_counter.logical_addr =
-                            calculate_logical_addr(&addr_counter.physical_addr);
-                    }
+                        if old_context.nobits {
+                            let ram_counter =
+                                std::mem::replace(&mut addr_counter, old_context.address);
+                            ram_end = Some(end_nobits_section(
+                                &old_context,
+                                ram_counter,
+                                &line.line_number,
+                            )?);
+                            return Ok(());
+                        }
 
-                    if let Some(size) = old_context.size {
-                        if size > addr_counter.num_bytes {
+                        if old_context.vaddr.is_some() {
+                            addr_counter.logical_addr =
+                                calculate_logical_addr(&addr_counter.physical_addr);
+                        }
+
+                        if let Some(size) = old_context.size
+                            && size > addr_counter.num_bytes
+                        {
                             let padding_size = size - addr_counter.num_bytes;
-                            bytecode.resize(bytecode.len() + padding_size as usize, 0x00);
+                            bytecode.resize(bytecode.len() + padding_size as usize, padding);
 
                             addr_counter.increment_by(padding_size);
                         }
+
+                        if old_context.float {
+                            addr_counter = old_context.address;
+                        }
                     }
-                }
-                Directive::Align(alignment) => {
-                    let extra_bytes = addr_counter.physical_addr % alignment;
-                    let pad_size = alignment - extra_bytes;
+                    Directive::Align(alignment) => {
+                        let extra_bytes = addr_counter.physical_addr % alignment;
+                        let pad_size = alignment - extra_bytes;
 
-                    if extra_bytes != 0 {
-                        bytecode.resize(bytecode.len() + pad_size as usize, 0x00);
-                        addr_counter.increment_by(pad_size);
+                        if extra_bytes != 0 {
+                            bytecode.resize(bytecode.len() + pad_size as usize, padding);
+                            addr_counter.increment_by(pad_size);
+                        }
                     }
+                    _ => {}
                 }
-                _ => {}
+            }
+
+            if let Some(instruction) = &line.instruction {
+                check_memory_access(instruction, line, symbol_table, warnings);
+
+                let instruction_bytes = encoder::encode_instruction(
+                    instruction,
+                    symbol_table,
+                    &addr_counter.logical_addr,
+                    &addr_counter.bank,
+                    &line.line_number,
+                )?;
+                // labels after this line were placed in Pass 1 using the calculated size
+                debug_assert_eq!(
+                    instruction_bytes.len() as u32,
+                    encoder::calculate_instruction_size(instruction),
+                    "size of {:?} differs between Pass 1 and Pass 2",
+                    instruction
+                );
+                addr_counter.increment_by(instruction_bytes.len() as u32);
+                bytecode.extend(instruction_bytes);
+            }
+            Ok(())
+        };
+
+        if let Err(e) = process_line() {
+            errors.push(e.at_line(line))?;
+
+            // keep the following lines at the addresses given to them in pass 1
+            if let Some(instruction) = &line.instruction
+                && bytecode.len() == rom_offset
+            {
+                let instruction_size = encoder::calculate_instruction_size(instruction);
+                bytecode.resize(rom_offset + instruction_size as usize, 0x00);
+                addr_counter.increment_by(instruction_size);
             }
         }
 
-        if let Some(instruction) = &line.instruction {
-            let instruction_bytes = encoder::encode_instruction(
-                instruction,
-                symbol_table,
-                &addr_counter.logical_addr,
-                &addr_counter.bank,
-                &line.line_number,
-            )?;
-            addr_counter.increment_by(instruction_bytes.len() as u32);
-            bytecode.extend(instruction_bytes);
+        // a floating section can start before the end of the ROM written so far
+        let rom_offset = rom_offset.min(bytecode.len());
+        listing.lines.push(ListingLine {
+            file: line.file.clone(),
+            line_number: line.line_number,
+            macro_depth: line.expansion.len(),
+            source: line.source.clone(),
+            physical_addr: line_start.physical_addr,
+            logical_addr: line_start.logical_addr,
+            bank: line_start.bank,
+            nobits,
+            rom_offset,
+            bytes: bytecode[rom_offset..].to_vec(),
+        });
+
+        if context_stack.is_empty()
+            && let Some((start, rest)) = displaced.take()
+        {
+            let written = bytecode.len() - start;
+            bytecode.extend_from_slice(&rest[written.min(rest.len())..]);
         }
     }
 
     // pad the resulting bytecode to the next bank size
     let mut num_banks = bytecode.len() as u32 / BANK_SIZE;
 
-    num_banks = if bytecode.len() as u32 % BANK_SIZE > 0 {
+    num_banks = if !(bytecode.len() as u32).is_multiple_of(BANK_SIZE) {
         num_banks + 1
     } else {
         num_banks
     };
 
-    num_banks = std::cmp::max(num_banks, 2);
+    num_banks = std::cmp::max(num_banks, min_banks);
 
-    bytecode.resize((num_banks * BANK_SIZE) as usize, 0xFF);
+    errors.finish()?;
+
+    bytecode.resize((num_banks * BANK_SIZE) as usize, pad_byte.unwrap_or(0xFF));
 
     // final bytecode
-    Ok(bytecode)
+    Ok((bytecode, listing))
+}
+
+// the bytes of the cartridge header, the checksums are patched in by patch_rom_checksums once the
+// full rom is generated
+pub(crate) fn encode_header(info: &HeaderInfo) -> Vec<u8> {
+    let mut header: Vec<u8> = Vec::new();
+
+    header.extend(info.boot_anim.as_bytes());
+
+    header.extend(info.title.as_bytes());
+    if header.len() < 0x14 {
+        header.resize(0x14, 0x00);
+    }
+
+    header.extend(info.developer.as_bytes());
+    if header.len() < 0x24 {
+        header.resize(0x24, 0x00);
+    }
+
+    header.push(info.version);
+
+    header.push(info.rom_size);
+
+    header.push(info.ram_size);
+
+    let mut cart_info: u8 = info.hardware_rev & 0x3;
+    cart_info = (cart_info << 3) | (info.region & 0x7);
+    cart_info <<= 3;
+    header.push(cart_info);
+
+    let mut features: u8 = info.interrupt_mode & 0x1;
+    features = (features << 2) | (info.mapper & 0x3);
+    features <<= 5;
+    header.push(features);
+
+    header.resize(0x60, 0x00);
+    header
+}
+
+// warn about absolute loads and stores that would fault when run
+fn check_memory_access(
+    instruction: &Instruction,
+    line: &AssemblyLine,
+    symbol_table: &SymbolTable,
+    warnings: &mut WarningCollector,
+) {
+    // (address operand, operand index, word access, write)
+    let (op, index, word, write) = match instruction {
+        Instruction::LdAbs(_, op) => (op, 1, true, false),
+        Instruction::StAbs(op, _) => (op, 0, true, true),
+        Instruction::StBAbs(op, _) => (op, 0, false, true),
+        Instruction::SetAbs(op, _) | Instruction::ResAbs(op, _) => (op, 0, false, true),
+        _ => return,
+    };
+
+    // unresolvable addresses are reported by the encoder
+    let Ok(addr) = resolve_operand_value(op, symbol_table, &line.line_number) else {
+        return;
+    };
+
+    if word && addr % 2 != 0 {
+        let warning = Warning::new(
+            WarningKind::OddAddress,
+            line,
+            format!(
+                "Word access to odd address 0x{:04x} will cause a Bus Error fault",
+                addr
+            ),
+        );
+        warnings.push(warning.at_operand(index));
+    }
+
+    if write && (0..ROM_END).contains(&addr) {
+        let warning = Warning::new(
+            WarningKind::RomWrite,
+            line,
+            format!(
+                "Write to ROM address 0x{:04x} will cause a Protected Memory fault",
+                addr
+            ),
+        );
+        warnings.push(warning.at_operand(index));
+    }
+}
+
+/// Warn about every label that is defined but never referenced.
+pub fn check_unused_labels(
+    lines: &[AssemblyLine],
+    referenced: &HashSet<String>,
+    warnings: &mut WarningCollector,
+) {
+    for line in lines {
+        if let Some(label) = &line.label
+            && !referenced.contains(label)
+        {
+            warnings.push(Warning::new(
+                WarningKind::UnusedLabel,
+                line,
+                // anonymous labels are shown without the number they are stored under
+                format!(
+                    "Label {} is never referenced",
+                    label.split('#').next().unwrap_or(label)
+                ),
+            ));
+        }
+    }
+}
+
+/// Pass 3: Calculate the cartridge header checksums over the final padded rom.
+pub fn patch_rom_checksums(rom: &mut [u8], header_addr: u16) -> Result<(), AssemblyError> {
+    checksum::patch_checksums(rom, header_addr as usize)
+}
+
+/// Verify the cartridge header checksums of an already assembled rom.
+pub fn verify_rom_checksums(rom: &[u8], header_addr: u16) -> Result<(), AssemblyError> {
+    checksum::verify_checksums(rom, header_addr as usize)
+}
+
+/// Collect the named labels, constants and sections of an assembled program for the symbol file.
+pub fn build_symbol_file(
+    symbol_table: &SymbolTable,
+    sections: &[SectionLayout],
+    constants: &HashMap<String, i32>,
+) -> SymbolFile {
+    let mut file = SymbolFile::default();
+
+    // anonymous labels are stored under names like `-#1`, which are not valid identifiers
+    for (name, symbol) in symbol_table.iter().filter(|(name, _)| !name.contains('#')) {
+        file.labels.push(LabelEntry {
+            name: name.clone(),
+            bank: symbol.bank,
+            address: symbol.logical_address,
+        });
+    }
+
+    for (name, value) in constants {
+        file.constants.push(ConstantEntry {
+            name: name.clone(),
+            value: *value,
+        });
+    }
+
+    for section in sections {
+        file.sections.push(SectionEntry {
+            name: section.name.clone(),
+            bank: section.bank,
+            start: section.logical_addr,
+            end: section.logical_addr + section.size.saturating_sub(1),
+            size: section.size,
+        });
+    }
+
+    file.sort();
+    file
+}
+
+/// The value of every constant once the labels are known, including constants defined from
+/// labels.
+pub fn resolve_constants(
+    constant_table: &ConstantTable,
+    symbol_table: &SymbolTable,
+) -> HashMap<String, i32> {
+    let mut constants: HashMap<String, i32> = constant_table
+        .iter()
+        .map(|(name, value)| (name.clone(), *value))
+        .collect();
+
+    // deferred constants that reference undefined labels are never used, so they are left out
+    for (name, expr) in constant_table.iter_deferred() {
+        if let Some(value) = evaluate_deferred_constant(expr, constant_table, symbol_table) {
+            constants.insert(name.clone(), value);
+        }
+    }
+
+    constants
+}
+
+// evaluate a deferred constant, following any other deferred constants it references
+fn evaluate_deferred_constant(
+    expr: &Expr,
+    constant_table: &ConstantTable,
+    symbol_table: &SymbolTable,
+) -> Option<i32> {
+    let value_of = |name: &str| {
+        if let Some(value) = constant_table.get(name) {
+            return Some(*value);
+        }
+        if let Some(symbol) = symbol_table.get(name) {
+            return Some(symbol.logical_address as i32);
+        }
+        constant_table
+            .get_deferred(name)
+            .and_then(|expr| evaluate_deferred_constant(expr, constant_table, symbol_table))
+    };
+    let bank_of = |name: &str| symbol_table.get(name).map(|s| s.bank as i32);
+
+    expression::evaluate(expr, &value_of, &bank_of, &0).ok()
+}
+
+// the address counter of a nobits section, which counts RAM addresses instead of rom addresses.
+// A nobits section without a vaddr continues after the previous nobits section
+fn start_nobits_section(
+    options: &SectionOptions,
+    ram_end: Option<u32>,
+    line_num: &usize,
+) -> Result<AddrCounter, AssemblyError> {
+    let start = options
+        .vaddr
+        .or(ram_end)
+        .ok_or_else(|| AssemblyError::StructuralError {
+            line: *line_num,
+            reason: "The first nobits section must be given a RAM address with vaddr.".to_string(),
+        })?;
+
+    let mut ram_counter = AddrCounter {
+        physical_addr: start,
+        logical_addr: start,
+        num_bytes: 0,
+        bank: 0,
+    };
+
+    if let Some(alignment) = options.align {
+        let extra_bytes = start % alignment;
+        if extra_bytes != 0 {
+            ram_counter.increment_by(alignment - extra_bytes);
+        }
+    }
+
+    if ram_region(ram_counter.logical_addr).is_none() {
+        let regions: Vec<String> = RAM_REGIONS
+            .iter()
+            .map(|r| format!("{} 0x{:04X}-0x{:04X}", r.name, r.start, r.end))
+            .collect();
+        return Err(AssemblyError::StructuralError {
+            line: *line_num,
+            reason: format!(
+                "nobits section address 0x{:04x} is not in a RAM region ({}).",
+                ram_counter.logical_addr,
+                regions.join(", ")
+            ),
+        });
+    }
+
+    Ok(ram_counter)
+}
+
+// check that a nobits section fits in its size and in the RAM region it starts in, returns the
+// address after the section
+fn end_nobits_section(
+    context: &Context,
+    mut ram_counter: AddrCounter,
+    line_num: &usize,
+) -> Result<u32, AssemblyError> {
+    let name = context.name.as_deref().unwrap_or("UNNAMED");
+
+    if let Some(size) = context.size {
+        if ram_counter.num_bytes > size {
+            return Err(AssemblyError::StructuralError {
+                line: *line_num,
+                reason: format!(
+                    "Section \"{}\" larger than the allotted section size of {} bytes, ({} bytes)",
+                    name, size, ram_counter.num_bytes
+                ),
+            });
+        }
+        ram_counter.increment_by(size - ram_counter.num_bytes);
+    }
+
+    // the start address was checked when the section started
+    if let Some(region) = ram_region(context.start.logical_addr)
+        && ram_counter.logical_addr > region.end + 1
+    {
+        return Err(AssemblyError::StructuralError {
+            line: *line_num,
+            reason: format!(
+                "Section \"{}\" overflows {} (0x{:04X}-0x{:04X}) by {} bytes.",
+                name,
+                region.name,
+                region.start,
+                region.end,
+                ram_counter.logical_addr - (region.end + 1)
+            ),
+        });
+    }
+
+    Ok(ram_counter.logical_addr)
+}
+
+// a line inside a nobits section, which may only reserve RAM
+fn reserve_nobits_space(
+    line: &AssemblyLine,
+    ram_counter: &mut AddrCounter,
+    symbol_table: &SymbolTable,
+) -> Result<(), AssemblyError> {
+    let emitted = if line.instruction.is_some() {
+        Some("Instructions")
+    } else {
+        match &line.directive {
+            Some(Directive::Org(_)) => Some(".org"),
+            Some(Directive::Bank(_)) => Some(".bank"),
+            Some(Directive::Byte(_)) => Some(".byte"),
+            Some(Directive::Word(_)) => Some(".word"),
+            Some(Directive::Ascii(_)) => Some(".ascii"),
+            Some(Directive::Asciz(_)) => Some(".asciz"),
+            Some(Directive::Fill(_, Some(_))) => Some(".fill with a value"),
+            Some(Directive::Incbin(_)) => Some(".incbin"),
+            Some(Directive::Header(_)) => Some("The cartridge header"),
+            Some(Directive::Interrupt(_)) => Some("The interrupt table"),
+            _ => None,
+        }
+    };
+
+    if let Some(emitted) = emitted {
+        return Err(AssemblyError::StructuralError {
+            line: line.line_number,
+            reason: format!(
+                "{} cannot be used in a nobits section, RAM can only be reserved with .ds, .res, .fill without a value and .align.",
+                emitted
+            ),
+        });
+    }
+
+    match &line.directive {
+        Some(Directive::Fill(count, None)) => {
+            let count = resolve_location_operand(count, symbol_table, &line.line_number)?;
+            ram_counter.increment_by(count as u32);
+        }
+        Some(Directive::Align(alignment)) => {
+            let extra_bytes = ram_counter.logical_addr % alignment;
+            if extra_bytes != 0 {
+                ram_counter.increment_by(alignment - extra_bytes);
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+// resolve a .org address, labels used here must already be defined
+fn resolve_location_operand(
+    op: &Operand,
+    symbol_table: &SymbolTable,
+    line_num: &usize,
+) -> Result<u16, AssemblyError> {
+    let value = resolve_operand_value(op, symbol_table, line_num)?;
+    check_value_range(value, 0, u16::MAX as i32, "an unsigned 16 bit", line_num)?;
+    Ok(value as u16)
+}
+
+// resolve a .fill or .padbyte value
+fn resolve_byte_operand(
+    op: &Operand,
+    symbol_table: &SymbolTable,
+    line_num: &usize,
+) -> Result<u8, AssemblyError> {
+    let value = resolve_operand_value(op, symbol_table, line_num)?;
+    check_value_range(value, 0, u8::MAX as i32, "an unsigned 8 bit", line_num)?;
+    Ok(value as u8)
+}
+
+// resolve a .bank number, labels used here must already be defined
+fn resolve_bank_operand(
+    op: &Operand,
+    symbol_table: &SymbolTable,
+    line_num: &usize,
+) -> Result<u32, AssemblyError> {
+    let value = resolve_operand_value(op, symbol_table, line_num)?;
+    if !(0..=256).contains(&value) {
+        return Err(AssemblyError::SemanticError {
+            line: *line_num,
+            reason: ".bank number must be an unsigned value between 0 and 256".to_string(),
+        });
+    }
+    Ok(value as u32)
 }
 
 fn calculate_physical_addr(logical_addr: &u16, bank: &u32) -> u32 {
     if *bank <= 1 {
         *logical_addr as u32
     } else {
-        (*bank as u32 * BANK_SIZE) + (*logical_addr as u32 - 0x4000)
+        (*bank * BANK_SIZE) + (*logical_addr as u32 - 0x4000)
     }
 }
 
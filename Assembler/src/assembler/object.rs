@@ -0,0 +1,894 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use super::encoder::utility_functions::check_value_range;
+use super::relaxation::{self, BranchSite};
+use super::section_stack::AddrCounter;
+use super::symbol_table::{Symbol, SymbolTable};
+use super::{contains_bank_expr, encode_header, encoder, reserve_nobits_space};
+use crate::ast::{AssemblyLine, BranchForm, Directive, Expr, Instruction, Operand};
+use crate::errors::{AssemblyError, ErrorCollector};
+use crate::expression;
+use crate::file_reader::FileReader;
+use crate::object_file::*;
+use std::collections::{HashMap, HashSet};
+
+// bank given to labels outside the section being encoded, so that no relative jump can reach them
+const OTHER_SECTION: u32 = u32::MAX;
+
+// offsets of the bank and address words in the code of CALL.far and JMP.far, and of the
+// trampoline address of the via forms
+const FAR_BANK_OFFSET: u32 = 1;
+const FAR_ADDR_OFFSET: u32 = 4;
+const FAR_VIA_OFFSET: u32 = 7;
+
+// what the address of a label moves with when the object is linked
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Base<'a> {
+    Section(usize),
+    Extern(&'a str),
+}
+
+// the value of an operand in an object file
+#[derive(Debug, PartialEq)]
+enum Value {
+    Constant(i32),
+    Address(String, i32), // the address of a label plus an addend, written by the linker
+    Bank(String),         // bank(label)
+}
+
+// where the labels of an object file are, relative to the start of their section
+struct Layout {
+    sections: Vec<ObjectSection>,          // without their data
+    labels: HashMap<String, (usize, u32)>, // section index and offset
+    globals: HashSet<String>,
+    externs: Vec<String>,
+    branches: Vec<BranchSite>, // short .auto branches, the section index is used as their bank
+}
+
+impl Layout {
+    fn base_of(&self, name: &str, line_num: &usize) -> Result<Base<'_>, AssemblyError> {
+        if let Some((section, _)) = self.labels.get(name) {
+            return Ok(Base::Section(*section));
+        }
+
+        match self.externs.iter().find(|e| *e == name) {
+            Some(name) => Ok(Base::Extern(name)),
+            None => Err(AssemblyError::SemanticError {
+                line: *line_num,
+                reason: format!("Undefined label: {}", name),
+            }),
+        }
+    }
+
+    fn offset_of(&self, name: &str) -> u32 {
+        self.labels.get(name).map_or(0, |(_, offset)| *offset)
+    }
+
+    // labels at their offset with their section as the bank, so that the encoder only accepts
+    // relative jumps within a section
+    fn symbol_table(&self) -> SymbolTable {
+        let mut symbol_table: SymbolTable = self
+            .labels
+            .iter()
+            .map(|(name, (section, offset))| {
+                let symbol = Symbol {
+                    logical_address: *offset,
+                    bank: *section as u32,
+                };
+                (name.clone(), symbol)
+            })
+            .collect();
+
+        for name in &self.externs {
+            let symbol = Symbol {
+                logical_address: 0,
+                bank: OTHER_SECTION,
+            };
+            symbol_table.insert(name.clone(), symbol);
+        }
+
+        symbol_table
+    }
+}
+
+/// Assemble the lines of a program into a relocatable object file. Every label, instruction and
+/// data directive must be inside a named `.section`, sections are placed by the linker script so
+/// `.org`, `.bank` and the `vaddr`/`paddr` section options cannot be used. Uses of labels whose
+/// address is only known once the object is linked are recorded as relocations. Padding is
+/// filled with `pad_byte`, 0x00 by default, until a .padbyte directive changes it.
+pub fn build_object<F: FileReader>(
+    lines: &mut [AssemblyLine],
+    reader: &F,
+    pad_byte: Option<u8>,
+    max_errors: usize,
+) -> Result<ObjectFile, AssemblyError> {
+    // sections can be placed anywhere, so .auto branches only stay short within a section
+    let layout = loop {
+        let layout = lay_out(lines, reader, max_errors)?;
+        if !relaxation::relax_branches(lines, &layout.branches, &layout.symbol_table()) {
+            break layout;
+        }
+    };
+
+    let sections = encode_sections(lines, &layout, reader, pad_byte, max_errors)?;
+
+    let mut symbols: Vec<ObjectSymbol> = layout
+        .labels
+        .iter()
+        .map(|(name, (section, offset))| ObjectSymbol {
+            name: name.clone(),
+            section: *section,
+            offset: *offset,
+            global: layout.globals.contains(name),
+        })
+        .collect();
+    symbols.sort_by(|a, b| (a.section, a.offset, &a.name).cmp(&(b.section, b.offset, &b.name)));
+
+    Ok(ObjectFile {
+        version: OBJECT_FORMAT_VERSION,
+        sections,
+        symbols,
+        externs: layout.externs,
+    })
+}
+
+// pass 1 of an object file, find the size of every section and the offset of every label
+fn lay_out<F: FileReader>(
+    lines: &[AssemblyLine],
+    reader: &F,
+    max_errors: usize,
+) -> Result<Layout, AssemblyError> {
+    let mut errors = ErrorCollector::new(max_errors);
+    let mut layout = Layout {
+        sections: Vec::new(),
+        labels: HashMap::new(),
+        globals: HashSet::new(),
+        externs: Vec::new(),
+        branches: Vec::new(),
+    };
+    let mut open: Option<(usize, Option<u32>)> = None; // the open section and its size option
+    let mut offset: u32 = 0;
+    let mut declared: Vec<(&String, usize, bool)> = Vec::new(); // name, line index, global
+
+    for (line_index, line) in lines.iter().enumerate() {
+        let mut process_line = || -> Result<(), AssemblyError> {
+            if let Some(label) = &line.label {
+                let Some((section, _)) = open else {
+                    return Err(outside_section(line, &format!("Label \"{}\"", label)));
+                };
+
+                if layout
+                    .labels
+                    .insert(label.clone(), (section, offset))
+                    .is_some()
+                {
+                    return Err(AssemblyError::SemanticError {
+                        line: line.line_number,
+                        reason: format!("Duplicate label definition: {}", label),
+                    });
+                }
+            }
+
+            match &line.directive {
+                Some(Directive::SectionStart(options)) => {
+                    if let Some((section, _)) = open {
+                        return Err(AssemblyError::StructuralError {
+                            line: line.line_number,
+                            reason: format!(
+                                "Cannot nest a section within a section, already within the \"{}\" section.",
+                                layout.sections[section].name
+                            ),
+                        });
+                    }
+
+                    let Some(name) = &options.name else {
+                        return Err(AssemblyError::StructuralError {
+                            line: line.line_number,
+                            reason: "Sections in an object file must be named, the linker script places them by name.".to_string(),
+                        });
+                    };
+
+                    if options.vaddr.is_some() || options.paddr.is_some() {
+                        return Err(AssemblyError::StructuralError {
+                            line: line.line_number,
+                            reason: format!(
+                                "Section \"{}\" cannot set vaddr or paddr in an object file, sections are placed by the linker script.",
+                                name
+                            ),
+                        });
+                    }
+
+                    layout.sections.push(ObjectSection {
+                        name: name.clone(),
+                        align: options.align.unwrap_or(1),
+                        nobits: options.nobits,
+                        bank0: options.bank0,
+                        size: 0,
+                        data: Vec::new(),
+                        relocations: Vec::new(),
+                    });
+                    open = Some((layout.sections.len() - 1, options.size));
+                    offset = 0;
+                    return Ok(());
+                }
+                Some(Directive::SectionEnd) => {
+                    let Some((section, size)) = open.take() else {
+                        return Err(AssemblyError::StructuralError {
+                            line: line.line_number,
+                            reason: ".section_end found without a preceding .section statement."
+                                .to_string(),
+                        });
+                    };
+
+                    if let Some(size) = size {
+                        if offset > size {
+                            return Err(AssemblyError::StructuralError {
+                                line: line.line_number,
+                                reason: format!(
+                                    "Section \"{}\" larger than the allotted section size of {} bytes, ({} bytes)",
+                                    layout.sections[section].name, size, offset
+                                ),
+                            });
+                        }
+                        offset = size;
+                    }
+
+                    layout.sections[section].size = offset;
+                    return Ok(());
+                }
+                Some(Directive::Org(_)) => return Err(placed_by_linker(line, ".org")),
+                Some(Directive::Bank(_)) => return Err(placed_by_linker(line, ".bank")),
+                Some(Directive::Global(names)) => {
+                    declared.extend(names.iter().map(|name| (name, line_index, true)));
+                    layout.globals.extend(names.iter().cloned());
+                }
+                Some(Directive::Extern(names)) => {
+                    declared.extend(names.iter().map(|name| (name, line_index, false)));
+                    for name in names {
+                        if !layout.externs.contains(name) {
+                            layout.externs.push(name.clone());
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            let Some((section, _)) = open else {
+                if line.instruction.is_some() {
+                    return Err(outside_section(line, "Instructions"));
+                }
+                return match data_size(line, 0, reader)? {
+                    0 => Ok(()),
+                    _ => Err(outside_section(line, "Data")),
+                };
+            };
+
+            if let Some(instruction) = &line.instruction
+                && relaxation::is_short_branch(instruction)
+            {
+                layout.branches.push(BranchSite {
+                    line_index,
+                    logical_address: offset,
+                    bank: section as u32,
+                });
+            }
+
+            // nobits sections only reserve RAM, with offsets from the start of the section
+            if layout.sections[section].nobits {
+                let mut ram_counter = AddrCounter::new();
+                ram_counter.logical_addr = offset;
+                reserve_nobits_space(line, &mut ram_counter, &SymbolTable::new())?;
+                offset = ram_counter.logical_addr;
+            } else {
+                offset += data_size(line, offset, reader)?;
+            }
+
+            // .align only holds once the linker places the section at a multiple of it
+            if let Some(Directive::Align(alignment)) = &line.directive {
+                let section = &mut layout.sections[section];
+                section.align = lcm(section.align, *alignment);
+            }
+
+            Ok(())
+        };
+
+        if let Err(e) = process_line() {
+            errors.push(e.at_line(line))?;
+        }
+    }
+
+    if let Some((section, _)) = open {
+        errors.push(AssemblyError::StructuralErrorNoLine {
+            reason: format!(
+                "section \"{}\" has no matching .section_end statement.",
+                layout.sections[section].name
+            ),
+        })?;
+    }
+
+    for (name, line_index, global) in declared {
+        let defined = layout.labels.contains_key(name);
+        let reason = match (global, defined) {
+            (true, false) => format!("Label \"{}\" is declared .global but never defined.", name),
+            (false, true) => format!(
+                "Label \"{}\" is declared .extern but defined in this file.",
+                name
+            ),
+            _ => continue,
+        };
+
+        let line = &lines[line_index];
+        let error = AssemblyError::SemanticError {
+            line: line.line_number,
+            reason,
+        };
+        errors.push(error.at_line(line))?;
+    }
+
+    errors.finish()?;
+    Ok(layout)
+}
+
+// pass 2 of an object file, encode the data of every section and record its relocations
+fn encode_sections<F: FileReader>(
+    lines: &[AssemblyLine],
+    layout: &Layout,
+    reader: &F,
+    pad_byte: Option<u8>,
+    max_errors: usize,
+) -> Result<Vec<ObjectSection>, AssemblyError> {
+    let mut errors = ErrorCollector::new(max_errors);
+    let symbol_table = layout.symbol_table();
+    let mut sections = layout.sections.clone();
+    let mut open: Option<usize> = None;
+    let mut next_section = 0;
+    let mut pad_byte = pad_byte.unwrap_or(0x00);
+
+    for line in lines {
+        let line_start = open.map(|section| sections[section].data.len());
+
+        let mut process_line = || -> Result<(), AssemblyError> {
+            match &line.directive {
+                Some(Directive::SectionStart(_)) => {
+                    open = Some(next_section);
+                    next_section += 1;
+                    return Ok(());
+                }
+                Some(Directive::SectionEnd) => {
+                    if let Some(section) = open.take().map(|s| &mut sections[s])
+                        && !section.nobits
+                    {
+                        section.data.resize(section.size as usize, pad_byte);
+                    }
+                    return Ok(());
+                }
+                Some(Directive::PadByte(value)) => {
+                    let value = constant_value(value, layout, &line.line_number)?;
+                    check_value_range(
+                        value,
+                        0,
+                        u8::MAX as i32,
+                        "an unsigned 8 bit",
+                        &line.line_number,
+                    )?;
+                    pad_byte = value as u8;
+                }
+                _ => {}
+            }
+
+            // lines outside of sections were rejected by pass 1, and nobits sections have no data
+            let Some(section) = open.map(|s| &mut sections[s]).filter(|s| !s.nobits) else {
+                return Ok(());
+            };
+
+            if let Some(directive) = &line.directive {
+                let offset = section.data.len() as u32;
+                let (bytes, relocations) =
+                    encode_directive(directive, line, layout, offset, pad_byte, reader)?;
+                section.data.extend(bytes);
+                section.relocations.extend(relocations);
+            }
+
+            if let Some(instruction) = &line.instruction {
+                let offset = section.data.len() as u32;
+                let (bytes, relocations) = encode_instruction(
+                    instruction,
+                    layout,
+                    &symbol_table,
+                    open.unwrap_or_default(),
+                    offset,
+                    &line.line_number,
+                )?;
+                section.data.extend(bytes);
+                section.relocations.extend(relocations);
+            }
+
+            Ok(())
+        };
+
+        if let Err(e) = process_line() {
+            errors.push(e.at_line(line))?;
+
+            // keep the following lines at the offsets given to them in pass 1
+            if let (Some(instruction), Some(start), Some(section)) =
+                (&line.instruction, line_start, open)
+                && sections[section].data.len() == start
+            {
+                let size = encoder::calculate_instruction_size(instruction);
+                sections[section].data.resize(start + size as usize, 0x00);
+            }
+        }
+    }
+
+    errors.finish()?;
+    Ok(sections)
+}
+
+// the number of bytes a line adds to a section at the given offset
+pub(super) fn data_size<F: FileReader>(
+    line: &AssemblyLine,
+    offset: u32,
+    reader: &F,
+) -> Result<u32, AssemblyError> {
+    let mut size = line
+        .instruction
+        .as_ref()
+        .map_or(0, encoder::calculate_instruction_size);
+
+    size += match &line.directive {
+        Some(Directive::Byte(bytes)) => bytes.len() as u32,
+        Some(Directive::Word(words)) => words.len() as u32 * 2,
+        Some(Directive::Ascii(text)) => text.len() as u32,
+        Some(Directive::Asciz(text)) => text.len() as u32 + 1,
+        Some(Directive::Fill(count, _)) => {
+            let count =
+                super::resolve_location_operand(count, &SymbolTable::new(), &line.line_number)?;
+            count as u32
+        }
+        Some(Directive::Incbin(path)) => read_binary(path, line, reader)?.len() as u32,
+        Some(Directive::Header(info)) => encode_header(info).len() as u32,
+        Some(Directive::Interrupt(_)) => 32,
+        Some(Directive::Align(alignment)) => (alignment - offset % alignment) % alignment,
+        _ => 0,
+    };
+
+    Ok(size)
+}
+
+fn encode_directive<F: FileReader>(
+    directive: &Directive,
+    line: &AssemblyLine,
+    layout: &Layout,
+    offset: u32,
+    pad_byte: u8,
+    reader: &F,
+) -> Result<(Vec<u8>, Vec<Relocation>), AssemblyError> {
+    let line_num = &line.line_number;
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut relocations = Vec::new();
+
+    match directive {
+        Directive::Byte(values) => {
+            for value in values {
+                bytes.push(constant_value(value, layout, line_num)? as u8);
+            }
+        }
+        Directive::Word(words) | Directive::Interrupt(words) => {
+            for word in words {
+                let word_offset = offset + bytes.len() as u32;
+                let (kind, symbol, addend) = match operand_value(word, layout, line_num)? {
+                    Value::Constant(value) => {
+                        bytes.extend((value as u16).to_le_bytes());
+                        continue;
+                    }
+                    Value::Address(symbol, addend) => (RelocationKind::Word, symbol, addend),
+                    Value::Bank(symbol) => (RelocationKind::Bank, symbol, 0),
+                };
+                relocations.push(Relocation {
+                    offset: word_offset,
+                    kind,
+                    symbol,
+                    addend,
+                });
+                bytes.extend([0x00, 0x00]);
+            }
+
+            if matches!(directive, Directive::Interrupt(_)) {
+                bytes.resize(32, 0x00);
+            }
+        }
+        Directive::Fill(count, value) => {
+            let count = constant_value(count, layout, line_num)?;
+            let value = match value {
+                Some(value) => constant_value(value, layout, line_num)? as u8,
+                None => pad_byte,
+            };
+            bytes.resize(count as usize, value);
+        }
+        Directive::Ascii(text) => bytes.extend(text),
+        Directive::Asciz(text) => {
+            bytes.extend(text);
+            bytes.push(0x00);
+        }
+        Directive::Incbin(path) => bytes = read_binary(path, line, reader)?,
+        Directive::Header(info) => bytes = encode_header(info),
+        Directive::Align(alignment) => {
+            let pad_size = (alignment - offset % alignment) % alignment;
+            bytes.resize(pad_size as usize, pad_byte);
+        }
+        _ => {}
+    }
+
+    Ok((bytes, relocations))
+}
+
+// encode an instruction at an offset into a section, the addresses of labels are left as zero
+// and recorded as relocations
+fn encode_instruction(
+    instruction: &Instruction,
+    layout: &Layout,
+    symbol_table: &SymbolTable,
+    section: usize,
+    offset: u32,
+    line_num: &usize,
+) -> Result<(Vec<u8>, Vec<Relocation>), AssemblyError> {
+    if let Instruction::CallFar(target)
+    | Instruction::JmpFar(target)
+    | Instruction::CallFarVia(target, _)
+    | Instruction::JmpFarVia(target, _) = instruction
+    {
+        return encode_far(instruction, target, layout, offset, line_num);
+    }
+
+    let size = encoder::calculate_instruction_size(instruction);
+    let mut instruction = instruction.clone();
+    let mut relocations = Vec::new();
+
+    for (op, kind) in label_operands(&mut instruction) {
+        let (kind, symbol, addend) = match (operand_value(op, layout, line_num)?, kind) {
+            (Value::Constant(_), _) => continue,
+            // jumps within a section do not change when the section is placed
+            (Value::Address(symbol, _), Some(RelocationKind::Relative))
+                if layout.base_of(&symbol, line_num)? == Base::Section(section) =>
+            {
+                continue;
+            }
+            (Value::Address(symbol, addend), Some(kind)) => (kind, symbol, addend),
+            (Value::Bank(symbol), Some(RelocationKind::Word)) => (RelocationKind::Bank, symbol, 0),
+            (Value::Address(symbol, _) | Value::Bank(symbol), _) => {
+                return Err(address_not_known(&symbol, line_num));
+            }
+        };
+
+        // relative offsets follow the opcode, every address is in the last word of the instruction
+        let value_offset = match kind {
+            RelocationKind::Relative => 1,
+            _ => size - 2,
+        };
+        relocations.push(Relocation {
+            offset: offset + value_offset,
+            kind,
+            symbol,
+            addend,
+        });
+        *op = Operand::Immediate(0);
+    }
+
+    let bytes = encoder::encode_instruction(
+        &instruction,
+        symbol_table,
+        &offset,
+        &(section as u32),
+        line_num,
+    )?;
+    Ok((bytes, relocations))
+}
+
+// CALL.far and JMP.far load the bank and address of their target, both are relocated
+fn encode_far(
+    instruction: &Instruction,
+    target: &String,
+    layout: &Layout,
+    offset: u32,
+    line_num: &usize,
+) -> Result<(Vec<u8>, Vec<Relocation>), AssemblyError> {
+    let relocation = |value_offset: u32, kind: RelocationKind, symbol: &String| Relocation {
+        offset: offset + value_offset,
+        kind,
+        symbol: symbol.clone(),
+        addend: 0,
+    };
+
+    // placeholder addresses that pass the checks of the encoder, a switchable bank for the
+    // target and bank 0 for the trampoline
+    let mut symbol_table = SymbolTable::new();
+    let mut relocations = Vec::new();
+
+    if let Instruction::CallFarVia(_, via) | Instruction::JmpFarVia(_, via) = instruction {
+        layout.base_of(via, line_num)?;
+        let symbol = Symbol {
+            logical_address: 0,
+            bank: 0,
+        };
+        symbol_table.insert(via.clone(), symbol);
+        relocations.push(relocation(FAR_VIA_OFFSET, RelocationKind::Word, via));
+    }
+
+    layout.base_of(target, line_num)?;
+    let symbol = Symbol {
+        logical_address: 0,
+        bank: 1,
+    };
+    symbol_table.insert(target.clone(), symbol);
+    relocations.insert(0, relocation(FAR_BANK_OFFSET, RelocationKind::Bank, target));
+    relocations.insert(1, relocation(FAR_ADDR_OFFSET, RelocationKind::Word, target));
+
+    let bytes = encoder::encode_instruction(
+        instruction,
+        &symbol_table,
+        &offset,
+        &OTHER_SECTION,
+        line_num,
+    )?;
+    Ok((bytes, relocations))
+}
+
+// the operands of an instruction that can refer to labels, with the kind of relocation used when
+// the operand is an address. Operands without a relocation kind must be constant.
+fn label_operands(instruction: &mut Instruction) -> Vec<(&mut Operand, Option<RelocationKind>)> {
+    use RelocationKind::{Relative, Word};
+
+    match instruction {
+        Instruction::Ldi(_, op)
+        | Instruction::LdAbs(_, op)
+        | Instruction::StAbs(op, _)
+        | Instruction::LdBAbs(_, op)
+        | Instruction::StBAbs(op, _)
+        | Instruction::PushI(op)
+        | Instruction::AddAccI(op)
+        | Instruction::SubAccI(op)
+        | Instruction::AndAccI(op)
+        | Instruction::OrAccI(op)
+        | Instruction::XorAccI(op)
+        | Instruction::CmpAccI(op)
+        | Instruction::AdcAccI(op)
+        | Instruction::SbcAccI(op)
+        | Instruction::AddIReg(_, op)
+        | Instruction::SubIReg(_, op)
+        | Instruction::AndIReg(_, op)
+        | Instruction::OrIReg(_, op)
+        | Instruction::XorIReg(_, op)
+        | Instruction::CmpIReg(_, op)
+        | Instruction::JmpI(op)
+        | Instruction::JccI(_, op)
+        | Instruction::CallI(op)
+        | Instruction::CallccI(_, op)
+        | Instruction::JmpAuto(op, BranchForm::Long)
+        | Instruction::JccAuto(_, op, BranchForm::Long)
+        | Instruction::DjnzAuto(op, BranchForm::Long) => vec![(op, Some(Word))],
+        Instruction::JrI(op)
+        | Instruction::JrccI(_, op)
+        | Instruction::Djnz(op)
+        | Instruction::JmpAuto(op, BranchForm::Short)
+        | Instruction::JccAuto(_, op, BranchForm::Short)
+        | Instruction::DjnzAuto(op, BranchForm::Short) => vec![(op, Some(Relative))],
+        Instruction::BitAbs(abs, bit)
+        | Instruction::SetAbs(abs, bit)
+        | Instruction::ResAbs(abs, bit) => vec![(abs, Some(Word)), (bit, None)],
+        Instruction::LdIndexed(_, _, op)
+        | Instruction::StIndexed(_, op, _)
+        | Instruction::LdiB(_, op)
+        | Instruction::Lea(_, _, op)
+        | Instruction::AddSp(op)
+        | Instruction::BitReg(_, op)
+        | Instruction::SetReg(_, op)
+        | Instruction::ResReg(_, op)
+        | Instruction::BitIndirect(_, op)
+        | Instruction::SetIndirect(_, op)
+        | Instruction::ResIndirect(_, op)
+        | Instruction::Syscall(op) => vec![(op, None)],
+        _ => vec![],
+    }
+}
+
+fn operand_value(op: &Operand, layout: &Layout, line_num: &usize) -> Result<Value, AssemblyError> {
+    match op {
+        Operand::Immediate(value) => Ok(Value::Constant(*value)),
+        Operand::AbsAddr(addr) => Ok(Value::Constant(*addr as i32)),
+        Operand::Label(name) | Operand::AbsLabel(name) | Operand::IndexedLabel(_, name) => {
+            layout.base_of(name, line_num)?;
+            Ok(Value::Address(name.clone(), 0))
+        }
+        Operand::Expr(expr) | Operand::AbsExpr(expr) | Operand::IndexedExpr(_, expr) => {
+            expr_value(expr, layout, line_num)
+        }
+        _ => Ok(Value::Constant(0)),
+    }
+}
+
+fn constant_value(op: &Operand, layout: &Layout, line_num: &usize) -> Result<i32, AssemblyError> {
+    match operand_value(op, layout, line_num)? {
+        Value::Constant(value) => Ok(value),
+        Value::Address(symbol, _) | Value::Bank(symbol) => {
+            Err(address_not_known(&symbol, line_num))
+        }
+    }
+}
+
+// Find whether an expression is a constant or the address of a single label plus an addend, by
+// moving each section and extern it refers to and checking how the value changes. A constant
+// does not move with any of them, an address moves with exactly one.
+fn expr_value(expr: &Expr, layout: &Layout, line_num: &usize) -> Result<Value, AssemblyError> {
+    if let Expr::Bank(name) = expr {
+        layout.base_of(name, line_num)?;
+        return Ok(Value::Bank(name.clone()));
+    }
+
+    if contains_bank_expr(expr) {
+        return Err(AssemblyError::SemanticError {
+            line: *line_num,
+            reason: "bank() can only be used on its own in an object file, the bank of a label is only known once the object is linked.".to_string(),
+        });
+    }
+
+    let names = expression::referenced_symbols(expr);
+    let mut bases: Vec<Base> = Vec::new();
+    for name in &names {
+        let base = layout.base_of(name, line_num)?;
+        if !bases.contains(&base) {
+            bases.push(base);
+        }
+    }
+
+    let evaluate = |moved: Option<Base>, distance: i32| {
+        let value_of = |name: &str| {
+            let base = layout.base_of(name, line_num).ok()?;
+            let shift = if Some(base) == moved { distance } else { 0 };
+            Some(layout.offset_of(name) as i32 + shift)
+        };
+        expression::evaluate(expr, &value_of, &|_| None, line_num)
+    };
+
+    let value = evaluate(None, 0)?;
+    let mut relocated = None;
+    for base in bases {
+        let moved = (
+            evaluate(Some(base), 1)? - value,
+            evaluate(Some(base), 0x100)? - value,
+        );
+        match moved {
+            (0, 0) => {}
+            (1, 0x100) if relocated.is_none() => relocated = Some(base),
+            _ => {
+                return Err(AssemblyError::SemanticError {
+                    line: *line_num,
+                    reason: "Expression cannot be relocated, it must be a constant or the address of a single label plus a constant.".to_string(),
+                });
+            }
+        }
+    }
+
+    let Some(base) = relocated else {
+        return Ok(Value::Constant(value));
+    };
+
+    // the addend is relative to the first label that moves with the address
+    let name = names
+        .into_iter()
+        .find(|name| layout.base_of(name, line_num).ok() == Some(base))
+        .unwrap();
+    Ok(Value::Address(
+        name.clone(),
+        value - layout.offset_of(name) as i32,
+    ))
+}
+
+fn read_binary<F: FileReader>(
+    path: &str,
+    line: &AssemblyLine,
+    reader: &F,
+) -> Result<Vec<u8>, AssemblyError> {
+    reader
+        .read_binary(std::path::Path::new(path))
+        .map_err(|e| AssemblyError::StructuralError {
+            line: line.line_number,
+            reason: format!("Failed to read binary file '{}': {}", path, e),
+        })
+}
+
+fn outside_section(line: &AssemblyLine, what: &str) -> AssemblyError {
+    AssemblyError::StructuralError {
+        line: line.line_number,
+        reason: format!("{} must be inside a .section in an object file.", what),
+    }
+}
+
+fn placed_by_linker(line: &AssemblyLine, directive: &str) -> AssemblyError {
+    AssemblyError::StructuralError {
+        line: line.line_number,
+        reason: format!(
+            "{} cannot be used in an object file, sections are placed by the linker script.",
+            directive
+        ),
+    }
+}
+
+fn address_not_known(symbol: &str, line_num: &usize) -> AssemblyError {
+    AssemblyError::SemanticError {
+        line: *line_num,
+        reason: format!(
+            "Label \"{}\" cannot be used in this operand of an object file, its address is only known once the object is linked.",
+            symbol
+        ),
+    }
+}
+
+pub(super) fn lcm(a: u32, b: u32) -> u32 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::BinaryOp;
+
+    fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+        Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    fn symbol(name: &str) -> Expr {
+        Expr::Symbol(name.to_string())
+    }
+
+    #[test]
+    fn test_expr_value() {
+        let layout = Layout {
+            sections: Vec::new(),
+            labels: HashMap::from([
+                ("start".to_string(), (0, 4)),
+                ("end".to_string(), (0, 10)),
+                ("data".to_string(), (1, 2)),
+            ]),
+            globals: HashSet::new(),
+            externs: vec!["ext".to_string()],
+            branches: Vec::new(),
+        };
+        let value = |expr: &Expr| expr_value(expr, &layout, &1);
+
+        // labels in the same section move together
+        let size = binary(BinaryOp::Sub, symbol("end"), symbol("start"));
+        assert_eq!(value(&size), Ok(Value::Constant(6)));
+
+        let offset = binary(BinaryOp::Add, symbol("start"), Expr::Number(3));
+        assert_eq!(value(&offset), Ok(Value::Address("start".to_string(), 3)));
+
+        let offset = binary(BinaryOp::Add, size, symbol("ext"));
+        assert_eq!(value(&offset), Ok(Value::Address("ext".to_string(), 6)));
+
+        let bank = Expr::Bank("data".to_string());
+        assert_eq!(value(&bank), Ok(Value::Bank("data".to_string())));
+
+        // sections are placed independently, and scaled addresses cannot be relocated
+        assert!(value(&binary(BinaryOp::Sub, symbol("data"), symbol("start"))).is_err());
+        assert!(value(&binary(BinaryOp::Mul, symbol("start"), Expr::Number(2))).is_err());
+        assert!(value(&symbol("missing")).is_err());
+    }
+}
@@ -93,6 +93,12 @@ pub fn process_instruction_constants(
         Instruction::JrI(op) | Instruction::JrccI(_, op) | Instruction::Djnz(op) => {
             replace_constant_with_signed_byte(op, constant_table, line_number)?;
         }
+        // the operand of a relaxed branch is the target address in both forms
+        Instruction::JmpAuto(op, _)
+        | Instruction::JccAuto(_, op, _)
+        | Instruction::DjnzAuto(op, _) => {
+            replace_constant_with_word(op, constant_table, line_number)?;
+        }
         Instruction::CallI(op) | Instruction::CallccI(_, op) => {
             replace_constant_with_word(op, constant_table, line_number)?;
         }
@@ -113,9 +119,13 @@ pub fn process_directive_constants(
         Directive::Org(op) => {
             replace_constant_with_word(op, constant_table, line_number)?;
         }
+        Directive::Bank(op) => {
+            replace_constant_with_word(op, constant_table, line_number)?;
+        }
         Directive::Byte(ops) => {
+            // values that do not fit in a byte are truncated with a warning in pass 2
             for op in ops {
-                replace_constant_with_unsigned_byte(op, constant_table, line_number)?;
+                replace_constant(op, constant_table, line_number)?;
             }
         }
         Directive::Word(ops) => {
@@ -128,6 +138,15 @@ pub fn process_directive_constants(
                 replace_constant_with_word(op, constant_table, line_number)?;
             }
         }
+        Directive::Fill(count, value) => {
+            replace_constant_with_word(count, constant_table, line_number)?;
+            if let Some(value) = value {
+                replace_constant_with_unsigned_byte(value, constant_table, line_number)?;
+            }
+        }
+        Directive::PadByte(value) | Directive::Charmap(Charmap::Map(_, value)) => {
+            replace_constant_with_unsigned_byte(value, constant_table, line_number)?;
+        }
         _ => {}
     }
     Ok(())
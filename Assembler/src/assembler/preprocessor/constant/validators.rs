@@ -17,25 +17,24 @@ limitations under the License.
 use crate::assembler::AssemblyError;
 use crate::assembler::ConstantTable;
 use crate::ast::*;
+use crate::expression;
 
 pub fn replace_constant_with_word(
     op: &mut Operand,
     constant_table: &ConstantTable,
     line_number: &usize,
 ) -> Result<(), AssemblyError> {
-    if let Operand::Label(label) = op {
-        if let Some(val) = constant_table.get(label) {
-            if *val > u16::MAX as i32 || *val < 0 {
-                return Err(AssemblyError::SemanticError {
-                    line: *line_number,
-                    reason: format!(
-                        "Expected constant to be an unsigned 16 bit value (max: {}, min: 0)",
-                        u16::MAX
-                    ),
-                });
-            }
-            *op = Operand::Immediate(*val);
+    if let Some(val) = resolve_constant(op, constant_table, line_number)? {
+        if val > u16::MAX as i32 || val < 0 {
+            return Err(AssemblyError::SemanticError {
+                line: *line_number,
+                reason: format!(
+                    "Expected constant to be an unsigned 16 bit value (max: {}, min: 0)",
+                    u16::MAX
+                ),
+            });
         }
+        *op = Operand::Immediate(val);
     }
     Ok(())
 }
@@ -45,20 +44,18 @@ pub fn replace_constant_with_signed_byte(
     constant_table: &ConstantTable,
     line_number: &usize,
 ) -> Result<(), AssemblyError> {
-    if let Operand::Label(label) = op {
-        if let Some(val) = constant_table.get(label) {
-            if *val > i8::MAX as i32 || *val < i8::MIN as i32 {
-                return Err(AssemblyError::SemanticError {
-                    line: *line_number,
-                    reason: format!(
-                        "Expected constant to be a signed 8 bit value (max: {}, min: {})",
-                        i8::MAX,
-                        i8::MIN
-                    ),
-                });
-            }
-            *op = Operand::Immediate(*val);
+    if let Some(val) = resolve_constant(op, constant_table, line_number)? {
+        if val > i8::MAX as i32 || val < i8::MIN as i32 {
+            return Err(AssemblyError::SemanticError {
+                line: *line_number,
+                reason: format!(
+                    "Expected constant to be a signed 8 bit value (max: {}, min: {})",
+                    i8::MAX,
+                    i8::MIN
+                ),
+            });
         }
+        *op = Operand::Immediate(val);
     }
     Ok(())
 }
@@ -68,19 +65,74 @@ pub fn replace_constant_with_unsigned_byte(
     constant_table: &ConstantTable,
     line_number: &usize,
 ) -> Result<(), AssemblyError> {
-    if let Operand::Label(label) = op {
-        if let Some(val) = constant_table.get(label) {
-            if *val > u8::MAX as i32 || *val < u8::MIN as i32 {
-                return Err(AssemblyError::SemanticError {
-                    line: *line_number,
-                    reason: format!(
-                        "Expected constant to be an unsigned 8 bit value (max: {}, min: 0)",
-                        u8::MAX
-                    ),
-                });
-            }
-            *op = Operand::Immediate(*val);
+    if let Some(val) = resolve_constant(op, constant_table, line_number)? {
+        if val > u8::MAX as i32 || val < u8::MIN as i32 {
+            return Err(AssemblyError::SemanticError {
+                line: *line_number,
+                reason: format!(
+                    "Expected constant to be an unsigned 8 bit value (max: {}, min: 0)",
+                    u8::MAX
+                ),
+            });
         }
+        *op = Operand::Immediate(val);
+    }
+    Ok(())
+}
+
+// substitute constants without checking the range of the value, for operands that are checked
+// when they are encoded
+pub fn replace_constant(
+    op: &mut Operand,
+    constant_table: &ConstantTable,
+    line_number: &usize,
+) -> Result<(), AssemblyError> {
+    if let Some(val) = resolve_constant(op, constant_table, line_number)? {
+        *op = Operand::Immediate(val);
     }
     Ok(())
 }
+
+// Substitute constants into a label or expression operand. Returns the value of the operand if it
+// no longer depends on any labels, otherwise the operand is left as an expression to be resolved
+// against the symbol table during pass 2.
+fn resolve_constant(
+    op: &mut Operand,
+    constant_table: &ConstantTable,
+    line_number: &usize,
+) -> Result<Option<i32>, AssemblyError> {
+    let expr = match op {
+        Operand::Label(label) => {
+            if let Some(val) = constant_table.get(label) {
+                return Ok(Some(*val));
+            }
+            match constant_table.get_deferred(label) {
+                Some(deferred) => inline_constants(deferred, constant_table),
+                None => return Ok(None),
+            }
+        }
+        Operand::Expr(expr) => inline_constants(expr, constant_table),
+        _ => return Ok(None),
+    };
+
+    if expression::is_constant(&expr) {
+        Ok(Some(expression::evaluate_constant(&expr, line_number)?))
+    } else {
+        *op = Operand::Expr(expr);
+        Ok(None)
+    }
+}
+
+// replace every constant referenced by an expression with its value, label dependent constants are
+// replaced by their own (inlined) expression. Circular definitions are rejected in pass 0.
+fn inline_constants(expr: &Expr, constant_table: &ConstantTable) -> Expr {
+    expression::replace_symbols(expr, &|name| {
+        if let Some(val) = constant_table.get(name) {
+            Some(Expr::Number(*val))
+        } else {
+            constant_table
+                .get_deferred(name)
+                .map(|deferred| inline_constants(deferred, constant_table))
+        }
+    })
+}
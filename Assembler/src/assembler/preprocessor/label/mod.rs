@@ -0,0 +1,298 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::assembler::AssemblyError;
+use crate::ast::*;
+use std::collections::HashSet;
+
+// an anonymous label definition, e.g. "-:" or "++:"
+struct AnonymousLabel {
+    family: String, // the run of "+" or "-" characters
+    line_index: usize,
+    name: String,
+}
+
+// Tracks which labels are visible from the line being processed, so that local and anonymous
+// label references can be replaced with the unique names they are stored under in the symbol
+// table. Local labels are stored as "global.local".
+pub struct LabelScope {
+    global: Option<String>,
+    anonymous: Vec<AnonymousLabel>,
+    line_index: usize,
+    referenced: HashSet<String>, // full names of every label reference resolved so far
+}
+
+impl LabelScope {
+    pub fn new(lines: &[AssemblyLine]) -> LabelScope {
+        let anonymous = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(line_index, line)| {
+                let label = line.label.as_ref()?;
+                is_anonymous(label).then_some((line_index, label))
+            })
+            .enumerate()
+            .map(|(count, (line_index, label))| AnonymousLabel {
+                family: label.clone(),
+                line_index,
+                name: format!("{}#{}", label, count + 1),
+            })
+            .collect();
+
+        LabelScope {
+            global: None,
+            anonymous,
+            line_index: 0,
+            referenced: HashSet::new(),
+        }
+    }
+
+    // move the scope to a line, renaming the label it defines if it is local or anonymous
+    pub fn enter_line(
+        &mut self,
+        line_index: usize,
+        line: &mut AssemblyLine,
+    ) -> Result<(), AssemblyError> {
+        self.line_index = line_index;
+
+        let Some(label) = &mut line.label else {
+            return Ok(());
+        };
+
+        if is_anonymous(label) {
+            if let Some(anon) = self.anonymous.iter().find(|a| a.line_index == line_index) {
+                *label = anon.name.clone();
+            }
+        } else if label.starts_with('.') {
+            *label = self.full_name(label, &line.line_number)?;
+        } else if line.expansion.is_empty() {
+            // labels generated by a macro expansion don't start a new routine, so local labels
+            // written after the invocation still belong to the global label above it
+            self.global = Some(label.clone());
+        }
+
+        Ok(())
+    }
+
+    // get the full name of a label reference, and record that it was referenced
+    pub fn resolve(&mut self, name: &str, line_number: &usize) -> Result<String, AssemblyError> {
+        let full_name = self.full_name(name, line_number)?;
+        self.referenced.insert(full_name.clone());
+        Ok(full_name)
+    }
+
+    // the full names of every label referenced by the lines processed so far
+    pub fn into_referenced(self) -> HashSet<String> {
+        self.referenced
+    }
+
+    fn full_name(&self, name: &str, line_number: &usize) -> Result<String, AssemblyError> {
+        if name.starts_with('.') {
+            return match &self.global {
+                Some(global) => Ok(format!("{}{}", global, name)),
+                None => Err(AssemblyError::SemanticError {
+                    line: *line_number,
+                    reason: format!("Local label {} has no preceding global label.", name),
+                }),
+            };
+        }
+
+        if !is_anonymous(name) {
+            return Ok(name.to_string());
+        }
+
+        // "-" references look backwards for the closest definition, "+" references look forwards
+        let backwards = name.starts_with('-');
+        let found = if backwards {
+            self.anonymous
+                .iter()
+                .rev()
+                .find(|a| a.family == name && a.line_index < self.line_index)
+        } else {
+            self.anonymous
+                .iter()
+                .find(|a| a.family == name && a.line_index > self.line_index)
+        };
+
+        found
+            .map(|a| a.name.clone())
+            .ok_or_else(|| AssemblyError::SemanticError {
+                line: *line_number,
+                reason: format!(
+                    "No anonymous label \"{}\" {} this line.",
+                    name,
+                    if backwards { "before" } else { "after" }
+                ),
+            })
+    }
+}
+
+fn is_anonymous(name: &str) -> bool {
+    !name.is_empty() && (name.chars().all(|c| c == '+') || name.chars().all(|c| c == '-'))
+}
+
+pub fn process_instruction_labels(
+    instruction: &mut Instruction,
+    scope: &mut LabelScope,
+    line_number: &usize,
+) -> Result<(), AssemblyError> {
+    match instruction {
+        Instruction::Ldi(_, op)
+        | Instruction::LdAbs(_, op)
+        | Instruction::LdIndexed(_, _, op)
+        | Instruction::StAbs(op, _)
+        | Instruction::StIndexed(_, op, _)
+        | Instruction::LdiB(_, op)
+        | Instruction::LdBAbs(_, op)
+        | Instruction::StBAbs(op, _)
+        | Instruction::Lea(_, _, op)
+        | Instruction::PushI(op) => {
+            scope_operand(op, scope, line_number)?;
+        }
+        Instruction::AddAccI(op)
+        | Instruction::SubAccI(op)
+        | Instruction::AndAccI(op)
+        | Instruction::OrAccI(op)
+        | Instruction::XorAccI(op)
+        | Instruction::CmpAccI(op)
+        | Instruction::AdcAccI(op)
+        | Instruction::SbcAccI(op) => {
+            scope_operand(op, scope, line_number)?;
+        }
+        Instruction::AddIReg(_, op)
+        | Instruction::SubIReg(_, op)
+        | Instruction::AndIReg(_, op)
+        | Instruction::OrIReg(_, op)
+        | Instruction::XorIReg(_, op)
+        | Instruction::CmpIReg(_, op)
+        | Instruction::AddSp(op) => {
+            scope_operand(op, scope, line_number)?;
+        }
+        Instruction::BitReg(_, op)
+        | Instruction::SetReg(_, op)
+        | Instruction::ResReg(_, op)
+        | Instruction::BitIndirect(_, op)
+        | Instruction::SetIndirect(_, op)
+        | Instruction::ResIndirect(_, op) => {
+            scope_operand(op, scope, line_number)?;
+        }
+        Instruction::BitAbs(abs, bit)
+        | Instruction::SetAbs(abs, bit)
+        | Instruction::ResAbs(abs, bit) => {
+            scope_operand(abs, scope, line_number)?;
+            scope_operand(bit, scope, line_number)?;
+        }
+        Instruction::JmpI(op)
+        | Instruction::JccI(_, op)
+        | Instruction::JrI(op)
+        | Instruction::JrccI(_, op)
+        | Instruction::Djnz(op)
+        | Instruction::JmpAuto(op, _)
+        | Instruction::JccAuto(_, op, _)
+        | Instruction::DjnzAuto(op, _)
+        | Instruction::CallI(op)
+        | Instruction::CallccI(_, op)
+        | Instruction::Syscall(op) => {
+            scope_operand(op, scope, line_number)?;
+        }
+        Instruction::CallFar(label) | Instruction::JmpFar(label) => {
+            *label = scope.resolve(label, line_number)?;
+        }
+        Instruction::CallFarVia(label, via) | Instruction::JmpFarVia(label, via) => {
+            *label = scope.resolve(label, line_number)?;
+            *via = scope.resolve(via, line_number)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub fn process_directive_labels(
+    directive: &mut Directive,
+    scope: &mut LabelScope,
+    line_number: &usize,
+) -> Result<(), AssemblyError> {
+    match directive {
+        Directive::Org(op)
+        | Directive::Bank(op)
+        | Directive::Define(_, op)
+        | Directive::PadByte(op)
+        | Directive::Fill(op, None) => {
+            scope_operand(op, scope, line_number)?;
+        }
+        Directive::Fill(count, Some(value)) => {
+            scope_operand(count, scope, line_number)?;
+            scope_operand(value, scope, line_number)?;
+        }
+        Directive::Byte(ops) | Directive::Word(ops) | Directive::Interrupt(ops) => {
+            for op in ops {
+                scope_operand(op, scope, line_number)?;
+            }
+        }
+        Directive::Struct(_, fields) => {
+            for length in fields.iter_mut().filter_map(|f| f.length.as_mut()) {
+                scope_expr(length, scope, line_number)?;
+            }
+        }
+        Directive::Enum(_, members) => {
+            for value in members.iter_mut().filter_map(|m| m.value.as_mut()) {
+                scope_expr(value, scope, line_number)?;
+            }
+        }
+        // exported labels are used by other object files
+        Directive::Global(names) | Directive::Extern(names) => {
+            for name in names {
+                *name = scope.resolve(name, line_number)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn scope_operand(
+    op: &mut Operand,
+    scope: &mut LabelScope,
+    line_number: &usize,
+) -> Result<(), AssemblyError> {
+    match op {
+        Operand::Label(name) | Operand::AbsLabel(name) | Operand::IndexedLabel(_, name) => {
+            *name = scope.resolve(name, line_number)?;
+        }
+        Operand::Expr(expr) | Operand::AbsExpr(expr) | Operand::IndexedExpr(_, expr) => {
+            scope_expr(expr, scope, line_number)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn scope_expr(
+    expr: &mut Expr,
+    scope: &mut LabelScope,
+    line_number: &usize,
+) -> Result<(), AssemblyError> {
+    match expr {
+        Expr::Number(_) => {}
+        Expr::Symbol(name) | Expr::Bank(name) => *name = scope.resolve(name, line_number)?,
+        Expr::Unary(_, inner) => scope_expr(inner, scope, line_number)?,
+        Expr::Binary(_, lhs, rhs) => {
+            scope_expr(lhs, scope, line_number)?;
+            scope_expr(rhs, scope, line_number)?;
+        }
+    }
+    Ok(())
+}
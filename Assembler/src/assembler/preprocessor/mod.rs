@@ -15,3 +15,4 @@ limitations under the License.
 */
 
 pub mod constant;
+pub mod label;
@@ -0,0 +1,84 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::assembler::encoder;
+use crate::assembler::symbol_table::SymbolTable;
+use crate::ast::{AssemblyLine, BranchForm, Instruction};
+
+/// The address pass 1 placed a short `.auto` branch at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchSite {
+    pub line_index: usize,
+    pub logical_address: u32,
+    pub bank: u32,
+}
+
+// the form of a relaxed branch, None for every other instruction
+fn branch_form(instruction: &mut Instruction) -> Option<&mut BranchForm> {
+    match instruction {
+        Instruction::JmpAuto(_, form)
+        | Instruction::JccAuto(_, _, form)
+        | Instruction::DjnzAuto(_, form) => Some(form),
+        _ => None,
+    }
+}
+
+/// Whether the instruction is a relaxed branch that is still assembled as a relative jump.
+pub fn is_short_branch(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::JmpAuto(_, BranchForm::Short)
+            | Instruction::JccAuto(_, _, BranchForm::Short)
+            | Instruction::DjnzAuto(_, BranchForm::Short)
+    )
+}
+
+/// Switch every short branch placed by pass 1 that cannot reach its target to the long form,
+/// returns whether any branch was switched. Pass 1 must then be repeated, since the longer
+/// branches move the labels after them. Branches are never switched back to the short form, so
+/// the layout settles after at most one repeat per branch.
+pub fn relax_branches(
+    lines: &mut [AssemblyLine],
+    sites: &[BranchSite],
+    symbol_table: &SymbolTable,
+) -> bool {
+    let mut changed = false;
+
+    for site in sites {
+        let line = &mut lines[site.line_index];
+        let Some(instruction) = &mut line.instruction else {
+            continue;
+        };
+
+        // the short form reaches the target if it can be encoded at this address, errors that
+        // are not about the distance are reported by the long form in pass 2
+        let reaches = encoder::encode_instruction(
+            instruction,
+            symbol_table,
+            &site.logical_address,
+            &site.bank,
+            &line.line_number,
+        )
+        .is_ok();
+
+        if !reaches && let Some(form) = branch_form(instruction) {
+            *form = BranchForm::Long;
+            changed = true;
+        }
+    }
+
+    changed
+}
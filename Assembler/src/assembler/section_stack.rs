@@ -46,7 +46,59 @@ pub struct Context {
     pub vaddr: Option<u32>,
     pub paddr: Option<u32>,
     pub align: Option<u32>,
-    pub address: AddrCounter,
+    pub nobits: bool,
+    pub bank0: bool,
+    pub float: bool,
+    pub address: AddrCounter, // address counter before the section started
+    pub start: AddrCounter,   // address of the first byte of the section, after alignment
 }
 
 pub type ContextStack = Vec<Context>;
+
+// A RAM address space that nobits sections can be placed in, see Memory_Map.md.
+#[derive(Debug, PartialEq)]
+pub struct RamRegion {
+    pub name: &'static str,
+    pub start: u32,
+    pub end: u32, // address of the last byte
+}
+
+pub const RAM_REGIONS: [RamRegion; 4] = [
+    RamRegion {
+        name: "Cartridge RAM",
+        start: 0x8000,
+        end: 0x8FFF,
+    },
+    RamRegion {
+        name: "WRAM0",
+        start: 0xB000,
+        end: 0xCFFF,
+    },
+    RamRegion {
+        name: "WRAM1",
+        start: 0xD000,
+        end: 0xDFFF,
+    },
+    RamRegion {
+        name: "HRAM",
+        start: 0xFE00,
+        end: 0xFFFF,
+    },
+];
+
+// the RAM region an address belongs to
+pub fn ram_region(addr: u32) -> Option<&'static RamRegion> {
+    RAM_REGIONS
+        .iter()
+        .find(|region| (region.start..=region.end).contains(&addr))
+}
+
+// The final placement of a section, recorded when its .section_end is reached in pass 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionLayout {
+    pub name: Option<String>,
+    pub bank: u32,
+    pub logical_addr: u32,
+    pub physical_addr: u32,
+    pub size: u32,
+}
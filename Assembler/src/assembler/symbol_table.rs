@@ -17,13 +17,14 @@ limitations under the License.
 use crate::errors::AssemblyError;
 use std::collections::HashMap;
 
-#[derive(Debug, PartialEq)]
+/// The address of a label.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Symbol {
     pub logical_address: u32,
     pub bank: u32,
 }
 
-// The symbol table stores label names and their calculated addresses.
+/// The symbol table stores label names and their calculated addresses.
 pub type SymbolTable = HashMap<String, Symbol>;
 
 pub fn get_symbol<'a>(
@@ -14,6 +14,9 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::fmt;
+use std::path::PathBuf;
+
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct HeaderInfo {
     pub boot_anim: String,
@@ -40,6 +43,47 @@ pub enum Register {
     R7,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UnaryOp {
+    Neg,        // -x
+    Not,        // ~x
+    LogicalNot, // !x
+    Low,        // <x, low byte
+    High,       // >x, high byte
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BinaryOp {
+    Add,        // +
+    Sub,        // -
+    Mul,        // *
+    Div,        // /
+    Mod,        // %
+    Shl,        // <<
+    Shr,        // >>
+    And,        // &
+    Or,         // |
+    Xor,        // ^
+    Eq,         // ==
+    Ne,         // !=
+    Lt,         // <
+    Gt,         // >
+    Le,         // <=
+    Ge,         // >=
+    LogicalAnd, // &&
+    LogicalOr,  // ||
+}
+
+// An arithmetic expression, resolved against the constant and symbol tables.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(i32),
+    Symbol(String), // constant or label
+    Bank(String),   // bank(label)
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
 // Represents all possible forms an argument to an instruction can take.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Operand {
@@ -48,9 +92,12 @@ pub enum Operand {
     Indirect(Register), // e.g., (R1)
     AbsAddr(u16),       // e.g., (0x2020)
     AbsLabel(String),
+    AbsExpr(Expr),                  // e.g., (IO_BASE + 2)
     Indexed(Register, i8),          // e.g., (R1, 0x10) or (R1, -2)
     IndexedLabel(Register, String), // e.g., (R1, const_val)
+    IndexedExpr(Register, Expr),    // e.g., (R1, OFFSET + 2)
     Label(String),                  // e.g., my_label
+    Expr(Expr),                     // e.g., label_end - label
     PreDecrement(Register),
     PostIncrement(Register),
     String(String),
@@ -190,6 +237,19 @@ pub enum Instruction {
     CallFarVia(String, String),      // Call.far label via label
     JmpFar(String),                  // JMP.far label
     JmpFarVia(String, String),       // JMP.far label via label
+
+    // Relaxed branches, the assembler picks the form that reaches the target
+    JmpAuto(Operand, BranchForm), // JMP.auto label, JR or JMP
+    JccAuto(ConditionCode, Operand, BranchForm), // Jcc.auto label, JRcc or Jcc
+    DjnzAuto(Operand, BranchForm), // DJNZ.auto label, DJNZ or DEC R5 + JNZ
+}
+
+// The form a relaxed branch is assembled as. Branches start short and are switched to the long
+// form when their target is out of range of a relative jump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchForm {
+    Short, // JR, JRcc, DJNZ
+    Long,  // JMP, Jcc, DEC R5 + JNZ
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -206,18 +266,56 @@ pub enum ConditionCode {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Directive {
-    Org(Operand),                 // .org 0x1234 AND .org label
-    Bank(Operand),                // .bank 3
-    Byte(Vec<Operand>),           // .byte 0x01, 0x02, 0x03
-    Word(Vec<Operand>),           // .word 0x0001, 0x0002, 0x0003 AND .word label, label, label
-    Define(String, Operand),      // .define label 0x01
-    Include(String),              // .include path
-    Incbin(String),               // .incbin path
-    Header(HeaderInfo),           // .header_start ... .header_end
-    Interrupt(Vec<Operand>),      // .interrupt_table ... .table_end
+    Org(Operand),                          // .org 0x1234 AND .org label
+    Bank(Operand),                         // .bank 3
+    Byte(Vec<Operand>),                    // .byte 0x01, 0x02, 0x03
+    Word(Vec<Operand>), // .word 0x0001, 0x0002, 0x0003 AND .word label, label, label
+    Define(String, Operand), // .define label 0x01 AND .define label end - start
+    Include(String),    // .include path
+    Incbin(String),     // .incbin path
+    Header(HeaderInfo), // .header_start ... .header_end
+    Interrupt(Vec<Operand>), // .interrupt_table ... .table_end
     SectionStart(SectionOptions), // .section
-    SectionEnd,                   // .section_end
-    Align(u32),                   // .align 3
+    SectionEnd,         // .section_end
+    Align(u32),         // .align 3
+    If(Expr),           // .if VERSION >= 2
+    Elif(Expr),         // .elif DEBUG
+    Else,               // .else
+    Endif,              // .endif
+    Ifdef(String),      // .ifdef DEBUG
+    Ifndef(String),     // .ifndef DEBUG
+    Ascii(Vec<u8>),     // .ascii "Hello"
+    Asciz(Vec<u8>),     // .asciz "Hello" AND .string "Hello", followed by a zero byte
+    Charmap(Charmap),   // .charmap font AND .charmap 'A', 0x21
+    Fill(Operand, Option<Operand>), // .fill 16, 0xFF AND .ds 16, filled with the padding byte
+    PadByte(Operand),   // .padbyte 0xFF
+    Struct(String, Vec<StructField>), // .struct Sprite ... .endstruct
+    Enum(Option<String>, Vec<EnumMember>), // .enum State ... .endenum
+    Global(Vec<String>), // .global main, update
+    Extern(Vec<String>), // .extern draw_sprite
+}
+
+// A field of a .struct, e.g. "tiles .b 4". Arrays have a length, single values do not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructField {
+    pub name: String,
+    pub size: u32, // size of one element in bytes
+    pub length: Option<Expr>,
+}
+
+// A member of an .enum, e.g. "JUMPING = 4".
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumMember {
+    pub name: String,
+    pub value: Option<Expr>,
+}
+
+// A change to the character map that .ascii and .asciz text is translated through.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Charmap {
+    Ascii,                 // every character is stored as its own value
+    Font,                  // characters are stored as System Library default font tile indices
+    Map(Operand, Operand), // map a character, or each character of a string, to a value
 }
 
 #[derive(Default, Debug, Clone, PartialEq)]
@@ -227,10 +325,52 @@ pub struct SectionOptions {
     pub vaddr: Option<u32>,
     pub paddr: Option<u32>,
     pub align: Option<u32>,
+    pub nobits: bool, // RAM variables, labels are given addresses but no bytes are emitted
+    pub bank0: bool,  // must be in the fixed bank 0, written as bank0 or fixed
+}
+
+impl SectionOptions {
+    // a ROM section without a vaddr or paddr is placed in free ROM bank space by the assembler
+    pub fn floating(&self) -> bool {
+        self.vaddr.is_none() && self.paddr.is_none() && !self.nobits
+    }
 }
 
 // --- Assembly Line Structure ---
 
+// One level of macro expansion, the macro that was invoked and the line it was invoked on. Each
+// repetition of a .rept block is also recorded as an expansion, named ".rept".
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroExpansion {
+    pub name: String,
+    pub invocation_line: usize,
+    pub iteration: Option<usize>, // value of the loop counter, for .rept repetitions
+}
+
+impl fmt::Display for MacroExpansion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.iteration {
+            Some(iteration) => write!(
+                f,
+                "iteration {} of .rept on line {}",
+                iteration, self.invocation_line
+            ),
+            None => write!(
+                f,
+                "expansion of macro \"{}\" invoked on line {}",
+                self.name, self.invocation_line
+            ),
+        }
+    }
+}
+
+// A piece of a source line, the column is 1-based and both values count characters.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub column: usize,
+    pub length: usize,
+}
+
 // Represents a single line of code, which can have a label, an instruction, or both.
 #[derive(Debug, Clone, Default)]
 pub struct AssemblyLine {
@@ -238,4 +378,9 @@ pub struct AssemblyLine {
     pub label: Option<String>,
     pub instruction: Option<Instruction>,
     pub directive: Option<Directive>, // Add directives later: pub directive: Option<Directive>,
+    pub expansion: Vec<MacroExpansion>, // macros this line was expanded from, outermost first
+    pub file: PathBuf,                // source file the line was read from
+    pub source: String,               // original text of the line, after macro substitution
+    pub span: Span,                   // the label, instruction or directive within `source`
+    pub operand_spans: Vec<Span>,     // each operand of the instruction or directive
 }
@@ -0,0 +1,99 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use anyhow::{Context, Result};
+use cicasm::diagnostics;
+use cicasm::disassembler::{DisassemblyOptions, disassemble};
+use cicasm::symbol_file::SymbolFile;
+use clap::Parser as clap_parser;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(clap_parser)]
+#[clap(version = "0.3.14", author = "Connor Nolan")]
+struct Opts {
+    /// ROM image to disassemble
+    input: PathBuf,
+
+    /// Disassembled source file path (optional, default: print to stdout)
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Disassemble a boot ROM (no header, exactly 16 KiB, interrupt vector table at
+    /// 0x3FE0-0x3FFF)
+    #[clap(short, long)]
+    boot: bool,
+
+    /// Symbol file written by cicasm --symbols, in either format, whose labels are printed in
+    /// place of addresses
+    #[clap(long, value_name = "PATH")]
+    symbols: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let opts: Opts = Opts::parse();
+
+    match run(opts) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", diagnostics::render_error(&error));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(opts: Opts) -> Result<()> {
+    let rom = fs::read(&opts.input)
+        .with_context(|| format!("Failed to read ROM file {}", opts.input.display()))?;
+
+    let symbols = match &opts.symbols {
+        Some(path) => Some(read_symbols(path)?),
+        None => None,
+    };
+
+    let options = DisassemblyOptions {
+        boot_rom: opts.boot,
+    };
+    let source = disassemble(&rom, symbols.as_ref(), &options)?;
+
+    match &opts.output {
+        Some(path) => {
+            fs::write(path, source)?;
+            println!(
+                "Successfully disassembled {} to {}",
+                opts.input.display(),
+                path.display()
+            );
+        }
+        None => print!("{}", source),
+    }
+
+    Ok(())
+}
+
+// the JSON format is recognised by its opening brace
+fn read_symbols(path: &PathBuf) -> Result<SymbolFile> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read symbol file {}", path.display()))?;
+
+    if text.trim_start().starts_with('{') {
+        SymbolFile::from_json(&text)
+            .with_context(|| format!("{} is not a symbol file", path.display()))
+    } else {
+        Ok(SymbolFile::from_sym(&text)?)
+    }
+}
@@ -0,0 +1,85 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use anyhow::{Context, Result};
+use cicasm::DEFAULT_MAX_ERRORS;
+use cicasm::diagnostics;
+use cicasm::link;
+use cicasm::object_file::ObjectFile;
+use clap::Parser as clap_parser;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(clap_parser)]
+#[clap(version = "0.3.14", author = "Connor Nolan")]
+struct Opts {
+    /// Object files written by cicasm -c, sections with the same name are placed in this order
+    #[clap(required = true)]
+    objects: Vec<PathBuf>,
+
+    /// Linker script listing the sections placed in each ROM bank and RAM region
+    #[clap(short = 'T', long, value_name = "PATH")]
+    script: PathBuf,
+
+    /// Linked ROM file path
+    #[clap(short, long, default_value = "linked.bin")]
+    output: PathBuf,
+
+    /// Maximum number of errors to report before stopping, 0 reports every error
+    #[clap(long, value_name = "COUNT", default_value_t = DEFAULT_MAX_ERRORS)]
+    max_errors: usize,
+}
+
+fn main() -> ExitCode {
+    let opts: Opts = Opts::parse();
+
+    match run(opts) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", diagnostics::render_error(&error));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(opts: Opts) -> Result<()> {
+    let mut objects = Vec::new();
+    for path in &opts.objects {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read object file {}", path.display()))?;
+        let object = ObjectFile::from_json(&json)
+            .with_context(|| format!("{} is not an object file", path.display()))?;
+        objects.push((path.display().to_string(), object));
+    }
+
+    let script = fs::read_to_string(&opts.script)
+        .with_context(|| format!("Failed to read linker script {}", opts.script.display()))?;
+
+    let linked = link(&objects, &script, opts.max_errors)?;
+    fs::write(&opts.output, &linked.rom)?;
+
+    println!(
+        "Successfully linked {} object files to {}",
+        objects.len(),
+        opts.output.display()
+    );
+    for (bank, free) in linked.free_bytes.iter().enumerate() {
+        println!("bank {:02}: {:5} bytes free", bank, free);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,312 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::ast::Span;
+use crate::errors::{AssemblyError, SourceLocation};
+use crate::parser::Rule;
+use crate::warnings::Warning;
+use std::fmt::Write;
+
+/// Render an error from `assemble` for the terminal. Assembly errors are shown rustc-style with
+/// their file, line and column, the source line and a caret under the offending operand.
+pub fn render_error(error: &anyhow::Error) -> String {
+    let mut out = match error.chain().find(|e| is_diagnostic(*e)) {
+        Some(e) => match e.downcast_ref::<AssemblyError>() {
+            Some(assembly_error) => render(assembly_error),
+            None => format!("error: syntax error\n{}", e.to_string().trim_end()),
+        },
+        None => return format!("error: {:?}", error),
+    };
+
+    // context added to the error by the command line tools, which comes before the diagnostic in
+    // the chain. The innermost is listed first as for macro expansions
+    let contexts: Vec<_> = error.chain().take_while(|e| !is_diagnostic(*e)).collect();
+    for context in contexts.iter().rev() {
+        let _ = write!(out, "\n  = note: {}", context);
+    }
+
+    out
+}
+
+// errors that carry their own location
+fn is_diagnostic(error: &(dyn std::error::Error + 'static)) -> bool {
+    error.is::<AssemblyError>() || error.is::<pest::error::Error<Rule>>()
+}
+
+/// Render an assembly error, and each error it contains, as diagnostics.
+pub fn render(error: &AssemblyError) -> String {
+    match error {
+        AssemblyError::MultipleErrors {
+            errors,
+            limit_reached,
+        } => {
+            let mut out: String = errors.iter().map(|e| render(e) + "\n\n").collect();
+            if *limit_reached {
+                let _ = write!(
+                    out,
+                    "error: stopped after reaching the limit of {} errors",
+                    errors.len()
+                );
+            } else {
+                let _ = write!(out, "error: aborting due to {} errors", errors.len());
+            }
+            out
+        }
+        AssemblyError::SourceError { location, error } => {
+            let (message, notes) = describe(error);
+            let span = operand_index(error)
+                .and_then(|index| location.operand_spans.get(index).copied())
+                .unwrap_or(location.span);
+            render_located("error", location, &message, span, &notes)
+        }
+        _ => {
+            let (message, notes) = describe(error);
+            let mut out = format!("error: {}", message);
+            for note in notes {
+                let _ = write!(out, "\n  = note: {}", note);
+            }
+            out
+        }
+    }
+}
+
+/// Render a warning, underlining the operand it is about.
+pub fn render_warning(warning: &Warning) -> String {
+    let location = &warning.location;
+    let message = format!("{} [-W{}]", warning.reason, warning.kind);
+    let span = warning
+        .operand
+        .and_then(|index| location.operand_spans.get(index).copied())
+        .unwrap_or(location.span);
+
+    // innermost expansion first, as for errors
+    let notes: Vec<String> = warning
+        .expansion
+        .iter()
+        .rev()
+        .map(|frame| format!("in {}", frame))
+        .collect();
+
+    render_located("warning", location, &message, span, &notes)
+}
+
+fn render_located(
+    severity: &str,
+    location: &SourceLocation,
+    message: &str,
+    span: Span,
+    notes: &[String],
+) -> String {
+    let gutter = " ".repeat(location.line.to_string().len());
+
+    // keep tabs in the caret line so that it lines up with the source line
+    let indent: String = location
+        .source
+        .chars()
+        .take(span.column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{}: {}", severity, message);
+    let _ = writeln!(
+        out,
+        "{}--> {}:{}:{}",
+        gutter,
+        location.file.display(),
+        location.line,
+        span.column
+    );
+    let _ = writeln!(out, "{} |", gutter);
+    let _ = writeln!(out, "{} | {}", location.line, location.source);
+    let _ = write!(
+        out,
+        "{} | {}{}",
+        gutter,
+        indent,
+        "^".repeat(span.length.max(1))
+    );
+
+    for note in notes {
+        let _ = write!(out, "\n{} = note: {}", gutter, note);
+    }
+
+    out
+}
+
+// the message of an error without its line number, and the macro expansions it happened in
+fn describe(error: &AssemblyError) -> (String, Vec<String>) {
+    match error {
+        AssemblyError::StructuralError { reason, .. }
+        | AssemblyError::SemanticError { reason, .. }
+        | AssemblyError::CircularIncludeError { reason, .. } => (reason.clone(), Vec::new()),
+        AssemblyError::MacroExpansionError { expansion, error } => {
+            let (message, mut notes) = describe(error);
+            notes.push(format!("in {}", expansion));
+            (message, notes)
+        }
+        AssemblyError::SourceError { error, .. } | AssemblyError::OperandError { error, .. } => {
+            describe(error)
+        }
+        AssemblyError::PestError(error) => (
+            format!("syntax error\n{}", error.to_string().trim_end()),
+            Vec::new(),
+        ),
+        _ => (error.to_string(), Vec::new()),
+    }
+}
+
+// the index of the operand an error is about, if it names one
+fn operand_index(error: &AssemblyError) -> Option<usize> {
+    match error {
+        AssemblyError::OperandError { operand, .. } => Some(*operand),
+        AssemblyError::MacroExpansionError { error, .. } => operand_index(error),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::MacroExpansion;
+    use crate::warnings::WarningKind;
+    use std::path::PathBuf;
+
+    fn location(source: &str, span: Span, operand_spans: Vec<Span>) -> SourceLocation {
+        SourceLocation {
+            file: PathBuf::from("main.asm"),
+            line: 12,
+            source: source.to_string(),
+            span,
+            operand_spans,
+        }
+    }
+
+    #[test]
+    fn test_render_underlines_operand() {
+        let error = AssemblyError::SemanticError {
+            line: 12,
+            reason: "Undefined label: nowhere".to_string(),
+        }
+        .at_operand(1);
+        let location = location(
+            "    LDI R1, nowhere",
+            Span {
+                column: 5,
+                length: 15,
+            },
+            vec![
+                Span {
+                    column: 9,
+                    length: 2,
+                },
+                Span {
+                    column: 13,
+                    length: 7,
+                },
+            ],
+        );
+
+        let rendered = render(&AssemblyError::SourceError {
+            location: Box::new(location),
+            error: Box::new(error),
+        });
+
+        assert_eq!(
+            rendered,
+            "error: Undefined label: nowhere\n\
+             \x20 --> main.asm:12:13\n\
+             \x20  |\n\
+             12 |     LDI R1, nowhere\n\
+             \x20  |             ^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_render_underlines_line_with_macro_notes() {
+        let error = AssemblyError::MacroExpansionError {
+            expansion: MacroExpansion {
+                name: "go".to_string(),
+                invocation_line: 30,
+                iteration: None,
+            },
+            error: Box::new(AssemblyError::SemanticError {
+                line: 12,
+                reason: "Division by zero in expression.".to_string(),
+            }),
+        };
+        let location = location(
+            "\t.byte 1 / 0",
+            Span {
+                column: 2,
+                length: 11,
+            },
+            vec![Span {
+                column: 8,
+                length: 5,
+            }],
+        );
+
+        let rendered = render(&AssemblyError::SourceError {
+            location: Box::new(location),
+            error: Box::new(error),
+        });
+
+        assert!(rendered.contains("--> main.asm:12:2\n"), "{}", rendered);
+        assert!(rendered.contains("   | \t^^^^^^^^^^^\n"), "{}", rendered);
+        assert!(
+            rendered.ends_with("= note: in expansion of macro \"go\" invoked on line 30"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn test_render_warning_underlines_operand() {
+        let warning = Warning {
+            kind: WarningKind::ByteTruncation,
+            reason: "Value 300 does not fit in a byte, truncated to 0x2c".to_string(),
+            location: location(
+                "    .byte 1, 300",
+                Span {
+                    column: 5,
+                    length: 12,
+                },
+                vec![
+                    Span {
+                        column: 11,
+                        length: 1,
+                    },
+                    Span {
+                        column: 14,
+                        length: 3,
+                    },
+                ],
+            ),
+            operand: Some(1),
+            expansion: Vec::new(),
+        };
+
+        assert_eq!(
+            render_warning(&warning),
+            "warning: Value 300 does not fit in a byte, truncated to 0x2c [-Wbyte-truncation]\n\
+             \x20 --> main.asm:12:14\n\
+             \x20  |\n\
+             12 |     .byte 1, 300\n\
+             \x20  |              ^^^"
+        );
+    }
+}
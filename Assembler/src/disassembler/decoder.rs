@@ -0,0 +1,787 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::assembler::encoder::constants::*;
+use crate::ast::{ConditionCode, Instruction, Operand, Register};
+
+type RegOp = fn(Register) -> Instruction;
+type RegRegOp = fn(Register, Register) -> Instruction;
+type RegWordOp = fn(Register, Operand) -> Instruction;
+type WordOp = fn(Operand) -> Instruction;
+type CcOp = fn(ConditionCode, Operand) -> Instruction;
+type BitRegOp = fn(Register, Operand) -> Instruction;
+type BitAbsOp = fn(Operand, Operand) -> Instruction;
+type IndexOp = fn(Register, Register, Operand) -> Instruction;
+
+// one byte opcodes without operands
+const SINGLE_OPCODES: [(u8, Instruction); 16] = [
+    (NOP_OPCODE, Instruction::Nop),
+    (HALT_OPCODE, Instruction::Halt),
+    (NEG_ACC_OPCODE, Instruction::NegAcc),
+    (NOR_ACC_OPCODE, Instruction::NotAcc),
+    (SWAP_ACC_OPCODE, Instruction::SwapAcc),
+    (CCF_OPCODE, Instruction::Ccf),
+    (SCF_OPCODE, Instruction::Scf),
+    (RCF_OPCODE, Instruction::Rcf),
+    (ENTER_OPCODE, Instruction::Enter),
+    (LEAVE_OPCODE, Instruction::Leave),
+    (PUSH_F_OPCODE, Instruction::PushF),
+    (POP_F_OPCODE, Instruction::PopF),
+    (RET_OPCODE, Instruction::Ret),
+    (RETI_OPCODE, Instruction::Reti),
+    (EI_OPCODE, Instruction::Ei),
+    (DI_OPCODE, Instruction::Di),
+];
+
+// one byte opcodes with the register in the low 3 bits
+const REG_OPCODES: [(u8, RegOp); 8] = [
+    (ADD_ACC_BASE_OPCODE, Instruction::AddAcc),
+    (SUB_ACC_BASE_OPCODE, Instruction::SubAcc),
+    (AND_ACC_BASE_OPCODE, Instruction::AndAcc),
+    (OR_ACC_BASE_OPCODE, Instruction::OrAcc),
+    (XOR_ACC_BASE_OPCODE, Instruction::XorAcc),
+    (CMP_ACC_BASE_OPCODE, Instruction::CmpAcc),
+    (JMP_INDIR_BASE_OPCODE, Instruction::JmpIndirect),
+    (CALL_INDIR_BASE_OPCODE, Instruction::CallIndirect),
+];
+
+const STACK_OPCODES: [(u8, RegOp); 2] = [
+    (PUSH_REG_BASE_OPCODE, Instruction::Push),
+    (POP_REG_BASE_OPCODE, Instruction::Pop),
+];
+
+// opcodes followed by a b'00dddsss register byte
+const REG_REG_OPCODES: [(u8, RegRegOp); 8] = [
+    (ADD_OPCODE, Instruction::AddReg),
+    (SUB_OPCODE, Instruction::SubReg),
+    (AND_OPCODE, Instruction::AndReg),
+    (OR_OPCODE, Instruction::OrReg),
+    (XOR_OPCODE, Instruction::XorReg),
+    (CMP_OPCODE, Instruction::CmpReg),
+    (ADC_OPCODE, Instruction::AdcReg),
+    (SBC_OPCODE, Instruction::SbcReg),
+];
+
+// opcodes followed by a register byte and a 16 bit immediate
+const REG_IMM_OPCODES: [(u8, RegWordOp); 6] = [
+    (ADDI_OPCODE, Instruction::AddIReg),
+    (SUBI_OPCODE, Instruction::SubIReg),
+    (ANDI_OPCODE, Instruction::AndIReg),
+    (ORI_OPCODE, Instruction::OrIReg),
+    (XORI_OPCODE, Instruction::XorIReg),
+    (CMPI_OPCODE, Instruction::CmpIReg),
+];
+
+// opcodes with the register in the low 3 bits, followed by a 16 bit immediate or address
+const REG_WORD_OPCODES: [(u8, RegWordOp); 5] = [
+    (LDI_BASE_OPCODE, Instruction::Ldi),
+    (LD_ABS_BASE_OPCODE, Instruction::LdAbs),
+    (LDB_ABS_BASE_OPCODE, Instruction::LdBAbs),
+    (ST_ABS_BASE_OPCODE, |rs, addr| Instruction::StAbs(addr, rs)),
+    (STB_ABS_BASE_OPCODE, |rs, addr| {
+        Instruction::StBAbs(addr, rs)
+    }),
+];
+
+// opcodes followed by a 16 bit immediate or address
+const WORD_OPCODES: [(u8, WordOp); 11] = [
+    (JMP_IMM_OPCODE, Instruction::JmpI),
+    (CALL_IMM_OPCODE, Instruction::CallI),
+    (PUSH_IMM_OPCODE, Instruction::PushI),
+    (ADDI_ACC_OPCODE, Instruction::AddAccI),
+    (SUBI_ACC_OPCODE, Instruction::SubAccI),
+    (ANDI_ACC_OPCODE, Instruction::AndAccI),
+    (ORI_ACC_OPCODE, Instruction::OrAccI),
+    (XORI_ACC_OPCODE, Instruction::XorAccI),
+    (CMPI_ACC_OPCODE, Instruction::CmpAccI),
+    (ADCI_ACC_OPCODE, Instruction::AdcAccI),
+    (SBCI_ACC_OPCODE, Instruction::SbcAccI),
+];
+
+// opcodes followed by a signed 8 bit offset
+const OFFSET_OPCODES: [(u8, WordOp); 3] = [
+    (JR_OPCODE, Instruction::JrI),
+    (DJNZ_OPCODE, Instruction::Djnz),
+    (ADD_SP_OPCODE, Instruction::AddSp),
+];
+
+// FD prefixed sub-opcodes with the register in the low 3 bits
+const FD_REG_SUB_OPCODES: [(u8, RegOp); 13] = [
+    (SRA_BASE_SUB_OPCODE, Instruction::Sra),
+    (SHL_BASE_SUB_OPCODE, Instruction::Shl),
+    (SHR_BASE_SUB_OPCODE, Instruction::Shr),
+    (ROL_BASE_SUB_OPCODE, Instruction::Rol),
+    (ROR_BASE_SUB_OPCODE, Instruction::Ror),
+    (ADDB_BASE_SUB_OPCODE, Instruction::AddBAcc),
+    (SUBB_BASE_SUB_OPCODE, Instruction::SubBAcc),
+    (ANDB_BASE_SUB_OPCODE, Instruction::AndBAcc),
+    (ORB_BASE_SUB_OPCODE, Instruction::OrBAcc),
+    (XORB_BASE_SUB_OPCODE, Instruction::XorBAcc),
+    (CMPB_BASE_SUB_OPCODE, Instruction::CmpBAcc),
+    (DEC_BASE_SUB_OPCODE, Instruction::Dec),
+    (INC_BASE_SUB_OPCODE, Instruction::Inc),
+];
+
+// FD prefixed sub-opcodes with the bit ID in the low 3 bits, followed by a register byte
+const FD_BIT_REG_SUB_OPCODES: [(u8, BitRegOp); 6] = [
+    (BIT_REG_BASE_SUB_OPCODE, Instruction::BitReg),
+    (SET_REG_BASE_SUB_OPCODE, Instruction::SetReg),
+    (RES_REG_BASE_SUB_OPCODE, Instruction::ResReg),
+    (BIT_INDIR_BASE_SUB_OPCODE, Instruction::BitIndirect),
+    (SET_INDIR_BASE_SUB_OPCODE, Instruction::SetIndirect),
+    (RES_INDIR_BASE_SUB_OPCODE, Instruction::ResIndirect),
+];
+
+// FD prefixed sub-opcodes with the bit ID in the low 3 bits, followed by an address
+const FD_BIT_ABS_SUB_OPCODES: [(u8, BitAbsOp); 3] = [
+    (BIT_ABS_BASE_SUB_OPCODE, Instruction::BitAbs),
+    (SET_ABS_BASE_SUB_OPCODE, Instruction::SetAbs),
+    (RES_ABS_BASE_SUB_OPCODE, Instruction::ResAbs),
+];
+
+// FE prefixed sub-opcodes, b'xxdddsss
+const FE_SUB_OPCODES: [(u8, RegRegOp); 4] = [
+    (LD_INDIR_BASE_SUB_OPCODE, Instruction::LdIndirect),
+    (ST_INDIR_BASE_SUB_OPCODE, Instruction::StIndirect),
+    (LDB_INDIR_BASE_SUB_OPCODE, Instruction::LdBIndirect),
+    (STB_INDIR_BASE_SUB_OPCODE, Instruction::StBIndirect),
+];
+
+// FF prefixed sub-opcodes, b'xxdddsss followed by a signed 8 bit offset
+const FF_INDEX_SUB_OPCODES: [(u8, IndexOp); 3] = [
+    (LD_INDEX_BASE_SUB_OPCODE, Instruction::LdIndexed),
+    (ST_INDEX_BASE_SUB_OPCODE, |rd, rs, offset| {
+        Instruction::StIndexed(rd, offset, rs)
+    }),
+    (LEA_BASE_SUB_OPCODE, Instruction::Lea),
+];
+
+// FF prefixed sub-opcodes with rs in the low 3 bits, followed by an rd register byte
+const FF_STEP_SUB_OPCODES: [(u8, RegRegOp); 8] = [
+    (LD_POST_INC_BASE_SUB_OPCODE, Instruction::LdPostInc),
+    (ST_POST_INC_BASE_SUB_OPCODE, Instruction::StPostInc),
+    (LD_PRE_DEC_BASE_SUB_OPCODE, Instruction::LdPreDec),
+    (ST_PRE_DEC_BASE_SUB_OPCODE, Instruction::StPreDec),
+    (LDB_POST_INC_BASE_SUB_OPCODE, Instruction::LdBPostInc),
+    (STB_POST_INC_BASE_SUB_OPCODE, Instruction::StBPostInc),
+    (LDB_PRE_DEC_BASE_SUB_OPCODE, Instruction::LdBPreDec),
+    (STB_PRE_DEC_BASE_SUB_OPCODE, Instruction::StBPreDec),
+];
+
+/// Decode the instruction at the start of `bytes`, returning it with its size in bytes. Returns
+/// `None` for an undefined opcode, or when `bytes` ends before the instruction does.
+///
+/// Operands are decoded as `Operand::Immediate` values: 16 bit immediates and addresses as
+/// unsigned values, relative jump offsets and index offsets as signed values, so that encoding
+/// the instruction again gives back the same bytes.
+pub fn decode(bytes: &[u8]) -> Option<(Instruction, usize)> {
+    let (&opcode, operands) = bytes.split_first()?;
+
+    let (instruction, operand_size) = match opcode {
+        FD_PREFIX => decode_fd(operands)?,
+        FE_PREFIX => decode_fe(operands)?,
+        FF_PREFIX => decode_ff(operands)?,
+        _ => decode_main(opcode, operands)?,
+    };
+
+    Some((instruction, operand_size + 1))
+}
+
+fn decode_main(opcode: u8, operands: &[u8]) -> Option<(Instruction, usize)> {
+    if let Some((_, instruction)) = SINGLE_OPCODES.iter().find(|(op, _)| *op == opcode) {
+        return Some((instruction.clone(), 0));
+    }
+
+    if let Some((base, build)) =
+        find_reg_opcode(&REG_OPCODES, opcode).or_else(|| find_reg_opcode(&STACK_OPCODES, opcode))
+    {
+        return Some((build(register(opcode - base)?), 0));
+    }
+
+    // LD rd, rs uses every opcode from 0x80 to 0xBF, b'10dddsss
+    if opcode & 0xC0 == LD_REG_REG_BASE_OPCODE {
+        let (rd, rs) = register_pair(opcode)?;
+        return Some((Instruction::LdReg(rd, rs), 0));
+    }
+
+    if let Some((_, build)) = REG_REG_OPCODES.iter().find(|(op, _)| *op == opcode) {
+        let byte = *operands.first()?;
+        if byte & 0xC0 != 0 {
+            return None;
+        }
+        let (rd, rs) = register_pair(byte)?;
+        return Some((build(rd, rs), 1));
+    }
+
+    if let Some((_, build)) = REG_IMM_OPCODES.iter().find(|(op, _)| *op == opcode) {
+        let rd = register(*operands.first()?)?;
+        return Some((build(rd, word(operands.get(1..)?)?), 3));
+    }
+
+    if let Some((base, build)) = find_reg_opcode(&REG_WORD_OPCODES, opcode) {
+        let r = register(opcode - base)?;
+        return Some((build(r, word(operands)?), 2));
+    }
+
+    if let Some((_, build)) = WORD_OPCODES.iter().find(|(op, _)| *op == opcode) {
+        return Some((build(word(operands)?), 2));
+    }
+
+    if let Some((_, build)) = OFFSET_OPCODES.iter().find(|(op, _)| *op == opcode) {
+        return Some((build(signed_byte(operands)?), 1));
+    }
+
+    if opcode == SYSCALL_OPCODE {
+        return Some((Instruction::Syscall(unsigned_byte(operands)?), 1));
+    }
+
+    let cc_opcodes: [(u8, CcOp, usize); 3] = [
+        (JCC_BASE_OPCODE, Instruction::JccI, 2),
+        (JRCC_BASE_OPCODE, Instruction::JrccI, 1),
+        (CALLCC_BASE_OPCODE, Instruction::CallccI, 2),
+    ];
+    let (base, build, size) = cc_opcodes
+        .into_iter()
+        .find(|(base, _, _)| opcode.wrapping_sub(*base) < 8)?;
+    let cc = condition_code(opcode - base)?;
+    let operand = match size {
+        1 => signed_byte(operands)?,
+        _ => word(operands)?,
+    };
+    Some((build(cc, operand), size))
+}
+
+// FD prefix, bit, byte and shift operations
+fn decode_fd(operands: &[u8]) -> Option<(Instruction, usize)> {
+    let (&sub_opcode, operands) = operands.split_first()?;
+
+    if let Some((base, build)) = find_reg_opcode(&FD_REG_SUB_OPCODES, sub_opcode) {
+        return Some((build(register(sub_opcode - base)?), 1));
+    }
+
+    if let Some((base, build)) = find_reg_opcode(&FD_BIT_REG_SUB_OPCODES, sub_opcode) {
+        let bit = Operand::Immediate((sub_opcode - base) as i32);
+        return Some((build(register(*operands.first()?)?, bit), 2));
+    }
+
+    if let Some((base, build)) = find_reg_opcode(&FD_BIT_ABS_SUB_OPCODES, sub_opcode) {
+        let bit = Operand::Immediate((sub_opcode - base) as i32);
+        return Some((build(word(operands)?, bit), 3));
+    }
+
+    if sub_opcode.wrapping_sub(LDIB_BASE_SUB_OPCODE) < 8 {
+        let rd = register(sub_opcode - LDIB_BASE_SUB_OPCODE)?;
+        return Some((Instruction::LdiB(rd, unsigned_byte(operands)?), 2));
+    }
+
+    None
+}
+
+// FE prefix, register indirect loads and stores
+fn decode_fe(operands: &[u8]) -> Option<(Instruction, usize)> {
+    let sub_opcode = *operands.first()?;
+    let (_, build) = FE_SUB_OPCODES
+        .iter()
+        .find(|(base, _)| sub_opcode & 0xC0 == *base)?;
+    let (rd, rs) = register_pair(sub_opcode)?;
+    Some((build(rd, rs), 1))
+}
+
+// FF prefix, indexed, pre-decrement and post-increment loads and stores
+fn decode_ff(operands: &[u8]) -> Option<(Instruction, usize)> {
+    let (&sub_opcode, operands) = operands.split_first()?;
+
+    if let Some((_, build)) = FF_INDEX_SUB_OPCODES
+        .iter()
+        .find(|(base, _)| sub_opcode & 0xC0 == *base)
+    {
+        let (rd, rs) = register_pair(sub_opcode)?;
+        return Some((build(rd, rs, signed_byte(operands)?), 2));
+    }
+
+    let (base, build) = find_reg_opcode(&FF_STEP_SUB_OPCODES, sub_opcode)?;
+    let rd = register(*operands.first()?)?;
+    let rs = register(sub_opcode - base)?;
+    Some((build(rd, rs), 2))
+}
+
+// find the group of 8 opcodes that the opcode belongs to
+fn find_reg_opcode<T: Copy>(opcodes: &[(u8, T)], opcode: u8) -> Option<(u8, T)> {
+    opcodes
+        .iter()
+        .find(|(base, _)| opcode.wrapping_sub(*base) < 8)
+        .copied()
+}
+
+// a register index, any higher bits must be clear
+fn register(index: u8) -> Option<Register> {
+    match index {
+        0 => Some(Register::R0),
+        1 => Some(Register::R1),
+        2 => Some(Register::R2),
+        3 => Some(Register::R3),
+        4 => Some(Register::R4),
+        5 => Some(Register::R5),
+        6 => Some(Register::R6),
+        7 => Some(Register::R7),
+        _ => None,
+    }
+}
+
+// the rd and rs registers of a b'xxdddsss byte
+fn register_pair(byte: u8) -> Option<(Register, Register)> {
+    Some((register((byte >> 3) & 0x07)?, register(byte & 0x07)?))
+}
+
+fn condition_code(offset: u8) -> Option<ConditionCode> {
+    match offset {
+        0 => Some(ConditionCode::V),
+        1 => Some(ConditionCode::Nv),
+        2 => Some(ConditionCode::N),
+        3 => Some(ConditionCode::Nn),
+        4 => Some(ConditionCode::C),
+        5 => Some(ConditionCode::Nc),
+        6 => Some(ConditionCode::Z),
+        7 => Some(ConditionCode::Nz),
+        _ => None,
+    }
+}
+
+fn word(bytes: &[u8]) -> Option<Operand> {
+    let bytes: [u8; 2] = bytes.get(..2)?.try_into().ok()?;
+    Some(Operand::Immediate(u16::from_le_bytes(bytes) as i32))
+}
+
+fn unsigned_byte(bytes: &[u8]) -> Option<Operand> {
+    Some(Operand::Immediate(*bytes.first()? as i32))
+}
+
+fn signed_byte(bytes: &[u8]) -> Option<Operand> {
+    Some(Operand::Immediate(*bytes.first()? as i8 as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::encoder::{calculate_instruction_size, encode_instruction};
+    use crate::assembler::{Symbol, SymbolTable};
+    use crate::ast::BranchForm;
+    use std::collections::HashSet;
+
+    const REGISTERS: [Register; 8] = [
+        Register::R0,
+        Register::R1,
+        Register::R2,
+        Register::R3,
+        Register::R4,
+        Register::R5,
+        Register::R6,
+        Register::R7,
+    ];
+    const CONDITION_CODES: [ConditionCode; 8] = [
+        ConditionCode::V,
+        ConditionCode::Nv,
+        ConditionCode::N,
+        ConditionCode::Nn,
+        ConditionCode::C,
+        ConditionCode::Nc,
+        ConditionCode::Z,
+        ConditionCode::Nz,
+    ];
+    const WORDS: [i32; 10] = [
+        0, 1, 0x7F, 0x80, 0xFF, 0x100, 0x1234, 0x7FFF, 0x8000, 0xFFFF,
+    ];
+    const BYTES: [i32; 5] = [0, 1, 0x7F, 0x80, 0xFF];
+    const OFFSETS: [i32; 5] = [-128, -1, 0, 1, 127];
+    const BITS: [i32; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+
+    fn imm(value: i32) -> Operand {
+        Operand::Immediate(value)
+    }
+
+    #[test]
+    fn test_decode_main_map() {
+        assert_eq!(decode(&[0x00]), Some((Instruction::Nop, 1)));
+        assert_eq!(
+            decode(&[0x02, 0x34, 0x12]),
+            Some((Instruction::Ldi(Register::R1, imm(0x1234)), 3))
+        );
+        assert_eq!(
+            decode(&[0x93]),
+            Some((Instruction::LdReg(Register::R2, Register::R3), 1))
+        );
+        assert_eq!(
+            decode(&[0x6A, 0xFE]),
+            Some((Instruction::JrccI(ConditionCode::Nz, imm(-2)), 2))
+        );
+        assert_eq!(
+            decode(&[0xF3, 0x00, 0xC0]),
+            Some((Instruction::StAbs(imm(0xC000), Register::R2), 3))
+        );
+    }
+
+    #[test]
+    fn test_decode_prefix_maps() {
+        assert_eq!(
+            decode(&[0xFD, 0x73, 0x02, 0xC0]),
+            Some((Instruction::BitAbs(imm(0xC002), imm(3)), 4))
+        );
+        assert_eq!(
+            decode(&[0xFE, 0x4A]),
+            Some((Instruction::StIndirect(Register::R1, Register::R2), 2))
+        );
+        assert_eq!(
+            decode(&[0xFF, 0x4A, 0xFE]),
+            Some((
+                Instruction::StIndexed(Register::R1, imm(-2), Register::R2),
+                3
+            ))
+        );
+        assert_eq!(
+            decode(&[0xFF, 0xE2, 0x03]),
+            Some((Instruction::LdBPostInc(Register::R3, Register::R2), 3))
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert_eq!(decode(&[]), None);
+        assert_eq!(decode(&[0x02, 0x34]), None); // truncated
+        assert_eq!(decode(&[0xFD, 0xB8]), None); // undefined sub-opcode
+        assert_eq!(decode(&[0x10, 0x43]), None); // register byte with high bits set
+        assert_eq!(decode(&[0x09, 0x08, 0x00, 0x00]), None); // register index out of range
+        assert_eq!(decode(&[0xFF, 0xC0, 0x08]), None);
+    }
+
+    // every decodable instruction, with every register, condition code, bit ID and a range of
+    // immediate values
+    fn every_instruction() -> Vec<Instruction> {
+        let mut all: Vec<Instruction> = SINGLE_OPCODES.iter().map(|(_, i)| i.clone()).collect();
+
+        let reg_ops = REG_OPCODES
+            .iter()
+            .chain(&STACK_OPCODES)
+            .chain(&FD_REG_SUB_OPCODES);
+        for (_, build) in reg_ops {
+            all.extend(REGISTERS.map(build));
+        }
+
+        let reg_reg_ops = REG_REG_OPCODES
+            .iter()
+            .chain(&FE_SUB_OPCODES)
+            .chain(&FF_STEP_SUB_OPCODES);
+        let ld_reg: RegRegOp = Instruction::LdReg;
+        for build in reg_reg_ops.map(|(_, build)| build).chain([&ld_reg]) {
+            for rd in REGISTERS {
+                all.extend(REGISTERS.map(|rs| build(rd, rs)));
+            }
+        }
+
+        for (_, build) in REG_IMM_OPCODES.iter().chain(&REG_WORD_OPCODES) {
+            for r in REGISTERS {
+                all.extend(WORDS.map(|w| build(r, Operand::Immediate(w))));
+            }
+        }
+
+        for (_, build) in &WORD_OPCODES {
+            all.extend(WORDS.map(|w| build(Operand::Immediate(w))));
+        }
+        for (_, build) in &OFFSET_OPCODES {
+            all.extend(OFFSETS.map(|o| build(Operand::Immediate(o))));
+        }
+        all.extend(BYTES.map(|b| Instruction::Syscall(Operand::Immediate(b))));
+
+        for cc in CONDITION_CODES {
+            for w in WORDS {
+                all.push(Instruction::JccI(cc.clone(), Operand::Immediate(w)));
+                all.push(Instruction::CallccI(cc.clone(), Operand::Immediate(w)));
+            }
+            for o in OFFSETS {
+                all.push(Instruction::JrccI(cc.clone(), Operand::Immediate(o)));
+            }
+        }
+
+        for bit in BITS.map(Operand::Immediate) {
+            for (_, build) in &FD_BIT_REG_SUB_OPCODES {
+                all.extend(REGISTERS.map(|r| build(r, bit.clone())));
+            }
+            for (_, build) in &FD_BIT_ABS_SUB_OPCODES {
+                all.extend(WORDS.map(|w| build(Operand::Immediate(w), bit.clone())));
+            }
+        }
+
+        for r in REGISTERS {
+            all.extend(BYTES.map(|b| Instruction::LdiB(r, Operand::Immediate(b))));
+        }
+
+        for (_, build) in &FF_INDEX_SUB_OPCODES {
+            for rd in REGISTERS {
+                for rs in REGISTERS {
+                    all.extend(OFFSETS.map(|o| build(rd, rs, Operand::Immediate(o))));
+                }
+            }
+        }
+
+        all
+    }
+
+    // whether the disassembler can produce a variant, far and relaxed branches are assembled from
+    // other instructions. There is no wildcard arm, so a new variant does not compile until it is
+    // sorted into one of the two groups, and test_round_trip_every_encoding then checks that
+    // every_instruction covers it
+    fn is_decodable(instruction: &Instruction) -> bool {
+        match instruction {
+            Instruction::Nop
+            | Instruction::Halt
+            | Instruction::Ei
+            | Instruction::Di
+            | Instruction::Ret
+            | Instruction::Reti
+            | Instruction::Ccf
+            | Instruction::Scf
+            | Instruction::Rcf
+            | Instruction::Enter
+            | Instruction::Leave
+            | Instruction::LdReg(..)
+            | Instruction::Ldi(..)
+            | Instruction::LdIndirect(..)
+            | Instruction::LdAbs(..)
+            | Instruction::LdIndexed(..)
+            | Instruction::LdPreDec(..)
+            | Instruction::LdPostInc(..)
+            | Instruction::StIndirect(..)
+            | Instruction::StAbs(..)
+            | Instruction::StIndexed(..)
+            | Instruction::StPreDec(..)
+            | Instruction::StPostInc(..)
+            | Instruction::LdiB(..)
+            | Instruction::LdBIndirect(..)
+            | Instruction::LdBPreDec(..)
+            | Instruction::LdBPostInc(..)
+            | Instruction::LdBAbs(..)
+            | Instruction::StBIndirect(..)
+            | Instruction::StBPreDec(..)
+            | Instruction::StBPostInc(..)
+            | Instruction::StBAbs(..)
+            | Instruction::Lea(..)
+            | Instruction::Push(..)
+            | Instruction::Pop(..)
+            | Instruction::PushI(..)
+            | Instruction::PushF
+            | Instruction::PopF
+            | Instruction::AddAcc(..)
+            | Instruction::SubAcc(..)
+            | Instruction::AndAcc(..)
+            | Instruction::OrAcc(..)
+            | Instruction::XorAcc(..)
+            | Instruction::CmpAcc(..)
+            | Instruction::NegAcc
+            | Instruction::NotAcc
+            | Instruction::SwapAcc
+            | Instruction::AddAccI(..)
+            | Instruction::SubAccI(..)
+            | Instruction::AndAccI(..)
+            | Instruction::OrAccI(..)
+            | Instruction::XorAccI(..)
+            | Instruction::CmpAccI(..)
+            | Instruction::AdcAccI(..)
+            | Instruction::SbcAccI(..)
+            | Instruction::AddReg(..)
+            | Instruction::SubReg(..)
+            | Instruction::AndReg(..)
+            | Instruction::OrReg(..)
+            | Instruction::XorReg(..)
+            | Instruction::CmpReg(..)
+            | Instruction::AdcReg(..)
+            | Instruction::SbcReg(..)
+            | Instruction::AddIReg(..)
+            | Instruction::SubIReg(..)
+            | Instruction::AndIReg(..)
+            | Instruction::OrIReg(..)
+            | Instruction::XorIReg(..)
+            | Instruction::CmpIReg(..)
+            | Instruction::AddSp(..)
+            | Instruction::Inc(..)
+            | Instruction::Dec(..)
+            | Instruction::AddBAcc(..)
+            | Instruction::SubBAcc(..)
+            | Instruction::AndBAcc(..)
+            | Instruction::OrBAcc(..)
+            | Instruction::XorBAcc(..)
+            | Instruction::CmpBAcc(..)
+            | Instruction::Sra(..)
+            | Instruction::Shl(..)
+            | Instruction::Shr(..)
+            | Instruction::Rol(..)
+            | Instruction::Ror(..)
+            | Instruction::BitReg(..)
+            | Instruction::SetReg(..)
+            | Instruction::ResReg(..)
+            | Instruction::BitAbs(..)
+            | Instruction::SetAbs(..)
+            | Instruction::ResAbs(..)
+            | Instruction::BitIndirect(..)
+            | Instruction::SetIndirect(..)
+            | Instruction::ResIndirect(..)
+            | Instruction::JmpI(..)
+            | Instruction::JmpIndirect(..)
+            | Instruction::JrI(..)
+            | Instruction::JccI(..)
+            | Instruction::JrccI(..)
+            | Instruction::Djnz(..)
+            | Instruction::CallI(..)
+            | Instruction::CallIndirect(..)
+            | Instruction::CallccI(..)
+            | Instruction::Syscall(..) => true,
+            Instruction::CallFar(..)
+            | Instruction::CallFarVia(..)
+            | Instruction::JmpFar(..)
+            | Instruction::JmpFarVia(..)
+            | Instruction::JmpAuto(..)
+            | Instruction::JccAuto(..)
+            | Instruction::DjnzAuto(..) => false,
+        }
+    }
+
+    fn encode(instruction: &Instruction, symbol_table: &SymbolTable) -> Vec<u8> {
+        encode_instruction(instruction, symbol_table, &0x1000, &0, &0)
+            .unwrap_or_else(|e| panic!("{:?} failed to encode: {}", instruction, e))
+    }
+
+    #[test]
+    fn test_round_trip_every_instruction() {
+        let symbol_table = SymbolTable::new();
+        for instruction in every_instruction() {
+            assert!(is_decodable(&instruction), "{:?}", instruction);
+            let bytes = encode(&instruction, &symbol_table);
+            assert_eq!(
+                bytes.len() as u32,
+                calculate_instruction_size(&instruction),
+                "size of {:?}",
+                instruction
+            );
+            assert_eq!(
+                decode(&bytes),
+                Some((instruction.clone(), bytes.len())),
+                "decoding {:?} from {:02x?}",
+                instruction,
+                bytes
+            );
+        }
+    }
+
+    #[test]
+    fn test_round_trip_every_encoding() {
+        // every opcode and sub-opcode, with operand bytes that are valid and invalid registers
+        let symbol_table = SymbolTable::new();
+        let operand_bytes = [0x00, 0x05, 0x07, 0x08, 0x80, 0xFF];
+        let mut decoded = 0;
+        let mut variants = HashSet::new();
+
+        for first in 0..=0xFF {
+            for second in 0..=0xFF {
+                for third in operand_bytes {
+                    for fourth in operand_bytes {
+                        let bytes = [first, second, third, fourth];
+                        let Some((instruction, size)) = decode(&bytes) else {
+                            continue;
+                        };
+                        assert_eq!(size as u32, calculate_instruction_size(&instruction));
+                        assert_eq!(
+                            encode(&instruction, &symbol_table),
+                            bytes[..size],
+                            "encoding {:?}",
+                            instruction
+                        );
+                        assert!(is_decodable(&instruction), "{:?}", instruction);
+                        decoded += 1;
+                        variants.insert(std::mem::discriminant(&instruction));
+                    }
+                }
+            }
+        }
+
+        // every opcode except the 8 undefined FD sub-opcode blocks decodes with some operands
+        assert!(decoded > 0);
+
+        // every_instruction has every variant the decoder produces, and nothing else
+        let covered: HashSet<_> = every_instruction()
+            .iter()
+            .map(std::mem::discriminant)
+            .collect();
+        assert_eq!(covered, variants);
+        for opcode in 0..=0xFFu8 {
+            let decodable = (0..=0xFF).any(|second| decode(&[opcode, second, 0, 0]).is_some());
+            assert!(decodable, "opcode 0x{:02x} never decodes", opcode);
+        }
+        for sub_opcode in 0..=0xFFu8 {
+            let decodable = decode(&[FD_PREFIX, sub_opcode, 0, 0]).is_some();
+            assert_eq!(
+                decodable,
+                sub_opcode < 0xB8,
+                "FD sub-opcode 0x{:02x}",
+                sub_opcode
+            );
+        }
+    }
+
+    #[test]
+    fn test_far_and_relaxed_branch_sizes() {
+        // these are assembled from other instructions, so only their size can be checked
+        let mut symbol_table = SymbolTable::new();
+        let symbols = [("far", 1, 0x4000), ("via", 0, 0x0200), ("near", 0, 0x1010)];
+        for (name, bank, logical_address) in symbols {
+            let symbol = Symbol {
+                logical_address,
+                bank,
+            };
+            symbol_table.insert(name.to_string(), symbol);
+        }
+
+        let near = || Operand::Label("near".to_string());
+        let mut instructions = vec![
+            Instruction::CallFar("far".to_string()),
+            Instruction::CallFarVia("far".to_string(), "via".to_string()),
+            Instruction::JmpFar("far".to_string()),
+            Instruction::JmpFarVia("far".to_string(), "via".to_string()),
+        ];
+        for form in [BranchForm::Short, BranchForm::Long] {
+            instructions.push(Instruction::JmpAuto(near(), form));
+            instructions.push(Instruction::DjnzAuto(near(), form));
+            for cc in CONDITION_CODES {
+                instructions.push(Instruction::JccAuto(cc, near(), form));
+            }
+        }
+
+        for instruction in instructions {
+            let bytes = encode(&instruction, &symbol_table);
+            assert_eq!(
+                bytes.len() as u32,
+                calculate_instruction_size(&instruction),
+                "size of {:?}",
+                instruction
+            );
+
+            // and the instructions they are assembled from decode back
+            let mut offset = 0;
+            while offset < bytes.len() {
+                let (_, size) = decode(&bytes[offset..]).unwrap();
+                offset += size;
+            }
+            assert_eq!(offset, bytes.len());
+        }
+    }
+}
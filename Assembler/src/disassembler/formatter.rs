@@ -0,0 +1,195 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::ast::{ConditionCode, Instruction, Operand, Register};
+
+/// Render an instruction in the syntax accepted by cicasm. Immediate operands are written the
+/// way `decoder::decode` produces them, addresses may also be given as `Operand::Label`.
+pub fn format_instruction(instruction: &Instruction) -> String {
+    match instruction {
+        Instruction::Nop => "NOP".to_string(),
+        Instruction::Halt => "HALT".to_string(),
+        Instruction::Ei => "EI".to_string(),
+        Instruction::Di => "DI".to_string(),
+        Instruction::Ret => "RET".to_string(),
+        Instruction::Reti => "RETI".to_string(),
+        Instruction::Ccf => "CCF".to_string(),
+        Instruction::Scf => "SCF".to_string(),
+        Instruction::Rcf => "RCF".to_string(),
+        Instruction::Enter => "ENTER".to_string(),
+        Instruction::Leave => "LEAVE".to_string(),
+
+        // load and store
+        Instruction::LdReg(rd, rs) => format!("LD {}, {}", reg(rd), reg(rs)),
+        Instruction::Ldi(rd, imm) => format!("LDI {}, {}", reg(rd), word(imm)),
+        Instruction::LdIndirect(rd, rs) => format!("LD {}, ({})", reg(rd), reg(rs)),
+        Instruction::LdAbs(rd, addr) => format!("LD {}, ({})", reg(rd), word(addr)),
+        Instruction::LdIndexed(rd, rs, offset) => {
+            format!("LD {}, ({}, {})", reg(rd), reg(rs), signed(offset))
+        }
+        Instruction::LdPreDec(rd, rs) => format!("LD {}, -({})", reg(rd), reg(rs)),
+        Instruction::LdPostInc(rd, rs) => format!("LD {}, ({})+", reg(rd), reg(rs)),
+        Instruction::StIndirect(rd, rs) => format!("ST ({}), {}", reg(rd), reg(rs)),
+        Instruction::StAbs(addr, rs) => format!("ST ({}), {}", word(addr), reg(rs)),
+        Instruction::StIndexed(rd, offset, rs) => {
+            format!("ST ({}, {}), {}", reg(rd), signed(offset), reg(rs))
+        }
+        Instruction::StPreDec(rd, rs) => format!("ST -({}), {}", reg(rd), reg(rs)),
+        Instruction::StPostInc(rd, rs) => format!("ST ({})+, {}", reg(rd), reg(rs)),
+        Instruction::LdiB(rd, imm) => format!("LDI.b {}, {}", reg(rd), byte(imm)),
+        Instruction::LdBIndirect(rd, rs) => format!("LD.b {}, ({})", reg(rd), reg(rs)),
+        Instruction::LdBPreDec(rd, rs) => format!("LD.b {}, -({})", reg(rd), reg(rs)),
+        Instruction::LdBPostInc(rd, rs) => format!("LD.b {}, ({})+", reg(rd), reg(rs)),
+        Instruction::LdBAbs(rd, addr) => format!("LD.b {}, ({})", reg(rd), word(addr)),
+        Instruction::StBIndirect(rd, rs) => format!("ST.b ({}), {}", reg(rd), reg(rs)),
+        Instruction::StBPreDec(rd, rs) => format!("ST.b -({}), {}", reg(rd), reg(rs)),
+        Instruction::StBPostInc(rd, rs) => format!("ST.b ({})+, {}", reg(rd), reg(rs)),
+        Instruction::StBAbs(addr, rs) => format!("ST.b ({}), {}", word(addr), reg(rs)),
+        Instruction::Lea(rd, rs, offset) => {
+            format!("LEA {}, ({}, {})", reg(rd), reg(rs), signed(offset))
+        }
+
+        // stack
+        Instruction::Push(r) => format!("PUSH {}", reg(r)),
+        Instruction::Pop(r) => format!("POP {}", reg(r)),
+        Instruction::PushI(imm) => format!("PUSH {}", word(imm)),
+        Instruction::PushF => "PUSH F".to_string(),
+        Instruction::PopF => "POP F".to_string(),
+
+        // accumulator
+        Instruction::AddAcc(r) => format!("ADD {}", reg(r)),
+        Instruction::SubAcc(r) => format!("SUB {}", reg(r)),
+        Instruction::AndAcc(r) => format!("AND {}", reg(r)),
+        Instruction::OrAcc(r) => format!("OR {}", reg(r)),
+        Instruction::XorAcc(r) => format!("XOR {}", reg(r)),
+        Instruction::CmpAcc(r) => format!("CMP {}", reg(r)),
+        Instruction::NegAcc => "NEG".to_string(),
+        Instruction::NotAcc => "NOT".to_string(),
+        Instruction::SwapAcc => "SWAP".to_string(),
+        Instruction::AddAccI(imm) => format!("ADDI {}", word(imm)),
+        Instruction::SubAccI(imm) => format!("SUBI {}", word(imm)),
+        Instruction::AndAccI(imm) => format!("ANDI {}", word(imm)),
+        Instruction::OrAccI(imm) => format!("ORI {}", word(imm)),
+        Instruction::XorAccI(imm) => format!("XORI {}", word(imm)),
+        Instruction::CmpAccI(imm) => format!("CMPI {}", word(imm)),
+        Instruction::AdcAccI(imm) => format!("ADCI {}", word(imm)),
+        Instruction::SbcAccI(imm) => format!("SBCI {}", word(imm)),
+        Instruction::AddBAcc(r) => format!("ADD.b {}", reg(r)),
+        Instruction::SubBAcc(r) => format!("SUB.b {}", reg(r)),
+        Instruction::AndBAcc(r) => format!("AND.b {}", reg(r)),
+        Instruction::OrBAcc(r) => format!("OR.b {}", reg(r)),
+        Instruction::XorBAcc(r) => format!("XOR.b {}", reg(r)),
+        Instruction::CmpBAcc(r) => format!("CMP.b {}", reg(r)),
+
+        // register arithmetic
+        Instruction::AddReg(rd, rs) => format!("ADD {}, {}", reg(rd), reg(rs)),
+        Instruction::SubReg(rd, rs) => format!("SUB {}, {}", reg(rd), reg(rs)),
+        Instruction::AndReg(rd, rs) => format!("AND {}, {}", reg(rd), reg(rs)),
+        Instruction::OrReg(rd, rs) => format!("OR {}, {}", reg(rd), reg(rs)),
+        Instruction::XorReg(rd, rs) => format!("XOR {}, {}", reg(rd), reg(rs)),
+        Instruction::CmpReg(rd, rs) => format!("CMP {}, {}", reg(rd), reg(rs)),
+        Instruction::AdcReg(rd, rs) => format!("ADC {}, {}", reg(rd), reg(rs)),
+        Instruction::SbcReg(rd, rs) => format!("SBC {}, {}", reg(rd), reg(rs)),
+        Instruction::AddIReg(rd, imm) => format!("ADDI {}, {}", reg(rd), word(imm)),
+        Instruction::SubIReg(rd, imm) => format!("SUBI {}, {}", reg(rd), word(imm)),
+        Instruction::AndIReg(rd, imm) => format!("ANDI {}, {}", reg(rd), word(imm)),
+        Instruction::OrIReg(rd, imm) => format!("ORI {}, {}", reg(rd), word(imm)),
+        Instruction::XorIReg(rd, imm) => format!("XORI {}, {}", reg(rd), word(imm)),
+        Instruction::CmpIReg(rd, imm) => format!("CMPI {}, {}", reg(rd), word(imm)),
+        Instruction::AddSp(offset) => format!("ADD SP, {}", signed(offset)),
+        Instruction::Inc(r) => format!("INC {}", reg(r)),
+        Instruction::Dec(r) => format!("DEC {}", reg(r)),
+
+        // shifts and bits
+        Instruction::Sra(r) => format!("SRA {}", reg(r)),
+        Instruction::Shl(r) => format!("SHL {}", reg(r)),
+        Instruction::Shr(r) => format!("SHR {}", reg(r)),
+        Instruction::Rol(r) => format!("ROL {}", reg(r)),
+        Instruction::Ror(r) => format!("ROR {}", reg(r)),
+        Instruction::BitReg(r, bit) => format!("BIT {}, {}", reg(r), signed(bit)),
+        Instruction::SetReg(r, bit) => format!("SET {}, {}", reg(r), signed(bit)),
+        Instruction::ResReg(r, bit) => format!("RES {}, {}", reg(r), signed(bit)),
+        Instruction::BitAbs(addr, bit) => format!("BIT ({}), {}", word(addr), signed(bit)),
+        Instruction::SetAbs(addr, bit) => format!("SET ({}), {}", word(addr), signed(bit)),
+        Instruction::ResAbs(addr, bit) => format!("RES ({}), {}", word(addr), signed(bit)),
+        Instruction::BitIndirect(r, bit) => format!("BIT ({}), {}", reg(r), signed(bit)),
+        Instruction::SetIndirect(r, bit) => format!("SET ({}), {}", reg(r), signed(bit)),
+        Instruction::ResIndirect(r, bit) => format!("RES ({}), {}", reg(r), signed(bit)),
+
+        // control flow
+        Instruction::JmpI(target) => format!("JMP {}", word(target)),
+        Instruction::JmpIndirect(r) => format!("JMP ({})", reg(r)),
+        Instruction::JrI(target) => format!("JR {}", signed(target)),
+        Instruction::JccI(cc, target) => format!("J{} {}", condition(cc), word(target)),
+        Instruction::JrccI(cc, target) => format!("JR{} {}", condition(cc), signed(target)),
+        Instruction::Djnz(target) => format!("DJNZ {}", signed(target)),
+        Instruction::CallI(target) => format!("CALL {}", word(target)),
+        Instruction::CallIndirect(r) => format!("CALL ({})", reg(r)),
+        Instruction::CallccI(cc, target) => format!("CALL{} {}", condition(cc), word(target)),
+        Instruction::Syscall(imm) => format!("SYSCALL {}", byte(imm)),
+
+        // never decoded, these are assembled from the instructions above
+        Instruction::CallFar(label) => format!("CALL.far {}", label),
+        Instruction::CallFarVia(label, via) => format!("CALL.far {} via {}", label, via),
+        Instruction::JmpFar(label) => format!("JMP.far {}", label),
+        Instruction::JmpFarVia(label, via) => format!("JMP.far {} via {}", label, via),
+        Instruction::JmpAuto(target, _) => format!("JMP.auto {}", word(target)),
+        Instruction::JccAuto(cc, target, _) => {
+            format!("J{}.auto {}", condition(cc), word(target))
+        }
+        Instruction::DjnzAuto(target, _) => format!("DJNZ.auto {}", word(target)),
+    }
+}
+
+fn reg(r: &Register) -> String {
+    format!("{:?}", r)
+}
+
+fn condition(cc: &ConditionCode) -> String {
+    format!("{:?}", cc).to_uppercase()
+}
+
+// 16 bit immediates and addresses
+fn word(op: &Operand) -> String {
+    match op {
+        Operand::Immediate(value) => format!("0x{:04X}", value),
+        _ => operand(op),
+    }
+}
+
+// 8 bit immediates
+fn byte(op: &Operand) -> String {
+    match op {
+        Operand::Immediate(value) => format!("0x{:02X}", value),
+        _ => operand(op),
+    }
+}
+
+// offsets and bit IDs
+fn signed(op: &Operand) -> String {
+    match op {
+        Operand::Immediate(value) => value.to_string(),
+        _ => operand(op),
+    }
+}
+
+fn operand(op: &Operand) -> String {
+    match op {
+        Operand::Label(name) => name.clone(),
+        Operand::Register(r) => reg(r),
+        _ => format!("{:?}", op),
+    }
+}
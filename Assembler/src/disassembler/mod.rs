@@ -0,0 +1,484 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+pub mod decoder;
+pub mod formatter;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::assembler::{BANK_SIZE, encode_header, verify_rom_checksums};
+use crate::ast::{HeaderInfo, Instruction, Operand};
+use crate::errors::AssemblyError;
+use crate::symbol_file::SymbolFile;
+
+pub use decoder::decode;
+pub use formatter::format_instruction;
+
+const HEADER_SIZE: usize = 0x60;
+const HEADER_CHECKSUMS: std::ops::Range<usize> = 0x29..0x2C;
+const INTERRUPT_TABLE_SIZE: usize = 0x20;
+const CARTRIDGE_INTERRUPT_TABLE_ADDR: u16 = 0x0060;
+const CARTRIDGE_CODE_START: u16 = 0x0080;
+const BOOT_INTERRUPT_TABLE_ADDR: u16 = 0x3FE0;
+const SWITCHABLE_BANK_START: u16 = 0x4000;
+const RAM_START: u32 = 0x8000;
+
+const MIN_FILL_RUN: usize = 16; // runs of identical bytes at least this long become a .fill
+const BYTES_PER_LINE: usize = 8; // bytes per .byte line
+const COMMENT_COLUMN: usize = 32;
+
+/// How a ROM image is laid out.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DisassemblyOptions {
+    pub boot_rom: bool, // a 16 KiB boot ROM, with no header and the interrupt table at 0x3FE0
+}
+
+/// Disassemble a ROM image into source that cicasm assembles back into the same bytes.
+///
+/// Every bank is decoded from start to end. Bytes that are not a valid instruction are written as
+/// `.byte` lines, and long runs of the same byte as `.fill`. When a symbol file is given, its
+/// labels are defined at their addresses and used in place of the addresses of jumps, calls and
+/// absolute loads and stores. RAM labels are written as `.define` constants.
+pub fn disassemble(
+    rom: &[u8],
+    symbols: Option<&SymbolFile>,
+    options: &DisassemblyOptions,
+) -> Result<String, AssemblyError> {
+    let bank_size = BANK_SIZE as usize;
+    if options.boot_rom && rom.len() != bank_size {
+        return Err(AssemblyError::FileError {
+            reason: format!(
+                "A boot ROM must be exactly {} bytes, found {} bytes",
+                bank_size,
+                rom.len()
+            ),
+        });
+    }
+    if !options.boot_rom && rom.len() < CARTRIDGE_CODE_START as usize {
+        return Err(AssemblyError::FileError {
+            reason: format!(
+                "A cartridge ROM must be at least {} bytes, found {} bytes",
+                CARTRIDGE_CODE_START,
+                rom.len()
+            ),
+        });
+    }
+
+    let layout = Layout::new(rom.len(), options);
+    let labels = Labels::new(symbols, &layout);
+    let mut out = Output {
+        text: String::new(),
+        labels: &labels,
+    };
+
+    for (address, name) in &labels.ram {
+        let _ = writeln!(out.text, ".define {} 0x{:04X}", name, address);
+    }
+    if !labels.ram.is_empty() {
+        out.text.push('\n');
+    }
+
+    for (bank, bank_bytes) in rom.chunks(bank_size).enumerate() {
+        let bank = bank as u32;
+        if bank == 0 {
+            if options.boot_rom {
+                out.code(bank, 0, &bank_bytes[..BOOT_INTERRUPT_TABLE_ADDR as usize]);
+                out.interrupt_table(bank_bytes, BOOT_INTERRUPT_TABLE_ADDR);
+            } else {
+                out.header(rom);
+                out.interrupt_table(bank_bytes, CARTRIDGE_INTERRUPT_TABLE_ADDR);
+                let code = &bank_bytes[CARTRIDGE_CODE_START as usize..];
+                out.code(bank, CARTRIDGE_CODE_START, code);
+            }
+        } else {
+            let _ = writeln!(out.text, "\n.bank {}", bank);
+            let _ = writeln!(out.text, ".org 0x{:04X}", SWITCHABLE_BANK_START);
+            out.code(bank, SWITCHABLE_BANK_START, bank_bytes);
+        }
+    }
+
+    Ok(out.text)
+}
+
+// the logical address ranges of the rom that are disassembled as code
+struct Layout {
+    num_banks: u32,
+    code: Vec<(u32, u32)>, // [start, end) per bank
+    blocks: Vec<u32>,      // addresses of the header and interrupt table in bank 0
+}
+
+impl Layout {
+    fn new(rom_len: usize, options: &DisassemblyOptions) -> Layout {
+        let num_banks = rom_len.div_ceil(BANK_SIZE as usize) as u32;
+        let code = (0..num_banks)
+            .map(|bank| {
+                let bank_len = (rom_len - (bank * BANK_SIZE) as usize).min(BANK_SIZE as usize);
+                match (bank, options.boot_rom) {
+                    (0, true) => (0, BOOT_INTERRUPT_TABLE_ADDR as u32),
+                    (0, false) => (CARTRIDGE_CODE_START as u32, bank_len as u32),
+                    _ => (
+                        SWITCHABLE_BANK_START as u32,
+                        SWITCHABLE_BANK_START as u32 + bank_len as u32,
+                    ),
+                }
+            })
+            .collect();
+        let blocks = match options.boot_rom {
+            true => vec![BOOT_INTERRUPT_TABLE_ADDR as u32],
+            false => vec![0, CARTRIDGE_INTERRUPT_TABLE_ADDR as u32],
+        };
+
+        Layout {
+            num_banks,
+            code,
+            blocks,
+        }
+    }
+
+    // a label can be defined at the start of any line, which the code is split at, or before the
+    // header and interrupt table
+    fn can_define(&self, bank: u32, address: u32) -> bool {
+        let Some((start, end)) = self.code.get(bank as usize) else {
+            return false;
+        };
+        (address >= *start && address < *end) || (bank == 0 && self.blocks.contains(&address))
+    }
+}
+
+// the labels of a symbol file that can be defined in the disassembly
+#[derive(Default)]
+struct Labels {
+    definitions: HashMap<(u32, u32), Vec<String>>, // label lines at each rom address
+    references: HashMap<(u32, u32), String>,       // name used for each rom address
+    ram: BTreeMap<u32, String>,                    // .define for each ram address
+}
+
+impl Labels {
+    fn new(symbols: Option<&SymbolFile>, layout: &Layout) -> Labels {
+        let mut labels = Labels::default();
+        let Some(symbols) = symbols else {
+            return labels;
+        };
+
+        // global labels come before the local labels at the same address, so that they are
+        // in scope
+        let mut entries: Vec<_> = symbols.labels.iter().collect();
+        entries.sort_by_key(|l| (l.bank, l.address, l.name.contains('.'), &l.name));
+
+        let mut seen = HashSet::new();
+        let mut scope: Option<&str> = None;
+        for label in entries {
+            if label.address >= RAM_START {
+                if is_identifier(&label.name) && seen.insert(label.name.as_str()) {
+                    labels
+                        .ram
+                        .entry(label.address)
+                        .or_insert(label.name.clone());
+                }
+                continue;
+            }
+
+            let in_bank = match label.bank {
+                0 => label.address < SWITCHABLE_BANK_START as u32,
+                bank => bank < layout.num_banks && label.address >= SWITCHABLE_BANK_START as u32,
+            };
+            if !in_bank || !layout.can_define(label.bank, label.address) {
+                continue;
+            }
+
+            // local labels are stored as "global.local", and can only be defined after their
+            // global label
+            let line = match label.name.split_once('.') {
+                None if is_identifier(&label.name) => {
+                    scope = Some(&label.name);
+                    label.name.clone()
+                }
+                Some((global, local))
+                    if scope == Some(global) && is_identifier(global) && is_identifier(local) =>
+                {
+                    format!(".{}", local)
+                }
+                _ => continue,
+            };
+            if !seen.insert(label.name.as_str()) {
+                continue;
+            }
+
+            let key = (label.bank, label.address);
+            labels.definitions.entry(key).or_default().push(line);
+            labels.references.entry(key).or_insert(label.name.clone());
+        }
+
+        labels
+    }
+
+    // the label for an address used by code in a bank, bank 0 code only sees the fixed bank
+    fn lookup(&self, bank: u32, address: i32) -> Option<&String> {
+        let address = u32::try_from(address).ok()?;
+        if address >= RAM_START {
+            return self.ram.get(&address);
+        }
+        let bank = match address < SWITCHABLE_BANK_START as u32 {
+            true => 0,
+            false if bank == 0 => return None,
+            false => bank,
+        };
+        self.references.get(&(bank, address))
+    }
+}
+
+// a label name that can be defined as is, which register names can not
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_alpha = chars.next().is_some_and(|c| c.is_ascii_alphabetic());
+    let is_register = name.len() == 2
+        && name.starts_with(['r', 'R'])
+        && name.ends_with(|c: char| ('0'..='7').contains(&c));
+    starts_alpha && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && !is_register
+}
+
+struct Output<'a> {
+    text: String,
+    labels: &'a Labels,
+}
+
+impl Output<'_> {
+    fn label_lines(&mut self, bank: u32, address: u32) {
+        if let Some(lines) = self.labels.definitions.get(&(bank, address)) {
+            for line in lines {
+                let _ = writeln!(self.text, "{}:", line);
+            }
+        }
+    }
+
+    // a line followed by a comment with its address and bytes
+    fn line(&mut self, source: &str, bank: u32, address: u32, bytes: &[u8]) {
+        let mut line = format!("    {}", source);
+        if line.len() < COMMENT_COLUMN {
+            line.push_str(&" ".repeat(COMMENT_COLUMN - line.len()));
+        } else {
+            line.push(' ');
+        }
+        let _ = write!(line, "; {:02x}:{:04x}", bank, address);
+        if !bytes.is_empty() {
+            line.push(' ');
+            for byte in bytes {
+                let _ = write!(line, " {:02x}", byte);
+            }
+        }
+        let _ = writeln!(self.text, "{}", line);
+    }
+
+    // the cartridge header, rebuilt from its fields. The checksums are always recalculated by
+    // cicasm, so they are not written
+    fn header(&mut self, rom: &[u8]) {
+        let bytes = &rom[..HEADER_SIZE];
+        let info = decode_header(bytes);
+
+        self.label_lines(0, 0);
+        let encoded = encode_header(&info);
+        let mut matches = encoded.len() == HEADER_SIZE;
+        for (i, (a, b)) in encoded.iter().zip(bytes).enumerate() {
+            matches &= a == b || HEADER_CHECKSUMS.contains(&i);
+        }
+        if !matches {
+            self.text
+                .push_str("; warning: the header contains bytes that cicasm can not reproduce\n");
+        }
+        if rom.len().is_multiple_of(BANK_SIZE as usize) && verify_rom_checksums(rom, 0).is_err() {
+            self.text.push_str(
+                "; warning: the header checksums are wrong, cicasm writes correct ones\n",
+            );
+        }
+
+        self.text.push_str(".header_start\n");
+        let fields = [
+            (".boot_anim", quoted(&info.boot_anim)),
+            (".title", quoted(&info.title)),
+            (".developer", quoted(&info.developer)),
+            (".version", info.version.to_string()),
+            (".rom_size", info.rom_size.to_string()),
+            (".ram_size", info.ram_size.to_string()),
+            (".hardware_rev", info.hardware_rev.to_string()),
+            (".region", info.region.to_string()),
+            (".interrupt_mode", info.interrupt_mode.to_string()),
+            (".mapper", info.mapper.to_string()),
+        ];
+        for (directive, value) in fields {
+            let _ = writeln!(self.text, "    {} {}", directive, value);
+        }
+        self.text.push_str(".header_end\n\n");
+    }
+
+    fn interrupt_table(&mut self, bank_bytes: &[u8], address: u16) {
+        let start = address as usize;
+        let table = &bank_bytes[start..start + INTERRUPT_TABLE_SIZE];
+
+        self.label_lines(0, address as u32);
+        self.text.push_str(".interrupt_table\n");
+        for (i, vector) in table.chunks(2).enumerate() {
+            let value = u16::from_le_bytes([vector[0], vector[1]]);
+            let source = match self.labels.lookup(0, value as i32) {
+                Some(name) => format!(".word {}", name),
+                None => format!(".word 0x{:04X}", value),
+            };
+            self.line(&source, 0, (start + i * 2) as u32, vector);
+        }
+        self.text.push_str(".table_end\n\n");
+    }
+
+    fn code(&mut self, bank: u32, start: u16, bytes: &[u8]) {
+        let start = start as u32;
+        let end = start + bytes.len() as u32;
+
+        // lines are split at every label, so that it can be defined
+        let mut boundaries: Vec<u32> = self
+            .labels
+            .definitions
+            .keys()
+            .filter(|(b, address)| *b == bank && *address > start && *address < end)
+            .map(|(_, address)| *address)
+            .collect();
+        boundaries.sort_unstable();
+        boundaries.push(end);
+
+        let mut pending: Vec<u8> = Vec::new(); // undecodable bytes, written as .byte
+        let mut address = start;
+        let mut next_boundary = 0;
+        while address < end {
+            if boundaries[next_boundary] <= address {
+                next_boundary += 1;
+            }
+            let stop = boundaries[next_boundary];
+            let remaining = &bytes[(address - start) as usize..(stop - start) as usize];
+            let run = remaining.iter().take_while(|b| **b == remaining[0]).count();
+
+            if self.labels.definitions.contains_key(&(bank, address)) {
+                self.bytes(bank, address, &mut pending);
+                self.label_lines(bank, address);
+            }
+
+            if run >= MIN_FILL_RUN {
+                self.bytes(bank, address, &mut pending);
+                let source = format!(".fill {}, 0x{:02X}", run, remaining[0]);
+                self.line(&source, bank, address, &[]);
+                address += run as u32;
+            } else if let Some((instruction, size)) = decoder::decode(remaining) {
+                self.bytes(bank, address, &mut pending);
+                let instruction = self.with_labels(instruction, bank, address);
+                self.line(
+                    &format_instruction(&instruction),
+                    bank,
+                    address,
+                    &remaining[..size],
+                );
+                address += size as u32;
+            } else {
+                pending.push(remaining[0]);
+                address += 1;
+                if pending.len() == BYTES_PER_LINE {
+                    self.bytes(bank, address, &mut pending);
+                }
+            }
+        }
+        self.bytes(bank, address, &mut pending);
+    }
+
+    // write the pending undecodable bytes, which end at address
+    fn bytes(&mut self, bank: u32, address: u32, pending: &mut Vec<u8>) {
+        if pending.is_empty() {
+            return;
+        }
+        let values: Vec<String> = pending.iter().map(|b| format!("0x{:02X}", b)).collect();
+        let source = format!(".byte {}", values.join(", "));
+        let bytes = std::mem::take(pending);
+        self.line(&source, bank, address - bytes.len() as u32, &bytes);
+    }
+
+    // replace the addresses used by an instruction with their labels
+    fn with_labels(&self, instruction: Instruction, bank: u32, address: u32) -> Instruction {
+        let absolute = |op: Operand| match op {
+            Operand::Immediate(value) => match self.labels.lookup(bank, value) {
+                Some(name) => Operand::Label(name.clone()),
+                None => op,
+            },
+            _ => op,
+        };
+        // relative jumps are written as their target when it has a label
+        let relative = |op: Operand| match op {
+            Operand::Immediate(offset) => match self.labels.lookup(bank, address as i32 + offset) {
+                Some(name) => Operand::Label(name.clone()),
+                None => op,
+            },
+            _ => op,
+        };
+
+        match instruction {
+            Instruction::JmpI(op) => Instruction::JmpI(absolute(op)),
+            Instruction::CallI(op) => Instruction::CallI(absolute(op)),
+            Instruction::JccI(cc, op) => Instruction::JccI(cc, absolute(op)),
+            Instruction::CallccI(cc, op) => Instruction::CallccI(cc, absolute(op)),
+            Instruction::LdAbs(rd, op) => Instruction::LdAbs(rd, absolute(op)),
+            Instruction::LdBAbs(rd, op) => Instruction::LdBAbs(rd, absolute(op)),
+            Instruction::StAbs(op, rs) => Instruction::StAbs(absolute(op), rs),
+            Instruction::StBAbs(op, rs) => Instruction::StBAbs(absolute(op), rs),
+            Instruction::BitAbs(op, bit) => Instruction::BitAbs(absolute(op), bit),
+            Instruction::SetAbs(op, bit) => Instruction::SetAbs(absolute(op), bit),
+            Instruction::ResAbs(op, bit) => Instruction::ResAbs(absolute(op), bit),
+            Instruction::JrI(op) => Instruction::JrI(relative(op)),
+            Instruction::JrccI(cc, op) => Instruction::JrccI(cc, relative(op)),
+            Instruction::Djnz(op) => Instruction::Djnz(relative(op)),
+            other => other,
+        }
+    }
+}
+
+// the fields of a cartridge header, text fields end at their first zero byte
+fn decode_header(bytes: &[u8]) -> HeaderInfo {
+    HeaderInfo {
+        boot_anim: bytes[0x00..0x04].iter().map(|b| printable(*b)).collect(),
+        title: text(&bytes[0x04..0x14]),
+        developer: text(&bytes[0x14..0x24]),
+        version: bytes[0x24],
+        rom_size: bytes[0x25],
+        ram_size: bytes[0x26],
+        hardware_rev: bytes[0x27] >> 6,
+        region: (bytes[0x27] >> 3) & 0x7,
+        interrupt_mode: bytes[0x28] >> 7,
+        mapper: (bytes[0x28] >> 5) & 0x3,
+    }
+}
+
+fn text(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|b| **b != 0)
+        .map(|b| printable(*b))
+        .collect()
+}
+
+// characters that can't be written in a string literal are replaced with '?'
+fn printable(byte: u8) -> char {
+    match byte {
+        b' '..=b'~' if byte != b'"' && byte != b'\\' => byte as char,
+        _ => '?',
+    }
+}
+
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text)
+}
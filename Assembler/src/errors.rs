@@ -14,12 +14,37 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::ast::{AssemblyLine, MacroExpansion, Span};
+use std::path::PathBuf;
 use thiserror::Error;
 
+// The source line an error was found on, kept with the error so that it can be shown with the
+// offending operand underlined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    pub line: usize,
+    pub source: String,
+    pub span: Span,
+    pub operand_spans: Vec<Span>,
+}
+
+impl SourceLocation {
+    pub fn of(line: &AssemblyLine) -> Self {
+        Self {
+            file: line.file.clone(),
+            line: line.line_number,
+            source: line.source.clone(),
+            span: line.span,
+            operand_spans: line.operand_spans.clone(),
+        }
+    }
+}
+
 #[derive(Error, Debug, PartialEq)]
 pub enum AssemblyError {
     #[error("Syntax Error: {0}")]
-    PestError(#[from] pest::error::Error<crate::parser::Rule>),
+    PestError(#[from] Box<pest::error::Error<crate::parser::Rule>>),
 
     #[error("Structural Error on line {line}: {reason}")]
     StructuralError { line: usize, reason: String },
@@ -35,4 +60,237 @@ pub enum AssemblyError {
 
     #[error("Header Info Error: {reason}")]
     HeaderInfoError { reason: String },
+
+    #[error("Link Error: {reason}")]
+    LinkError { reason: String },
+
+    #[error("File Error: {reason}")]
+    FileError { reason: String },
+
+    #[error("{error}\n    in {expansion}")]
+    MacroExpansionError {
+        expansion: MacroExpansion,
+        error: Box<AssemblyError>,
+    },
+
+    #[error("{error}")]
+    OperandError {
+        operand: usize, // index of the operand that caused the error
+        error: Box<AssemblyError>,
+    },
+
+    #[error("{error}")]
+    SourceError {
+        location: Box<SourceLocation>,
+        error: Box<AssemblyError>,
+    },
+
+    #[error("{}", list_errors(.errors, *.limit_reached))]
+    MultipleErrors {
+        errors: Vec<AssemblyError>,
+        limit_reached: bool, // assembly stopped early because of the error limit
+    },
+}
+
+impl AssemblyError {
+    /// Annotate an error with the macro expansion chain of the line that caused it, so the
+    /// message points at both the line inside the macro body and every invocation site.
+    pub fn in_expansion(self, expansion: &[MacroExpansion]) -> AssemblyError {
+        expansion
+            .iter()
+            .rev()
+            .fold(self, |err, frame| AssemblyError::MacroExpansionError {
+                expansion: frame.clone(),
+                error: Box::new(err),
+            })
+    }
+
+    /// Annotate an error with the index of the operand that caused it, so its diagnostic
+    /// underlines that operand. An error that already names its operand keeps it.
+    pub fn at_operand(self, operand: usize) -> AssemblyError {
+        match self {
+            AssemblyError::OperandError { .. } => self,
+            error => AssemblyError::OperandError {
+                operand,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// Attach the source line an error was found on, used to render diagnostics.
+    pub fn with_location(self, line: &AssemblyLine) -> AssemblyError {
+        AssemblyError::SourceError {
+            location: Box::new(SourceLocation::of(line)),
+            error: Box::new(self),
+        }
+    }
+
+    /// Annotate an error with both the macro expansion chain and the location of its line.
+    pub fn at_line(self, line: &AssemblyLine) -> AssemblyError {
+        self.in_expansion(&line.expansion).with_location(line)
+    }
+}
+
+// the parser builds its errors with anyhow, the assembly or syntax error inside is returned so
+// that it can still be matched on. Errors without one, e.g. a source file that cannot be read,
+// keep their message
+impl From<anyhow::Error> for AssemblyError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<AssemblyError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+
+        match error.downcast::<pest::error::Error<crate::parser::Rule>>() {
+            Ok(error) => AssemblyError::PestError(Box::new(error)),
+            Err(error) => AssemblyError::FileError {
+                reason: format!("{:#}", error),
+            },
+        }
+    }
+}
+
+fn list_errors(errors: &[AssemblyError], limit_reached: bool) -> String {
+    let mut message: String = errors.iter().map(|e| format!("{}\n", e)).collect();
+
+    if limit_reached {
+        message.push_str(&format!(
+            "Stopped after reaching the limit of {} errors.",
+            errors.len()
+        ));
+    } else {
+        message.push_str(&format!("{} errors found.", errors.len()));
+    }
+
+    message
+}
+
+/// Collects the errors of an assembler pass, so that every line can be checked before the pass
+/// fails. A `max_errors` of 0 collects every error.
+#[derive(Debug, Default)]
+pub struct ErrorCollector {
+    errors: Vec<AssemblyError>,
+    max_errors: usize,
+}
+
+impl ErrorCollector {
+    pub fn new(max_errors: usize) -> Self {
+        Self {
+            errors: Vec::new(),
+            max_errors,
+        }
+    }
+
+    /// Record an error, returns every error collected so far once the limit has been reached.
+    pub fn push(&mut self, error: AssemblyError) -> Result<(), AssemblyError> {
+        self.errors.push(error);
+
+        if self.max_errors != 0 && self.errors.len() >= self.max_errors {
+            return Err(combine_errors(std::mem::take(&mut self.errors), true));
+        }
+
+        Ok(())
+    }
+
+    /// Record the error of a result, if there is one.
+    pub fn check<T>(
+        &mut self,
+        result: Result<T, AssemblyError>,
+    ) -> Result<Option<T>, AssemblyError> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error) => {
+                self.push(error)?;
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Fail if any errors were collected.
+    pub fn finish(self) -> Result<(), AssemblyError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(combine_errors(self.errors, false))
+        }
+    }
+}
+
+// a single error is returned as is, so that it can still be matched on
+fn combine_errors(mut errors: Vec<AssemblyError>, limit_reached: bool) -> AssemblyError {
+    if errors.len() == 1 && !limit_reached {
+        errors.pop().unwrap()
+    } else {
+        AssemblyError::MultipleErrors {
+            errors,
+            limit_reached,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(line: usize) -> AssemblyError {
+        AssemblyError::SemanticError {
+            line,
+            reason: "Undefined label: x".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_collect_single_error() {
+        let mut errors = ErrorCollector::new(10);
+        assert!(errors.push(error(1)).is_ok());
+        assert_eq!(errors.finish(), Err(error(1)));
+    }
+
+    #[test]
+    fn test_collect_multiple_errors() {
+        let mut errors = ErrorCollector::new(0);
+        for line in 1..=3 {
+            assert!(errors.push(error(line)).is_ok());
+        }
+
+        let combined = errors.finish().unwrap_err();
+        assert_eq!(
+            combined.to_string(),
+            "Semantic Error on line 1: Undefined label: x\n\
+             Semantic Error on line 2: Undefined label: x\n\
+             Semantic Error on line 3: Undefined label: x\n\
+             3 errors found."
+        );
+    }
+
+    #[test]
+    fn test_from_anyhow() {
+        let wrapped = anyhow::Error::from(error(4)).context("In expansion of macro \"m\"");
+        assert_eq!(AssemblyError::from(wrapped), error(4));
+
+        let message = anyhow::anyhow!("Mock file not found: a.asm").context("Failed to read a.asm");
+        assert_eq!(
+            AssemblyError::from(message),
+            AssemblyError::FileError {
+                reason: "Failed to read a.asm: Mock file not found: a.asm".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_error_limit() {
+        let mut errors = ErrorCollector::new(2);
+        assert!(errors.push(error(1)).is_ok());
+        assert_eq!(
+            errors.push(error(2)),
+            Err(AssemblyError::MultipleErrors {
+                errors: vec![error(1), error(2)],
+                limit_reached: true,
+            })
+        );
+    }
 }
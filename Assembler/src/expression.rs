@@ -0,0 +1,230 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::errors::AssemblyError;
+
+/// Evaluate an expression. `value_of` resolves a symbol to its value (a constant or the logical
+/// address of a label) and `bank_of` resolves a label to its rom bank for `bank(label)`.
+pub fn evaluate<V, B>(
+    expr: &Expr,
+    value_of: &V,
+    bank_of: &B,
+    line_num: &usize,
+) -> Result<i32, AssemblyError>
+where
+    V: Fn(&str) -> Option<i32>,
+    B: Fn(&str) -> Option<i32>,
+{
+    match expr {
+        Expr::Number(value) => Ok(*value),
+        Expr::Symbol(name) => value_of(name).ok_or_else(|| AssemblyError::SemanticError {
+            line: *line_num,
+            reason: format!("Undefined symbol in expression: {}", name),
+        }),
+        Expr::Bank(name) => bank_of(name).ok_or_else(|| AssemblyError::SemanticError {
+            line: *line_num,
+            reason: format!("Undefined label in bank() expression: {}", name),
+        }),
+        Expr::Unary(op, inner) => {
+            let value = evaluate(inner, value_of, bank_of, line_num)?;
+            Ok(match op {
+                UnaryOp::Neg => value.wrapping_neg(),
+                UnaryOp::Not => !value,
+                UnaryOp::LogicalNot => i32::from(value == 0),
+                UnaryOp::Low => value & 0xFF,
+                UnaryOp::High => (value >> 8) & 0xFF,
+            })
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            let lhs = evaluate(lhs, value_of, bank_of, line_num)?;
+            let rhs = evaluate(rhs, value_of, bank_of, line_num)?;
+            apply_binary_op(op, lhs, rhs, line_num)
+        }
+    }
+}
+
+/// Evaluate an expression that does not reference any symbols.
+pub fn evaluate_constant(expr: &Expr, line_num: &usize) -> Result<i32, AssemblyError> {
+    evaluate(expr, &|_| None, &|_| None, line_num)
+}
+
+/// Returns true if the expression can be evaluated without any symbol lookups.
+pub fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(_) => true,
+        Expr::Symbol(_) | Expr::Bank(_) => false,
+        Expr::Unary(_, inner) => is_constant(inner),
+        Expr::Binary(_, lhs, rhs) => is_constant(lhs) && is_constant(rhs),
+    }
+}
+
+/// Collect the names of every symbol (including `bank()` arguments) referenced by an expression.
+pub fn referenced_symbols(expr: &Expr) -> Vec<&String> {
+    let mut symbols = Vec::new();
+    collect_symbols(expr, &mut symbols);
+    symbols
+}
+
+/// Rebuild an expression, replacing each symbol for which `replace` returns a new expression.
+pub fn replace_symbols<F>(expr: &Expr, replace: &F) -> Expr
+where
+    F: Fn(&str) -> Option<Expr>,
+{
+    match expr {
+        Expr::Symbol(name) => replace(name).unwrap_or_else(|| expr.clone()),
+        Expr::Number(_) | Expr::Bank(_) => expr.clone(),
+        Expr::Unary(op, inner) => Expr::Unary(*op, Box::new(replace_symbols(inner, replace))),
+        Expr::Binary(op, lhs, rhs) => Expr::Binary(
+            *op,
+            Box::new(replace_symbols(lhs, replace)),
+            Box::new(replace_symbols(rhs, replace)),
+        ),
+    }
+}
+
+fn collect_symbols<'a>(expr: &'a Expr, symbols: &mut Vec<&'a String>) {
+    match expr {
+        Expr::Number(_) => {}
+        Expr::Symbol(name) | Expr::Bank(name) => symbols.push(name),
+        Expr::Unary(_, inner) => collect_symbols(inner, symbols),
+        Expr::Binary(_, lhs, rhs) => {
+            collect_symbols(lhs, symbols);
+            collect_symbols(rhs, symbols);
+        }
+    }
+}
+
+fn apply_binary_op(
+    op: &BinaryOp,
+    lhs: i32,
+    rhs: i32,
+    line_num: &usize,
+) -> Result<i32, AssemblyError> {
+    match op {
+        BinaryOp::Add => Ok(lhs.wrapping_add(rhs)),
+        BinaryOp::Sub => Ok(lhs.wrapping_sub(rhs)),
+        BinaryOp::Mul => Ok(lhs.wrapping_mul(rhs)),
+        BinaryOp::Div | BinaryOp::Mod if rhs == 0 => Err(AssemblyError::SemanticError {
+            line: *line_num,
+            reason: "Division by zero in expression.".to_string(),
+        }),
+        BinaryOp::Div => Ok(lhs.wrapping_div(rhs)),
+        BinaryOp::Mod => Ok(lhs.wrapping_rem(rhs)),
+        BinaryOp::Shl | BinaryOp::Shr if !(0..32).contains(&rhs) => {
+            Err(AssemblyError::SemanticError {
+                line: *line_num,
+                reason: format!("Invalid shift amount in expression: {}", rhs),
+            })
+        }
+        BinaryOp::Shl => Ok(lhs << rhs),
+        BinaryOp::Shr => Ok(lhs >> rhs),
+        BinaryOp::And => Ok(lhs & rhs),
+        BinaryOp::Or => Ok(lhs | rhs),
+        BinaryOp::Xor => Ok(lhs ^ rhs),
+        BinaryOp::Eq => Ok(i32::from(lhs == rhs)),
+        BinaryOp::Ne => Ok(i32::from(lhs != rhs)),
+        BinaryOp::Lt => Ok(i32::from(lhs < rhs)),
+        BinaryOp::Gt => Ok(i32::from(lhs > rhs)),
+        BinaryOp::Le => Ok(i32::from(lhs <= rhs)),
+        BinaryOp::Ge => Ok(i32::from(lhs >= rhs)),
+        BinaryOp::LogicalAnd => Ok(i32::from(lhs != 0 && rhs != 0)),
+        BinaryOp::LogicalOr => Ok(i32::from(lhs != 0 || rhs != 0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(value: i32) -> Box<Expr> {
+        Box::new(Expr::Number(value))
+    }
+
+    fn sym(name: &str) -> Box<Expr> {
+        Box::new(Expr::Symbol(name.to_string()))
+    }
+
+    #[test]
+    fn test_evaluate_constant_expression() {
+        // (1 << 3) | 0x01
+        let expr = Expr::Binary(
+            BinaryOp::Or,
+            Box::new(Expr::Binary(BinaryOp::Shl, num(1), num(3))),
+            num(0x01),
+        );
+        assert!(is_constant(&expr));
+        assert_eq!(evaluate_constant(&expr, &1), Ok(0x09));
+    }
+
+    #[test]
+    fn test_evaluate_low_high_bank() {
+        let value_of = |name: &str| (name == "label").then_some(0x4321);
+        let bank_of = |name: &str| (name == "label").then_some(3);
+
+        let low = Expr::Unary(UnaryOp::Low, sym("label"));
+        let high = Expr::Unary(UnaryOp::High, sym("label"));
+        let bank = Expr::Bank("label".to_string());
+
+        assert_eq!(evaluate(&low, &value_of, &bank_of, &1), Ok(0x21));
+        assert_eq!(evaluate(&high, &value_of, &bank_of, &1), Ok(0x43));
+        assert_eq!(evaluate(&bank, &value_of, &bank_of, &1), Ok(3));
+    }
+
+    #[test]
+    fn test_evaluate_undefined_symbol() {
+        let expr = Expr::Binary(BinaryOp::Sub, sym("end"), sym("start"));
+        assert!(!is_constant(&expr));
+        assert_eq!(referenced_symbols(&expr), vec!["end", "start"]);
+        assert_eq!(
+            evaluate_constant(&expr, &7),
+            Err(AssemblyError::SemanticError {
+                line: 7,
+                reason: "Undefined symbol in expression: end".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_evaluate_division_by_zero() {
+        let expr = Expr::Binary(BinaryOp::Mod, num(4), num(0));
+        assert!(evaluate_constant(&expr, &1).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_comparison() {
+        // 3 < 4 && !(2 == 3)
+        let expr = Expr::Binary(
+            BinaryOp::LogicalAnd,
+            Box::new(Expr::Binary(BinaryOp::Lt, num(3), num(4))),
+            Box::new(Expr::Unary(
+                UnaryOp::LogicalNot,
+                Box::new(Expr::Binary(BinaryOp::Eq, num(2), num(3))),
+            )),
+        );
+        assert_eq!(evaluate_constant(&expr, &1), Ok(1));
+
+        let expr = Expr::Binary(BinaryOp::Ge, num(-1), num(0));
+        assert_eq!(evaluate_constant(&expr, &1), Ok(0));
+    }
+
+    #[test]
+    fn test_replace_symbols() {
+        let expr = Expr::Binary(BinaryOp::Add, sym("A"), sym("B"));
+        let replaced = replace_symbols(&expr, &|name| (name == "A").then_some(Expr::Number(2)));
+        assert_eq!(replaced, Expr::Binary(BinaryOp::Add, num(2), sym("B")));
+    }
+}
@@ -66,9 +66,10 @@ impl FileReader for MockFileReader {
     fn read_to_string(&self, path: &Path) -> Result<String> {
         match self.files.get(path) {
             Some(FileData::Text(content)) => Ok(content.clone()),
-            Some(FileData::Binary(_)) => {
-                Err(anyhow::anyhow!("Cannot read binary file as string: {}", path.display()))
-            }
+            Some(FileData::Binary(_)) => Err(anyhow::anyhow!(
+                "Cannot read binary file as string: {}",
+                path.display()
+            )),
             None => Err(anyhow::anyhow!("Mock file not found: {}", path.display())),
         }
     }
@@ -76,9 +77,10 @@ impl FileReader for MockFileReader {
     fn read_binary(&self, path: &Path) -> Result<Vec<u8>> {
         match self.files.get(path) {
             Some(FileData::Binary(content)) => Ok(content.clone()),
-            Some(FileData::Text(_)) => {
-                Err(anyhow::anyhow!("Cannot read text file as binary: {}", path.display()))
-            }
+            Some(FileData::Text(_)) => Err(anyhow::anyhow!(
+                "Cannot read text file as binary: {}",
+                path.display()
+            )),
             None => Err(anyhow::anyhow!("Mock file not found: {}", path.display())),
         }
     }
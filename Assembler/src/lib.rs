@@ -16,51 +16,237 @@ limitations under the License.
 
 pub mod assembler;
 pub mod ast;
+pub mod diagnostics;
+pub mod disassembler;
 pub mod errors;
+pub mod expression;
 pub mod file_reader;
+pub mod linker;
+pub mod listing;
+pub mod object_file;
 pub mod parser;
+pub mod rom_file;
+pub mod symbol_file;
+pub mod warnings;
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use errors::AssemblyError;
 use file_reader::FileReader;
-use std::collections::HashSet;
+use linker::LinkedRom;
+use listing::Listing;
+use object_file::ObjectFile;
+use symbol_file::SymbolFile;
+use warnings::{Warning, WarningCollector, WarningOptions};
+
+pub use assembler::{SectionLayout, Symbol, SymbolTable};
 
 extern crate pest;
 extern crate pest_derive;
 
+/// Number of errors reported before assembly stops, 0 reports every error.
+pub const DEFAULT_MAX_ERRORS: usize = 20;
+
+/// Number of banks a cartridge ROM is padded to at least, the fixed bank 0 and one switchable bank.
+pub const DEFAULT_MIN_BANKS: u32 = 2;
+
+/// How problems found during assembly are reported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblyOptions {
+    pub max_errors: usize, // errors reported before assembly stops, 0 for no limit
+    pub warnings: WarningOptions,
+    pub pad_byte: Option<u8>, // padding value until the first .padbyte, None for the defaults
+    pub min_banks: u32,       // banks the ROM is padded to at least, 1 for a 16 KiB boot ROM
+}
+
+impl Default for AssemblyOptions {
+    fn default() -> Self {
+        Self {
+            max_errors: DEFAULT_MAX_ERRORS,
+            warnings: WarningOptions::default(),
+            pad_byte: None,
+            min_banks: DEFAULT_MIN_BANKS,
+        }
+    }
+}
+
+/// Everything produced by assembling a program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblyOutput {
+    pub rom: Vec<u8>,
+    pub symbols: SymbolTable,            // address of every label, by name
+    pub constants: HashMap<String, i32>, // value of every constant, by name
+    pub sections: Vec<SectionLayout>,
+    // address of every source line that emits or reserves something, by file and line number.
+    // Lines expanded from macros and .rept blocks are left out
+    pub line_addresses: BTreeMap<(PathBuf, usize), Symbol>,
+    pub listing: Listing,
+    pub warnings: Vec<Warning>,
+    pub free_bytes: Vec<u32>, // bytes not used by the program, by bank
+}
+
+impl AssemblyOutput {
+    /// The labels, constants and sections written to a symbol file.
+    pub fn symbol_file(&self) -> SymbolFile {
+        assembler::build_symbol_file(&self.symbols, &self.sections, &self.constants)
+    }
+}
+
 pub fn assemble<F: FileReader>(
     source_path: &Path,
     final_logical_addr: u16,
     expected_interrupt_table_addr: Option<u16>,
     expected_header_addr: Option<u16>,
+    defines: &[(String, i32)],
     reader: &F,
-) -> Result<Vec<u8>> {
+) -> Result<AssemblyOutput, AssemblyError> {
+    assemble_with_options(
+        source_path,
+        final_logical_addr,
+        expected_interrupt_table_addr,
+        expected_header_addr,
+        defines,
+        &AssemblyOptions::default(),
+        reader,
+    )
+}
+
+/// Assemble a program with the error limit, warnings and padding given in `options`. Each pass
+/// checks every line before failing, reporting up to `options.max_errors` errors.
+pub fn assemble_with_options<F: FileReader>(
+    source_path: &Path,
+    final_logical_addr: u16,
+    expected_interrupt_table_addr: Option<u16>,
+    expected_header_addr: Option<u16>,
+    defines: &[(String, i32)],
+    options: &AssemblyOptions,
+    reader: &F,
+) -> Result<AssemblyOutput, AssemblyError> {
+    let max_errors = options.max_errors;
+    let mut warnings = WarningCollector::new(options.warnings.clone());
+
     let mut include_stack: HashSet<PathBuf> = HashSet::new();
-    let mut parsed_lines = parser::parse_source_recursive(source_path, &mut include_stack, reader)
-        .context("Failed during parsing stage")?;
+    let mut parsed_lines = parser::parse_source_recursive(source_path, &mut include_stack, reader)?;
 
-    let constant_table = assembler::build_constant_table(&parsed_lines)
-        .context("Failed during assembler phase 0")?;
+    // phase 0
+    let mut constant_table =
+        assembler::build_constant_table(&mut parsed_lines, defines, max_errors)?;
 
-    assembler::process_constants(&mut parsed_lines, &constant_table)
-        .context("Failed during assembler phase 0.5")?;
+    // phase 0.25
+    let referenced_labels =
+        assembler::resolve_label_scopes(&mut parsed_lines, &mut constant_table, max_errors)?;
 
-    let symbol_table = assembler::build_symbol_table(
+    // phase 0.5
+    assembler::process_constants(&mut parsed_lines, &constant_table, max_errors)?;
+
+    // phase 0.75
+    assembler::apply_charmaps(&mut parsed_lines, max_errors)?;
+
+    // pass 1 is repeated until every .auto branch reaches its target
+    assembler::move_floating_sections(&mut parsed_lines);
+    let (symbol_table, sections, mut free_bytes) = loop {
+        let (symbol_table, sections, branches, free_bytes) = assembler::build_symbol_table(
+            &parsed_lines,
+            &final_logical_addr,
+            expected_interrupt_table_addr,
+            expected_header_addr,
+            &constant_table,
+            reader,
+            max_errors,
+        )?;
+
+        if !assembler::relaxation::relax_branches(&mut parsed_lines, &branches, &symbol_table) {
+            break (symbol_table, sections, free_bytes);
+        }
+    };
+
+    // pass 2
+    let (mut rom, mut listing) = assembler::generate_bytecode(
         &parsed_lines,
-        &final_logical_addr,
-        expected_interrupt_table_addr,
-        expected_header_addr,
-        &constant_table,
+        &symbol_table,
+        &sections,
         reader,
-    )
-    .context("Failed during assembler phase 1")?;
+        max_errors,
+        &mut warnings,
+        options.pad_byte,
+        options.min_banks,
+    )?;
+
+    assembler::check_unused_labels(&parsed_lines, &referenced_labels, &mut warnings);
+    let warnings = warnings.finish(max_errors)?;
+
+    // pass 3
+    if let Some(header_addr) = expected_header_addr {
+        assembler::patch_rom_checksums(&mut rom, header_addr)?;
+    }
+
+    listing.update_bytes(&rom);
+
+    // banks the program does not reach are padding only
+    free_bytes.resize(
+        rom.len() / assembler::BANK_SIZE as usize,
+        assembler::BANK_SIZE,
+    );
+
+    let mut line_addresses = BTreeMap::new();
+    for line in listing.lines.iter().filter(|line| line.macro_depth == 0) {
+        line_addresses
+            .entry((line.file.clone(), line.line_number))
+            .or_insert(Symbol {
+                logical_address: line.logical_addr,
+                bank: line.bank,
+            });
+    }
 
-    let machine_code = assembler::generate_bytecode(&parsed_lines, &symbol_table, reader)
-        .context("Failed during assembler phase 2")?;
+    let constants = assembler::resolve_constants(&constant_table, &symbol_table);
 
-    let mut final_rom = Vec::new();
-    final_rom.extend(machine_code);
+    Ok(AssemblyOutput {
+        rom,
+        symbols: symbol_table,
+        constants,
+        sections,
+        line_addresses,
+        listing,
+        warnings,
+        free_bytes,
+    })
+}
+
+/// Assemble a program into a relocatable object file, which is placed into a ROM by `link`. Each
+/// pass checks every line before failing, reporting up to `options.max_errors` errors.
+pub fn assemble_object<F: FileReader>(
+    source_path: &Path,
+    defines: &[(String, i32)],
+    options: &AssemblyOptions,
+    reader: &F,
+) -> Result<ObjectFile, AssemblyError> {
+    let max_errors = options.max_errors;
+
+    let mut include_stack: HashSet<PathBuf> = HashSet::new();
+    let mut parsed_lines = parser::parse_source_recursive(source_path, &mut include_stack, reader)?;
+
+    let mut constant_table =
+        assembler::build_constant_table(&mut parsed_lines, defines, max_errors)?;
+    assembler::resolve_label_scopes(&mut parsed_lines, &mut constant_table, max_errors)?;
+    assembler::process_constants(&mut parsed_lines, &constant_table, max_errors)?;
+    assembler::apply_charmaps(&mut parsed_lines, max_errors)?;
+
+    assembler::object::build_object(&mut parsed_lines, reader, options.pad_byte, max_errors)
+}
+
+/// Link object files into a cartridge ROM, placing their sections as listed in the linker script
+/// or in free space and patching in the header checksums. Objects are given with the name they
+/// are reported under.
+pub fn link(
+    objects: &[(String, ObjectFile)],
+    linker_script: &str,
+    max_errors: usize,
+) -> Result<LinkedRom, AssemblyError> {
+    let script = linker::script::parse_script(linker_script)?;
+    linker::link(objects, &script, max_errors)
+}
 
-    Ok(final_rom)
+pub fn verify_checksums(rom: &[u8], header_addr: u16) -> Result<(), AssemblyError> {
+    assembler::verify_rom_checksums(rom, header_addr)
 }
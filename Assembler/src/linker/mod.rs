@@ -0,0 +1,586 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+pub mod script;
+
+use crate::assembler::{
+    BANK_SIZE, FreeSpace, RAM_REGIONS, SWITCHABLE_BANK_END, SWITCHABLE_BANK_START,
+    patch_rom_checksums, ram_region,
+};
+use crate::errors::{AssemblyError, ErrorCollector};
+use crate::object_file::*;
+use script::{LinkerScript, Region};
+use std::collections::{HashMap, HashSet};
+
+// a cartridge has the fixed bank 0 and at least one switchable bank
+const MIN_BANKS: u32 = 2;
+
+// where a section or symbol was placed, RAM is in bank 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Address {
+    bank: u32,
+    logical_addr: u32,
+}
+
+/// A linked cartridge ROM and how much space is left in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkedRom {
+    pub rom: Vec<u8>,
+    pub free_bytes: Vec<u32>, // bytes not used by any section, by bank
+}
+
+impl Address {
+    fn physical_addr(&self) -> usize {
+        let bank_start = if self.bank == 0 {
+            0
+        } else {
+            SWITCHABLE_BANK_START
+        };
+        (self.bank * BANK_SIZE + self.logical_addr - bank_start) as usize
+    }
+}
+
+/// Link object files into a cartridge ROM. Sections are placed as given by the linker script, the
+/// sections it does not list are packed into the free space of the ROM banks, then every
+/// relocation is written and the header checksums are patched in. Objects are given with the name
+/// they are reported under in errors.
+pub fn link(
+    objects: &[(String, ObjectFile)],
+    script: &LinkerScript,
+    max_errors: usize,
+) -> Result<LinkedRom, AssemblyError> {
+    let mut errors = ErrorCollector::new(max_errors);
+
+    for (name, object) in objects {
+        if object.version != OBJECT_FORMAT_VERSION {
+            return Err(link_error(format!(
+                "{} uses object format version {}, expected version {}.",
+                name, object.version, OBJECT_FORMAT_VERSION
+            )));
+        }
+    }
+
+    let mut free = FreeSpace::new();
+    let placed = place_sections(objects, script, &mut free, &mut errors)?;
+    let symbols = resolve_symbols(objects, &placed, &mut errors)?;
+    errors.finish()?;
+    let mut errors = ErrorCollector::new(max_errors);
+
+    let num_banks = placed
+        .iter()
+        .zip(objects)
+        .flat_map(|(sections, (_, object))| sections.iter().zip(&object.sections))
+        .filter(|(_, section)| !section.nobits)
+        .filter_map(|(address, _)| address.map(|a| a.bank + 1))
+        .fold(MIN_BANKS.max(script.banks.unwrap_or(0)), u32::max);
+    let mut rom = vec![0xFF; (num_banks * BANK_SIZE) as usize];
+
+    for (index, (name, object)) in objects.iter().enumerate() {
+        for (section, address) in object.sections.iter().zip(&placed[index]) {
+            let Some(address) = address.filter(|_| !section.nobits) else {
+                continue;
+            };
+
+            let start = address.physical_addr();
+            rom[start..start + section.data.len()].copy_from_slice(&section.data);
+
+            for relocation in &section.relocations {
+                // every symbol was checked by resolve_symbols
+                let Some(target) = symbols[index].get(relocation.symbol.as_str()) else {
+                    continue;
+                };
+
+                if let Err(reason) = apply_relocation(&mut rom, address, relocation, *target) {
+                    errors.push(link_error(format!(
+                        "{}, section \"{}\": {}",
+                        name, section.name, reason
+                    )))?;
+                }
+            }
+        }
+    }
+
+    errors.finish()?;
+    patch_rom_checksums(&mut rom, 0x0000)?;
+
+    let free_bytes = (0..num_banks).map(|bank| free.free_bytes(bank)).collect();
+    Ok(LinkedRom { rom, free_bytes })
+}
+
+// place every section of every object in the region the linker script lists it in, sections
+// with the same name are placed one after another in the order the objects were given. ROM
+// sections the script does not list are then placed in the first free space that fits them,
+// largest first
+fn place_sections(
+    objects: &[(String, ObjectFile)],
+    script: &LinkerScript,
+    free: &mut FreeSpace,
+    errors: &mut ErrorCollector,
+) -> Result<Vec<Vec<Option<Address>>>, AssemblyError> {
+    let mut placed: Vec<Vec<Option<Address>>> = objects
+        .iter()
+        .map(|(_, object)| vec![None; object.sections.len()])
+        .collect();
+    let mut listed: HashSet<&str> = HashSet::new();
+
+    for block in &script.regions {
+        let (region_name, bank, start, end) = match block.region {
+            Region::Bank(0) => ("ROM bank 0".to_string(), 0, 0, SWITCHABLE_BANK_START),
+            Region::Bank(bank) => (
+                format!("ROM bank {}", bank),
+                bank,
+                SWITCHABLE_BANK_START,
+                SWITCHABLE_BANK_END,
+            ),
+            Region::Ram(addr) => match ram_region(addr) {
+                Some(region) => (region.name.to_string(), 0, addr, region.end + 1),
+                None => {
+                    let regions: Vec<String> = RAM_REGIONS
+                        .iter()
+                        .map(|r| format!("{} 0x{:04X}-0x{:04X}", r.name, r.start, r.end))
+                        .collect();
+                    errors.push(link_error(format!(
+                        "ram address 0x{:04x} is not in a RAM region ({}).",
+                        addr,
+                        regions.join(", ")
+                    )))?;
+                    continue;
+                }
+            },
+        };
+        let in_rom = matches!(block.region, Region::Bank(_));
+        let mut next_addr = start;
+
+        if let (Region::Bank(bank), Some(banks)) = (block.region, script.banks)
+            && bank >= banks
+        {
+            errors.push(link_error(format!(
+                "ROM bank {} is listed by the linker script, but the ROM only has {} banks.",
+                bank, banks
+            )))?;
+            continue;
+        }
+
+        for placement in &block.placements {
+            let name = placement.section.as_str();
+            if !listed.insert(name) {
+                errors.push(link_error(format!(
+                    "linker script line {}: Section \"{}\" is listed more than once.",
+                    placement.line, name
+                )))?;
+                continue;
+            }
+
+            if let Some(addr) = placement.address {
+                if addr < next_addr {
+                    errors.push(link_error(format!(
+                        "linker script line {}: Section \"{}\" cannot be placed at 0x{:04x}, the sections before it end at 0x{:04x}.",
+                        placement.line, name, addr, next_addr
+                    )))?;
+                    continue;
+                }
+                next_addr = addr;
+            }
+
+            let mut found = false;
+            for (index, (object_name, object)) in objects.iter().enumerate() {
+                for (section_index, section) in object.sections.iter().enumerate() {
+                    if section.name != name {
+                        continue;
+                    }
+                    found = true;
+
+                    if section.nobits == in_rom {
+                        let reason = if in_rom {
+                            "is a nobits section and must be placed in RAM"
+                        } else {
+                            "holds data and must be placed in a ROM bank"
+                        };
+                        errors.push(link_error(format!(
+                            "Section \"{}\" of {} {}.",
+                            name, object_name, reason
+                        )))?;
+                        continue;
+                    }
+
+                    if section.bank0 && bank != 0 {
+                        errors.push(link_error(format!(
+                            "Section \"{}\" of {} is a bank0 section and cannot be placed in {}.",
+                            name, object_name, region_name
+                        )))?;
+                        continue;
+                    }
+
+                    next_addr = next_addr.next_multiple_of(section.align.max(1));
+                    placed[index][section_index] = Some(Address {
+                        bank,
+                        logical_addr: next_addr,
+                    });
+                    if in_rom {
+                        free.reserve(bank, next_addr, next_addr + section.size);
+                    }
+                    next_addr += section.size;
+                }
+            }
+
+            if !found {
+                errors.push(link_error(format!(
+                    "linker script line {}: Section \"{}\" is not defined by any object file.",
+                    placement.line, name
+                )))?;
+            }
+
+            if next_addr > end {
+                errors.push(link_error(format!(
+                    "{} overflow, section \"{}\" ends at 0x{:04x}.",
+                    region_name, name, next_addr
+                )))?;
+            }
+        }
+    }
+
+    let mut floating = Vec::new();
+    for (index, (name, object)) in objects.iter().enumerate() {
+        for (section_index, section) in object.sections.iter().enumerate() {
+            if listed.contains(section.name.as_str()) {
+                continue;
+            }
+
+            // RAM has no banks to fill, every nobits section must be given an address
+            if section.nobits {
+                errors.push(link_error(format!(
+                    "nobits section \"{}\" of {} is not placed by the linker script.",
+                    section.name, name
+                )))?;
+                continue;
+            }
+            floating.push((index, section_index));
+        }
+    }
+
+    // placing the largest sections first leaves the smaller ones to fill the gaps
+    floating.sort_by_key(|&(index, section_index)| {
+        std::cmp::Reverse(objects[index].1.sections[section_index].size)
+    });
+
+    for (index, section_index) in floating {
+        let (name, object) = &objects[index];
+        let section = &object.sections[section_index];
+
+        // without a bank count, every existing bank is tried before a new one is added
+        let banks = if section.bank0 {
+            0..1
+        } else {
+            1..script.banks.unwrap_or(free.banks.len() as u32 + 1)
+        };
+        let align = section.align.max(1);
+        let address = banks.clone().find_map(|bank| {
+            free.find(bank, section.size, align)
+                .map(|logical_addr| Address { bank, logical_addr })
+        });
+
+        match address {
+            Some(address) => {
+                free.reserve(
+                    address.bank,
+                    address.logical_addr,
+                    address.logical_addr + section.size,
+                );
+                placed[index][section_index] = Some(address);
+            }
+            None => {
+                let space = match (section.bank0, script.banks) {
+                    (true, _) => "ROM bank 0".to_string(),
+                    (false, Some(_)) if banks.len() > 1 => {
+                        format!("ROM banks {}-{}", banks.start, banks.end - 1)
+                    }
+                    (false, Some(_)) if banks.len() == 1 => format!("ROM bank {}", banks.start),
+                    _ => "any ROM bank".to_string(),
+                };
+                errors.push(link_error(format!(
+                    "Section \"{}\" of {} ({} bytes) does not fit in the free space of {}.",
+                    section.name, name, section.size, space
+                )))?;
+            }
+        }
+    }
+
+    Ok(placed)
+}
+
+// the address of every symbol each object can use, its own labels and the .global labels of the
+// other objects that it declared with .extern
+fn resolve_symbols<'a>(
+    objects: &'a [(String, ObjectFile)],
+    placed: &[Vec<Option<Address>>],
+    errors: &mut ErrorCollector,
+) -> Result<Vec<HashMap<&'a str, Address>>, AssemblyError> {
+    let mut locals: Vec<HashMap<&str, Address>> = Vec::new();
+    let mut globals: HashMap<&str, (Address, &str)> = HashMap::new();
+
+    for ((name, object), sections) in objects.iter().zip(placed) {
+        let mut symbols = HashMap::new();
+
+        for symbol in &object.symbols {
+            // sections that could not be placed have already been reported
+            let Some(Some(section)) = sections.get(symbol.section) else {
+                continue;
+            };
+            let address = Address {
+                bank: section.bank,
+                logical_addr: section.logical_addr + symbol.offset,
+            };
+            symbols.insert(symbol.name.as_str(), address);
+
+            if !symbol.global {
+                continue;
+            }
+
+            if let Some((_, defined_in)) = globals.insert(&symbol.name, (address, name)) {
+                errors.push(link_error(format!(
+                    "Global label \"{}\" is defined by both {} and {}.",
+                    symbol.name, defined_in, name
+                )))?;
+            }
+        }
+
+        locals.push(symbols);
+    }
+
+    for ((name, object), symbols) in objects.iter().zip(&mut locals) {
+        for symbol in &object.externs {
+            match globals.get(symbol.as_str()) {
+                Some((address, _)) => {
+                    symbols.insert(symbol, *address);
+                }
+                None => errors.push(link_error(format!(
+                    "Undefined label \"{}\", declared .extern by {} but not exported with .global by any object file.",
+                    symbol, name
+                )))?,
+            }
+        }
+    }
+
+    Ok(locals)
+}
+
+// write the value of a relocation into the rom, `section` is where its section was placed
+fn apply_relocation(
+    rom: &mut [u8],
+    section: Address,
+    relocation: &Relocation,
+    target: Address,
+) -> Result<(), String> {
+    let location = section.physical_addr() + relocation.offset as usize;
+    let value = target.logical_addr as i64 + relocation.addend as i64;
+
+    match relocation.kind {
+        RelocationKind::Word => {
+            if !(0..=u16::MAX as i64).contains(&value) {
+                return Err(format!(
+                    "Address of \"{}\" plus {} does not fit in 16 bits.",
+                    relocation.symbol, relocation.addend
+                ));
+            }
+            rom[location..location + 2].copy_from_slice(&(value as u16).to_le_bytes());
+        }
+        RelocationKind::Bank => {
+            rom[location..location + 2].copy_from_slice(&(target.bank as u16).to_le_bytes());
+        }
+        RelocationKind::Relative => {
+            // the offset is measured from the start of the jump instruction, before the offset
+            let jump_addr = section.logical_addr as i64 + relocation.offset as i64 - 1;
+            if target.bank != section.bank {
+                return Err(format!(
+                    "Label \"{}\" exists in a different bank than the relative jump at 0x{:04x}.",
+                    relocation.symbol, jump_addr
+                ));
+            }
+
+            let rel = value - jump_addr;
+            if rel < i8::MIN as i64 || rel > i8::MAX as i64 {
+                return Err(format!(
+                    "Label \"{}\" too far away for relative jump, must be within {} bytes of the jump at 0x{:04x}.",
+                    relocation.symbol,
+                    i8::MAX,
+                    jump_addr
+                ));
+            }
+            rom[location] = rel as u8;
+        }
+    }
+
+    Ok(())
+}
+
+fn link_error(reason: String) -> AssemblyError {
+    AssemblyError::LinkError { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use script::parse_script;
+
+    fn section(name: &str, data: Vec<u8>, relocations: Vec<Relocation>) -> ObjectSection {
+        ObjectSection {
+            name: name.to_string(),
+            align: 1,
+            nobits: false,
+            bank0: false,
+            size: data.len() as u32,
+            data,
+            relocations,
+        }
+    }
+
+    fn symbol(name: &str, section: usize, offset: u32, global: bool) -> ObjectSymbol {
+        ObjectSymbol {
+            name: name.to_string(),
+            section,
+            offset,
+            global,
+        }
+    }
+
+    fn relocation(offset: u32, kind: RelocationKind, symbol: &str) -> Relocation {
+        Relocation {
+            offset,
+            kind,
+            symbol: symbol.to_string(),
+            addend: 0,
+        }
+    }
+
+    #[test]
+    fn test_link_relocations() {
+        // JMP far_label / JR local / CALL.far far_label
+        let main = ObjectFile {
+            version: OBJECT_FORMAT_VERSION,
+            sections: vec![section(
+                "Code",
+                vec![
+                    0x51, 0x00, 0x00, 0x5A, 0x00, 0x05, 0x00, 0x00, 0x06, 0x00, 0x00, 0x2F, 0x21,
+                ],
+                vec![
+                    relocation(1, RelocationKind::Word, "far_label"),
+                    relocation(4, RelocationKind::Relative, "local"),
+                    relocation(6, RelocationKind::Bank, "far_label"),
+                    relocation(9, RelocationKind::Word, "far_label"),
+                ],
+            )],
+            symbols: vec![symbol("local", 0, 0, false)],
+            externs: vec!["far_label".to_string()],
+        };
+        let far = ObjectFile {
+            version: OBJECT_FORMAT_VERSION,
+            sections: vec![section("Far", vec![0x00, 0x01], Vec::new())],
+            symbols: vec![symbol("far_label", 0, 1, true)],
+            externs: Vec::new(),
+        };
+
+        let script = parse_script("bank 0\n  Code at 0x0100\nbank 3\n  Far\n").unwrap();
+        let objects = [("main.o".to_string(), main), ("far.o".to_string(), far)];
+        let rom = link(&objects, &script, 0).unwrap().rom;
+
+        assert_eq!(rom.len(), 4 * BANK_SIZE as usize);
+        assert_eq!(
+            rom[0x100..0x10D],
+            [
+                0x51, 0x01, 0x40, 0x5A, 0xFD, 0x05, 0x03, 0x00, 0x06, 0x01, 0x40, 0x2F, 0x21
+            ]
+        );
+        assert_eq!(rom[3 * BANK_SIZE as usize..][..2], [0x00, 0x01]);
+    }
+
+    #[test]
+    fn test_link_errors() {
+        let mut object = ObjectFile {
+            version: OBJECT_FORMAT_VERSION,
+            sections: vec![
+                section(
+                    "Code",
+                    vec![0x51, 0x00, 0x00],
+                    vec![relocation(1, RelocationKind::Word, "missing")],
+                ),
+                section("Data", vec![0x00], Vec::new()),
+            ],
+            symbols: Vec::new(),
+            externs: vec!["missing".to_string()],
+        };
+
+        let mut vars = section("Vars", Vec::new(), Vec::new());
+        vars.nobits = true;
+        vars.size = 4;
+        object.sections.push(vars);
+
+        let script = parse_script("banks 2\nbank 0\n  Code\nbank 2\n").unwrap();
+        let error = link(&[("main.o".to_string(), object)], &script, 0).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Link Error: ROM bank 2 is listed by the linker script, but the ROM only has 2 banks.\n\
+             Link Error: nobits section \"Vars\" of main.o is not placed by the linker script.\n\
+             Link Error: Undefined label \"missing\", declared .extern by main.o but not exported with .global by any object file.\n\
+             3 errors found."
+        );
+    }
+
+    #[test]
+    fn test_link_floating_sections() {
+        let mut vectors = section("Vectors", vec![0xAA; 0x20], Vec::new());
+        vectors.bank0 = true;
+        let mut aligned = section("Aligned", vec![0xBB; 4], Vec::new());
+        aligned.align = 0x100;
+
+        let object = ObjectFile {
+            version: OBJECT_FORMAT_VERSION,
+            sections: vec![
+                section("Code", vec![0x00; 0x60], Vec::new()),
+                vectors,
+                section("Small", vec![0xCC; 0x100], Vec::new()),
+                section("Large", vec![0xDD; 0x3F00], Vec::new()),
+                aligned,
+            ],
+            symbols: Vec::new(),
+            externs: Vec::new(),
+        };
+
+        let script = parse_script("banks 4\nbank 0\n  Code\nbank 1\n  Small at 0x4002\n").unwrap();
+        let objects = [("main.o".to_string(), object)];
+        let linked = link(&objects, &script, 0).unwrap();
+        let rom = &linked.rom;
+
+        // bank0 sections go after the listed sections of bank 0, here the header
+        assert_eq!(rom[0x60..0x80], [0xAA; 0x20]);
+        // the largest section does not fit before or after Small, so it is placed in bank 2
+        assert_eq!(rom[2 * BANK_SIZE as usize], 0xDD);
+        // the aligned section fills the gap after Small
+        assert_eq!(rom[BANK_SIZE as usize + 0x200..][..4], [0xBB; 4]);
+        assert_eq!(
+            linked.free_bytes,
+            vec![0x4000 - 0x80, 0x4000 - 0x104, 0x100, 0x4000]
+        );
+
+        let mut object = objects[0].1.clone();
+        object.sections[3].size = 0x4001;
+        object.sections[3].data = vec![0xDD; 0x4001];
+        let error = link(&[("main.o".to_string(), object)], &script, 0).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Link Error: Section \"Large\" of main.o (16385 bytes) does not fit in the free space of ROM banks 1-3."
+        );
+    }
+}
@@ -0,0 +1,228 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::errors::AssemblyError;
+
+/// A ROM bank or RAM address space that sections are placed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Bank(u32), // bank 0 is 0x0000-0x3FFF, every other bank is 0x4000-0x7FFF
+    Ram(u32),  // nobits sections placed from this address
+}
+
+/// A section listed in the linker script, placed at the next free address of its region or at a
+/// fixed address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    pub section: String,
+    pub address: Option<u32>,
+    pub line: usize,
+}
+
+/// A region and the sections placed in it, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionBlock {
+    pub region: Region,
+    pub placements: Vec<Placement>,
+}
+
+/// The parsed linker script. ROM sections that are not listed in it are placed in the first
+/// free space of a switchable bank, or of bank 0 for `bank0` sections.
+///
+/// ```text
+/// ; comments start with a semicolon
+/// banks 8
+/// bank 0
+///     Header at 0x0000
+///     Vectors at 0x0060
+///     Code
+/// bank 1
+///     Levels
+/// ram 0xC000
+///     Variables
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkerScript {
+    pub regions: Vec<RegionBlock>,
+    pub banks: Option<u32>, // number of ROM banks, None adds banks as sections need them
+}
+
+/// Parse the text of a linker script.
+pub fn parse_script(text: &str) -> Result<LinkerScript, AssemblyError> {
+    let mut script = LinkerScript::default();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let content = line.split(';').next().unwrap_or_default();
+        let words: Vec<&str> = content.split_whitespace().collect();
+
+        let region = match words.as_slice() {
+            [] => continue,
+            ["banks", count] => {
+                if script.banks.is_some() {
+                    return Err(script_error(
+                        line_number,
+                        "The number of banks is given more than once.".to_string(),
+                    ));
+                }
+                let banks = parse_number(count, line_number)?;
+                if banks < super::MIN_BANKS {
+                    return Err(script_error(
+                        line_number,
+                        format!("A ROM has at least {} banks.", super::MIN_BANKS),
+                    ));
+                }
+                script.banks = Some(banks);
+                continue;
+            }
+            ["bank", number] => Region::Bank(parse_number(number, line_number)?),
+            ["ram", addr] => Region::Ram(parse_number(addr, line_number)?),
+            [section] | [section, "at", _] => {
+                let address = match words.get(2) {
+                    Some(addr) => Some(parse_number(addr, line_number)?),
+                    None => None,
+                };
+
+                let Some(block) = script.regions.last_mut() else {
+                    return Err(script_error(
+                        line_number,
+                        format!(
+                            "Section \"{}\" must be listed after a bank or ram line.",
+                            section
+                        ),
+                    ));
+                };
+
+                block.placements.push(Placement {
+                    section: section.to_string(),
+                    address,
+                    line: line_number,
+                });
+                continue;
+            }
+            _ => {
+                return Err(script_error(
+                    line_number,
+                    format!(
+                        "Expected \"banks N\", \"bank N\", \"ram ADDR\" or \"SECTION [at ADDR]\", found \"{}\".",
+                        content.trim()
+                    ),
+                ));
+            }
+        };
+
+        if let Region::Bank(bank) = region
+            && script.regions.iter().any(|block| block.region == region)
+        {
+            return Err(script_error(
+                line_number,
+                format!("Bank {} is listed more than once.", bank),
+            ));
+        }
+
+        script.regions.push(RegionBlock {
+            region,
+            placements: Vec::new(),
+        });
+    }
+
+    Ok(script)
+}
+
+// a decimal or 0x/$ prefixed hexadecimal number
+fn parse_number(text: &str, line_number: usize) -> Result<u32, AssemblyError> {
+    let parsed = if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix('$')) {
+        u32::from_str_radix(hex, 16)
+    } else {
+        text.parse::<u32>()
+    };
+
+    parsed.map_err(|_| script_error(line_number, format!("Invalid number \"{}\".", text)))
+}
+
+fn script_error(line_number: usize, reason: String) -> AssemblyError {
+    AssemblyError::LinkError {
+        reason: format!("linker script line {}: {}", line_number, reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let script = parse_script(
+            "; layout\nbank 0\n  Header at 0x0000\n  Code ; main code\n\nram $C000\n  Vars\nbanks 4\n",
+        )
+        .unwrap();
+
+        assert_eq!(script.banks, Some(4));
+        assert_eq!(
+            script.regions,
+            vec![
+                RegionBlock {
+                    region: Region::Bank(0),
+                    placements: vec![
+                        Placement {
+                            section: "Header".to_string(),
+                            address: Some(0),
+                            line: 3,
+                        },
+                        Placement {
+                            section: "Code".to_string(),
+                            address: None,
+                            line: 4,
+                        },
+                    ],
+                },
+                RegionBlock {
+                    region: Region::Ram(0xC000),
+                    placements: vec![Placement {
+                        section: "Vars".to_string(),
+                        address: None,
+                        line: 7,
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_script_errors() {
+        let error = parse_script("Code\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Link Error: linker script line 1: Section \"Code\" must be listed after a bank or ram line."
+        );
+
+        let error = parse_script("bank 1\nbank 0x1\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Link Error: linker script line 2: Bank 1 is listed more than once."
+        );
+
+        let error = parse_script("banks 4\nbanks 8\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Link Error: linker script line 2: The number of banks is given more than once."
+        );
+
+        assert!(parse_script("banks 1\n").is_err());
+        assert!(parse_script("bank one\n").is_err());
+        assert!(parse_script("bank 0\n  Code at\n").is_err());
+    }
+}
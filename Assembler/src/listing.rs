@@ -0,0 +1,201 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+// number of bytes shown on each row of the listing
+const BYTES_PER_ROW: usize = 8;
+// lines that emit more rows than this (usually padding) are cut short
+const MAX_ROWS: usize = 4;
+
+// The placement and output of a single assembly line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListingLine {
+    pub file: PathBuf,
+    pub line_number: usize,
+    pub macro_depth: usize, // number of macro expansions the line came from
+    pub source: String,
+    pub physical_addr: u32,
+    pub logical_addr: u32,
+    pub bank: u32,
+    pub nobits: bool, // the line is in a nobits section, it has no physical address or bank
+    pub rom_offset: usize, // offset of the first emitted byte in the rom image
+    pub bytes: Vec<u8>,
+}
+
+/// The address and emitted bytes of every assembled line, in source order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Listing {
+    pub lines: Vec<ListingLine>,
+}
+
+impl Listing {
+    /// Copy the emitted bytes of every line from the final rom image, so that values patched in
+    /// after code generation (e.g. the header checksums) are shown.
+    pub fn update_bytes(&mut self, rom: &[u8]) {
+        for line in &mut self.lines {
+            let end = line.rom_offset + line.bytes.len();
+            if end <= rom.len() {
+                line.bytes.copy_from_slice(&rom[line.rom_offset..end]);
+            }
+        }
+    }
+
+    /// Render the listing as text, one row per line with up to 8 bytes per row.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("; cicasm listing\n");
+        out.push_str("; phys  bank:addr bytes                     line  source\n");
+
+        let mut current_file: Option<&PathBuf> = None;
+
+        for line in &self.lines {
+            if current_file != Some(&line.file) {
+                let _ = writeln!(out, "\n; {}", line.file.display());
+                current_file = Some(&line.file);
+            }
+
+            let rows: Vec<&[u8]> = line.bytes.chunks(BYTES_PER_ROW).collect();
+            let first_row = rows.first().copied().unwrap_or_default();
+            let marker = if line.macro_depth > 0 { "+" } else { " " };
+
+            let _ = writeln!(
+                out,
+                "{}   {:<24} {:>5}{} {}",
+                format_address(line, 0),
+                format_bytes(first_row),
+                line.line_number,
+                marker,
+                line.source
+            );
+
+            for (index, row) in rows.iter().enumerate().skip(1) {
+                if index == MAX_ROWS {
+                    let remaining = line.bytes.len() - index * BYTES_PER_ROW;
+                    let _ = writeln!(out, "{:19}... {} more bytes", "", remaining);
+                    break;
+                }
+
+                let offset = (index * BYTES_PER_ROW) as u32;
+                let _ = writeln!(
+                    out,
+                    "{}   {}",
+                    format_address(line, offset),
+                    format_bytes(row).trim_end()
+                );
+            }
+        }
+
+        out
+    }
+}
+
+// lines in RAM show `ram` in place of the physical address and no bank
+fn format_address(line: &ListingLine, offset: u32) -> String {
+    if line.nobits {
+        format!("   ram  --:{:04x}", line.logical_addr + offset)
+    } else {
+        format!(
+            "{:06x}  {:02x}:{:04x}",
+            line.physical_addr + offset,
+            line.bank,
+            line.logical_addr + offset
+        )
+    }
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(line_number: usize, source: &str, addr: u32, bytes: &[u8]) -> ListingLine {
+        ListingLine {
+            file: PathBuf::from("main.asm"),
+            line_number,
+            macro_depth: 0,
+            source: source.to_string(),
+            physical_addr: addr,
+            logical_addr: addr,
+            bank: 0,
+            nobits: false,
+            rom_offset: addr as usize,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_render_listing() {
+        let listing = Listing {
+            lines: vec![
+                line(1, "start:", 0, &[]),
+                line(2, "    LDI R1, 0x1234", 0, &[0x02, 0x34, 0x12]),
+                line(3, "    .org 0x40", 3, &[0x00; 0x3D]),
+            ],
+        };
+
+        let rendered = listing.render();
+        let rows: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(
+            rows[4],
+            "000000  00:0000                                1  start:"
+        );
+        assert_eq!(
+            rows[5],
+            "000000  00:0000   02 34 12                     2      LDI R1, 0x1234"
+        );
+        assert_eq!(rows[7], "00000b  00:000b   00 00 00 00 00 00 00 00");
+        assert_eq!(rows[10], "                   ... 29 more bytes");
+    }
+
+    #[test]
+    fn test_render_nobits_line() {
+        let listing = Listing {
+            lines: vec![ListingLine {
+                nobits: true,
+                logical_addr: 0xC000,
+                ..line(2, "player_x: .ds 2", 0x0100, &[])
+            }],
+        };
+
+        let rendered = listing.render();
+        let rows: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(
+            rows[4],
+            "   ram  --:c000                                2  player_x: .ds 2"
+        );
+    }
+
+    #[test]
+    fn test_update_bytes() {
+        let mut listing = Listing {
+            lines: vec![line(1, ".byte 0, 0", 2, &[0x00, 0x00])],
+        };
+        listing.update_bytes(&[0x10, 0x11, 0x12, 0x13]);
+        assert_eq!(listing.lines[0].bytes, [0x12, 0x13]);
+    }
+}
@@ -15,13 +15,22 @@ limitations under the License.
 */
 
 use anyhow::Result;
-use cicasm::assemble;
+use cicasm::AssemblyOptions;
+use cicasm::DEFAULT_MAX_ERRORS;
+use cicasm::assemble_object;
+use cicasm::assemble_with_options;
+use cicasm::diagnostics;
 use cicasm::file_reader::AsmFileReader;
+use cicasm::rom_file;
+use cicasm::verify_checksums;
+use cicasm::warnings::WarningOptions;
 use clap::Parser as clap_parser;
+use clap::ValueEnum;
 use std::env;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::ExitCode;
 
 #[derive(clap_parser)]
 #[clap(version = "0.3.14", author = "Connor Nolan")]
@@ -29,23 +38,187 @@ struct Opts {
     /// Input file to assemble
     input: PathBuf,
 
-    /// Assembled output file path (optional, default: ./assembled.bin)
+    /// Assembled output file path (optional, default: ./assembled.bin, .hex or .srec, or
+    /// ./assembled.o with -c)
     #[clap(short, long)]
     output: Option<PathBuf>,
 
-    /// Assemble program as a boot ROM (no header, starts at 0x0000, 16 KiB max, must have
+    /// Format of the assembled output file
+    #[clap(long, value_enum, default_value_t = OutputFormat::Bin)]
+    format: OutputFormat,
+
+    /// Write one file per 16 KiB bank instead of a single ROM image, named after the output file
+    /// with the bank number added, e.g. game_bank01.bin. Addresses in each file start at 0
+    #[clap(long)]
+    split_banks: bool,
+
+    /// Assemble program as a boot ROM (no header, starts at 0x0000, exactly 16 KiB, must have
     /// interrupt vector table at 0x3FE0-0x3FFF)
     #[clap(short, long)]
     boot: bool,
+
+    /// Assemble into a relocatable object file instead of a ROM, to be linked into a ROM by cicld
+    /// (default output: ./assembled.o)
+    #[clap(short = 'c', long, conflicts_with_all = ["boot", "format", "split_banks", "symbols", "listing"])]
+    compile: bool,
+
+    /// Verify the header and global ROM checksums of an already assembled cartridge ROM given as
+    /// the input file, instead of assembling it
+    #[clap(long, conflicts_with_all = ["output", "boot", "split_banks", "compile"])]
+    verify: bool,
+
+    /// Define a constant before assembly, as NAME=value or NAME (defined as 1). Can be used with
+    /// .if/.ifdef to select build variants
+    #[clap(short = 'D', value_name = "NAME[=VALUE]", value_parser = parse_define)]
+    define: Vec<(String, i32)>,
+
+    /// Write the address of every label, the value of every constant and the layout of every
+    /// section to a symbol file, for use by debuggers and emulators
+    #[clap(long, value_name = "PATH")]
+    symbols: Option<PathBuf>,
+
+    /// Format of the symbol file
+    #[clap(long, value_enum, default_value_t = SymbolFormat::Plain, requires = "symbols")]
+    symbol_format: SymbolFormat,
+
+    /// Maximum number of errors to report before stopping, 0 reports every error
+    #[clap(long, value_name = "COUNT", default_value_t = DEFAULT_MAX_ERRORS)]
+    max_errors: usize,
+
+    /// Write a listing with the address, bank and emitted bytes of every source line
+    #[clap(long, value_name = "PATH")]
+    listing: Option<PathBuf>,
+
+    /// Byte used for .org, .align, .ds and section padding and for the unused space of the last
+    /// bank, until changed by .padbyte. By default padding is 0x00 and unused space is 0xFF
+    #[clap(long, value_name = "BYTE", value_parser = parse_byte)]
+    pad_byte: Option<u8>,
+
+    /// Enable a warning with -WNAME or disable it with -Wno-NAME. -Wall enables every warning,
+    /// -Werror turns warnings into errors and -Worg-padding=BYTES sets the .org padding limit.
+    /// Warnings: unused-label, org-padding, odd-address, rom-write, byte-truncation
+    #[clap(short = 'W', value_name = "WARNING", value_parser = parse_warning_flag)]
+    warnings: Vec<String>,
 }
 
-fn main() -> Result<()> {
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// Raw ROM image
+    Bin,
+    /// Intel HEX
+    Ihex,
+    /// Motorola S-record
+    Srec,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Bin => "bin",
+            OutputFormat::Ihex => "hex",
+            OutputFormat::Srec => "srec",
+        }
+    }
+
+    fn render(self, rom: &[u8]) -> Vec<u8> {
+        match self {
+            OutputFormat::Bin => rom.to_vec(),
+            OutputFormat::Ihex => rom_file::to_ihex(rom).into_bytes(),
+            OutputFormat::Srec => rom_file::to_srec(rom).into_bytes(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SymbolFormat {
+    /// "bank:addr name" lines
+    Plain,
+    Json,
+}
+
+// parse a command line constant definition, NAME=value or NAME
+fn parse_define(arg: &str) -> Result<(String, i32), String> {
+    let (name, value) = arg.split_once('=').unwrap_or((arg, "1"));
+
+    let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_name {
+        return Err(format!("invalid constant name: \"{}\"", name));
+    }
+
+    let parsed = if let Some(hex) = value.strip_prefix("0x").or(value.strip_prefix('$')) {
+        i32::from_str_radix(hex, 16)
+    } else {
+        value.parse::<i32>()
+    };
+
+    let value = parsed.map_err(|_| format!("invalid value for {}: \"{}\"", name, value))?;
+
+    Ok((name.to_string(), value))
+}
+
+// parse a decimal or 0x/$ prefixed hexadecimal byte value
+fn parse_byte(arg: &str) -> Result<u8, String> {
+    let parsed = if let Some(hex) = arg.strip_prefix("0x").or(arg.strip_prefix('$')) {
+        u8::from_str_radix(hex, 16)
+    } else {
+        arg.parse::<u8>()
+    };
+
+    parsed.map_err(|_| format!("invalid byte value: \"{}\"", arg))
+}
+
+// the path of the file holding a single bank, the bank number is added to the file name
+fn bank_path(output_path: &Path, bank: usize) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let name = match output_path.extension() {
+        Some(ext) => format!("{}_bank{:02}.{}", stem, bank, ext.to_string_lossy()),
+        None => format!("{}_bank{:02}", stem, bank),
+    };
+    output_path.with_file_name(name)
+}
+
+// check a -W flag, they are applied in order once every flag has been parsed
+fn parse_warning_flag(arg: &str) -> Result<String, String> {
+    WarningOptions::default().apply_flag(arg)?;
+    Ok(arg.to_string())
+}
+
+fn main() -> ExitCode {
     let opts: Opts = Opts::parse();
+
+    match run(opts) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", diagnostics::render_error(&error));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(opts: Opts) -> Result<()> {
+    if opts.verify {
+        let rom = fs::read(&opts.input)?;
+        verify_checksums(&rom, 0x0000)?;
+
+        println!("Checksums of {} are valid", opts.input.display());
+
+        return Ok(());
+    }
+
     let mut final_logical_addr: u16 = 0x7FFF;
     let mut expected_interrupt_table_addr: Option<u16> = Some(0x0060);
     let mut expected_header_addr: Option<u16> = Some(0x0000);
     let mut output_path: PathBuf = env::current_dir()?;
-    output_path.push("assembled.bin");
+    let extension = if opts.compile {
+        "o"
+    } else {
+        opts.format.extension()
+    };
+    output_path.push(format!("assembled.{}", extension));
 
     if let Some(out) = opts.output {
         output_path = out;
@@ -60,21 +233,87 @@ fn main() -> Result<()> {
     let reader = AsmFileReader;
     let input_path: &Path = Path::new(&opts.input);
 
-    let final_rom = assemble(
+    let mut options = AssemblyOptions {
+        max_errors: opts.max_errors,
+        pad_byte: opts.pad_byte,
+        ..AssemblyOptions::default()
+    };
+    if opts.boot {
+        options.min_banks = 1;
+    }
+    for flag in &opts.warnings {
+        options
+            .warnings
+            .apply_flag(flag)
+            .map_err(anyhow::Error::msg)?;
+    }
+
+    if opts.compile {
+        let object = assemble_object(input_path, &opts.define, &options, &reader)?;
+        fs::write(&output_path, object.to_json()?)?;
+
+        println!(
+            "Successfully assembled {} to {}",
+            opts.input.display(),
+            output_path.display()
+        );
+
+        return Ok(());
+    }
+
+    let output = assemble_with_options(
         input_path,
         final_logical_addr,
         expected_interrupt_table_addr,
         expected_header_addr,
+        &opts.define,
+        &options,
         &reader,
     )?;
 
-    fs::write(&output_path, final_rom)?;
+    for warning in &output.warnings {
+        eprintln!("{}\n", diagnostics::render_warning(warning));
+    }
+
+    let written = if opts.split_banks {
+        let banks: Vec<&[u8]> = rom_file::split_banks(&output.rom).collect();
+        for (bank, data) in banks.iter().enumerate() {
+            fs::write(bank_path(&output_path, bank), opts.format.render(data))?;
+        }
+        match banks.len() {
+            1 => bank_path(&output_path, 0).display().to_string(),
+            count => format!(
+                "{} - {}",
+                bank_path(&output_path, 0).display(),
+                bank_path(&output_path, count - 1).display()
+            ),
+        }
+    } else {
+        fs::write(&output_path, opts.format.render(&output.rom))?;
+        output_path.display().to_string()
+    };
+
+    if let Some(symbols_path) = &opts.symbols {
+        let symbols = output.symbol_file();
+        let contents = match opts.symbol_format {
+            SymbolFormat::Plain => symbols.to_sym(),
+            SymbolFormat::Json => symbols.to_json()?,
+        };
+        fs::write(symbols_path, contents)?;
+    }
+
+    if let Some(listing_path) = &opts.listing {
+        fs::write(listing_path, output.listing.render())?;
+    }
 
     println!(
         "Successfully assembled {} to {}",
         opts.input.display(),
-        output_path.display()
+        written
     );
+    for (bank, free) in output.free_bytes.iter().enumerate() {
+        println!("bank {:02}: {:5} bytes free", bank, free);
+    }
 
     Ok(())
 }
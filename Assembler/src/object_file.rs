@@ -0,0 +1,84 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+
+/// Version of the object file format, objects written by another version cannot be linked.
+pub const OBJECT_FORMAT_VERSION: u32 = 1;
+
+/// How the value of a relocation is written into the section data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelocationKind {
+    /// The 16 bit logical address of the symbol plus the addend, little endian.
+    Word,
+    /// The 16 bit bank number of the symbol, little endian, e.g. the `LDI R4, bank` of `CALL.far`.
+    Bank,
+    /// The signed 8 bit offset of a relative jump, from the jump instruction at `offset - 1` to
+    /// the symbol plus the addend. The symbol must be in the same bank as the jump.
+    Relative,
+}
+
+/// A use of a symbol whose address is only known once the section has been placed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relocation {
+    pub offset: u32, // offset of the value within the section data
+    pub kind: RelocationKind,
+    pub symbol: String,
+    pub addend: i32,
+}
+
+/// A named block of code or data, placed by the linker script. Sections the script does not list
+/// are placed in free space by the linker.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectSection {
+    pub name: String,
+    pub align: u32, // the section must start at a multiple of this, 1 when not aligned
+    pub nobits: bool,
+    #[serde(default)]
+    pub bank0: bool, // a section not listed in the linker script is only placed in bank 0
+    pub size: u32,
+    pub data: Vec<u8>, // empty for nobits sections
+    pub relocations: Vec<Relocation>,
+}
+
+/// A label defined in an object file, at an offset into one of its sections.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectSymbol {
+    pub name: String,
+    pub section: usize,
+    pub offset: u32,
+    pub global: bool, // exported with .global, other labels can only be used by this object
+}
+
+/// A relocatable object file written by `cicasm -c`, linked into a ROM by `cicld`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectFile {
+    pub version: u32,
+    pub sections: Vec<ObjectSection>,
+    pub symbols: Vec<ObjectSymbol>,
+    pub externs: Vec<String>, // labels declared with .extern, defined by another object file
+}
+
+impl ObjectFile {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<ObjectFile> {
+        serde_json::from_str(json)
+    }
+}
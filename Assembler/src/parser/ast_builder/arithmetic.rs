@@ -21,7 +21,7 @@ use anyhow::{Context, Result};
 
 impl<'a> AstBuilder<'a> {
     // build and check operands for a 2 operand add instruction
-    pub fn build_add_2_op(mut self) -> Result<Instruction> {
+    pub fn build_add_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let rs = self.expect_register().context(INVALID_SRC_OP_MSG)?;
 
@@ -29,7 +29,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a add immediate instruction
-    pub fn build_addi_2_op(mut self) -> Result<Instruction> {
+    pub fn build_addi_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self.expect_addr_or_label().context(INVALID_SRC_OP_MSG)?;
 
@@ -37,35 +37,35 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a 1 operand add instruction
-    pub fn build_add_1_op(mut self) -> Result<Instruction> {
+    pub fn build_add_1_op(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::AddAcc(r))
     }
 
     // build and check operands for a ADD SP instruction
-    pub fn build_add_sp(mut self) -> Result<Instruction> {
+    pub fn build_add_sp(&mut self) -> Result<Instruction> {
         let val = self.expect_signed_byte_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::AddSp(val))
     }
 
     // build and check operands for an add.b instruction
-    pub fn build_add_b(mut self) -> Result<Instruction> {
+    pub fn build_add_b(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::AddBAcc(r))
     }
 
     // build and check operands for an add accumulator immediate instruction
-    pub fn build_addi_1_op(mut self) -> Result<Instruction> {
+    pub fn build_addi_1_op(&mut self) -> Result<Instruction> {
         let src = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::AddAccI(src))
     }
 
     // build and check operands for a 2 operand sub instruction
-    pub fn build_sub_2_op(mut self) -> Result<Instruction> {
+    pub fn build_sub_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let rs = self.expect_register().context(INVALID_SRC_OP_MSG)?;
 
@@ -73,7 +73,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a sub immediate instruction
-    pub fn build_subi_2_op(mut self) -> Result<Instruction> {
+    pub fn build_subi_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self.expect_addr_or_label().context(INVALID_SRC_OP_MSG)?;
 
@@ -81,28 +81,28 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a 1 operand sub instruction
-    pub fn build_sub_1_op(mut self) -> Result<Instruction> {
+    pub fn build_sub_1_op(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::SubAcc(r))
     }
 
     // build and check operands for an sub.b instruction
-    pub fn build_sub_b(mut self) -> Result<Instruction> {
+    pub fn build_sub_b(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::SubBAcc(r))
     }
 
     // build and check operands for a sub accumulator immediate instruction
-    pub fn build_subi_1_op(mut self) -> Result<Instruction> {
+    pub fn build_subi_1_op(&mut self) -> Result<Instruction> {
         let src = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::SubAccI(src))
     }
 
     // build and check operands for a 2 operand and instruction
-    pub fn build_and_2_op(mut self) -> Result<Instruction> {
+    pub fn build_and_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let rs = self.expect_register().context(INVALID_SRC_OP_MSG)?;
 
@@ -110,7 +110,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a and immediate instruction
-    pub fn build_andi_2_op(mut self) -> Result<Instruction> {
+    pub fn build_andi_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self.expect_addr_or_label().context(INVALID_SRC_OP_MSG)?;
 
@@ -118,28 +118,28 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a 1 operand and instruction
-    pub fn build_and_1_op(mut self) -> Result<Instruction> {
+    pub fn build_and_1_op(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::AndAcc(r))
     }
 
     // build and check operands for an and.b instruction
-    pub fn build_and_b(mut self) -> Result<Instruction> {
+    pub fn build_and_b(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::AndBAcc(r))
     }
 
     // build and check operands for a and accumulator immediate instruction
-    pub fn build_andi_1_op(mut self) -> Result<Instruction> {
+    pub fn build_andi_1_op(&mut self) -> Result<Instruction> {
         let src = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::AndAccI(src))
     }
 
     // build and check operands for a 2 operand or instruction
-    pub fn build_or_2_op(mut self) -> Result<Instruction> {
+    pub fn build_or_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let rs = self.expect_register().context(INVALID_SRC_OP_MSG)?;
 
@@ -147,7 +147,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a or immediate instruction
-    pub fn build_ori_2_op(mut self) -> Result<Instruction> {
+    pub fn build_ori_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self.expect_addr_or_label().context(INVALID_SRC_OP_MSG)?;
 
@@ -155,28 +155,28 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a 1 operand or instruction
-    pub fn build_or_1_op(mut self) -> Result<Instruction> {
+    pub fn build_or_1_op(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::OrAcc(r))
     }
 
     // build and check operands for an or.b instruction
-    pub fn build_or_b(mut self) -> Result<Instruction> {
+    pub fn build_or_b(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::OrBAcc(r))
     }
 
     // build and check operands for an or accumulator immediate instruction
-    pub fn build_ori_1_op(mut self) -> Result<Instruction> {
+    pub fn build_ori_1_op(&mut self) -> Result<Instruction> {
         let src = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::OrAccI(src))
     }
 
     // build and check operands for a 2 operand xor instruction
-    pub fn build_xor_2_op(mut self) -> Result<Instruction> {
+    pub fn build_xor_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let rs = self.expect_register().context(INVALID_SRC_OP_MSG)?;
 
@@ -184,7 +184,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a xor immediate instruction
-    pub fn build_xori_2_op(mut self) -> Result<Instruction> {
+    pub fn build_xori_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self.expect_addr_or_label().context(INVALID_SRC_OP_MSG)?;
 
@@ -192,28 +192,28 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a 1 operand xor instruction
-    pub fn build_xor_1_op(mut self) -> Result<Instruction> {
+    pub fn build_xor_1_op(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::XorAcc(r))
     }
 
     // build and check operands for an xor.b instruction
-    pub fn build_xor_b(mut self) -> Result<Instruction> {
+    pub fn build_xor_b(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::XorBAcc(r))
     }
 
     // build and check operands for a xor accumulator immediate instruction
-    pub fn build_xori_1_op(mut self) -> Result<Instruction> {
+    pub fn build_xori_1_op(&mut self) -> Result<Instruction> {
         let src = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::XorAccI(src))
     }
 
     // build and check operands for a 2 operand cmp instruction
-    pub fn build_cmp_2_op(mut self) -> Result<Instruction> {
+    pub fn build_cmp_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let rs = self.expect_register().context(INVALID_SRC_OP_MSG)?;
 
@@ -221,7 +221,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a cmp immediate instruction
-    pub fn build_cmpi_2_op(mut self) -> Result<Instruction> {
+    pub fn build_cmpi_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self.expect_addr_or_label().context(INVALID_SRC_OP_MSG)?;
 
@@ -229,28 +229,28 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a 1 operand cmp instruction
-    pub fn build_cmp_1_op(mut self) -> Result<Instruction> {
+    pub fn build_cmp_1_op(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::CmpAcc(r))
     }
 
     // build and check operands for an cmp.b instruction
-    pub fn build_cmp_b(mut self) -> Result<Instruction> {
+    pub fn build_cmp_b(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::CmpBAcc(r))
     }
 
     // build and check operands for a cmp accumulator immediate instruction
-    pub fn build_cmpi_1_op(mut self) -> Result<Instruction> {
+    pub fn build_cmpi_1_op(&mut self) -> Result<Instruction> {
         let src = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::CmpAccI(src))
     }
 
     // build and check operands for a 2 operand adc instruction
-    pub fn build_adc_2_op(mut self) -> Result<Instruction> {
+    pub fn build_adc_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let rs = self.expect_register().context(INVALID_SRC_OP_MSG)?;
 
@@ -258,14 +258,14 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for an ADC accumulator immediate instruction
-    pub fn build_adci_1_op(mut self) -> Result<Instruction> {
+    pub fn build_adci_1_op(&mut self) -> Result<Instruction> {
         let src = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::AdcAccI(src))
     }
 
     // build and check operands for a 2 operand sbc instruction
-    pub fn build_sbc_2_op(mut self) -> Result<Instruction> {
+    pub fn build_sbc_2_op(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let rs = self.expect_register().context(INVALID_SRC_OP_MSG)?;
 
@@ -273,21 +273,21 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for an SBC accumulator immediate instruction
-    pub fn build_sbci_1_op(mut self) -> Result<Instruction> {
+    pub fn build_sbci_1_op(&mut self) -> Result<Instruction> {
         let src = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::SbcAccI(src))
     }
 
     // build and check operands for a 1 operand inc instruction
-    pub fn build_inc(mut self) -> Result<Instruction> {
+    pub fn build_inc(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::Inc(r))
     }
 
     // build and check operands for a 1 operand dec instruction
-    pub fn build_dec(mut self) -> Result<Instruction> {
+    pub fn build_dec(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::Dec(r))
@@ -22,42 +22,42 @@ use anyhow::{Context, Result};
 
 impl<'a> AstBuilder<'a> {
     // build and check operands for a sra instruction
-    pub fn build_sra(mut self) -> Result<Instruction> {
+    pub fn build_sra(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::Sra(r))
     }
 
     // build and check operands for a shl instruction
-    pub fn build_shl(mut self) -> Result<Instruction> {
+    pub fn build_shl(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::Shl(r))
     }
 
     // build and check operands for a shr instruction
-    pub fn build_shr(mut self) -> Result<Instruction> {
+    pub fn build_shr(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::Shr(r))
     }
 
     // build and check operands for a rol instruction
-    pub fn build_rol(mut self) -> Result<Instruction> {
+    pub fn build_rol(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::Rol(r))
     }
 
     // build and check operands for a ror instruction
-    pub fn build_ror(mut self) -> Result<Instruction> {
+    pub fn build_ror(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::Ror(r))
     }
 
     // build and check operands for a bit check instruction
-    pub fn build_bit(mut self) -> Result<Instruction> {
+    pub fn build_bit(&mut self) -> Result<Instruction> {
         let op = self.pop_operand().context(INVALID_OP_MSG)?;
         let bit = self
             .expect_bit_id_or_label()
@@ -68,6 +68,7 @@ impl<'a> AstBuilder<'a> {
             Operand::Indirect(r) => Ok(Instruction::BitIndirect(r, bit)),
             Operand::AbsAddr(addr) => Ok(Instruction::BitAbs(Operand::Immediate(addr as i32), bit)),
             Operand::AbsLabel(label) => Ok(Instruction::BitAbs(Operand::Label(label), bit)),
+            Operand::AbsExpr(expr) => Ok(Instruction::BitAbs(Operand::Expr(expr), bit)),
             _ => {
                 Err(AssemblyError::StructuralError {
                     line: self.line_number,
@@ -78,7 +79,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a set instruction
-    pub fn build_set(mut self) -> Result<Instruction> {
+    pub fn build_set(&mut self) -> Result<Instruction> {
         let op = self.pop_operand().context(INVALID_OP_MSG)?;
         let bit = self
             .expect_bit_id_or_label()
@@ -89,6 +90,7 @@ impl<'a> AstBuilder<'a> {
             Operand::Indirect(r) => Ok(Instruction::SetIndirect(r, bit)),
             Operand::AbsAddr(addr) => Ok(Instruction::SetAbs(Operand::Immediate(addr as i32), bit)),
             Operand::AbsLabel(label) => Ok(Instruction::SetAbs(Operand::Label(label), bit)),
+            Operand::AbsExpr(expr) => Ok(Instruction::SetAbs(Operand::Expr(expr), bit)),
             _ => {
                 Err(AssemblyError::StructuralError {
                     line: self.line_number,
@@ -99,7 +101,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a res instruction
-    pub fn build_res(mut self) -> Result<Instruction> {
+    pub fn build_res(&mut self) -> Result<Instruction> {
         let op = self.pop_operand().context(INVALID_OP_MSG)?;
         let bit = self
             .expect_bit_id_or_label()
@@ -110,6 +112,7 @@ impl<'a> AstBuilder<'a> {
             Operand::Indirect(r) => Ok(Instruction::ResIndirect(r, bit)),
             Operand::AbsAddr(addr) => Ok(Instruction::ResAbs(Operand::Immediate(addr as i32), bit)),
             Operand::AbsLabel(label) => Ok(Instruction::ResAbs(Operand::Label(label), bit)),
+            Operand::AbsExpr(expr) => Ok(Instruction::ResAbs(Operand::Expr(expr), bit)),
             _ => {
                 Err(AssemblyError::StructuralError {
                     line: self.line_number,
@@ -18,17 +18,17 @@ use crate::parser::AstBuilder;
 use crate::parser::ast_builder::AssemblyError;
 use crate::parser::ast_builder::constants::*;
 use crate::parser::ast_builder::utility_functions::*;
-use crate::parser::{Instruction, Operand};
+use crate::parser::{BranchForm, Instruction, Operand};
 use anyhow::{Context, Result};
 
 impl<'a> AstBuilder<'a> {
     // build and check operands for a jump instruction
-    pub fn build_jmp(mut self) -> Result<Instruction> {
+    pub fn build_jmp(&mut self) -> Result<Instruction> {
         let op = self.pop_operand().context(INVALID_OP_MSG)?;
 
         match op {
             Operand::Indirect(r) => Ok(Instruction::JmpIndirect(r)),
-            Operand::Label(_) => Ok(Instruction::JmpI(op)),
+            Operand::Label(_) | Operand::Expr(_) => Ok(Instruction::JmpI(op)),
             Operand::Immediate(imm) => {
                 check_unsigned_word(imm, self.line_number)?;
                 Ok(Instruction::JmpI(op))
@@ -44,14 +44,14 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a jump relative instruction
-    pub fn build_jr(mut self) -> Result<Instruction> {
+    pub fn build_jr(&mut self) -> Result<Instruction> {
         let op = self.expect_sbyte_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::JrI(op))
     }
 
     // build and check operands for a conditional jump instruction
-    pub fn build_jcc(mut self) -> Result<Instruction> {
+    pub fn build_jcc(&mut self) -> Result<Instruction> {
         let cc = self.pop_cc().context("Invalid condidtion code.")?;
         let op = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
 
@@ -59,7 +59,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a conditional jump relative instruction
-    pub fn build_jrcc(mut self) -> Result<Instruction> {
+    pub fn build_jrcc(&mut self) -> Result<Instruction> {
         let cc = self.pop_cc().context("Invalid condidtion code.")?;
         let op = self.expect_sbyte_or_label().context(INVALID_OP_MSG)?;
 
@@ -67,19 +67,41 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a DJNZ instruction
-    pub fn build_djnz(mut self) -> Result<Instruction> {
+    pub fn build_djnz(&mut self) -> Result<Instruction> {
         let op = self.expect_sbyte_or_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::Djnz(op))
     }
 
+    // build a relaxed jump, the operand is always the target address
+    pub fn build_jmp_auto(&mut self) -> Result<Instruction> {
+        let op = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
+
+        Ok(Instruction::JmpAuto(op, BranchForm::Short))
+    }
+
+    // build a relaxed conditional jump
+    pub fn build_jcc_auto(&mut self) -> Result<Instruction> {
+        let cc = self.pop_cc().context("Invalid condidtion code.")?;
+        let op = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
+
+        Ok(Instruction::JccAuto(cc, op, BranchForm::Short))
+    }
+
+    // build a relaxed DJNZ
+    pub fn build_djnz_auto(&mut self) -> Result<Instruction> {
+        let op = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
+
+        Ok(Instruction::DjnzAuto(op, BranchForm::Short))
+    }
+
     // build and check operands for a call instruction
-    pub fn build_call(mut self) -> Result<Instruction> {
+    pub fn build_call(&mut self) -> Result<Instruction> {
         let op = self.pop_operand().context(INVALID_OP_MSG)?;
 
         match op {
             Operand::Indirect(r) => Ok(Instruction::CallIndirect(r)),
-            Operand::Label(_) => Ok(Instruction::CallI(op)),
+            Operand::Label(_) | Operand::Expr(_) => Ok(Instruction::CallI(op)),
             Operand::Immediate(imm) => {
                 check_unsigned_word(imm, self.line_number)?;
                 Ok(Instruction::CallI(op))
@@ -95,7 +117,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a conditional call instruction
-    pub fn build_callcc(mut self) -> Result<Instruction> {
+    pub fn build_callcc(&mut self) -> Result<Instruction> {
         let cc = self.pop_cc().context("Invalid condition code.")?;
         let op = self.expect_addr_or_label().context(INVALID_OP_MSG)?;
 
@@ -103,7 +125,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a SYSCALL instruction
-    pub fn build_syscall(mut self) -> Result<Instruction> {
+    pub fn build_syscall(&mut self) -> Result<Instruction> {
         let index = self
             .expect_unsigned_byte_or_label()
             .context(INVALID_OP_MSG)?;
@@ -114,14 +136,14 @@ impl<'a> AstBuilder<'a> {
     // ----------- Synthetic Control Flow Instructions -----------
 
     // build a call.far instruction
-    pub fn build_call_far(mut self) -> Result<Instruction> {
+    pub fn build_call_far(&mut self) -> Result<Instruction> {
         let label = self.expect_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::CallFar(label))
     }
 
     // build a call.far via instruction
-    pub fn build_call_far_via(mut self) -> Result<Instruction> {
+    pub fn build_call_far_via(&mut self) -> Result<Instruction> {
         let call_label = self.expect_label().context(INVALID_OP_MSG)?;
         let via_label = self.expect_label().context(INVALID_OP_MSG)?;
 
@@ -129,14 +151,14 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build a jump.far instruction
-    pub fn build_jmp_far(mut self) -> Result<Instruction> {
+    pub fn build_jmp_far(&mut self) -> Result<Instruction> {
         let label = self.expect_label().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::JmpFar(label))
     }
 
     // build a jump.far via instruction
-    pub fn build_jmp_far_via(mut self) -> Result<Instruction> {
+    pub fn build_jmp_far_via(&mut self) -> Result<Instruction> {
         let call_label = self.expect_label().context(INVALID_OP_MSG)?;
         let via_label = self.expect_label().context(INVALID_OP_MSG)?;
 
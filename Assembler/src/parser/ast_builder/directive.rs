@@ -14,12 +14,17 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::ast::Charmap;
+use crate::ast::EnumMember;
+use crate::ast::Expr;
 use crate::ast::HeaderInfo;
 use crate::ast::SectionOptions;
+use crate::ast::StructField;
 use crate::parser::AstBuilder;
 use crate::parser::Rule;
 use crate::parser::ast_builder::AssemblyError;
 use crate::parser::ast_builder::constants::*;
+use crate::parser::ast_builder::operand_builders::{build_expr, build_string_literal};
 use crate::parser::ast_builder::utility_functions::*;
 use crate::parser::{Directive, Operand};
 use anyhow::{Context, Result};
@@ -38,7 +43,7 @@ impl<'a> AstBuilder<'a> {
 
         match id {
             Operand::Immediate(val) => {
-                if val > 256 || val < 0 {
+                if !(0..=256).contains(&val) {
                     Err(AssemblyError::StructuralError {
                         line: self.line_number,
                         reason: ".bank number must be an unsigned value between 0 and 256"
@@ -49,7 +54,7 @@ impl<'a> AstBuilder<'a> {
                     Ok(Directive::Bank(id))
                 }
             }
-            Operand::Label(_) => Ok(Directive::Bank(id)),
+            Operand::Label(_) | Operand::Expr(_) => Ok(Directive::Bank(id)),
             _ => Err(AssemblyError::StructuralError {
                 line: self.line_number,
                 reason: ".bank argument must be an immediate value.".to_string(),
@@ -65,11 +70,8 @@ impl<'a> AstBuilder<'a> {
 
         for op in ops {
             match op {
-                Operand::Immediate(val) => {
-                    check_unsigned_byte(val, self.line_number)?;
-                    bytes.push(op);
-                }
-                Operand::Label(_) => bytes.push(op),
+                // values that do not fit in a byte are truncated with a warning in pass 2
+                Operand::Immediate(_) | Operand::Label(_) | Operand::Expr(_) => bytes.push(op),
                 _ => {
                     return Err(AssemblyError::StructuralError {
                         line: self.line_number,
@@ -95,7 +97,7 @@ impl<'a> AstBuilder<'a> {
                         .with_context(|| format!("Invalid word value: {}", val))?;
                     words.push(op);
                 }
-                Operand::Label(_) => words.push(op),
+                Operand::Label(_) | Operand::Expr(_) => words.push(op),
                 _ => {
                     return Err(AssemblyError::StructuralError {
                         line: self.line_number,
@@ -110,21 +112,196 @@ impl<'a> AstBuilder<'a> {
         Ok(Directive::Word(words))
     }
 
-    // build a word data directive
-    pub fn build_define_directive(mut self) -> Result<Directive> {
-        let label = self.expect_label().context("Invalid define label.")?;
-        let value = self.pop_operand().context("Invalid define value.")?;
+    // build a .fill directive, the value defaults to the padding byte
+    pub fn build_fill_directive(mut self) -> Result<Directive> {
+        let count = self.expect_value_operand(".fill count")?;
+        let value = match self.pairs.peek() {
+            Some(_) => Some(self.expect_value_operand(".fill value")?),
+            None => None,
+        };
 
-        match value {
-            Operand::Immediate(_) => Ok(Directive::Define(label, value)),
-            // TODO: allow more value operand types in the future, like expressions
-            _ => {
+        Ok(Directive::Fill(count, value))
+    }
+
+    // build a .ds or .res directive, which fill with the padding byte
+    pub fn build_ds_directive(mut self) -> Result<Directive> {
+        let count = self.expect_value_operand(".ds count")?;
+
+        Ok(Directive::Fill(count, None))
+    }
+
+    // build a .padbyte directive
+    pub fn build_padbyte_directive(mut self) -> Result<Directive> {
+        let value = self.expect_value_operand(".padbyte value")?;
+
+        Ok(Directive::PadByte(value))
+    }
+
+    // a number, constant or expression operand
+    fn expect_value_operand(&mut self, name: &str) -> Result<Operand> {
+        let op = self
+            .pop_operand()
+            .with_context(|| format!("Invalid {}.", name))?;
+
+        match op {
+            Operand::Immediate(_) | Operand::Label(_) | Operand::Expr(_) => Ok(op),
+            _ => Err(AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: format!("{} must be a number or an expression.", name),
+            }
+            .into()),
+        }
+    }
+
+    // build an .ascii directive
+    pub fn build_ascii_directive(mut self) -> Result<Directive> {
+        Ok(Directive::Ascii(self.expect_string_bytes()?))
+    }
+
+    // build an .asciz or .string directive
+    pub fn build_asciz_directive(mut self) -> Result<Directive> {
+        Ok(Directive::Asciz(self.expect_string_bytes()?))
+    }
+
+    // the text of a string literal that is stored in the rom
+    fn expect_string_bytes(&mut self) -> Result<Vec<u8>> {
+        let pair = self
+            .pairs
+            .next()
+            .ok_or_else(|| AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: "Expected a string literal.".to_string(),
+            })?;
+
+        let Operand::String(text) = build_string_literal(pair)? else {
+            unreachable!("String literal builder always returns a string operand");
+        };
+
+        string_to_bytes(&text, self.line_number)
+    }
+
+    // build a charmap directive
+    pub fn build_charmap_directive(mut self) -> Result<Directive> {
+        let is_preset = self
+            .pairs
+            .peek()
+            .is_some_and(|pair| pair.as_rule() == Rule::charmap_preset);
+
+        if is_preset {
+            let preset = self.pairs.next().unwrap().as_str().to_ascii_lowercase();
+            return match preset.as_str() {
+                "font" => Ok(Directive::Charmap(Charmap::Font)),
+                _ => Ok(Directive::Charmap(Charmap::Ascii)),
+            };
+        }
+
+        let chars = self.pop_operand().context("Invalid charmap character.")?;
+        let value = self.pop_operand().context("Invalid charmap value.")?;
+
+        match (&chars, &value) {
+            (
+                Operand::Immediate(_) | Operand::String(_),
+                Operand::Immediate(_) | Operand::Label(_) | Operand::Expr(_),
+            ) => Ok(Directive::Charmap(Charmap::Map(chars, value))),
+            _ => Err(AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: ".charmap must map a character or string to a value.".to_string(),
+            }
+            .into()),
+        }
+    }
+
+    // build a .struct ... .endstruct block
+    pub fn build_struct_directive(mut self) -> Result<Directive> {
+        let name = self.pop_constant_name().context("Invalid .struct name.")?;
+        let mut fields: Vec<StructField> = Vec::new();
+
+        for field in self.pairs {
+            let line_number = field.line_col().0;
+            let mut inner = field.into_inner();
+
+            let field_name = inner.next().unwrap().as_str().to_string();
+            let size = match inner.next().unwrap().as_str().to_ascii_lowercase().as_str() {
+                ".w" => 2,
+                _ => 1,
+            };
+            let length = inner.next().map(build_expr).transpose()?;
+
+            if fields.iter().any(|f| f.name == field_name) {
                 return Err(AssemblyError::StructuralError {
-                    line: self.line_number,
-                    reason: ".define value must be a number.".to_string(),
+                    line: line_number,
+                    reason: format!("Duplicate field \"{}\" in .struct {}.", field_name, name),
+                }
+                .into());
+            }
+
+            fields.push(StructField {
+                name: field_name,
+                size,
+                length,
+            });
+        }
+
+        Ok(Directive::Struct(name, fields))
+    }
+
+    // build an .enum ... .endenum block, the name is optional
+    pub fn build_enum_directive(mut self) -> Result<Directive> {
+        let name = match self.pairs.peek() {
+            Some(pair) if pair.as_rule() == Rule::identifier => {
+                Some(self.pop_constant_name().context("Invalid .enum name.")?)
+            }
+            _ => None,
+        };
+        let mut members: Vec<EnumMember> = Vec::new();
+
+        for member in self.pairs {
+            let line_number = member.line_col().0;
+            let mut inner = member.into_inner();
+
+            let member_name = inner.next().unwrap().as_str().to_string();
+            let value = inner.next().map(build_expr).transpose()?;
+
+            if members.iter().any(|m| m.name == member_name) {
+                return Err(AssemblyError::StructuralError {
+                    line: line_number,
+                    reason: format!("Duplicate .enum member \"{}\".", member_name),
                 }
                 .into());
             }
+
+            members.push(EnumMember {
+                name: member_name,
+                value,
+            });
+        }
+
+        Ok(Directive::Enum(name, members))
+    }
+
+    // build a define directive
+    pub fn build_define_directive(mut self) -> Result<Directive> {
+        let label = self
+            .pairs
+            .next()
+            .ok_or_else(|| AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: "Invalid define label.".to_string(),
+            })?
+            .as_str()
+            .to_string();
+        let value = self.pop_operand().context("Invalid define value.")?;
+
+        match value {
+            // labels and expressions are resolved against the constant and symbol tables later
+            Operand::Immediate(_) | Operand::Label(_) | Operand::Expr(_) => {
+                Ok(Directive::Define(label, value))
+            }
+            _ => Err(AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: ".define value must be a number or an expression.".to_string(),
+            }
+            .into()),
         }
     }
 
@@ -134,13 +311,11 @@ impl<'a> AstBuilder<'a> {
 
         match op {
             Operand::String(s) => Ok(Directive::Include(s)),
-            _ => {
-                return Err(AssemblyError::StructuralError {
-                    line: self.line_number,
-                    reason: ".include value must be a path string.".to_string(),
-                }
-                .into());
+            _ => Err(AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: ".include value must be a path string.".to_string(),
             }
+            .into()),
         }
     }
 
@@ -150,13 +325,11 @@ impl<'a> AstBuilder<'a> {
 
         match op {
             Operand::String(s) => Ok(Directive::Incbin(s)),
-            _ => {
-                return Err(AssemblyError::StructuralError {
-                    line: self.line_number,
-                    reason: ".incbin value must be a path string.".to_string(),
-                }
-                .into());
+            _ => Err(AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: ".incbin value must be a path string.".to_string(),
             }
+            .into()),
         }
     }
 
@@ -169,7 +342,7 @@ impl<'a> AstBuilder<'a> {
                 continue; // Skip comments, newlines, etc.
             }
 
-            let line_number = info_line.as_span().start_pos().line_col().0;
+            let line_number = info_line.line_col().0;
             let Some(sub_directive) = info_line.into_inner().next() else {
                 continue;
             };
@@ -276,7 +449,7 @@ impl<'a> AstBuilder<'a> {
         let mut op_table: Vec<Operand> = Vec::new();
 
         for table_line in self.pairs {
-            let line_number = table_line.as_span().start_pos().line_col().0;
+            let line_number = table_line.line_col().0;
 
             if let Rule::word_directive = table_line.as_rule() {
                 let field_builder = AstBuilder::new(table_line.clone());
@@ -388,9 +561,48 @@ impl<'a> AstBuilder<'a> {
 
                     section_options.align = Some(alignment as u32);
                 }
+                Rule::nobits_attr => {
+                    if section_options.nobits {
+                        return Err(AssemblyError::StructuralError {
+                            line: self.line_number,
+                            reason: ".section nobits attribute defined multiple times.".to_string(),
+                        }
+                        .into());
+                    }
+                    section_options.nobits = true;
+                }
+                Rule::bank0_attr => {
+                    if section_options.bank0 {
+                        return Err(AssemblyError::StructuralError {
+                            line: self.line_number,
+                            reason: ".section bank0 attribute defined multiple times.".to_string(),
+                        }
+                        .into());
+                    }
+                    section_options.bank0 = true;
+                }
                 _ => {}
             }
         }
+
+        if section_options.nobits && section_options.paddr.is_some() {
+            return Err(AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: ".section paddr attribute cannot be used in a nobits section, nothing is placed in the ROM."
+                    .to_string(),
+            }
+            .into());
+        }
+
+        if section_options.nobits && section_options.bank0 {
+            return Err(AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: ".section bank0 attribute cannot be used in a nobits section, nothing is placed in the ROM."
+                    .to_string(),
+            }
+            .into());
+        }
+
         Ok(Directive::SectionStart(section_options))
     }
 
@@ -407,4 +619,71 @@ impl<'a> AstBuilder<'a> {
 
         Ok(Directive::Align(alignment as u32))
     }
+
+    // build an .if directive
+    pub fn build_if_directive(mut self) -> Result<Directive> {
+        let condition = self.pop_condition().context("Invalid .if condition.")?;
+
+        Ok(Directive::If(condition))
+    }
+
+    // build an .elif directive
+    pub fn build_elif_directive(mut self) -> Result<Directive> {
+        let condition = self.pop_condition().context("Invalid .elif condition.")?;
+
+        Ok(Directive::Elif(condition))
+    }
+
+    // build an .ifdef directive
+    pub fn build_ifdef_directive(mut self) -> Result<Directive> {
+        let name = self.pop_constant_name().context("Invalid .ifdef name.")?;
+
+        Ok(Directive::Ifdef(name))
+    }
+
+    // build an .ifndef directive
+    pub fn build_ifndef_directive(mut self) -> Result<Directive> {
+        let name = self.pop_constant_name().context("Invalid .ifndef name.")?;
+
+        Ok(Directive::Ifndef(name))
+    }
+
+    // build a .global directive
+    pub fn build_global_directive(self) -> Result<Directive> {
+        Ok(Directive::Global(self.collect_label_names()))
+    }
+
+    // build an .extern directive
+    pub fn build_extern_directive(self) -> Result<Directive> {
+        Ok(Directive::Extern(self.collect_label_names()))
+    }
+
+    fn collect_label_names(self) -> Vec<String> {
+        self.pairs.map(|pair| pair.as_str().to_string()).collect()
+    }
+
+    // conditions are kept as expressions, they are evaluated against the constant table in pass 0
+    fn pop_condition(&mut self) -> Result<Expr> {
+        let pair = self
+            .pairs
+            .next()
+            .ok_or_else(|| AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: "Expected a condition, but found none.".to_string(),
+            })?;
+
+        build_expr(pair)
+    }
+
+    fn pop_constant_name(&mut self) -> Result<String> {
+        let pair = self
+            .pairs
+            .next()
+            .ok_or_else(|| AssemblyError::StructuralError {
+                line: self.line_number,
+                reason: "Expected a constant name, but found none.".to_string(),
+            })?;
+
+        Ok(pair.as_str().to_string())
+    }
 }
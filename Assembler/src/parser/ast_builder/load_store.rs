@@ -22,7 +22,7 @@ use anyhow::{Context, Result};
 
 impl<'a> AstBuilder<'a> {
     // build and check operands for a load instruction
-    pub fn build_ld(mut self) -> Result<Instruction> {
+    pub fn build_ld(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self.pop_operand().context(INVALID_SRC_OP_MSG)?;
 
@@ -30,6 +30,7 @@ impl<'a> AstBuilder<'a> {
             Operand::Register(rs) => Ok(Instruction::LdReg(rd, rs)),
             Operand::AbsAddr(addr) => Ok(Instruction::LdAbs(rd, Operand::Immediate(addr as i32))),
             Operand::AbsLabel(label) => Ok(Instruction::LdAbs(rd, Operand::Label(label))),
+            Operand::AbsExpr(expr) => Ok(Instruction::LdAbs(rd, Operand::Expr(expr))),
             Operand::Indexed(rs, offset) => Ok(Instruction::LdIndexed(
                 rd,
                 rs,
@@ -38,6 +39,9 @@ impl<'a> AstBuilder<'a> {
             Operand::IndexedLabel(rs, label) => {
                 Ok(Instruction::LdIndexed(rd, rs, Operand::Label(label)))
             }
+            Operand::IndexedExpr(rs, expr) => {
+                Ok(Instruction::LdIndexed(rd, rs, Operand::Expr(expr)))
+            }
             Operand::Indirect(rs) => Ok(Instruction::LdIndirect(rd, rs)),
             Operand::PreDecrement(rs) => Ok(Instruction::LdPreDec(rd, rs)),
             Operand::PostIncrement(rs) => Ok(Instruction::LdPostInc(rd, rs)),
@@ -50,13 +54,14 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a store instruction
-    pub fn build_st(mut self) -> Result<Instruction> {
+    pub fn build_st(&mut self) -> Result<Instruction> {
         let dest = self.pop_operand().context(INVALID_DEST_OP_MSG)?;
         let rs = self.expect_register().context(INVALID_SRC_OP_MSG)?;
 
         match dest {
             Operand::AbsAddr(addr) => Ok(Instruction::StAbs(Operand::Immediate(addr as i32), rs)),
             Operand::AbsLabel(label) => Ok(Instruction::StAbs(Operand::Label(label), rs)),
+            Operand::AbsExpr(expr) => Ok(Instruction::StAbs(Operand::Expr(expr), rs)),
             Operand::Indirect(rd) => Ok(Instruction::StIndirect(rd, rs)),
             Operand::PostIncrement(rd) => Ok(Instruction::StPostInc(rd, rs)),
             Operand::PreDecrement(rd) => Ok(Instruction::StPreDec(rd, rs)),
@@ -68,6 +73,9 @@ impl<'a> AstBuilder<'a> {
             Operand::IndexedLabel(rd, label) => {
                 Ok(Instruction::StIndexed(rd, Operand::Label(label), rs))
             }
+            Operand::IndexedExpr(rd, expr) => {
+                Ok(Instruction::StIndexed(rd, Operand::Expr(expr), rs))
+            }
             _ => Err(AssemblyError::StructuralError {
                 line: self.line_number,
                 reason: "Invalid desitination operand to ST instruction.".to_string(),
@@ -77,7 +85,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a load immediate instruction
-    pub fn build_ldi(mut self) -> Result<Instruction> {
+    pub fn build_ldi(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self.expect_addr_or_label().context(INVALID_SRC_OP_MSG)?;
 
@@ -85,13 +93,14 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a load byte instruction
-    pub fn build_ld_b(mut self) -> Result<Instruction> {
+    pub fn build_ld_b(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self.pop_operand().context(INVALID_SRC_OP_MSG)?;
 
         match src {
             Operand::AbsAddr(addr) => Ok(Instruction::LdBAbs(rd, Operand::Immediate(addr as i32))),
             Operand::AbsLabel(label) => Ok(Instruction::LdBAbs(rd, Operand::Label(label))),
+            Operand::AbsExpr(expr) => Ok(Instruction::LdBAbs(rd, Operand::Expr(expr))),
             Operand::Indirect(rs) => Ok(Instruction::LdBIndirect(rd, rs)),
             Operand::PreDecrement(rs) => Ok(Instruction::LdBPreDec(rd, rs)),
             Operand::PostIncrement(rs) => Ok(Instruction::LdBPostInc(rd, rs)),
@@ -106,13 +115,14 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a store byte instruction
-    pub fn build_st_b(mut self) -> Result<Instruction> {
+    pub fn build_st_b(&mut self) -> Result<Instruction> {
         let dest = self.pop_operand().context(INVALID_DEST_OP_MSG)?;
         let rs = self.expect_register().context(INVALID_SRC_OP_MSG)?;
 
         match dest {
             Operand::AbsAddr(addr) => Ok(Instruction::StBAbs(Operand::Immediate(addr as i32), rs)),
             Operand::AbsLabel(label) => Ok(Instruction::StBAbs(Operand::Label(label), rs)),
+            Operand::AbsExpr(expr) => Ok(Instruction::StBAbs(Operand::Expr(expr), rs)),
             Operand::Indirect(rd) => Ok(Instruction::StBIndirect(rd, rs)),
             Operand::PreDecrement(rd) => Ok(Instruction::StBPreDec(rd, rs)),
             Operand::PostIncrement(rd) => Ok(Instruction::StBPostInc(rd, rs)),
@@ -127,7 +137,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a load byte immediate instruction
-    pub fn build_ldi_b(mut self) -> Result<Instruction> {
+    pub fn build_ldi_b(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self
             .expect_unsigned_byte_or_label()
@@ -137,7 +147,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a lea instruction
-    pub fn build_lea(mut self) -> Result<Instruction> {
+    pub fn build_lea(&mut self) -> Result<Instruction> {
         let rd = self.expect_register().context(INVALID_DEST_OP_MSG)?;
         let src = self.pop_operand().context(INVALID_SRC_OP_MSG)?;
 
@@ -146,6 +156,7 @@ impl<'a> AstBuilder<'a> {
                 Ok(Instruction::Lea(rd, rs, Operand::Immediate(offset as i32)))
             }
             Operand::IndexedLabel(rs, label) => Ok(Instruction::Lea(rd, rs, Operand::Label(label))),
+            Operand::IndexedExpr(rs, expr) => Ok(Instruction::Lea(rd, rs, Operand::Expr(expr))),
             _ => Err(AssemblyError::StructuralError {
                 line: self.line_number,
                 reason: "Invalid operands to LEA instruction.".to_string(),
@@ -32,25 +32,29 @@ use crate::parser::{Directive, Instruction};
 use anyhow::Result;
 use pest::iterators::{Pair, Pairs};
 
+pub use operand_builders::build_expr;
+
 #[derive(Clone)]
 pub struct AstBuilder<'a> {
     line_number: usize,
     rule: Rule,
     pairs: Pairs<'a, Rule>,
+    operand: Option<usize>, // index of the last operand popped, errors are reported on it
 }
 
 impl<'a> AstBuilder<'a> {
     pub fn new(pair: Pair<'a, Rule>) -> Self {
         Self {
-            line_number: pair.as_span().start_pos().line_col().0,
+            line_number: pair.line_col().0,
             rule: pair.as_rule(),
             pairs: pair.into_inner(),
+            operand: None,
         }
     }
 
     // Helper to build an Instruction from a pest Pair
-    pub fn build_instruction(self) -> Result<Instruction> {
-        match self.rule {
+    pub fn build_instruction(mut self) -> Result<Instruction> {
+        let instruction = match self.rule {
             Rule::nop => Ok(Instruction::Nop),
             Rule::halt => Ok(Instruction::Halt),
             Rule::st_2_op => self.build_st(),
@@ -106,6 +110,9 @@ impl<'a> AstBuilder<'a> {
             Rule::jmp_con => self.build_jcc(),
             Rule::jr_con => self.build_jrcc(),
             Rule::djnz => self.build_djnz(),
+            Rule::jmp_auto => self.build_jmp_auto(),
+            Rule::jmp_con_auto => self.build_jcc_auto(),
+            Rule::djnz_auto => self.build_djnz_auto(),
             Rule::call => self.build_call(),
             Rule::call_con => self.build_callcc(),
             Rule::syscall => self.build_syscall(),
@@ -134,6 +141,20 @@ impl<'a> AstBuilder<'a> {
             Rule::jmp_far => self.build_jmp_far(),
             Rule::jmp_far_via => self.build_jmp_far_via(),
             _ => unreachable!("Unknown instruction rule: {:?}", self.rule),
+        };
+
+        instruction.map_err(|e| self.operand_error(e))
+    }
+
+    // report an error on the operand that was being built when it happened
+    fn operand_error(&self, error: anyhow::Error) -> anyhow::Error {
+        let Some(operand) = self.operand else {
+            return error;
+        };
+
+        match error.downcast::<AssemblyError>() {
+            Ok(error) => error.at_operand(operand).into(),
+            Err(error) => error,
         }
     }
 
@@ -151,6 +172,22 @@ impl<'a> AstBuilder<'a> {
             Rule::section_start_directive => self.build_section_start_directive(),
             Rule::section_end_directive => Ok(Directive::SectionEnd),
             Rule::align_directive => self.build_align_directive(),
+            Rule::fill_directive => self.build_fill_directive(),
+            Rule::ds_directive => self.build_ds_directive(),
+            Rule::padbyte_directive => self.build_padbyte_directive(),
+            Rule::ascii_directive => self.build_ascii_directive(),
+            Rule::asciz_directive => self.build_asciz_directive(),
+            Rule::charmap_directive => self.build_charmap_directive(),
+            Rule::struct_directive_block => self.build_struct_directive(),
+            Rule::enum_directive_block => self.build_enum_directive(),
+            Rule::if_directive => self.build_if_directive(),
+            Rule::elif_directive => self.build_elif_directive(),
+            Rule::else_directive => Ok(Directive::Else),
+            Rule::endif_directive => Ok(Directive::Endif),
+            Rule::ifdef_directive => self.build_ifdef_directive(),
+            Rule::ifndef_directive => self.build_ifndef_directive(),
+            Rule::global_directive => self.build_global_directive(),
+            Rule::extern_directive => self.build_extern_directive(),
             _ => unreachable!("Unknown directive rule: {:?}", self.rule),
         }
     }
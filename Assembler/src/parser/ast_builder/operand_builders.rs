@@ -14,15 +14,44 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::ast::{BinaryOp, Expr, UnaryOp};
 use crate::errors::AssemblyError;
+use crate::expression::{evaluate_constant, is_constant};
 use crate::parser::ast_builder::utility_functions::*;
 use crate::parser::{ConditionCode, Operand};
 use crate::parser::{Pair, Rule};
 use anyhow::{Context, Result};
+use pest::pratt_parser::{Assoc, Op, PrattParser};
+use std::sync::LazyLock;
+
+// operator precedence for expressions, lowest first (same ordering as C)
+static PRATT_PARSER: LazyLock<PrattParser<Rule>> = LazyLock::new(|| {
+    PrattParser::new()
+        .op(Op::infix(Rule::op_lor, Assoc::Left))
+        .op(Op::infix(Rule::op_land, Assoc::Left))
+        .op(Op::infix(Rule::op_or, Assoc::Left))
+        .op(Op::infix(Rule::op_xor, Assoc::Left))
+        .op(Op::infix(Rule::op_and, Assoc::Left))
+        .op(Op::infix(Rule::op_eq, Assoc::Left) | Op::infix(Rule::op_ne, Assoc::Left))
+        .op(Op::infix(Rule::op_lt, Assoc::Left)
+            | Op::infix(Rule::op_gt, Assoc::Left)
+            | Op::infix(Rule::op_le, Assoc::Left)
+            | Op::infix(Rule::op_ge, Assoc::Left))
+        .op(Op::infix(Rule::op_shl, Assoc::Left) | Op::infix(Rule::op_shr, Assoc::Left))
+        .op(Op::infix(Rule::op_add, Assoc::Left) | Op::infix(Rule::op_sub, Assoc::Left))
+        .op(Op::infix(Rule::op_mul, Assoc::Left)
+            | Op::infix(Rule::op_div, Assoc::Left)
+            | Op::infix(Rule::op_mod, Assoc::Left))
+        .op(Op::prefix(Rule::op_neg)
+            | Op::prefix(Rule::op_not)
+            | Op::prefix(Rule::op_lnot)
+            | Op::prefix(Rule::op_low)
+            | Op::prefix(Rule::op_high))
+});
 
 // Helper to build an Operand from a pest Pair
 pub fn build_operand(pair: Pair<Rule>) -> Result<Operand> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let inner_pair = pair
         .into_inner()
         .next()
@@ -35,6 +64,8 @@ pub fn build_operand(pair: Pair<Rule>) -> Result<Operand> {
         Rule::immediate_hex => build_immediate_hex(inner_pair),
         Rule::immediate_dec => build_immediate_dec(inner_pair),
         Rule::identifier => build_identifier(inner_pair),
+        Rule::anon_ref => build_identifier(inner_pair),
+        Rule::expr => build_expr_operand(inner_pair),
         Rule::indirect => build_indirect(inner_pair),
         Rule::absolute => build_absolute(inner_pair),
         Rule::predec => build_pre_decrement(inner_pair),
@@ -47,7 +78,7 @@ pub fn build_operand(pair: Pair<Rule>) -> Result<Operand> {
 
 // build a register object from a pair
 pub fn build_register(pair: Pair<Rule>) -> Result<Operand> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let inner = pair
         .into_inner()
         .next()
@@ -61,7 +92,7 @@ pub fn build_register(pair: Pair<Rule>) -> Result<Operand> {
 
 // build an immediate object
 pub fn build_immediate_hex(pair: Pair<Rule>) -> Result<Operand> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let inner = pair
         .into_inner()
         .next()
@@ -79,7 +110,7 @@ pub fn build_immediate_hex(pair: Pair<Rule>) -> Result<Operand> {
 
 // build an immediate object
 pub fn build_immediate_dec(pair: Pair<Rule>) -> Result<Operand> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let dec_str = pair.as_str();
     let value = dec_str
         .parse::<i32>()
@@ -97,7 +128,7 @@ pub fn build_identifier(pair: Pair<Rule>) -> Result<Operand> {
 
 // build an indirect object
 pub fn build_indirect(pair: Pair<Rule>) -> Result<Operand> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let reg_pair = pair
         .into_inner()
         .next()
@@ -116,7 +147,7 @@ pub fn build_indirect(pair: Pair<Rule>) -> Result<Operand> {
 }
 
 pub fn build_absolute(pair: Pair<Rule>) -> Result<Operand> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let inner = pair
         .into_inner()
         .next()
@@ -125,34 +156,18 @@ pub fn build_absolute(pair: Pair<Rule>) -> Result<Operand> {
             reason: "Expected an address or label for absolute addressing.".to_string(),
         })?;
 
-    match inner.as_rule() {
-        Rule::immediate_hex => {
-            let hex = inner
-                .into_inner()
-                .next()
-                .ok_or_else(|| AssemblyError::StructuralError {
-                    line,
-                    reason: "Expected a hex value body.".to_string(),
-                })?;
-            let value = u16::from_str_radix(hex.as_str(), 16).map_err(|_| {
-                AssemblyError::StructuralError {
-                    line,
-                    reason: format!("Invalid hex value: {}", hex.as_str()),
-                }
-            })?;
-            Ok(Operand::AbsAddr(value))
+    match simplify_expr(build_expr(inner)?, line)? {
+        Expr::Number(value) => {
+            check_unsigned_word(value, line).context("Invalid absolute address.")?;
+            Ok(Operand::AbsAddr(value as u16))
         }
-        Rule::identifier => Ok(Operand::AbsLabel(inner.as_str().to_string())),
-        _ => Err(AssemblyError::StructuralError {
-            line,
-            reason: "Expected a hex value or label for absolute addressing.".to_string(),
-        }
-        .into()),
+        Expr::Symbol(label) => Ok(Operand::AbsLabel(label)),
+        expr => Ok(Operand::AbsExpr(expr)),
     }
 }
 
 pub fn build_pre_decrement(pair: Pair<Rule>) -> Result<Operand> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let reg_pair = pair
         .into_inner()
         .next()
@@ -171,7 +186,7 @@ pub fn build_pre_decrement(pair: Pair<Rule>) -> Result<Operand> {
 }
 
 pub fn build_post_increment(pair: Pair<Rule>) -> Result<Operand> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let reg_pair = pair
         .into_inner()
         .next()
@@ -190,7 +205,7 @@ pub fn build_post_increment(pair: Pair<Rule>) -> Result<Operand> {
 }
 
 pub fn build_indexed(pair: Pair<Rule>) -> Result<Operand> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let mut inner = pair.into_inner();
     let reg_pair = inner
         .next()
@@ -211,23 +226,117 @@ pub fn build_indexed(pair: Pair<Rule>) -> Result<Operand> {
 
     let reg = pair_to_reg(reg_pair)?;
 
-    match op_pair.as_rule() {
+    match simplify_expr(build_expr(op_pair)?, line)? {
+        Expr::Number(value) => {
+            check_signed_byte(value, line).context("Invalid offset for indexed operand.")?;
+            Ok(Operand::Indexed(reg, value as i8))
+        }
+        Expr::Symbol(label) => Ok(Operand::IndexedLabel(reg, label)),
+        expr => Ok(Operand::IndexedExpr(reg, expr)),
+    }
+}
+
+// build an operand from an expression, plain numbers and labels keep their simple forms
+pub fn build_expr_operand(pair: Pair<Rule>) -> Result<Operand> {
+    let line = pair.line_col().0;
+
+    match simplify_expr(build_expr(pair)?, line)? {
+        Expr::Number(value) => Ok(Operand::Immediate(value)),
+        Expr::Symbol(label) => Ok(Operand::Label(label)),
+        expr => Ok(Operand::Expr(expr)),
+    }
+}
+
+// build an expression tree from an expr pair
+pub fn build_expr(pair: Pair<Rule>) -> Result<Expr> {
+    PRATT_PARSER
+        .map_primary(build_expr_primary)
+        .map_prefix(|op, rhs| {
+            let op = match op.as_rule() {
+                Rule::op_neg => UnaryOp::Neg,
+                Rule::op_not => UnaryOp::Not,
+                Rule::op_lnot => UnaryOp::LogicalNot,
+                Rule::op_low => UnaryOp::Low,
+                Rule::op_high => UnaryOp::High,
+                _ => unreachable!("Unknown prefix operator: {:?}", op.as_rule()),
+            };
+            Ok(Expr::Unary(op, Box::new(rhs?)))
+        })
+        .map_infix(|lhs, op, rhs| {
+            let op = match op.as_rule() {
+                Rule::op_add => BinaryOp::Add,
+                Rule::op_sub => BinaryOp::Sub,
+                Rule::op_mul => BinaryOp::Mul,
+                Rule::op_div => BinaryOp::Div,
+                Rule::op_mod => BinaryOp::Mod,
+                Rule::op_shl => BinaryOp::Shl,
+                Rule::op_shr => BinaryOp::Shr,
+                Rule::op_and => BinaryOp::And,
+                Rule::op_or => BinaryOp::Or,
+                Rule::op_xor => BinaryOp::Xor,
+                Rule::op_eq => BinaryOp::Eq,
+                Rule::op_ne => BinaryOp::Ne,
+                Rule::op_lt => BinaryOp::Lt,
+                Rule::op_gt => BinaryOp::Gt,
+                Rule::op_le => BinaryOp::Le,
+                Rule::op_ge => BinaryOp::Ge,
+                Rule::op_land => BinaryOp::LogicalAnd,
+                Rule::op_lor => BinaryOp::LogicalOr,
+                _ => unreachable!("Unknown infix operator: {:?}", op.as_rule()),
+            };
+            Ok(Expr::Binary(op, Box::new(lhs?), Box::new(rhs?)))
+        })
+        .parse(pair.into_inner())
+}
+
+fn build_expr_primary(pair: Pair<Rule>) -> Result<Expr> {
+    let line = pair.line_col().0;
+
+    match pair.as_rule() {
         Rule::immediate_hex | Rule::immediate_dec => {
-            let imm =
-                pair_to_signed_byte(op_pair).context("Invalid offset for indexed operand.")?;
-            Ok(Operand::Indexed(reg, imm))
+            let operand = match pair.as_rule() {
+                Rule::immediate_hex => build_immediate_hex(pair)?,
+                _ => build_immediate_dec(pair)?,
+            };
+            let Operand::Immediate(value) = operand else {
+                unreachable!("Immediate builders always return an immediate operand");
+            };
+            Ok(Expr::Number(value))
+        }
+        Rule::identifier | Rule::local_identifier | Rule::scoped_identifier => {
+            Ok(Expr::Symbol(pair.as_str().to_string()))
+        }
+        Rule::char_literal => build_char_literal(pair),
+        Rule::bank_func => {
+            let label = pair
+                .into_inner()
+                .next()
+                .ok_or_else(|| AssemblyError::StructuralError {
+                    line,
+                    reason: "Expected a label for bank().".to_string(),
+                })?;
+            Ok(Expr::Bank(label.as_str().to_string()))
         }
-        Rule::label => Ok(Operand::IndexedLabel(reg, op_pair.as_str().to_string())),
+        Rule::expr => build_expr(pair),
         _ => Err(AssemblyError::StructuralError {
             line,
-            reason: "Expected an immediate value or label for offset.".to_string(),
+            reason: format!("Unexpected token in expression: {}", pair.as_str()),
         }
         .into()),
     }
 }
 
+// fold expressions that do not reference any symbols down to a single number
+fn simplify_expr(expr: Expr, line: usize) -> Result<Expr> {
+    if is_constant(&expr) {
+        Ok(Expr::Number(evaluate_constant(&expr, &line)?))
+    } else {
+        Ok(expr)
+    }
+}
+
 pub fn build_string_literal(pair: Pair<Rule>) -> Result<Operand> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let mut inner = pair.into_inner();
     let op_pair = inner.next().ok_or_else(|| AssemblyError::StructuralError {
         line,
@@ -235,7 +344,7 @@ pub fn build_string_literal(pair: Pair<Rule>) -> Result<Operand> {
     })?;
 
     match op_pair.as_rule() {
-        Rule::str_val => Ok(Operand::String(op_pair.as_str().to_string())),
+        Rule::str_val => Ok(Operand::String(unescape(op_pair.as_str(), line)?)),
         _ => Err(AssemblyError::StructuralError {
             line,
             reason: "Expected a string.".to_string(),
@@ -244,8 +353,24 @@ pub fn build_string_literal(pair: Pair<Rule>) -> Result<Operand> {
     }
 }
 
+// build the value of a character literal, e.g. 'A' or '\n'
+fn build_char_literal(pair: Pair<Rule>) -> Result<Expr> {
+    let line = pair.line_col().0;
+    let text = pair
+        .into_inner()
+        .next()
+        .ok_or_else(|| AssemblyError::StructuralError {
+            line,
+            reason: "Expected a character literal.".to_string(),
+        })?
+        .as_str();
+
+    let bytes = string_to_bytes(&unescape(text, line)?, line)?;
+    Ok(Expr::Number(bytes[0] as i32))
+}
+
 pub fn build_condition_code(pair: Pair<Rule>) -> Result<ConditionCode> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let cc = pair
         .into_inner()
         .next()
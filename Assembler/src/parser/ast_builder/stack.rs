@@ -23,7 +23,7 @@ use anyhow::{Context, Result};
 
 impl<'a> AstBuilder<'a> {
     // build and check operands for a push instruction
-    pub fn build_push(mut self) -> Result<Instruction> {
+    pub fn build_push(&mut self) -> Result<Instruction> {
         let op = self.pop_operand().context(INVALID_OP_MSG)?;
 
         match op {
@@ -32,7 +32,7 @@ impl<'a> AstBuilder<'a> {
                 check_unsigned_word(value, self.line_number)?;
                 Ok(Instruction::PushI(op))
             }
-            Operand::Label(_) => Ok(Instruction::PushI(op)),
+            Operand::Label(_) | Operand::Expr(_) => Ok(Instruction::PushI(op)),
             _ => Err(AssemblyError::StructuralError {
                 line: self.line_number,
                 reason: "Invalid operand to PUSH instruction.".to_string(),
@@ -42,7 +42,7 @@ impl<'a> AstBuilder<'a> {
     }
 
     // build and check operands for a pop instruction
-    pub fn build_pop(mut self) -> Result<Instruction> {
+    pub fn build_pop(&mut self) -> Result<Instruction> {
         let r = self.expect_register().context(INVALID_OP_MSG)?;
 
         Ok(Instruction::Pop(r))
@@ -22,7 +22,7 @@ use pest::iterators::Pair;
 
 // translate a pair to a register
 pub fn pair_to_reg(pair: Pair<Rule>) -> Result<Register> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     match pair.as_str() {
         "0" => Ok(Register::R0),
         "1" => Ok(Register::R1),
@@ -42,7 +42,7 @@ pub fn pair_to_reg(pair: Pair<Rule>) -> Result<Register> {
 
 // translate a pair to an unsigned byte and check value
 pub fn pair_to_unsigned_byte(pair: Pair<Rule>) -> Result<u8> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let val_str = pair.as_str();
 
     let val = val_str
@@ -59,7 +59,7 @@ pub fn pair_to_unsigned_byte(pair: Pair<Rule>) -> Result<u8> {
 
 // translate a pair to a signed byte and check value
 pub fn pair_to_signed_byte(pair: Pair<Rule>) -> Result<i8> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let val_str = pair.as_str();
 
     let val = val_str
@@ -76,7 +76,7 @@ pub fn pair_to_signed_byte(pair: Pair<Rule>) -> Result<i8> {
 
 // translate a pair to an unsigned word and check value
 pub fn pair_to_unsigned_word(pair: Pair<Rule>) -> Result<u16> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let val_str = pair.as_str();
 
     let val = val_str
@@ -93,7 +93,7 @@ pub fn pair_to_unsigned_word(pair: Pair<Rule>) -> Result<u16> {
 
 // translate a pair to a signed word and check value
 pub fn pair_to_signed_word(pair: Pair<Rule>) -> Result<i16> {
-    let line = pair.as_span().start_pos().line_col().0;
+    let line = pair.line_col().0;
     let val_str = pair.as_str();
 
     let val = val_str
@@ -163,21 +163,80 @@ pub fn check_signed_word(val: i32, line_num: usize) -> Result<(), AssemblyError>
                 i16::MAX,
                 i16::MIN
             ),
-        }
-        .into())
+        })
     } else {
         Ok(())
     }
 }
 
 pub fn check_bit_id(val: i32, line_num: usize) -> Result<(), AssemblyError> {
-    if val > 7 || val < 0 {
+    if !(0..=7).contains(&val) {
         Err(AssemblyError::StructuralError {
             line: line_num,
             reason: "Value must be a bit ID value between 0 and 7 inclusive".to_string(),
-        }
-        .into())
+        })
     } else {
         Ok(())
     }
 }
+
+// replace the escape sequences in the text of a string or character literal. Characters are
+// bytes, so \xHH escapes give the characters U+0000 to U+00FF
+pub fn unescape(text: &str, line_num: usize) -> Result<String> {
+    let mut result = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let value =
+                    u8::from_str_radix(&hex, 16).map_err(|_| AssemblyError::StructuralError {
+                        line: line_num,
+                        reason: format!("Invalid escape sequence: \\x{}", hex),
+                    })?;
+                value as char
+            }
+            other => {
+                return Err(AssemblyError::StructuralError {
+                    line: line_num,
+                    reason: format!(
+                        "Unknown escape sequence: \\{}",
+                        other.map(String::from).unwrap_or_default()
+                    ),
+                }
+                .into());
+            }
+        };
+        result.push(escaped);
+    }
+
+    Ok(result)
+}
+
+// the byte stored for each character of a string
+pub fn string_to_bytes(text: &str, line_num: usize) -> Result<Vec<u8>> {
+    text.chars()
+        .map(|c| {
+            u8::try_from(c).map_err(|_| {
+                AssemblyError::StructuralError {
+                    line: line_num,
+                    reason: format!("Character '{}' does not fit in a byte.", c),
+                }
+                .into()
+            })
+        })
+        .collect()
+}
@@ -24,13 +24,17 @@ use anyhow::{Context, Result};
 impl<'a> AstBuilder<'a> {
     // Helper to get the next operand
     pub fn pop_operand(&mut self) -> Result<Operand> {
-        let pair = self
-            .pairs
-            .next()
-            .ok_or_else(|| AssemblyError::StructuralError {
+        let Some(pair) = self.pairs.next() else {
+            // a missing operand is reported on the whole line
+            self.operand = None;
+            return Err(AssemblyError::StructuralError {
                 line: self.line_number,
                 reason: "Expected an operand, but found none.".to_string(),
-            })?;
+            }
+            .into());
+        };
+
+        self.operand = Some(self.operand.map_or(0, |index| index + 1));
         build_operand(pair)
     }
 
@@ -68,11 +72,7 @@ impl<'a> AstBuilder<'a> {
                 .context("Expected an unsigned byte value.")?;
             Ok(imm as u8)
         } else {
-            Err(AssemblyError::StructuralError {
-                line: self.line_number,
-                reason: "Expected an immediate byte value.".to_string(),
-            }
-            .into())
+            Err(self.value_error(&op, "Expected an immediate byte value."))
         }
     }
 
@@ -86,12 +86,8 @@ impl<'a> AstBuilder<'a> {
                     .context("Expected an unsigned byte value.")?;
                 Ok(op)
             }
-            Operand::Label(_) => Ok(op),
-            _ => Err(AssemblyError::StructuralError {
-                line: self.line_number,
-                reason: "Expected an immediate byte value or label.".to_string(),
-            }
-            .into()),
+            Operand::Label(_) | Operand::Expr(_) => Ok(op),
+            _ => Err(self.value_error(&op, "Expected an immediate byte value or label.")),
         }
     }
 
@@ -103,11 +99,7 @@ impl<'a> AstBuilder<'a> {
             check_signed_byte(imm, self.line_number).context("Expected a signed byte value.")?;
             Ok(imm as i8)
         } else {
-            Err(AssemblyError::StructuralError {
-                line: self.line_number,
-                reason: "Expected an immediate byte value.".to_string(),
-            }
-            .into())
+            Err(self.value_error(&op, "Expected an immediate byte value."))
         }
     }
 
@@ -121,12 +113,8 @@ impl<'a> AstBuilder<'a> {
                     .context("Expected a signed byte value.")?;
                 Ok(op)
             }
-            Operand::Label(_) => Ok(op),
-            _ => Err(AssemblyError::StructuralError {
-                line: self.line_number,
-                reason: "Expected an immediate byte value.".to_string(),
-            }
-            .into()),
+            Operand::Label(_) | Operand::Expr(_) => Ok(op),
+            _ => Err(self.value_error(&op, "Expected an immediate byte value.")),
         }
     }
 
@@ -138,11 +126,7 @@ impl<'a> AstBuilder<'a> {
             check_bit_id(imm, self.line_number).context("Expected a bit ID value.")?;
             Ok(imm as u8)
         } else {
-            Err(AssemblyError::StructuralError {
-                line: self.line_number,
-                reason: "Expected an immediate value.".to_string(),
-            }
-            .into())
+            Err(self.value_error(&op, "Expected an immediate value."))
         }
     }
 
@@ -155,12 +139,8 @@ impl<'a> AstBuilder<'a> {
                 check_bit_id(imm, self.line_number).context("Expected a bit ID value.")?;
                 Ok(op)
             }
-            Operand::Label(_) => Ok(op),
-            _ => Err(AssemblyError::StructuralError {
-                line: self.line_number,
-                reason: "Expected an immediate value.".to_string(),
-            }
-            .into()),
+            Operand::Label(_) | Operand::Expr(_) => Ok(op),
+            _ => Err(self.value_error(&op, "Expected an immediate value.")),
         }
     }
 
@@ -188,11 +168,7 @@ impl<'a> AstBuilder<'a> {
                     .context("Expected an address value.")?;
                 Ok(addr as u16)
             }
-            _ => Err(AssemblyError::StructuralError {
-                line: self.line_number,
-                reason: "Expected an address.".to_string(),
-            }
-            .into()),
+            _ => Err(self.value_error(&op, "Expected an address.")),
         }
     }
 
@@ -206,12 +182,8 @@ impl<'a> AstBuilder<'a> {
                     .context("Expected an address value.")?;
                 Ok(op)
             }
-            Operand::Label(_) => Ok(op),
-            _ => Err(AssemblyError::StructuralError {
-                line: self.line_number,
-                reason: "Expected an address or label.".to_string(),
-            }
-            .into()),
+            Operand::Label(_) | Operand::Expr(_) => Ok(op),
+            _ => Err(self.value_error(&op, "Expected an address or label.")),
         }
     }
 
@@ -225,12 +197,8 @@ impl<'a> AstBuilder<'a> {
                     .context("Expected a signed byte value.")?;
                 Ok(op)
             }
-            Operand::Label(_) => Ok(op),
-            _ => Err(AssemblyError::StructuralError {
-                line: self.line_number,
-                reason: "Expected an address or label.".to_string(),
-            }
-            .into()),
+            Operand::Label(_) | Operand::Expr(_) => Ok(op),
+            _ => Err(self.value_error(&op, "Expected an address or label.")),
         }
     }
 
@@ -261,16 +229,29 @@ impl<'a> AstBuilder<'a> {
         }
     }
 
+    // the error for an operand that is not the value that was expected. A whole operand wrapped
+    // in parentheses is an absolute address, which is easy to write by mistake for an expression
+    fn value_error(&self, op: &Operand, reason: &str) -> anyhow::Error {
+        let reason = match op {
+            Operand::AbsAddr(_) | Operand::AbsLabel(_) | Operand::AbsExpr(_) => {
+                "Parenthesized operand is a memory access, drop the parentheses for an immediate."
+            }
+            _ => reason,
+        };
+
+        AssemblyError::StructuralError {
+            line: self.line_number,
+            reason: reason.to_string(),
+        }
+        .into()
+    }
+
     pub fn expect_immediate(&mut self) -> Result<i32> {
         let op = self.pop_operand()?;
         if let Operand::Immediate(val) = op {
             Ok(val)
         } else {
-            Err(AssemblyError::StructuralError {
-                line: self.line_number,
-                reason: "Expected an immediate value.".to_string(),
-            }
-            .into())
+            Err(self.value_error(&op, "Expected an immediate value."))
         }
     }
 }
@@ -0,0 +1,233 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use super::Rule;
+use crate::errors::AssemblyError;
+use anyhow::Result;
+use pest::iterators::Pair;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub type MacroTable = HashMap<String, MacroDefinition>;
+
+// the text `\@` expands to, it starts with a letter so that it is a valid identifier on its own
+// and at the start of a label, e.g. `\@loop:` becomes `U1__loop:`
+fn unique_name(unique_id: usize) -> String {
+    format!("U{}__", unique_id)
+}
+
+// A macro defined with .macro ... .endm, the body is stored as raw source text.
+#[derive(Debug, Clone)]
+pub struct MacroDefinition {
+    pub params: Vec<String>,
+    pub body: String,
+    pub body_line: usize, // line number of the first line of the body
+    pub file: PathBuf,    // file the macro was defined in
+}
+
+impl MacroDefinition {
+    // substitute the invocation arguments into the macro body, `\name` is replaced with the
+    // argument for that parameter and `\@` with a name unique to this expansion.
+    // The result is padded with empty lines so that it parses with the line numbers of the body.
+    pub fn expand(
+        &self,
+        name: &str,
+        args: &[String],
+        unique_id: usize,
+        line_num: usize,
+    ) -> Result<String> {
+        if args.len() != self.params.len() {
+            return Err(AssemblyError::StructuralError {
+                line: line_num,
+                reason: format!(
+                    "Macro \"{}\" expects {} argument(s), found {}.",
+                    name,
+                    self.params.len(),
+                    args.len()
+                ),
+            }
+            .into());
+        }
+
+        let mut text = "\n".repeat(self.body_line - 1);
+        let mut chars = self.body.chars().peekable();
+        let mut body_line = self.body_line;
+
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                if c == '\n' {
+                    body_line += 1;
+                }
+                text.push(c);
+                continue;
+            }
+
+            if chars.next_if_eq(&'@').is_some() {
+                text.push_str(&unique_name(unique_id));
+                continue;
+            }
+
+            let mut param = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                param.push(c);
+            }
+
+            if param.is_empty() {
+                text.push(c);
+                continue;
+            }
+
+            match self.params.iter().position(|p| *p == param) {
+                Some(index) => text.push_str(&args[index]),
+                None => {
+                    return Err(AssemblyError::StructuralError {
+                        line: body_line,
+                        reason: format!("Unknown parameter \\{} in macro \"{}\".", param, name),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        Ok(text)
+    }
+}
+
+// substitute the loop counter into one repetition of a .rept body, `\name` is replaced with the
+// iteration number and `\@` with a name unique to this repetition. Any other `\` is kept, it
+// may belong to a nested .rept block. The result is padded with empty lines so that it parses with
+// the line numbers of the body.
+pub fn expand_repetition(
+    body: &str,
+    body_line: usize,
+    counter: Option<&str>,
+    iteration: usize,
+    unique_id: usize,
+) -> String {
+    let mut text = "\n".repeat(body_line - 1);
+    let mut rest = body;
+
+    while let Some(index) = rest.find('\\') {
+        text.push_str(&rest[..index]);
+        let after = &rest[index + 1..];
+
+        let name_len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+
+        if let Some(after_id) = after.strip_prefix('@') {
+            text.push_str(&unique_name(unique_id));
+            rest = after_id;
+        } else if counter.is_some_and(|counter| counter == &after[..name_len]) {
+            text.push_str(&iteration.to_string());
+            rest = &after[name_len..];
+        } else {
+            text.push('\\');
+            rest = after;
+        }
+    }
+    text.push_str(rest);
+
+    text
+}
+
+// build a macro definition from a macro_definition pair
+pub fn build_macro_definition(pair: Pair<Rule>, file: &Path) -> Result<(String, MacroDefinition)> {
+    let line_num = pair.line_col().0;
+    let mut name = String::new();
+    let mut params = Vec::new();
+    let mut body = String::new();
+    let mut body_line = line_num + 1;
+
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::identifier => name = inner.as_str().to_string(),
+            Rule::macro_params => {
+                for param in inner.into_inner() {
+                    let param = param.as_str().to_string();
+                    if params.contains(&param) {
+                        return Err(AssemblyError::StructuralError {
+                            line: line_num,
+                            reason: format!("Duplicate macro parameter: {}", param),
+                        }
+                        .into());
+                    }
+                    params.push(param);
+                }
+            }
+            Rule::macro_body => {
+                body_line = inner.line_col().0;
+                body = inner.as_str().to_string();
+            }
+            _ => {}
+        }
+    }
+
+    Ok((
+        name,
+        MacroDefinition {
+            params,
+            body,
+            body_line,
+            file: file.to_path_buf(),
+        },
+    ))
+}
+
+// split the raw argument text of a macro invocation on commas that are not nested in
+// parentheses, strings or character literals
+pub fn split_macro_args(text: &str) -> Vec<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None; // the quote of the literal being read
+    let mut escaped = false;
+
+    for c in text.chars() {
+        current.push(c);
+
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ',' if depth == 0 => {
+                current.pop();
+                args.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => {}
+        }
+    }
+    args.push(current.trim().to_string());
+
+    args
+}
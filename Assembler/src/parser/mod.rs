@@ -15,12 +15,14 @@ limitations under the License.
 */
 
 mod ast_builder;
+mod macros;
 
 use crate::ast::*;
 use crate::errors::AssemblyError;
 use crate::file_reader::FileReader;
 use anyhow::{Context, Result};
-use ast_builder::AstBuilder;
+use ast_builder::{AstBuilder, build_expr};
+use macros::{MacroTable, build_macro_definition, expand_repetition, split_macro_args};
 use pest::Parser;
 use pest::iterators::Pair;
 use pest_derive::Parser;
@@ -32,92 +34,392 @@ use std::path::{Path, PathBuf};
 #[grammar = "./grammar.pest"]
 pub struct CicadaParser;
 
+// limit on nested macro invocations, catches macros that invoke themselves
+const MAX_MACRO_DEPTH: usize = 64;
+// limit on the number of repetitions of a single .rept block
+const MAX_REPT_COUNT: usize = 65536;
+
+// state shared between every file and macro expansion parsed for a single program
+struct ParseContext<'a, F: FileReader> {
+    include_stack: &'a mut HashSet<PathBuf>,
+    macros: MacroTable,
+    expansion_count: usize,
+    reader: &'a F,
+}
+
 // main parser function, recursively discovers and opens source files
 pub fn parse_source_recursive<F: FileReader>(
     file_path: &Path,
     include_stack: &mut HashSet<PathBuf>,
     reader: &F,
 ) -> Result<Vec<AssemblyLine>> {
-    include_stack.insert(file_path.to_path_buf());
+    let mut context = ParseContext {
+        include_stack,
+        macros: MacroTable::new(),
+        expansion_count: 0,
+        reader,
+    };
+
+    parse_file(file_path, &mut context)
+}
 
-    let source = reader.read_to_string(file_path).with_context(|| {
+fn parse_file<F: FileReader>(
+    file_path: &Path,
+    context: &mut ParseContext<F>,
+) -> Result<Vec<AssemblyLine>> {
+    context.include_stack.insert(file_path.to_path_buf());
+
+    let source = context.reader.read_to_string(file_path).with_context(|| {
         format!(
             "Failed to read input file: {}",
             file_path.to_path_buf().display()
         )
     })?;
 
-    let pairs = CicadaParser::parse(Rule::program, &source)?;
+    let ast = parse_lines(&source, file_path, &[], context)?;
+
+    context.include_stack.remove(file_path);
+
+    Ok(ast)
+}
+
+// parse a block of source text, expanding macros and includes in place. `file` is the file the
+// text was read from and `expansion` is the chain of macro invocations that produced the text,
+// empty for the contents of a file.
+fn parse_lines<F: FileReader>(
+    source: &str,
+    file: &Path,
+    expansion: &[MacroExpansion],
+    context: &mut ParseContext<F>,
+) -> Result<Vec<AssemblyLine>> {
+    let pairs = CicadaParser::parse(Rule::program, source)
+        .map_err(|e| e.with_path(&file.display().to_string()))?;
+    let source_lines: Vec<&str> = source.lines().collect();
     let mut ast = Vec::new();
 
     for line_pair in pairs
         .flatten()
         .filter(|p| p.as_rule() == Rule::line_content)
     {
-        let mut inner = line_pair.into_inner();
-        let mut assembly_line = AssemblyLine::default();
-
-        // Check for a label first
-        if let Some(pair) = inner.peek() {
-            assembly_line.line_number = pair.as_span().start_pos().line_col().0;
-            match pair.as_rule() {
-                Rule::label => {
-                    assembly_line.label = Some(
-                        inner
-                            .next()
-                            .unwrap()
-                            .into_inner()
-                            .next()
-                            .unwrap()
-                            .as_str()
-                            .to_string(),
-                    );
-                }
-                Rule::instruction => {
-                    assembly_line.instruction = Some(build_instruction(
-                        inner.next().unwrap().into_inner().next().unwrap(),
-                    )?);
-                }
-                Rule::directive => {
-                    assembly_line.directive = Some(build_directive(
-                        inner.next().unwrap().into_inner().next().unwrap(),
-                    )?);
-                }
-                _ => {}
-            }
-        }
-
-        // Only add non-empty lines to our AST
-        if assembly_line.label.is_some()
-            || assembly_line.instruction.is_some()
-            || assembly_line.directive.is_some()
-        {
-            if let Some(Directive::Include(inc_str)) = assembly_line.directive {
-                // include directive detected, recurse and insert the sub ast
-                let inc_path = Path::new(&inc_str);
-
-                if include_stack.contains(inc_path) {
-                    return Err(AssemblyError::CircularIncludeError {
+        let line_text = line_pair.as_str();
+        let pair = line_pair.into_inner().next().unwrap();
+        let (line_number, column) = pair.line_col();
+        let mut assembly_line = AssemblyLine {
+            line_number,
+            expansion: expansion.to_vec(),
+            file: file.to_path_buf(),
+            source: source_lines[line_number - 1].trim_end().to_string(),
+            span: Span {
+                column,
+                length: pair.as_str().trim_end().chars().count(),
+            },
+            ..Default::default()
+        };
+
+        match pair.as_rule() {
+            Rule::macro_definition => {
+                let (name, definition) = build_macro_definition(pair, file)?;
+                if context.macros.contains_key(&name) {
+                    return Err(AssemblyError::StructuralError {
                         line: assembly_line.line_number,
-                        reason: format!("Circular include detected. ({})", inc_str),
+                        reason: format!("Duplicate macro definition: {}", name),
                     }
                     .into());
                 }
+                context.macros.insert(name, definition);
+            }
+            Rule::macro_invocation => {
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap().as_str();
+                let args = inner.next().map(|p| p.as_str()).unwrap_or("");
+
+                if !context.macros.contains_key(name) {
+                    return Err(invalid_line_error(source, file, &assembly_line));
+                }
 
-                let sub_ast = parse_source_recursive(inc_path, include_stack, reader)?;
+                let sub_ast =
+                    expand_macro(name, args, assembly_line.line_number, expansion, context)?;
+                ast.extend(sub_ast);
+            }
+            Rule::rept_block => {
+                let sub_ast = expand_rept(pair, file, expansion, context)?;
                 ast.extend(sub_ast);
-            } else {
-                // not include directive, insert normal assemblyline
+            }
+            Rule::label => {
+                assembly_line.label = Some(pair.into_inner().next().unwrap().as_str().to_string());
                 ast.push(assembly_line);
             }
+            Rule::instruction => {
+                // mnemonics are not matched as whole words, so an invocation of a macro whose
+                // name starts with a mnemonic (e.g. "pushregs") can parse as an instruction
+                if let Some((name, args)) = split_macro_name(line_text)
+                    && context.macros.contains_key(name)
+                {
+                    let sub_ast =
+                        expand_macro(name, args, assembly_line.line_number, expansion, context)?;
+                    ast.extend(sub_ast);
+                    continue;
+                }
+
+                let pair = pair.into_inner().next().unwrap();
+                assembly_line.operand_spans = operand_spans(&pair);
+                assembly_line.instruction =
+                    Some(build_instruction(pair).map_err(|e| locate_error(e, &assembly_line))?);
+                ast.push(assembly_line);
+            }
+            Rule::directive => {
+                let pair = pair.into_inner().next().unwrap();
+                assembly_line.operand_spans = operand_spans(&pair);
+                let directive =
+                    build_directive(pair).map_err(|e| locate_error(e, &assembly_line))?;
+
+                if let Directive::Include(inc_str) = directive {
+                    // include directive detected, recurse and insert the sub ast
+                    let inc_path = Path::new(&inc_str);
+
+                    if context.include_stack.contains(inc_path) {
+                        return Err(AssemblyError::CircularIncludeError {
+                            line: assembly_line.line_number,
+                            reason: format!("Circular include detected. ({})", inc_str),
+                        }
+                        .with_location(&assembly_line)
+                        .into());
+                    }
+
+                    let sub_ast = parse_file(inc_path, context)?;
+                    ast.extend(sub_ast);
+                } else {
+                    // not include directive, insert normal assemblyline
+                    assembly_line.directive = Some(directive);
+                    ast.push(assembly_line);
+                }
+            }
+            _ => {}
         }
     }
 
-    include_stack.remove(file_path);
+    Ok(ast)
+}
+
+// substitute the arguments into a macro body and parse the result
+fn expand_macro<F: FileReader>(
+    name: &str,
+    args: &str,
+    line_num: usize,
+    expansion: &[MacroExpansion],
+    context: &mut ParseContext<F>,
+) -> Result<Vec<AssemblyLine>> {
+    if expansion.len() >= MAX_MACRO_DEPTH {
+        return Err(AssemblyError::StructuralError {
+            line: line_num,
+            reason: format!(
+                "Macro expansion nested more than {} levels deep, does \"{}\" invoke itself?",
+                MAX_MACRO_DEPTH, name
+            ),
+        }
+        .into());
+    }
+
+    context.expansion_count += 1;
+    let definition = &context.macros[name];
+    let file = definition.file.clone();
+    let text = definition.expand(
+        name,
+        &split_macro_args(args),
+        context.expansion_count,
+        line_num,
+    )?;
+
+    let frame = MacroExpansion {
+        name: name.to_string(),
+        invocation_line: line_num,
+        iteration: None,
+    };
+    let mut chain = expansion.to_vec();
+    chain.push(frame.clone());
+
+    parse_lines(&text, &file, &chain, context).map_err(|error| expansion_error(error, &frame))
+}
+
+// parse the body of a .rept block once for every repetition, with the loop counter substituted
+fn expand_rept<F: FileReader>(
+    pair: Pair<Rule>,
+    file: &Path,
+    expansion: &[MacroExpansion],
+    context: &mut ParseContext<F>,
+) -> Result<Vec<AssemblyLine>> {
+    let line_num = pair.line_col().0;
+    let mut inner = pair.into_inner();
+    let args = split_macro_args(inner.next().unwrap().as_str());
+    let body = inner.next().unwrap();
+    let body_line = body.line_col().0;
+
+    if expansion.len() >= MAX_MACRO_DEPTH {
+        return Err(AssemblyError::StructuralError {
+            line: line_num,
+            reason: format!(
+                ".rept blocks and macro expansions nested more than {} levels deep.",
+                MAX_MACRO_DEPTH
+            ),
+        }
+        .into());
+    }
+
+    let (count, counter) = match args.as_slice() {
+        [count] => (rept_count(count, line_num)?, None),
+        [count, counter] if is_identifier(counter) => {
+            (rept_count(count, line_num)?, Some(counter.as_str()))
+        }
+        _ => {
+            return Err(AssemblyError::StructuralError {
+                line: line_num,
+                reason: ".rept expects a count and an optional loop counter name.".to_string(),
+            }
+            .into());
+        }
+    };
+
+    let mut ast = Vec::new();
+    for iteration in 0..count {
+        context.expansion_count += 1;
+        let text = expand_repetition(
+            body.as_str(),
+            body_line,
+            counter,
+            iteration,
+            context.expansion_count,
+        );
+
+        let frame = MacroExpansion {
+            name: ".rept".to_string(),
+            invocation_line: line_num,
+            iteration: Some(iteration),
+        };
+        let mut chain = expansion.to_vec();
+        chain.push(frame.clone());
+
+        ast.extend(
+            parse_lines(&text, file, &chain, context)
+                .map_err(|error| expansion_error(error, &frame))?,
+        );
+    }
 
     Ok(ast)
 }
 
+// the number of repetitions of a .rept block, which is needed before any constant is known
+fn rept_count(text: &str, line_num: usize) -> Result<usize> {
+    let invalid = || AssemblyError::StructuralError {
+        line: line_num,
+        reason: format!(
+            ".rept count must be a number or an expression of numbers, found \"{}\".",
+            text
+        ),
+    };
+
+    let mut pairs = CicadaParser::parse(Rule::rept_count, text).map_err(|_| invalid())?;
+    let expr = build_expr(pairs.next().unwrap().into_inner().next().unwrap())?;
+    if !crate::expression::is_constant(&expr) {
+        return Err(invalid().into());
+    }
+
+    let count = crate::expression::evaluate_constant(&expr, &line_num)?;
+    if !(0..=MAX_REPT_COUNT as i32).contains(&count) {
+        return Err(AssemblyError::StructuralError {
+            line: line_num,
+            reason: format!(
+                ".rept count must be between 0 and {}, found {}.",
+                MAX_REPT_COUNT, count
+            ),
+        }
+        .into());
+    }
+
+    Ok(count as usize)
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic())
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// split a line into a possible macro name and the rest of the line
+fn split_macro_name(line_text: &str) -> Option<(&str, &str)> {
+    let line_text = line_text.trim();
+    let name_len = line_text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(line_text.len());
+    let (name, args) = line_text.split_at(name_len);
+
+    if name.is_empty() || !(args.is_empty() || args.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    Some((name, args))
+}
+
+// a line that parsed as a macro invocation of an unknown macro is most likely a malformed
+// instruction or directive, parse the line on its own to get a syntax error pointing at the problem
+fn invalid_line_error(source: &str, file: &Path, line: &AssemblyLine) -> anyhow::Error {
+    let line_num = line.line_number;
+    let text = source.lines().nth(line_num - 1).unwrap_or_default();
+    let padded = format!("{}{}", "\n".repeat(line_num - 1), text);
+
+    match CicadaParser::parse(Rule::single_line, &padded) {
+        Err(err) => err.with_path(&file.display().to_string()).into(),
+        Ok(_) => AssemblyError::StructuralError {
+            line: line_num,
+            reason: format!("Unknown instruction or macro: {}", text.trim()),
+        }
+        .with_location(line)
+        .into(),
+    }
+}
+
+// the span of every operand of an instruction or directive
+fn operand_spans(pair: &Pair<Rule>) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut last_end = 0;
+
+    for inner in pair.clone().into_inner().flatten() {
+        let span = inner.as_span();
+        if matches!(inner.as_rule(), Rule::operand | Rule::data_operand) && span.start() >= last_end
+        {
+            spans.push(Span {
+                column: inner.line_col().1,
+                length: span.as_str().chars().count(),
+            });
+            last_end = span.end();
+        }
+    }
+
+    spans
+}
+
+// report an error found in the text of a macro expansion or .rept repetition with the expansion
+// it happened in, inside the location of its line so that it is still shown with its source
+fn expansion_error(error: anyhow::Error, frame: &MacroExpansion) -> anyhow::Error {
+    let frame = std::slice::from_ref(frame);
+
+    match AssemblyError::from(error) {
+        AssemblyError::SourceError { location, error } => AssemblyError::SourceError {
+            location,
+            error: Box::new(error.in_expansion(frame)),
+        },
+        error => error.in_expansion(frame),
+    }
+    .into()
+}
+
+// attach the location of a line to an error raised while building its AST, macro expansions
+// are added by expansion_error
+fn locate_error(error: anyhow::Error, line: &AssemblyLine) -> anyhow::Error {
+    match error.downcast::<AssemblyError>() {
+        Ok(error) => error.with_location(line).into(),
+        Err(error) => error,
+    }
+}
+
 fn build_instruction(pair: Pair<Rule>) -> Result<Instruction> {
     let builder = AstBuilder::new(pair.clone());
     builder.build_instruction()
@@ -158,7 +460,9 @@ mod tests {
 
         fn read_binary(&self, _path: &Path) -> Result<Vec<u8>> {
             // Parser tests don't use binary files, so this is a stub
-            Err(anyhow::anyhow!("Binary file reading not supported in parser tests"))
+            Err(anyhow::anyhow!(
+                "Binary file reading not supported in parser tests"
+            ))
         }
     }
 
@@ -775,6 +1079,8 @@ mod tests {
                 vaddr: None,
                 paddr: None,
                 align: None,
+                nobits: false,
+                bank0: false,
             }))
         );
         assert_eq!(lines[1].instruction, Some(Instruction::Nop));
@@ -796,6 +1102,8 @@ mod tests {
                 vaddr: None,
                 paddr: None,
                 align: None,
+                nobits: false,
+                bank0: false,
             }))
         );
     }
@@ -815,6 +1123,8 @@ mod tests {
                 vaddr: Some(0x4000),
                 paddr: None,
                 align: None,
+                nobits: false,
+                bank0: false,
             }))
         );
     }
@@ -834,6 +1144,8 @@ mod tests {
                 vaddr: None,
                 paddr: Some(0x8000),
                 align: None,
+                nobits: false,
+                bank0: false,
             }))
         );
     }
@@ -853,6 +1165,8 @@ mod tests {
                 vaddr: Some(0x4000),
                 paddr: Some(0x8000),
                 align: None,
+                nobits: false,
+                bank0: false,
             }))
         );
     }
@@ -872,6 +1186,8 @@ mod tests {
                 vaddr: None,
                 paddr: None,
                 align: None,
+                nobits: false,
+                bank0: false,
             }))
         );
         assert_eq!(lines[1].directive, Some(Directive::SectionEnd));
@@ -952,6 +1268,8 @@ mod tests {
                 vaddr: None,
                 paddr: None,
                 align: Some(4),
+                nobits: false,
+                bank0: false,
             }))
         );
         assert_eq!(lines[1].instruction, Some(Instruction::Nop));
@@ -973,6 +1291,8 @@ mod tests {
                 vaddr: None,
                 paddr: None,
                 align: Some(16),
+                nobits: false,
+                bank0: false,
             }))
         );
     }
@@ -992,14 +1312,15 @@ mod tests {
                 vaddr: None,
                 paddr: None,
                 align: Some(8),
+                nobits: false,
+                bank0: false,
             }))
         );
     }
 
     #[test]
     fn test_parse_section_with_all_attributes_including_align() {
-        let source =
-            ".section name=\"test\" size=64 vaddr=0x4000 paddr=0x8000 align=16\nNOP\n.section_end\n";
+        let source = ".section name=\"test\" size=64 vaddr=0x4000 paddr=0x8000 align=16\nNOP\n.section_end\n";
         let result = parse_test_source(source);
         assert!(result.is_ok());
         let lines = result.unwrap();
@@ -1012,6 +1333,8 @@ mod tests {
                 vaddr: Some(0x4000),
                 paddr: Some(0x8000),
                 align: Some(16),
+                nobits: false,
+                bank0: false,
             }))
         );
     }
@@ -1031,8 +1354,455 @@ mod tests {
                 vaddr: None,
                 paddr: None,
                 align: Some(2),
+                nobits: false,
+                bank0: false,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_section_nobits() {
+        let source = ".section name=\"vars\" vaddr=0xFE00 NOBITS\n.section_end\n";
+        let lines = parse_test_source(source).unwrap();
+        assert_eq!(
+            lines[0].directive,
+            Some(Directive::SectionStart(SectionOptions {
+                name: Some("vars".to_string()),
+                size: None,
+                vaddr: Some(0xFE00),
+                paddr: None,
+                align: None,
+                nobits: true,
+                bank0: false,
             }))
         );
+
+        assert!(parse_test_source(".section vaddr=0xFE00 paddr=0x100 nobits\n").is_err());
+        assert!(parse_test_source(".section nobitsx\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_section_bank0() {
+        let source = ".section name=\"Vectors\" FIXED\n.section_end\n";
+        let lines = parse_test_source(source).unwrap();
+        assert_eq!(
+            lines[0].directive,
+            Some(Directive::SectionStart(SectionOptions {
+                name: Some("Vectors".to_string()),
+                size: None,
+                vaddr: None,
+                paddr: None,
+                align: None,
+                nobits: false,
+                bank0: true,
+            }))
+        );
+
+        assert!(parse_test_source(".section bank0 fixed\n").is_err());
+        assert!(parse_test_source(".section vaddr=0xFE00 nobits bank0\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_expression_folds_constants() {
+        let source = "ldi r1, (1 << 3) | 0x01\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(
+            lines[0].instruction,
+            Some(Instruction::Ldi(Register::R1, Operand::Immediate(0x09)))
+        );
+    }
+
+    #[test]
+    fn test_parse_expression_precedence() {
+        let source = "ldi r1, 2 + 3 * 4 - -1\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(
+            lines[0].instruction,
+            Some(Instruction::Ldi(Register::R1, Operand::Immediate(15)))
+        );
+    }
+
+    #[test]
+    fn test_parse_expression_with_symbols() {
+        let source = "ldi r1, end - start\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(
+            lines[0].instruction,
+            Some(Instruction::Ldi(
+                Register::R1,
+                Operand::Expr(Expr::Binary(
+                    BinaryOp::Sub,
+                    Box::new(Expr::Symbol("end".to_string())),
+                    Box::new(Expr::Symbol("start".to_string()))
+                ))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_expression_low_high_bank() {
+        let source = "ldi.b r1, <data\nldi.b r2, >data\nldi.b r3, bank(data)\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        let data = Box::new(Expr::Symbol("data".to_string()));
+        assert_eq!(
+            lines[0].instruction,
+            Some(Instruction::LdiB(
+                Register::R1,
+                Operand::Expr(Expr::Unary(UnaryOp::Low, data.clone()))
+            ))
+        );
+        assert_eq!(
+            lines[1].instruction,
+            Some(Instruction::LdiB(
+                Register::R2,
+                Operand::Expr(Expr::Unary(UnaryOp::High, data))
+            ))
+        );
+        assert_eq!(
+            lines[2].instruction,
+            Some(Instruction::LdiB(
+                Register::R3,
+                Operand::Expr(Expr::Bank("data".to_string()))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_absolute_expression() {
+        let source = "ld r0, (IO_BASE + 2)\nst (0x10 + 0x20), r1\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(
+            lines[0].instruction,
+            Some(Instruction::LdAbs(
+                Register::R0,
+                Operand::Expr(Expr::Binary(
+                    BinaryOp::Add,
+                    Box::new(Expr::Symbol("IO_BASE".to_string())),
+                    Box::new(Expr::Number(2))
+                ))
+            ))
+        );
+        assert_eq!(
+            lines[1].instruction,
+            Some(Instruction::StAbs(Operand::Immediate(0x30), Register::R1))
+        );
+    }
+
+    #[test]
+    fn test_parse_indexed_label_and_expression() {
+        let source = "ld r0, (r1, OFFSET)\nlea r2, (r3, OFFSET * 2)\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(
+            lines[0].instruction,
+            Some(Instruction::LdIndexed(
+                Register::R0,
+                Register::R1,
+                Operand::Label("OFFSET".to_string())
+            ))
+        );
+        assert_eq!(
+            lines[1].instruction,
+            Some(Instruction::Lea(
+                Register::R2,
+                Register::R3,
+                Operand::Expr(Expr::Binary(
+                    BinaryOp::Mul,
+                    Box::new(Expr::Symbol("OFFSET".to_string())),
+                    Box::new(Expr::Number(2))
+                ))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_indexed_expression_out_of_range() {
+        let source = "ld r0, (r1, 100 + 100)\n";
+        let result = parse_test_source(source);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_define_expression() {
+        let source = ".define SPRITE_DATA_SIZE sprite_data_end - sprite_data\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(
+            lines[0].directive,
+            Some(Directive::Define(
+                "SPRITE_DATA_SIZE".to_string(),
+                Operand::Expr(Expr::Binary(
+                    BinaryOp::Sub,
+                    Box::new(Expr::Symbol("sprite_data_end".to_string())),
+                    Box::new(Expr::Symbol("sprite_data".to_string()))
+                ))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_conditional_directives() {
+        let source = ".ifdef DEBUG\n.if VERSION >= 2 && !RELEASE\n.elif VERSION == 1\n.else\n.endif\n.endif\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(
+            lines[0].directive,
+            Some(Directive::Ifdef("DEBUG".to_string()))
+        );
+        assert_eq!(
+            lines[1].directive,
+            Some(Directive::If(Expr::Binary(
+                BinaryOp::LogicalAnd,
+                Box::new(Expr::Binary(
+                    BinaryOp::Ge,
+                    Box::new(Expr::Symbol("VERSION".to_string())),
+                    Box::new(Expr::Number(2))
+                )),
+                Box::new(Expr::Unary(
+                    UnaryOp::LogicalNot,
+                    Box::new(Expr::Symbol("RELEASE".to_string()))
+                ))
+            )))
+        );
+        assert_eq!(
+            lines[2].directive,
+            Some(Directive::Elif(Expr::Binary(
+                BinaryOp::Eq,
+                Box::new(Expr::Symbol("VERSION".to_string())),
+                Box::new(Expr::Number(1))
+            )))
+        );
+        assert_eq!(lines[3].directive, Some(Directive::Else));
+        assert_eq!(lines[4].directive, Some(Directive::Endif));
+    }
+
+    #[test]
+    fn test_parse_comparison_precedence() {
+        // shifts bind tighter than comparisons, "<=" is not "<" followed by "="
+        let source = "LDI r1, 1 << 2 <= 4\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(
+            lines[0].instruction,
+            Some(Instruction::Ldi(Register::R1, Operand::Immediate(1)))
+        );
+    }
+
+    #[test]
+    fn test_parse_local_and_anonymous_labels() {
+        let source = ".loop:\n-:\n++:\nJR .loop\nJR -\nJR ++\nLDI r1, draw.loop + 1\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(lines[0].label, Some(".loop".to_string()));
+        assert_eq!(lines[1].label, Some("-".to_string()));
+        assert_eq!(lines[2].label, Some("++".to_string()));
+        assert_eq!(
+            lines[3].instruction,
+            Some(Instruction::JrI(Operand::Label(".loop".to_string())))
+        );
+        assert_eq!(
+            lines[4].instruction,
+            Some(Instruction::JrI(Operand::Label("-".to_string())))
+        );
+        assert_eq!(
+            lines[5].instruction,
+            Some(Instruction::JrI(Operand::Label("++".to_string())))
+        );
+        assert_eq!(
+            lines[6].instruction,
+            Some(Instruction::Ldi(
+                Register::R1,
+                Operand::Expr(Expr::Binary(
+                    BinaryOp::Add,
+                    Box::new(Expr::Symbol("draw.loop".to_string())),
+                    Box::new(Expr::Number(1))
+                ))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_macro_expansion() {
+        let source =
+            ".macro load reg, value\n    LDI \\reg, \\value\n.endm\nstart:\n    load r1, 2 + 3\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1].instruction,
+            Some(Instruction::Ldi(Register::R1, Operand::Immediate(5)))
+        );
+        assert_eq!(lines[1].line_number, 2);
+        assert_eq!(
+            lines[1].expansion,
+            vec![MacroExpansion {
+                name: "load".to_string(),
+                invocation_line: 5,
+                iteration: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_macro_unique_labels() {
+        let source = ".macro spin\nloop\\@:\n    JR loop\\@\n.endm\nspin\nspin\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].label, Some("loopU1__".to_string()));
+        assert_eq!(
+            lines[1].instruction,
+            Some(Instruction::JrI(Operand::Label("loopU1__".to_string())))
+        );
+        assert_eq!(lines[2].label, Some("loopU2__".to_string()));
+    }
+
+    #[test]
+    fn test_parse_macro_unique_label_prefix() {
+        // \@ can start a label or be the whole label
+        let source = ".macro spin\n\\@loop:\n    JR \\@loop\n\\@:\n    JR \\@\n.endm\nspin\nspin\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok(), "{:?}", result.err());
+        let lines = result.unwrap();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[0].label, Some("U1__loop".to_string()));
+        assert_eq!(
+            lines[1].instruction,
+            Some(Instruction::JrI(Operand::Label("U1__loop".to_string())))
+        );
+        assert_eq!(lines[2].label, Some("U1__".to_string()));
+        assert_eq!(
+            lines[3].instruction,
+            Some(Instruction::JrI(Operand::Label("U1__".to_string())))
+        );
+        assert_eq!(lines[4].label, Some("U2__loop".to_string()));
+        assert_eq!(lines[6].label, Some("U2__".to_string()));
+    }
+
+    #[test]
+    fn test_parse_macro_named_like_instruction() {
+        // "pushregs" starts with the "push" mnemonic
+        let source = ".macro pushregs\n    PUSH r1\n    PUSH r2\n.endm\npushregs\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].instruction, Some(Instruction::Push(Register::R1)));
+        assert_eq!(lines[1].instruction, Some(Instruction::Push(Register::R2)));
+    }
+
+    #[test]
+    fn test_parse_nested_macro_expansion() {
+        let source = ".macro inner\n    NOP\n.endm\n.macro outer\n    inner\n.endm\nouter\n";
+        let result = parse_test_source(source);
+        assert!(result.is_ok());
+        let lines = result.unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].line_number, 2);
+        assert_eq!(
+            lines[0].expansion,
+            vec![
+                MacroExpansion {
+                    name: "outer".to_string(),
+                    invocation_line: 7,
+                    iteration: None,
+                },
+                MacroExpansion {
+                    name: "inner".to_string(),
+                    invocation_line: 5,
+                    iteration: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rept_with_counter() {
+        let source = ".rept 3, i ; table\n    .byte \\i * 2\n.endr\nNOP\n";
+        let lines = parse_test_source(source).unwrap();
+        assert_eq!(lines.len(), 4);
+        for (i, line) in lines[..3].iter().enumerate() {
+            assert_eq!(
+                line.directive,
+                Some(Directive::Byte(vec![Operand::Immediate(i as i32 * 2)]))
+            );
+            assert_eq!(line.line_number, 2);
+            assert_eq!(
+                line.expansion,
+                vec![MacroExpansion {
+                    name: ".rept".to_string(),
+                    invocation_line: 1,
+                    iteration: Some(i),
+                }]
+            );
+        }
+        assert_eq!(lines[3].instruction, Some(Instruction::Nop));
+    }
+
+    #[test]
+    fn test_parse_nested_rept() {
+        let source = ".rept 2, row\n  .rept 1 + 1, col\n    .byte \\row * 10 + \\col\n  .endr\n.endr\n.rept 0\n    NOP\n.endr\n";
+        let lines = parse_test_source(source).unwrap();
+        let bytes: Vec<_> = lines.iter().map(|l| l.directive.clone().unwrap()).collect();
+        assert_eq!(
+            bytes,
+            [0, 1, 10, 11].map(|v| Directive::Byte(vec![Operand::Immediate(v)]))
+        );
+        assert_eq!(lines[3].expansion.len(), 2);
+        assert_eq!(lines[3].expansion[1].iteration, Some(1));
+    }
+
+    #[test]
+    fn test_parse_rept_unique_labels() {
+        let source = ".rept 2\n\\@:\n    JR \\@\n.endr\n";
+        let lines = parse_test_source(source).unwrap();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].label, Some("U1__".to_string()));
+        assert_eq!(
+            lines[1].instruction,
+            Some(Instruction::JrI(Operand::Label("U1__".to_string())))
+        );
+        assert_eq!(lines[2].label, Some("U2__".to_string()));
+    }
+
+    #[test]
+    fn test_parse_rept_errors() {
+        assert!(parse_test_source(".rept COUNT\nNOP\n.endr\n").is_err());
+        assert!(parse_test_source(".rept -1\nNOP\n.endr\n").is_err());
+        assert!(parse_test_source(".rept 2, 1i\nNOP\n.endr\n").is_err());
+        assert!(parse_test_source(".rept 2\nNOP\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_macro_wrong_argument_count() {
+        let source = ".macro load reg, value\n    LDI \\reg, \\value\n.endm\nload r1\n";
+        let result = parse_test_source(source);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown_macro() {
+        let source = "frobnicate r1\n";
+        let result = parse_test_source(source);
+        assert!(result.is_err());
     }
 
     // #[test]
@@ -1058,4 +1828,230 @@ mod tests {
     //         }
     //     }));
     // }
+
+    #[test]
+    fn test_parse_line_location() {
+        let mut mock_reader = MockFileReader::default();
+        mock_reader.add_file(
+            "main.asm",
+            "start:\n    LDI r1, value + 2 ; load\n.include \"inc.asm\"\n",
+        );
+        mock_reader.add_file("inc.asm", "  .byte 1, LIMIT\n");
+
+        let mut include_stack = HashSet::new();
+        let lines = parse_source_recursive(Path::new("main.asm"), &mut include_stack, &mock_reader)
+            .unwrap();
+
+        assert_eq!(lines[1].file, PathBuf::from("main.asm"));
+        assert_eq!(lines[1].source, "    LDI r1, value + 2 ; load");
+        assert_eq!(
+            lines[1].span,
+            Span {
+                column: 5,
+                length: 17
+            }
+        );
+        assert_eq!(
+            lines[1].operand_spans,
+            [
+                Span {
+                    column: 9,
+                    length: 2
+                },
+                Span {
+                    column: 13,
+                    length: 9
+                }
+            ]
+        );
+
+        assert_eq!(lines[2].file, PathBuf::from("inc.asm"));
+        assert_eq!(lines[2].line_number, 1);
+        assert_eq!(
+            lines[2].operand_spans,
+            [
+                Span {
+                    column: 9,
+                    length: 1
+                },
+                Span {
+                    column: 12,
+                    length: 5
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_character_literals() {
+        let source = "ldi r1, 'A' + 1
+.byte '\\n', '\\'', '\\x7f', ','
+";
+        let lines = parse_test_source(source).unwrap();
+        assert_eq!(
+            lines[0].instruction,
+            Some(Instruction::Ldi(Register::R1, Operand::Immediate(0x42)))
+        );
+        assert_eq!(
+            lines[1].directive,
+            Some(Directive::Byte(vec![
+                Operand::Immediate(0x0A),
+                Operand::Immediate(0x27),
+                Operand::Immediate(0x7F),
+                Operand::Immediate(0x2C),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_string_directives() {
+        let source =
+            ".ascii \"Hi, \\\"you\\\"; \\x01\" ; comment\n.asciz \"\"\n.string \"a\\tb\"\n";
+        let lines = parse_test_source(source).unwrap();
+        assert_eq!(
+            lines[0].directive,
+            Some(Directive::Ascii(b"Hi, \"you\"; \x01".to_vec()))
+        );
+        assert_eq!(lines[1].directive, Some(Directive::Asciz(Vec::new())));
+        assert_eq!(lines[2].directive, Some(Directive::Asciz(b"a\tb".to_vec())));
+    }
+
+    #[test]
+    fn test_parse_invalid_escape() {
+        let result = parse_test_source(".ascii \"\\q\"\n");
+        let message = format!("{:#}", result.unwrap_err());
+        assert!(
+            message.contains("Unknown escape sequence: \\q"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_parse_charmap_directives() {
+        let source = ".charmap font\n.charmap ASCII\n.charmap 'A', 0x21\n.charmap \"ab\", FIRST\n";
+        let lines = parse_test_source(source).unwrap();
+        assert_eq!(lines[0].directive, Some(Directive::Charmap(Charmap::Font)));
+        assert_eq!(lines[1].directive, Some(Directive::Charmap(Charmap::Ascii)));
+        assert_eq!(
+            lines[2].directive,
+            Some(Directive::Charmap(Charmap::Map(
+                Operand::Immediate(0x41),
+                Operand::Immediate(0x21)
+            )))
+        );
+        assert_eq!(
+            lines[3].directive,
+            Some(Directive::Charmap(Charmap::Map(
+                Operand::String("ab".to_string()),
+                Operand::Label("FIRST".to_string())
+            )))
+        );
+    }
+
+    #[test]
+    fn test_parse_macro_string_arguments() {
+        let source =
+            ".macro text t, c\n    .asciz \\t\n    .byte \\c\n.endm\ntext \"a, (b\", ','\n";
+        let lines = parse_test_source(source).unwrap();
+        assert_eq!(
+            lines[0].directive,
+            Some(Directive::Asciz(b"a, (b".to_vec()))
+        );
+        assert_eq!(
+            lines[1].directive,
+            Some(Directive::Byte(vec![Operand::Immediate(0x2C)]))
+        );
+    }
+
+    #[test]
+    fn test_parse_fill_directives() {
+        let source = ".fill 4, 0xFF\n.fill SIZE\n.ds 2 * 8\n.res 3\n.padbyte 0xFF\n";
+        let lines = parse_test_source(source).unwrap();
+        assert_eq!(
+            lines[0].directive,
+            Some(Directive::Fill(
+                Operand::Immediate(4),
+                Some(Operand::Immediate(0xFF))
+            ))
+        );
+        assert_eq!(
+            lines[1].directive,
+            Some(Directive::Fill(Operand::Label("SIZE".to_string()), None))
+        );
+        assert_eq!(
+            lines[2].directive,
+            Some(Directive::Fill(Operand::Immediate(16), None))
+        );
+        assert_eq!(
+            lines[3].directive,
+            Some(Directive::Fill(Operand::Immediate(3), None))
+        );
+        assert_eq!(
+            lines[4].directive,
+            Some(Directive::PadByte(Operand::Immediate(0xFF)))
+        );
+    }
+
+    #[test]
+    fn test_parse_struct_directive() {
+        let source = ".struct Sprite ; OAM entry\n    y .b\n\n    pos .W\n    tiles .b COUNT\n.endstruct\nNOP\n";
+        let lines = parse_test_source(source).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0].directive,
+            Some(Directive::Struct(
+                "Sprite".to_string(),
+                vec![
+                    StructField {
+                        name: "y".to_string(),
+                        size: 1,
+                        length: None,
+                    },
+                    StructField {
+                        name: "pos".to_string(),
+                        size: 2,
+                        length: None,
+                    },
+                    StructField {
+                        name: "tiles".to_string(),
+                        size: 1,
+                        length: Some(Expr::Symbol("COUNT".to_string())),
+                    },
+                ]
+            ))
+        );
+
+        assert!(parse_test_source(".struct S\n    x .b\n    x .w\n.endstruct\n").is_err());
+        assert!(parse_test_source(".struct S\n    x .l\n.endstruct\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_enum_directive() {
+        let source = ".enum State\n    IDLE, RUN\n    DEAD = 1 << 4\n.endenum\n.enum\n.endenum\n";
+        let lines = parse_test_source(source).unwrap();
+        let member = |name: &str, value: Option<Expr>| EnumMember {
+            name: name.to_string(),
+            value,
+        };
+        let shift = Expr::Binary(
+            BinaryOp::Shl,
+            Box::new(Expr::Number(1)),
+            Box::new(Expr::Number(4)),
+        );
+        assert_eq!(
+            lines[0].directive,
+            Some(Directive::Enum(
+                Some("State".to_string()),
+                vec![
+                    member("IDLE", None),
+                    member("RUN", None),
+                    member("DEAD", Some(shift))
+                ]
+            ))
+        );
+        assert_eq!(lines[1].directive, Some(Directive::Enum(None, Vec::new())));
+
+        assert!(parse_test_source(".enum\n    A\n    A\n.endenum\n").is_err());
+    }
 }
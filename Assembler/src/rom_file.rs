@@ -0,0 +1,156 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::assembler::BANK_SIZE;
+use std::fmt::Write;
+
+// data bytes per Intel HEX and SREC record
+const RECORD_SIZE: usize = 16;
+
+/// Split an assembled ROM into its 16 KiB banks, the ROM is always padded to a whole number of
+/// banks.
+pub fn split_banks(rom: &[u8]) -> impl Iterator<Item = &[u8]> {
+    rom.chunks(BANK_SIZE as usize)
+}
+
+// two's complement of the sum of the bytes, used by Intel HEX
+fn ihex_checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |sum, b| sum.wrapping_add(*b))
+        .wrapping_neg()
+}
+
+// one's complement of the sum of the bytes, used by SREC
+fn srec_checksum(bytes: &[u8]) -> u8 {
+    !bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn write_ihex_record(out: &mut String, addr: u16, record_type: u8, data: &[u8]) {
+    let mut record = vec![data.len() as u8];
+    record.extend(addr.to_be_bytes());
+    record.push(record_type);
+    record.extend(data);
+    let checksum = ihex_checksum(&record);
+
+    out.push(':');
+    for b in record {
+        let _ = write!(out, "{:02X}", b);
+    }
+    let _ = writeln!(out, "{:02X}", checksum);
+}
+
+/// Render a ROM image as Intel HEX, starting at address 0. An extended linear address record is
+/// written whenever the data crosses into the next 64 KiB.
+pub fn to_ihex(rom: &[u8]) -> String {
+    let mut out = String::new();
+    let mut upper_addr = 0;
+
+    for (i, data) in rom.chunks(RECORD_SIZE).enumerate() {
+        let addr = (i * RECORD_SIZE) as u32;
+        if addr >> 16 != upper_addr {
+            upper_addr = addr >> 16;
+            write_ihex_record(&mut out, 0, 0x04, &(upper_addr as u16).to_be_bytes());
+        }
+        write_ihex_record(&mut out, addr as u16, 0x00, data);
+    }
+
+    write_ihex_record(&mut out, 0, 0x01, &[]);
+    out
+}
+
+fn write_srec_record(out: &mut String, record_type: u8, addr: &[u8], data: &[u8]) {
+    let mut record = vec![(addr.len() + data.len() + 1) as u8];
+    record.extend(addr);
+    record.extend(data);
+    let checksum = srec_checksum(&record);
+
+    let _ = write!(out, "S{}", record_type);
+    for b in record {
+        let _ = write!(out, "{:02X}", b);
+    }
+    let _ = writeln!(out, "{:02X}", checksum);
+}
+
+/// Render a ROM image as Motorola SREC, starting at address 0. The smallest address size that
+/// fits the whole ROM is used: S1 records for up to 64 KiB, S2 up to 16 MiB and S3 above that.
+pub fn to_srec(rom: &[u8]) -> String {
+    let mut out = String::new();
+    let (addr_len, data_type) = match rom.len() {
+        0..=0x10000 => (2, 1),
+        0x10001..=0x1000000 => (3, 2),
+        _ => (4, 3),
+    };
+
+    write_srec_record(&mut out, 0, &[0, 0], b"cicasm");
+
+    let mut count = 0;
+    for (i, data) in rom.chunks(RECORD_SIZE).enumerate() {
+        let addr = ((i * RECORD_SIZE) as u32).to_be_bytes();
+        write_srec_record(&mut out, data_type, &addr[4 - addr_len..], data);
+        count += 1;
+    }
+
+    // the record count is only written when it fits in 16 bits
+    if count <= u16::MAX as usize {
+        write_srec_record(&mut out, 5, &(count as u16).to_be_bytes(), &[]);
+    }
+
+    // the termination record matches the address size of the data records
+    write_srec_record(&mut out, 10 - data_type, &vec![0; addr_len], &[]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ihex() {
+        let mut rom = vec![0xFF; 0x10010];
+        rom[0..4].copy_from_slice(&[0x02, 0x34, 0x12, 0x00]);
+
+        let hex = to_ihex(&rom);
+        let lines: Vec<&str> = hex.lines().collect();
+        assert_eq!(lines[0], ":1000000002341200FFFFFFFFFFFFFFFFFFFFFFFFB4");
+        assert_eq!(lines[4096], ":020000040001F9");
+        assert_eq!(lines[4097], ":10000000FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF00");
+        assert_eq!(lines[4098], ":00000001FF");
+        assert_eq!(lines.len(), 4099);
+    }
+
+    #[test]
+    fn test_srec() {
+        let rom = [0x02, 0x34, 0x12, 0x00];
+
+        let srec = to_srec(&rom);
+        let lines: Vec<&str> = srec.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "S009000063696361736D86",
+                "S107000002341200B0",
+                "S5030001FB",
+                "S9030000FC",
+            ]
+        );
+
+        // S2 records once the ROM no longer fits in 16 bit addresses
+        let srec = to_srec(&vec![0; 0x10010]);
+        assert!(srec.lines().nth(1).unwrap().starts_with("S214000000"));
+        assert_eq!(srec.lines().last(), Some("S804000000FB"));
+    }
+}
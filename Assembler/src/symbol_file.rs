@@ -0,0 +1,269 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+use crate::errors::AssemblyError;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabelEntry {
+    pub name: String,
+    pub bank: u32,
+    pub address: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConstantEntry {
+    pub name: String,
+    pub value: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SectionEntry {
+    pub name: Option<String>,
+    pub bank: u32,
+    pub start: u32, // logical address of the first byte
+    pub end: u32,   // logical address of the last byte
+    pub size: u32,
+}
+
+/// Every label, `.define` constant and section of an assembled program, for use by debuggers
+/// and emulators. Entries are sorted by bank and address, constants are sorted by name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SymbolFile {
+    pub labels: Vec<LabelEntry>,
+    pub constants: Vec<ConstantEntry>,
+    pub sections: Vec<SectionEntry>,
+}
+
+impl SymbolFile {
+    pub fn sort(&mut self) {
+        self.labels
+            .sort_by(|a, b| (a.bank, a.address, &a.name).cmp(&(b.bank, b.address, &b.name)));
+        self.constants.sort_by(|a, b| a.name.cmp(&b.name));
+        self.sections.sort_by_key(|s| (s.bank, s.start));
+    }
+
+    /// Render the plain text format, one `bank:addr name` line per label. Constants and sections
+    /// follow under their own headers.
+    pub fn to_sym(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("; cicasm symbol file\n");
+
+        out.push_str("\n[labels]\n");
+        for label in &self.labels {
+            let _ = writeln!(
+                out,
+                "{:02x}:{:04x} {}",
+                label.bank, label.address, label.name
+            );
+        }
+
+        out.push_str("\n[definitions]\n");
+        for constant in &self.constants {
+            let _ = writeln!(out, "{:08x} {}", constant.value, constant.name);
+        }
+
+        out.push_str("\n[sections]\n");
+        for section in &self.sections {
+            let _ = writeln!(
+                out,
+                "{:02x}:{:04x} {:02x}:{:04x} {:04x} {}",
+                section.bank,
+                section.start,
+                section.bank,
+                section.end,
+                section.size,
+                section.name.as_deref().unwrap_or("UNNAMED")
+            );
+        }
+
+        out
+    }
+
+    /// Render the JSON format.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Read a symbol file in the plain text format. Lines before the first header are read as
+    /// labels, so the `bank:addr name` files written by other toolchains can be read as well.
+    pub fn from_sym(text: &str) -> Result<SymbolFile, AssemblyError> {
+        let mut file = SymbolFile::default();
+        let mut header = "[labels]";
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                header = line;
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match header {
+                "[labels]" => parse_label(&fields).map(|label| file.labels.push(label)),
+                "[definitions]" => {
+                    parse_constant(&fields).map(|constant| file.constants.push(constant))
+                }
+                "[sections]" => parse_section(&fields).map(|section| file.sections.push(section)),
+                // entries under headers written by other toolchains are skipped
+                _ => Some(()),
+            };
+
+            if parsed.is_none() {
+                return Err(AssemblyError::FileError {
+                    reason: format!("Invalid symbol file line {}: {}", index + 1, line),
+                });
+            }
+        }
+
+        file.sort();
+        Ok(file)
+    }
+
+    /// Read a symbol file in the JSON format.
+    pub fn from_json(json: &str) -> serde_json::Result<SymbolFile> {
+        serde_json::from_str(json)
+    }
+}
+
+fn parse_label(fields: &[&str]) -> Option<LabelEntry> {
+    let [addr, name] = fields else { return None };
+    let (bank, address) = parse_bank_addr(addr)?;
+    Some(LabelEntry {
+        name: name.to_string(),
+        bank,
+        address,
+    })
+}
+
+fn parse_constant(fields: &[&str]) -> Option<ConstantEntry> {
+    let [value, name] = fields else { return None };
+    Some(ConstantEntry {
+        name: name.to_string(),
+        value: u32::from_str_radix(value, 16).ok()? as i32,
+    })
+}
+
+fn parse_section(fields: &[&str]) -> Option<SectionEntry> {
+    let [start, end, size, name] = fields else {
+        return None;
+    };
+    let (bank, start) = parse_bank_addr(start)?;
+    let (_, end) = parse_bank_addr(end)?;
+    Some(SectionEntry {
+        name: Some(name.to_string()).filter(|name| name != "UNNAMED"),
+        bank,
+        start,
+        end,
+        size: u32::from_str_radix(size, 16).ok()?,
+    })
+}
+
+// a bank:addr pair, e.g. 02:4000
+fn parse_bank_addr(text: &str) -> Option<(u32, u32)> {
+    let (bank, address) = text.split_once(':')?;
+    Some((
+        u32::from_str_radix(bank, 16).ok()?,
+        u32::from_str_radix(address, 16).ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> SymbolFile {
+        let mut file = SymbolFile {
+            labels: vec![
+                LabelEntry {
+                    name: "far_routine".to_string(),
+                    bank: 2,
+                    address: 0x4000,
+                },
+                LabelEntry {
+                    name: "start".to_string(),
+                    bank: 0,
+                    address: 0x0100,
+                },
+            ],
+            constants: vec![ConstantEntry {
+                name: "WIDTH".to_string(),
+                value: 160,
+            }],
+            sections: vec![SectionEntry {
+                name: Some("Main".to_string()),
+                bank: 0,
+                start: 0x0100,
+                end: 0x010f,
+                size: 0x10,
+            }],
+        };
+        file.sort();
+        file
+    }
+
+    #[test]
+    fn test_sym_format() {
+        let expected = "; cicasm symbol file\n\
+                        \n[labels]\n\
+                        00:0100 start\n\
+                        02:4000 far_routine\n\
+                        \n[definitions]\n\
+                        000000a0 WIDTH\n\
+                        \n[sections]\n\
+                        00:0100 00:010f 0010 Main\n";
+        assert_eq!(example().to_sym(), expected);
+    }
+
+    #[test]
+    fn test_json_format() {
+        let json: serde_json::Value = serde_json::from_str(&example().to_json().unwrap()).unwrap();
+        assert_eq!(json["labels"][0]["name"], "start");
+        assert_eq!(json["labels"][1]["bank"], 2);
+        assert_eq!(json["labels"][1]["address"], 0x4000);
+        assert_eq!(json["constants"][0]["value"], 160);
+        assert_eq!(json["sections"][0]["name"], "Main");
+        assert_eq!(json["sections"][0]["size"], 16);
+    }
+
+    #[test]
+    fn test_read_back() {
+        assert_eq!(
+            SymbolFile::from_sym(&example().to_sym()).unwrap(),
+            example()
+        );
+        assert_eq!(
+            SymbolFile::from_json(&example().to_json().unwrap()).unwrap(),
+            example()
+        );
+    }
+
+    #[test]
+    fn test_read_headerless_sym() {
+        let file = SymbolFile::from_sym("; other toolchain\n01:4000 far\n00:0150 main\n").unwrap();
+        assert_eq!(file.labels[0].name, "main");
+        assert_eq!(file.labels[1].bank, 1);
+        assert_eq!(file.labels[1].address, 0x4000);
+
+        assert!(SymbolFile::from_sym("[labels]\nmain\n").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::ast::{AssemblyLine, MacroExpansion};
+use crate::errors::{AssemblyError, SourceLocation};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Number of padding bytes a single `.org` may insert before it is reported.
+pub const DEFAULT_ORG_PADDING_LIMIT: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+    UnusedLabel,    // a label that is never referenced
+    OrgPadding,     // a .org that pads more bytes than the configured limit
+    OddAddress,     // a word load or store at an odd absolute address, a Bus Error fault
+    RomWrite,       // a store to an absolute address in cartridge ROM, a Protected Memory fault
+    ByteTruncation, // a .byte value that does not fit in 8 bits
+}
+
+impl WarningKind {
+    pub const ALL: [WarningKind; 5] = [
+        WarningKind::UnusedLabel,
+        WarningKind::OrgPadding,
+        WarningKind::OddAddress,
+        WarningKind::RomWrite,
+        WarningKind::ByteTruncation,
+    ];
+
+    /// The name used for the warning on the command line, e.g. `-Wno-unused-label`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WarningKind::UnusedLabel => "unused-label",
+            WarningKind::OrgPadding => "org-padding",
+            WarningKind::OddAddress => "odd-address",
+            WarningKind::RomWrite => "rom-write",
+            WarningKind::ByteTruncation => "byte-truncation",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<WarningKind> {
+        WarningKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A problem in the source that does not stop assembly, e.g. code that would fault when run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub reason: String,
+    pub location: SourceLocation,
+    pub operand: Option<usize>, // index of the operand the warning is about
+    pub expansion: Vec<MacroExpansion>,
+}
+
+impl Warning {
+    pub fn new(kind: WarningKind, line: &AssemblyLine, reason: String) -> Self {
+        Self {
+            kind,
+            reason,
+            location: SourceLocation::of(line),
+            operand: None,
+            expansion: line.expansion.clone(),
+        }
+    }
+
+    pub fn at_operand(mut self, index: usize) -> Self {
+        self.operand = Some(index);
+        self
+    }
+
+    /// The warning as an error, for `-Werror`.
+    pub fn into_error(mut self) -> AssemblyError {
+        // the error underlines the same operand as the warning
+        if let Some(span) = self
+            .operand
+            .and_then(|i| self.location.operand_spans.get(i))
+        {
+            self.location.span = *span;
+            self.location.operand_spans.clear();
+        }
+
+        let error = AssemblyError::SemanticError {
+            line: self.location.line,
+            reason: format!("{} [-Werror={}]", self.reason, self.kind),
+        };
+        AssemblyError::SourceError {
+            location: Box::new(self.location),
+            error: Box::new(error.in_expansion(&self.expansion)),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Warning on line {}: {} [-W{}]",
+            self.location.line, self.reason, self.kind
+        )
+    }
+}
+
+/// Which warnings are reported, and whether they fail the assembly. The warnings for code that
+/// would fault when run and for truncated data are enabled by default, `unused-label` and
+/// `org-padding` must be enabled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarningOptions {
+    enabled: HashSet<WarningKind>,
+    pub as_errors: bool,
+    pub org_padding_limit: u32,
+}
+
+impl Default for WarningOptions {
+    fn default() -> Self {
+        Self {
+            enabled: WarningKind::ALL
+                .into_iter()
+                .filter(|kind| !matches!(kind, WarningKind::UnusedLabel | WarningKind::OrgPadding))
+                .collect(),
+            as_errors: false,
+            org_padding_limit: DEFAULT_ORG_PADDING_LIMIT,
+        }
+    }
+}
+
+impl WarningOptions {
+    pub fn is_enabled(&self, kind: WarningKind) -> bool {
+        self.enabled.contains(&kind)
+    }
+
+    pub fn set(&mut self, kind: WarningKind, enabled: bool) {
+        if enabled {
+            self.enabled.insert(kind);
+        } else {
+            self.enabled.remove(&kind);
+        }
+    }
+
+    /// Apply a `-W` flag without its `-W` prefix: `NAME`, `no-NAME`, `all`, `no-all`, `error`,
+    /// `no-error` or `org-padding=BYTES`.
+    pub fn apply_flag(&mut self, flag: &str) -> Result<(), String> {
+        let (enable, name) = match flag.strip_prefix("no-") {
+            Some(name) => (false, name),
+            None => (true, flag),
+        };
+
+        if enable && let Some(limit) = name.strip_prefix("org-padding=") {
+            self.org_padding_limit = limit
+                .parse()
+                .map_err(|_| format!("invalid org-padding limit: \"{}\"", limit))?;
+            self.set(WarningKind::OrgPadding, true);
+            return Ok(());
+        }
+
+        match name {
+            "error" => self.as_errors = enable,
+            "all" => {
+                for kind in WarningKind::ALL {
+                    self.set(kind, enable);
+                }
+            }
+            _ => {
+                let kind = WarningKind::from_name(name)
+                    .ok_or_else(|| format!("unknown warning: \"{}\"", name))?;
+                self.set(kind, enable);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects the warnings of the assembler passes, dropping those that are not enabled.
+#[derive(Debug, Default)]
+pub struct WarningCollector {
+    options: WarningOptions,
+    warnings: Vec<Warning>,
+}
+
+impl WarningCollector {
+    pub fn new(options: WarningOptions) -> Self {
+        Self {
+            options,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn options(&self) -> &WarningOptions {
+        &self.options
+    }
+
+    pub fn push(&mut self, warning: Warning) {
+        if self.options.is_enabled(warning.kind) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Return the collected warnings, or fail with them as errors under `-Werror`.
+    pub fn finish(self, max_errors: usize) -> Result<Vec<Warning>, AssemblyError> {
+        if !self.options.as_errors {
+            return Ok(self.warnings);
+        }
+
+        let mut errors = crate::errors::ErrorCollector::new(max_errors);
+        for warning in self.warnings {
+            errors.push(warning.into_error())?;
+        }
+        errors.finish()?;
+
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_flags() {
+        let mut options = WarningOptions::default();
+        assert!(!options.is_enabled(WarningKind::UnusedLabel));
+        assert!(!options.is_enabled(WarningKind::OrgPadding));
+        assert!(options.is_enabled(WarningKind::RomWrite));
+
+        options.apply_flag("unused-label").unwrap();
+        options.apply_flag("no-rom-write").unwrap();
+        options.apply_flag("org-padding=1024").unwrap();
+        options.apply_flag("error").unwrap();
+
+        assert!(options.is_enabled(WarningKind::UnusedLabel));
+        assert!(!options.is_enabled(WarningKind::RomWrite));
+        assert_eq!(options.org_padding_limit, 1024);
+        assert!(options.as_errors);
+
+        options.apply_flag("no-all").unwrap();
+        assert!(WarningKind::ALL.iter().all(|k| !options.is_enabled(*k)));
+
+        assert!(options.apply_flag("no-such-warning").is_err());
+        assert!(options.apply_flag("org-padding=lots").is_err());
+    }
+}
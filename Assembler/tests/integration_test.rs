@@ -14,12 +14,32 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use cicasm::AssemblyOptions;
+use cicasm::AssemblyOutput;
+use cicasm::Symbol;
 use cicasm::assemble;
+use cicasm::assemble_object;
+use cicasm::assemble_with_options;
+use cicasm::diagnostics;
+use cicasm::disassembler::{DisassemblyOptions, disassemble};
+use cicasm::errors::AssemblyError;
 use cicasm::file_reader::MockFileReader;
+use cicasm::link;
+use cicasm::object_file::ObjectFile;
+use cicasm::verify_checksums;
+use cicasm::warnings::WarningKind;
 use std::path::Path;
 
 const BANK_SIZE: usize = 16384;
 
+// assembly options that report up to `limit` errors
+fn max_errors(limit: usize) -> AssemblyOptions {
+    AssemblyOptions {
+        max_errors: limit,
+        ..AssemblyOptions::default()
+    }
+}
+
 #[test]
 fn test_nop() {
     let mut reader = MockFileReader::default();
@@ -27,7 +47,9 @@ fn test_nop() {
 
     let entry_path = Path::new("test.asm");
 
-    let result = assemble(&entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(&entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0], 0x00);
@@ -41,7 +63,9 @@ fn test_ldi() {
 
     let entry_path = Path::new("test.asm");
 
-    let result = assemble(&entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(&entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0], 0x02); // LDI R1
@@ -57,7 +81,9 @@ fn test_jump() {
 
     let entry_path = Path::new("test.asm");
 
-    let result = assemble(&entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(&entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0], 0x00); // NOP
@@ -77,7 +103,9 @@ fn test_define() {
 
     let entry_path = Path::new("test.asm");
 
-    let result = assemble(&entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(&entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0], 0x01); // LDI r0
@@ -99,7 +127,9 @@ fn test_org() {
 
     let entry_path = Path::new("test.asm");
 
-    let result = assemble(&entry_path, 0x7FFF, None, None, &reader).unwrap();
+    let result = assemble(&entry_path, 0x7FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 3);
     assert_eq!(result[0x0000], 0x00); // Padding
@@ -158,7 +188,9 @@ fn test_include() {
 
     let entry_path = Path::new("test.asm");
 
-    let result = assemble(&entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(&entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x01); // LDI r0
@@ -199,7 +231,9 @@ fn test_header() {
 
     let entry_path = Path::new("test.asm");
 
-    let result = assemble(entry_path, 0x7FFF, None, Some(0x0000), &reader).unwrap();
+    let result = assemble(entry_path, 0x7FFF, None, Some(0x0000), &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
 
@@ -233,12 +267,75 @@ fn test_header() {
     // interrupt_mode = 1, mapper = 1 (01)
     assert_eq!(result[0x28], 0b10100000);
 
-    // The rest of the header should be 0 up to the checksums, which are not yet implemented in this test
-    for i in 0x29..0x60 {
+    // Header checksum, 8 bit sum of bytes 0x00-0x28
+    let header_sum = result[0x00..0x29]
+        .iter()
+        .fold(0u8, |sum, b| sum.wrapping_add(*b));
+    assert_eq!(result[0x29], header_sum);
+
+    // Global ROM checksum, 16 bit sum of every byte except the checksum itself
+    let global_sum = result
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 0x2A && *i != 0x2B)
+        .fold(0u16, |sum, (_, b)| sum.wrapping_add(*b as u16));
+    assert_eq!(u16::from_le_bytes([result[0x2A], result[0x2B]]), global_sum);
+
+    // The rest of the header should be 0
+    for i in 0x2C..0x60 {
         assert_eq!(result[i], 0x00);
     }
 }
 
+#[test]
+fn test_header_checksums_verify() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .header_start
+            .boot_anim "CICA"
+            .title "Test-Game"
+            .developer "Test-Dev"
+            .version 1
+        .header_end
+        .org 0x0080
+        LDI r0, 0x1234
+        .bank 1
+        .byte 0x12, 0x34
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+
+    let mut result = assemble(&entry_path, 0x7FFF, None, Some(0x0000), &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert!(verify_checksums(&result, 0x0000).is_ok());
+
+    // corrupt a byte in the switchable bank
+    result[0x4001] ^= 0xFF;
+    assert!(verify_checksums(&result, 0x0000).is_err());
+}
+
+#[test]
+fn test_no_checksums_without_header() {
+    let mut reader = MockFileReader::default();
+    reader.add_file("test.asm", "NOP\n");
+
+    let entry_path = Path::new("test.asm");
+
+    let result = assemble(&entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    // boot roms have no header, so nothing is patched
+    assert_eq!(result[0x29], 0xFF);
+    assert_eq!(result[0x2A], 0xFF);
+    assert_eq!(result[0x2B], 0xFF);
+}
+
 #[test]
 fn test_interrupt_table() {
     let mut reader = MockFileReader::default();
@@ -294,7 +391,9 @@ fn test_interrupt_table() {
 
     let entry_path = Path::new("test.asm");
 
-    let result = assemble(entry_path, 0x7FFF, Some(0x0060), None, &reader).unwrap();
+    let result = assemble(entry_path, 0x7FFF, Some(0x0060), None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
 
@@ -319,7 +418,7 @@ fn test_section_with_size() {
     reader.add_file(
         "test.asm",
         r#"
-        .section size=16
+        .section size=16 paddr=0x0000
         NOP
         NOP
         .section_end
@@ -328,7 +427,9 @@ fn test_section_with_size() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     // First two NOPs inside section
@@ -359,7 +460,9 @@ fn test_section_with_vaddr() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x7FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x7FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     // Section starts at bank 1, physical address 0x4000
@@ -386,7 +489,9 @@ fn test_section_with_paddr() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     // Padding before section
@@ -416,7 +521,9 @@ fn test_section_with_size_and_vaddr() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x7FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x7FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     // Section at bank 1, physical 0x4000, logical 0x4200
@@ -445,7 +552,7 @@ fn test_section_overflow() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader);
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader);
 
     assert!(
         result.is_err(),
@@ -473,7 +580,9 @@ fn test_section_with_labels() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x7FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x7FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     // Section NOPs
@@ -493,12 +602,12 @@ fn test_multiple_sections() {
     reader.add_file(
         "test.asm",
         r#"
-        .section size=8
+        .section size=8 paddr=0x0000
         NOP
         NOP
         .section_end
 
-        .section size=8
+        .section size=8 paddr=0x0008
         NOP
         NOP
         .section_end
@@ -508,7 +617,9 @@ fn test_multiple_sections() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     // First section
@@ -544,7 +655,9 @@ fn test_section_relative_jump() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x7FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x7FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     // Section at bank 1, physical 0x4000
@@ -569,7 +682,9 @@ fn test_align_2_already_aligned() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -591,7 +706,9 @@ fn test_align_2_needs_padding() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -613,7 +730,9 @@ fn test_align_4() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -638,7 +757,9 @@ fn test_align_8() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -665,7 +786,9 @@ fn test_align_with_labels() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -691,7 +814,9 @@ fn test_align_1_no_op() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -712,7 +837,9 @@ fn test_align_with_data() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x01); // .byte 0x01
@@ -744,7 +871,9 @@ fn test_multiple_aligns() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -776,7 +905,9 @@ fn test_align_with_org() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0101], 0x00); // NOP at 0x0101
@@ -800,7 +931,9 @@ fn test_align_non_power_of_2() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -826,7 +959,9 @@ fn test_align_in_bank_1() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x7FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x7FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     // Bank 1 starts at physical address 0x4000
@@ -847,7 +982,7 @@ fn test_section_with_align_4() {
         "test.asm",
         r#"
         NOP
-        .section align=4
+        .section align=4 paddr=0x0001
         NOP
         NOP
         .section_end
@@ -856,7 +991,9 @@ fn test_section_with_align_4() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP before section
@@ -886,7 +1023,9 @@ fn test_section_with_align_already_aligned() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -914,7 +1053,9 @@ fn test_section_with_size_and_align_padding_counts() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP before section
@@ -951,7 +1092,9 @@ fn test_section_with_align_8() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -979,7 +1122,9 @@ fn test_section_with_align_2() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -1004,7 +1149,9 @@ fn test_section_with_vaddr_and_align() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x7FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x7FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     // Bank 1 starts at physical 0x4000
@@ -1025,7 +1172,7 @@ fn test_section_align_with_labels() {
         "test.asm",
         r#"
         NOP
-        .section align=4
+        .section align=4 paddr=0x0001
         LABEL1:
         NOP
         LABEL2:
@@ -1037,7 +1184,9 @@ fn test_section_align_with_labels() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -1075,7 +1224,9 @@ fn test_multiple_sections_with_align() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -1107,7 +1258,9 @@ fn test_section_align_with_data() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x01); // .byte 0x01
@@ -1128,7 +1281,7 @@ fn test_section_size_align_overflow() {
         "test.asm",
         r#"
         NOP
-        .section size=8 align=4
+        .section size=8 align=4 paddr=0x0001
         NOP
         NOP
         NOP
@@ -1140,7 +1293,7 @@ fn test_section_size_align_overflow() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader);
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader);
 
     // Should fail because:
     // - 3 bytes of alignment padding (from 0x0001 to 0x0004)
@@ -1166,7 +1319,9 @@ fn test_section_align_1_no_padding() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
     assert_eq!(result[0x0000], 0x00); // NOP
@@ -1194,7 +1349,9 @@ fn test_incbin() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
 
@@ -1240,7 +1397,9 @@ fn test_incbin_with_labels() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
 
@@ -1277,7 +1436,9 @@ fn test_incbin_empty_file() {
     );
 
     let entry_path = Path::new("test.asm");
-    let result = assemble(entry_path, 0x3FFF, None, None, &reader).unwrap();
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
 
     assert_eq!(result.len(), BANK_SIZE * 2);
 
@@ -1286,3 +1447,1658 @@ fn test_incbin_empty_file() {
     assert_eq!(result[0x0001], 0x00);
     assert_eq!(result[0x0002], 0xFF); // Padding
 }
+
+#[test]
+fn test_define_label_difference() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .define SPRITE_DATA_SIZE sprite_data_end - sprite_data
+        LDI r1, SPRITE_DATA_SIZE
+        LDI r2, sprite_data
+        sprite_data:
+        .byte 1, 2, 3, 4
+        sprite_data_end:
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result[0x0000], 0x02); // LDI r1
+    assert_eq!(result[0x0001], 0x04); // low byte of SPRITE_DATA_SIZE
+    assert_eq!(result[0x0002], 0x00); // high byte of SPRITE_DATA_SIZE
+    assert_eq!(result[0x0003], 0x03); // LDI r2
+    assert_eq!(result[0x0004], 0x06); // low byte of sprite_data
+    assert_eq!(result[0x0005], 0x00); // high byte of sprite_data
+}
+
+#[test]
+fn test_expression_constant_flags() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        ".define FLAG 0x01\n.define MASK FLAG << 4\nLDI r1, (1 << 3) | FLAG\nLDI r2, MASK + 2 * 3\n",
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result[0x0000], 0x02); // LDI r1
+    assert_eq!(result[0x0001], 0x09);
+    assert_eq!(result[0x0002], 0x00);
+    assert_eq!(result[0x0003], 0x03); // LDI r2
+    assert_eq!(result[0x0004], 0x16);
+    assert_eq!(result[0x0005], 0x00);
+}
+
+#[test]
+fn test_expression_low_high_bank() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .bank 0
+        LDI.b r1, <target
+        LDI.b r2, >target
+        LDI.b r3, bank(target)
+        .bank 2
+        .org 0x4234
+        target:
+        NOP
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x7FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result[0x0000], 0xFD); // LDI.b r1
+    assert_eq!(result[0x0001], 0xA1);
+    assert_eq!(result[0x0002], 0x34); // low byte of target
+    assert_eq!(result[0x0005], 0x42); // high byte of target
+    assert_eq!(result[0x0008], 0x02); // bank of target
+}
+
+#[test]
+fn test_expression_data_directives() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        start:
+        .word end - start, end + 1
+        .byte <end, >end, end - start
+        end:
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result[0x0000], 0x07); // end - start
+    assert_eq!(result[0x0001], 0x00);
+    assert_eq!(result[0x0002], 0x08); // end + 1
+    assert_eq!(result[0x0003], 0x00);
+    assert_eq!(result[0x0004], 0x07); // <end
+    assert_eq!(result[0x0005], 0x00); // >end
+    assert_eq!(result[0x0006], 0x07); // end - start
+}
+
+#[test]
+fn test_expression_relative_jump() {
+    let mut reader = MockFileReader::default();
+    reader.add_file("test.asm", "start:\nNOP\nNOP\nJR start + 1\n");
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result[0x0002], 0x5A); // JR
+    assert_eq!(result[0x0003], 0xFF); // -1
+}
+
+#[test]
+fn test_expression_out_of_range() {
+    let mut reader = MockFileReader::default();
+    reader.add_file("test.asm", "LDI.b r1, data + 0x100\ndata:\nNOP\n");
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader);
+    assert!(result.is_err());
+
+    let mut reader = MockFileReader::default();
+    reader.add_file("test.asm", ".define BIG 0x100 * 0x100\nLDI r1, BIG\n");
+
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_expression_parenthesized_immediate() {
+    let entry_path = Path::new("test.asm");
+
+    for source in [
+        ".define BASE 0x10\nLDI r1, (BASE + 4)\n",
+        "LDI r1, (2+3)\n",
+        "ADDI r1, (2)\n",
+    ] {
+        let mut reader = MockFileReader::default();
+        reader.add_file("test.asm", source);
+
+        let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader);
+        let message = format!("{:#}", result.unwrap_err());
+        assert!(
+            message.contains(
+                "Parenthesized operand is a memory access, drop the parentheses for an immediate."
+            ),
+            "{}",
+            message
+        );
+    }
+}
+
+#[test]
+fn test_define_circular() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        ".define A B + 1\n.define B A + 1\nstart:\nLDI r1, A\n",
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_macro_expansion() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .macro store_const value, addr
+            LDI r1, \value
+            ST (\addr), r1
+        .endm
+        store_const 0x1234, 0x2000
+        store_const 7 * 3, (0x2000 + 2)
+        "#,
+    );
+    reader.add_file(
+        "expected.asm",
+        "LDI r1, 0x1234\nST (0x2000), r1\nLDI r1, 21\nST (0x2002), r1\n",
+    );
+
+    let result = assemble(Path::new("test.asm"), 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+    let expected = assemble(Path::new("expected.asm"), 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_macro_unique_labels() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .macro delay count
+            LDI r0, \count
+        wait\@:
+            DEC r0
+            JRNZ wait\@
+        .endm
+        delay 3
+        delay 5
+        "#,
+    );
+    reader.add_file(
+        "expected.asm",
+        "LDI r0, 3\nwait1:\nDEC r0\nJRNZ wait1\nLDI r0, 5\nwait2:\nDEC r0\nJRNZ wait2\n",
+    );
+
+    let result = assemble(Path::new("test.asm"), 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+    let expected = assemble(Path::new("expected.asm"), 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_macro_from_include() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "macros.asm",
+        ".macro clear reg\nLDI \\reg, 0\n.endm\n.macro clear_all\nclear r1\nclear r2\n.endm\n",
+    );
+    reader.add_file("test.asm", ".include \"macros.asm\"\nclear_all\n");
+    reader.add_file("expected.asm", "LDI r1, 0\nLDI r2, 0\n");
+
+    let result = assemble(Path::new("test.asm"), 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+    let expected = assemble(Path::new("expected.asm"), 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_macro_error_reports_invocation() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        ".macro go target\nNOP\nJMP \\target\n.endm\nstart:\ngo missing\n",
+    );
+
+    let result = assemble(Path::new("test.asm"), 0x3FFF, None, None, &[], &reader);
+    let message = format!("{:#}", result.unwrap_err());
+
+    assert!(message.contains("on line 3"), "{}", message);
+    assert!(
+        message.contains("in expansion of macro \"go\" invoked on line 6"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_rept_error_reports_iteration() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        ".rept 4, i\n    .byte \\i\n    .word table + \\i / (2 - \\i)\n.endr\ntable:\n",
+    );
+
+    let result = assemble(Path::new("test.asm"), 0x3FFF, None, None, &[], &reader);
+    let message = format!("{:#}", result.unwrap_err());
+
+    assert!(message.contains("on line 3"), "{}", message);
+    assert!(
+        message.contains("in iteration 2 of .rept on line 1"),
+        "{}",
+        message
+    );
+    assert!(!message.contains("iteration 1 "), "{}", message);
+}
+
+#[test]
+fn test_conditional_if_else() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .define VERSION 2
+        .if VERSION == 1
+            LDI r1, 1
+        .elif VERSION == 2
+            LDI r1, 2
+        .else
+            LDI r1, 3
+        .endif
+        .if VERSION > 5
+        skipped:
+            NOP
+        .endif
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result[0], 0x02); // LDI R1
+    assert_eq!(result[1], 0x02);
+    assert_eq!(result[2], 0x00);
+    assert_eq!(result[3], 0xFF); // Padding, the second block is not assembled
+}
+
+#[test]
+fn test_conditional_ifdef_command_line_define() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .ifndef DEBUG
+        .define DEBUG 0
+        .endif
+        .ifdef BOOT
+            LDI r1, 0x1111
+        .else
+            LDI r1, 0x2222
+        .endif
+        .if DEBUG
+            NOP
+        .endif
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+    assert_eq!(result[0..4], [0x02, 0x22, 0x22, 0xFF]);
+
+    let defines = [("BOOT".to_string(), 1), ("DEBUG".to_string(), 1)];
+    let result = assemble(entry_path, 0x3FFF, None, None, &defines, &reader)
+        .unwrap()
+        .rom;
+    assert_eq!(result[0..5], [0x02, 0x11, 0x11, 0x00, 0xFF]);
+}
+
+#[test]
+fn test_conditional_nested() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .define A 0
+        .define B A + 1
+        .if A
+            .if B
+                LDI r1, 1
+            .else
+                LDI r1, 2
+            .endif
+        .else
+            .if B
+                LDI r1, 3
+            .else
+                LDI r1, 4
+            .endif
+        .endif
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result[0..4], [0x02, 0x03, 0x00, 0xFF]);
+}
+
+#[test]
+fn test_conditional_errors() {
+    let entry_path = Path::new("test.asm");
+    let sources = [
+        ".if 1\nNOP\n",                     // missing .endif
+        "NOP\n.endif\n",                    // .endif without .if
+        ".else\n",                          // .else without .if
+        ".if 1\n.else\n.else\n.endif\n",    // duplicate .else
+        ".if 1\n.else\n.elif 1\n.endif\n",  // .elif after .else
+        ".if later\n.endif\nlater:\nNOP\n", // condition uses a label
+    ];
+
+    for source in sources {
+        let mut reader = MockFileReader::default();
+        reader.add_file("test.asm", source);
+        let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader);
+        assert!(result.is_err(), "{}", source);
+    }
+}
+
+#[test]
+fn test_local_labels() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        first:
+            NOP
+        .loop:
+            JR .loop
+        second:
+            NOP
+            NOP
+        .loop:
+            JR .loop
+            .word first.loop, second.loop, .loop
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result[0x0001], 0x5A); // JR first.loop
+    assert_eq!(result[0x0002], 0x00);
+    assert_eq!(result[0x0005], 0x5A); // JR second.loop
+    assert_eq!(result[0x0006], 0x00);
+    assert_eq!(result[0x0007..0x000D], [0x01, 0x00, 0x05, 0x00, 0x05, 0x00]);
+}
+
+#[test]
+fn test_local_labels_after_macro() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .macro delay count
+            LDI r0, \count
+        wait\@:
+            DEC r0
+            JRNZ wait\@
+        .endm
+        start:
+            delay 3
+        .loop:
+            JR .loop
+            .word start.loop
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result[0x0007..0x0009], [0x5A, 0x00]); // JR start.loop
+    assert_eq!(result[0x0009..0x000B], [0x07, 0x00]);
+}
+
+#[test]
+fn test_anonymous_labels() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        -:
+            NOP
+            JR +
+            JR -
+        +:
+            JR ++
+            NOP
+        ++:
+            JR -
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(result[0x0001..0x0003], [0x5A, 0x04]); // JR + (0x0005)
+    assert_eq!(result[0x0003..0x0005], [0x5A, 0xFD]); // JR - (0x0000)
+    assert_eq!(result[0x0005..0x0007], [0x5A, 0x03]); // JR ++ (0x0008)
+    assert_eq!(result[0x0008..0x000A], [0x5A, 0xF8]); // JR - (0x0000)
+}
+
+#[test]
+fn test_local_label_errors() {
+    let entry_path = Path::new("test.asm");
+    let sources = [
+        ".loop:\nNOP\n",                         // no global label before it
+        "start:\nJR .missing\n",                 // undefined local label
+        "start:\n.loop:\nNOP\n.loop:\nNOP\n",    // duplicate in the same scope
+        "JR -\n-:\nNOP\n",                       // no "-" label before the reference
+        "+:\nJR +\n",                            // no "+" label after the reference
+        "a:\n.far:\n.org 0x200\nb:\nJR a.far\n", // too far for a relative jump
+    ];
+
+    for source in sources {
+        let mut reader = MockFileReader::default();
+        reader.add_file("test.asm", source);
+        let result = assemble(entry_path, 0x3FFF, None, None, &[], &reader);
+        assert!(result.is_err(), "{}", source);
+    }
+}
+
+#[test]
+fn test_symbol_file() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .define WIDTH 160
+        .define DONE_PTR done + 2
+        start:
+            LDI R1, WIDTH
+        .loop:
+            JR .loop
+        .section name="Data" align=16
+        table:
+            .byte 1, 2, 3
+        .section_end
+        done:
+            NOP
+        .bank 2
+        .org 0x4000
+        far:
+            NOP
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let output =
+        assemble_with_options(entry_path, 0x7FFF, None, None, &[], &max_errors(0), &reader)
+            .unwrap();
+    let symbols = output.symbol_file();
+
+    let labels: Vec<(&str, u32, u32)> = symbols
+        .labels
+        .iter()
+        .map(|l| (l.name.as_str(), l.bank, l.address))
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            ("start", 0, 0x0000),
+            ("start.loop", 0, 0x0003),
+            ("done", 0, 0x0005),
+            ("table", 1, 0x4000),
+            ("far", 2, 0x4000),
+        ]
+    );
+
+    let constants: Vec<(&str, i32)> = symbols
+        .constants
+        .iter()
+        .map(|c| (c.name.as_str(), c.value))
+        .collect();
+    assert_eq!(constants, [("DONE_PTR", 0x07), ("WIDTH", 160)]);
+
+    assert_eq!(symbols.sections.len(), 1);
+    let section = &symbols.sections[0];
+    assert_eq!(section.name.as_deref(), Some("Data"));
+    assert_eq!(
+        (section.bank, section.start, section.end),
+        (1, 0x4000, 0x4002)
+    );
+    assert_eq!(section.size, 3);
+
+    assert!(symbols.to_sym().contains("\n02:4000 far\n"));
+}
+
+#[test]
+fn test_symbol_file_anonymous_labels() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        start:
+        -:
+            JR +
+        +:
+            JR -
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let output =
+        assemble_with_options(entry_path, 0x3FFF, None, None, &[], &max_errors(0), &reader)
+            .unwrap();
+    let symbols = output.symbol_file();
+
+    // anonymous labels have no name to write, only start is listed
+    let names: Vec<&str> = symbols.labels.iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, ["start"]);
+    assert_eq!(symbols.to_sym().matches('#').count(), 0);
+    assert!(!symbols.to_json().unwrap().contains('#'));
+}
+
+#[test]
+fn test_assembly_output() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        ".define WIDTH 160
+start:
+    LDI R1, WIDTH
+.org 0x0010
+.section name=\"Data\" vaddr=0x0010
+table:
+    .byte 1, 2
+.section_end
+",
+    );
+
+    let entry_path = Path::new("test.asm");
+    let output = assemble(entry_path, 0x3FFF, None, None, &[], &reader).unwrap();
+
+    assert_eq!(
+        output.symbols["table"],
+        Symbol {
+            logical_address: 0x0010,
+            bank: 0
+        }
+    );
+    assert_eq!(output.constants["WIDTH"], 160);
+
+    assert_eq!(output.sections.len(), 1);
+    assert_eq!(output.sections[0].name.as_deref(), Some("Data"));
+    assert_eq!(output.sections[0].logical_addr, 0x0010);
+    assert_eq!(output.sections[0].size, 2);
+
+    let addresses: Vec<(usize, u32)> = output
+        .line_addresses
+        .iter()
+        .filter(|((file, _), _)| file == Path::new("test.asm"))
+        .map(|((_, line), symbol)| (*line, symbol.logical_address))
+        .collect();
+    assert!(addresses.contains(&(3, 0x0000)), "{:?}", addresses);
+    assert!(addresses.contains(&(7, 0x0010)), "{:?}", addresses);
+
+    // errors are typed, so callers can match on them
+    reader.add_file("test.asm", "    LDI R1,\n");
+    let err = assemble(entry_path, 0x3FFF, None, None, &[], &reader).unwrap_err();
+    assert!(matches!(err, AssemblyError::PestError(_)), "{:?}", err);
+
+    let err = assemble(Path::new("missing.asm"), 0x3FFF, None, None, &[], &reader).unwrap_err();
+    assert!(matches!(err, AssemblyError::FileError { .. }), "{:?}", err);
+}
+
+#[test]
+fn test_listing() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        ".macro twice r\n    DEC \\r\n    DEC \\r\n.endm\nstart:\n    LDI R1, 0x1234\n    twice r0\n.include \"inc.asm\"\n.org 0x0010\n    .byte 1, 2\n",
+    );
+    reader.add_file("inc.asm", "helper:\n    NOP ; included\n");
+
+    let entry_path = Path::new("test.asm");
+    let listing =
+        assemble_with_options(entry_path, 0x3FFF, None, None, &[], &max_errors(0), &reader)
+            .unwrap()
+            .listing;
+
+    let lines: Vec<(&str, usize, u32, &[u8], &str)> = listing
+        .lines
+        .iter()
+        .map(|l| {
+            (
+                l.file.to_str().unwrap(),
+                l.line_number,
+                l.physical_addr,
+                l.bytes.as_slice(),
+                l.source.as_str(),
+            )
+        })
+        .collect();
+
+    assert_eq!(lines[0], ("test.asm", 5, 0x0000, &[][..], "start:"));
+    assert_eq!(
+        lines[1],
+        (
+            "test.asm",
+            6,
+            0x0000,
+            &[0x02, 0x34, 0x12][..],
+            "    LDI R1, 0x1234"
+        )
+    );
+    assert_eq!(
+        lines[2],
+        ("test.asm", 2, 0x0003, &[0xFD, 0xA8][..], "    DEC r0")
+    );
+    assert_eq!(listing.lines[2].macro_depth, 1);
+    assert_eq!(
+        lines[5],
+        ("inc.asm", 2, 0x0007, &[0x00][..], "    NOP ; included")
+    );
+    assert_eq!(
+        lines[6],
+        ("test.asm", 9, 0x0008, &[0x00; 8][..], ".org 0x0010")
+    );
+    assert_eq!(
+        lines[7],
+        ("test.asm", 10, 0x0010, &[0x01, 0x02][..], "    .byte 1, 2")
+    );
+}
+
+#[test]
+fn test_report_all_errors() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        "start:\n    JMP nowhere\n    LDI R1, missing\n    JR far_away\n.org 0x0300\nfar_away:\n    NOP\n",
+    );
+
+    let entry_path = Path::new("test.asm");
+    let result =
+        assemble_with_options(entry_path, 0x3FFF, None, None, &[], &max_errors(0), &reader);
+    let error = result.unwrap_err();
+    let AssemblyError::MultipleErrors {
+        errors,
+        limit_reached,
+    } = &error
+    else {
+        panic!("expected multiple errors, found: {:#}", error);
+    };
+
+    assert!(!limit_reached);
+    let lines: Vec<usize> = errors
+        .iter()
+        .map(|e| match e {
+            AssemblyError::SourceError { location, .. } => location.line,
+            _ => panic!("unexpected error: {}", e),
+        })
+        .collect();
+    assert_eq!(lines, [2, 3, 4]);
+
+    let result =
+        assemble_with_options(entry_path, 0x3FFF, None, None, &[], &max_errors(2), &reader);
+    let message = format!("{:#}", result.unwrap_err());
+    assert!(message.contains("Undefined label: missing"), "{}", message);
+    assert!(!message.contains("far_away"), "{}", message);
+    assert!(
+        message.contains("Stopped after reaching the limit of 2 errors."),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_error_diagnostics() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        "start:\n    NOP\n.include \"inc.asm\"\n    JMP nowhere\n",
+    );
+    reader.add_file("inc.asm", "helper:\n    LDI R1, missing + 1\n");
+
+    let result = assemble(Path::new("test.asm"), 0x3FFF, None, None, &[], &reader);
+    let rendered = diagnostics::render(&result.unwrap_err());
+
+    assert_eq!(
+        rendered,
+        "error: Undefined symbol in expression: missing\n\
+         \x20--> inc.asm:2:13\n\
+         \x20 |\n\
+         2 |     LDI R1, missing + 1\n\
+         \x20 |             ^^^^^^^^^^^\n\
+         \n\
+         error: Undefined label: nowhere\n\
+         \x20--> test.asm:4:9\n\
+         \x20 |\n\
+         4 |     JMP nowhere\n\
+         \x20 |         ^^^^^^^\n\
+         \n\
+         error: aborting due to 2 errors"
+    );
+}
+
+#[test]
+fn test_syntax_error_diagnostics() {
+    let mut reader = MockFileReader::default();
+    reader.add_file("s7w.asm", "start:\n    NOP\nldi r1, ,\n");
+
+    let result = assemble(Path::new("s7w.asm"), 0x3FFF, None, None, &[], &reader);
+    let rendered = diagnostics::render_error(&result.unwrap_err().into());
+
+    // the pest error is only shown once, not again as a note
+    assert_eq!(
+        rendered,
+        "error: syntax error\n\
+         \x20--> s7w.asm:3:9\n\
+         \x20 |\n\
+         3 | ldi r1, ,\n\
+         \x20 |         ^---\n\
+         \x20 |\n\
+         \x20 = expected operand"
+    );
+}
+
+#[test]
+fn test_error_diagnostics_underline_operand() {
+    let mut reader = MockFileReader::default();
+    reader.add_file("jump.asm", "start:\n    JR r1\n");
+    reader.add_file("data.asm", "start:\n    .word start, nowhere\n");
+
+    // the message does not name the operand, it is underlined from the index in the error
+    let result = assemble(Path::new("jump.asm"), 0x3FFF, None, None, &[], &reader);
+    assert_eq!(
+        diagnostics::render(&result.unwrap_err()),
+        "error: Expected an address or label.\n\
+         \x20--> jump.asm:2:8\n\
+         \x20 |\n\
+         2 |     JR r1\n\
+         \x20 |        ^^"
+    );
+
+    let result = assemble(Path::new("data.asm"), 0x3FFF, None, None, &[], &reader);
+    assert_eq!(
+        diagnostics::render(&result.unwrap_err()),
+        "error: Undefined label: nowhere\n\
+         \x20--> data.asm:2:18\n\
+         \x20 |\n\
+         2 |     .word start, nowhere\n\
+         \x20 |                  ^^^^^^^"
+    );
+}
+
+#[test]
+fn test_warnings() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        "start:\n    LD R1, (0x8001)\n    ST (0x1000), R1\n    .byte 0x1ff, -1\nunused:\n    JR start\n",
+    );
+
+    let entry_path = Path::new("test.asm");
+    let AssemblyOutput { rom, warnings, .. } = assemble_with_options(
+        entry_path,
+        0x3FFF,
+        None,
+        None,
+        &[],
+        &AssemblyOptions::default(),
+        &reader,
+    )
+    .unwrap();
+
+    // truncated and negative values are still emitted
+    assert_eq!(rom[6], 0xFF);
+    assert_eq!(rom[7], 0xFF);
+
+    let found: Vec<(WarningKind, usize)> =
+        warnings.iter().map(|w| (w.kind, w.location.line)).collect();
+    assert_eq!(
+        found,
+        [
+            (WarningKind::OddAddress, 2),
+            (WarningKind::RomWrite, 3),
+            (WarningKind::ByteTruncation, 4),
+        ]
+    );
+
+    let mut options = AssemblyOptions::default();
+    options.warnings.apply_flag("unused-label").unwrap();
+    options.warnings.apply_flag("no-rom-write").unwrap();
+    let warnings = assemble_with_options(entry_path, 0x3FFF, None, None, &[], &options, &reader)
+        .unwrap()
+        .warnings;
+    let kinds: Vec<WarningKind> = warnings.iter().map(|w| w.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            WarningKind::OddAddress,
+            WarningKind::ByteTruncation,
+            WarningKind::UnusedLabel,
+        ]
+    );
+
+    options.warnings.apply_flag("error").unwrap();
+    let result = assemble_with_options(entry_path, 0x3FFF, None, None, &[], &options, &reader);
+    let message = format!("{:#}", result.unwrap_err());
+    assert!(
+        message.contains("Label unused is never referenced [-Werror=unused-label]"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_define_byte_truncation() {
+    let mut reader = MockFileReader::default();
+    reader.add_file("test.asm", ".define BIG 0x1FF\n    .byte BIG\n");
+
+    let AssemblyOutput { rom, warnings, .. } =
+        assemble(Path::new("test.asm"), 0x3FFF, None, None, &[], &reader).unwrap();
+
+    // constants are truncated like literal values, instead of failing the assembly
+    assert_eq!(rom[0], 0xFF);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].kind, WarningKind::ByteTruncation);
+    assert_eq!(warnings[0].location.line, 2);
+    assert_eq!(
+        warnings[0].reason,
+        "Value 511 does not fit in a byte, truncated to 0xff"
+    );
+}
+
+#[test]
+fn test_string_data() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        message:
+            .asciz "Hi!\n"
+        .charmap font
+        tiles:
+            .ascii "Hi!"
+        .charmap '\n', 0xFF
+            .string "A\n"
+        .charmap ascii
+            LDI R1, 'A'
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let rom = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(&rom[0..5], b"Hi!\n\0");
+    assert_eq!(&rom[5..8], [0x28, 0x49, 0x01]);
+    assert_eq!(&rom[8..11], [0x21, 0xFF, 0x00]);
+    assert_eq!(&rom[11..14], [0x02, 0x41, 0x00]);
+
+    reader.add_file("bad.asm", ".charmap font\n.ascii \"\\t\"\n");
+    let result = assemble(Path::new("bad.asm"), 0x3FFF, None, None, &[], &reader);
+    let message = format!("{:#}", result.unwrap_err());
+    assert!(
+        message.contains("Character '\\t' has no value in the current .charmap"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_fill_and_pad_byte() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .define COUNT 3
+            NOP
+            .fill COUNT, 0xAA
+            .ds 2
+        .padbyte 0xFF
+            .res 1
+            .align 8
+            .org 0x0010
+            NOP
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let rom = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(&rom[0..4], [0x00, 0xAA, 0xAA, 0xAA]);
+    assert_eq!(&rom[4..6], [0x00, 0x00]);
+    assert_eq!(&rom[6..0x10], [0xFF; 10]);
+    assert_eq!(rom[0x10], 0x00);
+    assert!(rom[0x11..].iter().all(|b| *b == 0xFF));
+
+    // the command line padding byte applies until the first .padbyte, including the last bank
+    reader.add_file("zero.asm", "    NOP\n    .ds 2\n");
+    let options = AssemblyOptions {
+        pad_byte: Some(0x00),
+        ..AssemblyOptions::default()
+    };
+    let rom = assemble_with_options(
+        Path::new("zero.asm"),
+        0x3FFF,
+        None,
+        None,
+        &[],
+        &options,
+        &reader,
+    )
+    .unwrap()
+    .rom;
+    assert!(rom.iter().all(|b| *b == 0x00));
+}
+
+#[test]
+fn test_nobits_sections() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .section name="vars" vaddr=0xB000 nobits
+        player_x:
+            .ds 2
+        flags:
+            .res 1
+            .align 2
+        buffer:
+            .fill 16
+        .section_end
+        start:
+            LDI R1, player_x
+            NOP
+        .section name="more_vars" nobits
+        score:
+            .ds 2
+        .section_end
+        .section name="hram" vaddr=0xFE00 size=0x10 nobits
+        fast:
+            .ds 4
+        .section_end
+        end:
+            NOP
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let output =
+        assemble_with_options(entry_path, 0x3FFF, None, None, &[], &max_errors(0), &reader)
+            .unwrap();
+    let symbols = output.symbol_file();
+    let AssemblyOutput { rom, listing, .. } = output;
+
+    // the sections emit nothing, the code is placed as if they were not there
+    assert_eq!(&rom[0..5], [0x02, 0x00, 0xB0, 0x00, 0x00]);
+    assert!(rom[5..].iter().all(|b| *b == 0xFF));
+
+    let labels: Vec<(&str, u32)> = symbols
+        .labels
+        .iter()
+        .map(|l| (l.name.as_str(), l.address))
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            ("start", 0x0000),
+            ("end", 0x0004),
+            ("player_x", 0xB000),
+            ("flags", 0xB002),
+            ("buffer", 0xB004),
+            ("score", 0xB014),
+            ("fast", 0xFE00),
+        ]
+    );
+
+    let sections: Vec<(&str, u32, u32)> = symbols
+        .sections
+        .iter()
+        .map(|s| (s.name.as_deref().unwrap(), s.start, s.size))
+        .collect();
+    assert_eq!(
+        sections,
+        [
+            ("vars", 0xB000, 0x14),
+            ("more_vars", 0xB014, 2),
+            ("hram", 0xFE00, 0x10)
+        ]
+    );
+    assert!(listing.lines.iter().all(|l| l.bytes.len() <= 3));
+
+    // lines inside a nobits section are listed with their RAM address only
+    let rendered = listing.render();
+    assert!(rendered.contains("\n   ram  --:b000                                4 "));
+    assert!(rendered.contains("\n   ram  --:b014                               10 "));
+    assert!(rendered.contains("\n000000  00:0000   02 00 b0                    12 "));
+
+    // sections must stay within their RAM region and cannot emit bytes
+    reader.add_file(
+        "bad.asm",
+        r#"
+        .section vaddr=0xFFFC nobits
+            .ds 8
+        .section_end
+        .section vaddr=0x4000 nobits
+        .section_end
+        .section vaddr=0xC000 nobits
+            NOP
+            .byte 1
+        .section_end
+        "#,
+    );
+    let err = assemble_with_options(
+        Path::new("bad.asm"),
+        0x3FFF,
+        None,
+        None,
+        &[],
+        &max_errors(0),
+        &reader,
+    )
+    .unwrap_err();
+    let message = format!("{:#}", err);
+    assert!(
+        message.contains("Section \"UNNAMED\" overflows HRAM (0xFE00-0xFFFF) by 4 bytes."),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("nobits section address 0x4000 is not in a RAM region"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("Instructions cannot be used in a nobits section"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains(".byte cannot be used in a nobits section"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_struct_and_enum() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .define MAX_OBJECTS 4
+        .struct Sprite
+            y     .b
+            x     .b
+            tile  .b
+            attr  .b
+        .endstruct
+        .struct Object
+            pos   .w 2
+            name  .b NAME_LENGTH
+            hp    .w
+        .endstruct
+        .define NAME_LENGTH 6
+        .enum State
+            IDLE, RUNNING
+            DEAD = 0x10
+            GONE
+        .endenum
+            LD R1, (R2, Sprite.x)
+            ST (R2, Sprite.attr), R1
+            LDI R3, Object.SIZE * MAX_OBJECTS
+            LDI R4, State.GONE
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let output =
+        assemble_with_options(entry_path, 0x3FFF, None, None, &[], &max_errors(0), &reader)
+            .unwrap();
+    let symbols = output.symbol_file();
+    let AssemblyOutput { rom, .. } = output;
+
+    let constant = |name: &str| {
+        symbols
+            .constants
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.value)
+    };
+    assert_eq!(constant("Sprite.x"), Some(1));
+    assert_eq!(constant("Sprite.SIZE"), Some(4));
+    assert_eq!(constant("Object.name"), Some(4));
+    assert_eq!(constant("Object.hp"), Some(10));
+    assert_eq!(constant("Object.SIZE"), Some(12));
+    assert_eq!(constant("State.RUNNING"), Some(1));
+    assert_eq!(constant("State.GONE"), Some(0x11));
+
+    // LDI R3, 48 and LDI R4, 0x11 follow the two indexed accesses
+    let ldi = rom
+        .windows(3)
+        .position(|w| w == [0x04, 0x30, 0x00])
+        .unwrap();
+    assert_eq!(&rom[ldi + 3..ldi + 6], [0x05, 0x11, 0x00]);
+
+    reader.add_file(
+        "dup.asm",
+        ".struct Sprite\n    x .b\n.endstruct\n.enum Sprite\n    x\n.endenum\n",
+    );
+    let result = assemble(Path::new("dup.asm"), 0x3FFF, None, None, &[], &reader);
+    let message = format!("{:#}", result.unwrap_err());
+    assert!(
+        message.contains("Duplicate constant definition: Sprite.x"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_auto_branch_relaxation() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        start:
+            JNZ.auto target
+            JMP.auto far
+            .fill 123, 0
+        target:
+            DJNZ.auto start
+            DJNZ.auto target
+            .fill 200, 0
+        far:
+            JZ.auto far
+        "#,
+    );
+
+    let entry_path = Path::new("test.asm");
+    let rom = assemble(entry_path, 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    // JNZ.auto only goes out of range once JMP.auto has grown to a JMP
+    assert_eq!(rom[0x0000..0x0003], [0x62, 0x81, 0x00]); // JNZ target
+    assert_eq!(rom[0x0003..0x0006], [0x51, 0x50, 0x01]); // JMP far
+    assert_eq!(rom[0x0081..0x0086], [0xFD, 0xAD, 0x62, 0x00, 0x00]); // DEC R5, JNZ start
+    assert_eq!(rom[0x0086..0x0088], [0x6B, 0xFB]); // DJNZ target
+    assert_eq!(rom[0x0150..0x0152], [0x69, 0x00]); // JRZ far
+
+    reader.add_file("undefined.asm", "JMP.auto nowhere\n");
+    let result = assemble(Path::new("undefined.asm"), 0x3FFF, None, None, &[], &reader);
+    let message = format!("{:#}", result.unwrap_err());
+    assert!(message.contains("Undefined label: nowhere"), "{}", message);
+}
+
+#[test]
+fn test_boot_rom_single_bank() {
+    let mut reader = MockFileReader::default();
+    reader.add_file("test.asm", "start:\nNOP\nJMP start\n");
+
+    let options = AssemblyOptions {
+        min_banks: 1,
+        ..AssemblyOptions::default()
+    };
+    let entry_path = Path::new("test.asm");
+    let rom = assemble_with_options(entry_path, 0x3FFF, None, None, &[], &options, &reader)
+        .unwrap()
+        .rom;
+
+    assert_eq!(rom.len(), BANK_SIZE);
+    assert_eq!(rom[0x0000..0x0004], [0x00, 0x51, 0x00, 0x00]);
+    assert_eq!(rom[BANK_SIZE - 1], 0xFF);
+}
+
+#[test]
+fn test_link_objects() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "main.asm",
+        r#"
+        .extern far_routine
+        .global start
+
+        .section name="Code"
+        start:
+            LDI R0, table + 2
+            CALL.far far_routine
+            JMP.auto start
+        .section_end
+
+        .section name="Data"
+        table:
+            .word 0, start, far_routine
+        .section_end
+        "#,
+    );
+    reader.add_file(
+        "far.asm",
+        r#"
+        .global far_routine
+
+        .section name="Far"
+        far_routine:
+            RET
+        .section_end
+        "#,
+    );
+
+    let options = AssemblyOptions::default();
+    let main = assemble_object(Path::new("main.asm"), &[], &options, &reader).unwrap();
+    let far = assemble_object(Path::new("far.asm"), &[], &options, &reader).unwrap();
+
+    // objects are written to disk as JSON
+    let main = ObjectFile::from_json(&main.to_json().unwrap()).unwrap();
+    assert_eq!(main.externs, ["far_routine"]);
+    assert_eq!(main.sections[0].relocations.len(), 3);
+
+    let script = "bank 0\n  Code at 0x0100\n  Data\nbank 2\n  Far\n";
+    let objects = [("main.o".to_string(), main), ("far.o".to_string(), far)];
+    let rom = link(&objects, script, 0).unwrap().rom;
+
+    assert_eq!(rom.len(), BANK_SIZE * 3);
+    // LDI R0, table + 2 with the Data section placed after the 13 bytes of Code
+    assert_eq!(rom[0x100..0x103], [0x01, 0x0F, 0x01]);
+    // CALL.far loads the bank and address of far_routine
+    assert_eq!(rom[0x103..0x109], [0x05, 0x02, 0x00, 0x06, 0x00, 0x40]);
+    // JMP.auto stays short within its section
+    assert_eq!(rom[0x10B..0x10D], [0x5A, 0xF5]);
+    assert_eq!(rom[0x10D..0x113], [0x00, 0x00, 0x00, 0x01, 0x00, 0x40]);
+    assert_eq!(rom[BANK_SIZE * 2], 0xF9);
+    assert!(verify_checksums(&rom, 0x0000).is_ok());
+}
+
+#[test]
+fn test_link_floating_sections() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "main.asm",
+        r#"
+        .extern far_routine
+
+        .section name="Code"
+        start:
+            CALL.far far_routine
+            JR start
+        .section_end
+
+        .section name="Handlers" fixed
+        handler:
+            RET
+        .section_end
+        "#,
+    );
+    reader.add_file(
+        "far.asm",
+        r#"
+        .global far_routine
+
+        .section name="Far" align=0x100
+        far_routine:
+            RET
+        .section_end
+        "#,
+    );
+
+    let options = AssemblyOptions::default();
+    let main = assemble_object(Path::new("main.asm"), &[], &options, &reader).unwrap();
+    let far = assemble_object(Path::new("far.asm"), &[], &options, &reader).unwrap();
+    assert!(main.sections[1].bank0);
+
+    // only Code is listed, Handlers is kept in bank 0 and Far is packed into bank 1
+    let script = "banks 4\nbank 0\n  Code at 0x0100\n";
+    let objects = [("main.o".to_string(), main), ("far.o".to_string(), far)];
+    let linked = link(&objects, script, 0).unwrap();
+    let rom = &linked.rom;
+
+    assert_eq!(rom.len(), BANK_SIZE * 4);
+    assert_eq!(rom[0x100..0x106], [0x05, 0x01, 0x00, 0x06, 0x00, 0x40]);
+    assert_eq!(rom[0x0000], 0xF9);
+    assert_eq!(rom[BANK_SIZE], 0xF9);
+    assert_eq!(linked.free_bytes.len(), 4);
+    assert_eq!(linked.free_bytes[1], BANK_SIZE as u32 - 1);
+    assert_eq!(linked.free_bytes[3], BANK_SIZE as u32);
+
+    // bank0 sections must stay in bank 0 when assembled into a ROM
+    reader.add_file(
+        "main.asm",
+        ".bank 1\n.section name=\"Handlers\" vaddr=0x4000 bank0\n    RET\n.section_end\n",
+    );
+    let err = assemble(Path::new("main.asm"), 0x3FFF, None, None, &[], &reader).unwrap_err();
+    assert!(format!("{:#}", err).contains(
+        "Section \"Handlers\" must be in ROM bank 0, but ends at physical address 0x4001."
+    ));
+}
+
+#[test]
+fn test_floating_sections() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        start:
+            CALL.far far_routine
+            JMP start
+        code_end:
+
+        .section name="Far" align=0x100
+        far_routine:
+            RET
+        .section_end
+
+        .section name="Handlers" fixed
+        handler:
+            RET
+        .section_end
+
+        .bank 1
+        .org 0x4000
+            .fill 0x10, 0xAA
+
+        .section name="Big"
+        big:
+            .fill 0x3FF8, 0xBB
+        .section_end
+        "#,
+    );
+
+    let output = assemble(Path::new("test.asm"), 0x7FFF, None, None, &[], &reader).unwrap();
+    let rom = &output.rom;
+    let symbol = |name: &str| output.symbols[name];
+
+    // Big is placed first and only fits in a new bank, Far fills the space left in bank 1
+    assert_eq!(rom.len(), BANK_SIZE * 3);
+    assert_eq!(
+        symbol("big"),
+        Symbol {
+            logical_address: 0x4000,
+            bank: 2
+        }
+    );
+    assert_eq!(
+        symbol("far_routine"),
+        Symbol {
+            logical_address: 0x4100,
+            bank: 1
+        }
+    );
+    assert_eq!(symbol("handler"), symbol("code_end"));
+    assert_eq!(rom[0x0000..0x0006], [0x05, 0x01, 0x00, 0x06, 0x00, 0x41]);
+    assert_eq!(rom[0x000B], 0xF9);
+    assert_eq!(rom[BANK_SIZE..BANK_SIZE + 0x10], [0xAA; 0x10]);
+    assert_eq!(rom[BANK_SIZE + 0x100], 0xF9);
+    assert_eq!(rom[BANK_SIZE * 2], 0xBB);
+    assert_eq!(rom[BANK_SIZE * 2 + 0x3FF8], 0xFF);
+    // 8 bytes of CALL.far, 3 of JMP and the RET of Handlers are used in bank 0
+    assert_eq!(
+        output.free_bytes,
+        [BANK_SIZE as u32 - 12, BANK_SIZE as u32 - 0x11, 8]
+    );
+
+    // a bank0 section has nowhere else to go
+    reader.add_file(
+        "test.asm",
+        ".section name=\"Table\" bank0\n    .fill 0x4001\n.section_end\n",
+    );
+    let err = assemble(Path::new("test.asm"), 0x7FFF, None, None, &[], &reader).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains(
+            "Section \"Table\" (16385 bytes) does not fit in the free space of ROM bank 0."
+        )
+    );
+
+    reader.add_file(
+        "test.asm",
+        ".section\n    .org 0x4000\n    NOP\n.section_end\n",
+    );
+    let err = assemble(Path::new("test.asm"), 0x7FFF, None, None, &[], &reader).unwrap_err();
+    assert!(format!("{:#}", err).contains(".org and .bank cannot be used in a floating section"));
+
+    // sections are measured before any label is placed
+    reader.add_file(
+        "test.asm",
+        "size:\n.section name=\"Buffer\"\n    .fill size\n.section_end\n",
+    );
+    let err = assemble(Path::new("test.asm"), 0x7FFF, None, None, &[], &reader).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains(
+            "Section \"Buffer\" cannot be placed, the size of its contents is not known."
+        )
+    );
+}
+
+#[test]
+fn test_object_errors() {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        r#"
+        .global missing
+        .extern start
+        NOP
+        .section name="Code"
+        start:
+            .org 0x0100
+            JMP nowhere
+            .byte start
+        .section_end
+        "#,
+    );
+
+    let err = assemble_object(Path::new("test.asm"), &[], &max_errors(0), &reader).unwrap_err();
+    let message = format!("{:#}", err);
+    assert!(message.contains("Instructions must be inside a .section in an object file."));
+    assert!(message.contains(".org cannot be used in an object file"));
+    assert!(message.contains("Label \"missing\" is declared .global but never defined."));
+    assert!(message.contains("Label \"start\" is declared .extern but defined in this file."));
+
+    // pass 2 errors are only reported once the layout is valid
+    reader.add_file(
+        "test.asm",
+        ".section name=\"Code\"\nstart:\n    JMP nowhere\n    .byte start\n.section_end\n",
+    );
+    let err = assemble_object(Path::new("test.asm"), &[], &max_errors(0), &reader).unwrap_err();
+    let message = format!("{:#}", err);
+    assert!(message.contains("Undefined label: nowhere"));
+    assert!(message.contains(
+        "Label \"start\" cannot be used in this operand of an object file, its address is only known once the object is linked."
+    ));
+}
+
+// assemble a cartridge program, or a boot ROM when min_banks is 1
+fn assemble_rom(source: &str, boot: bool) -> AssemblyOutput {
+    let mut reader = MockFileReader::default();
+    reader.add_file("test.asm", source);
+
+    let options = AssemblyOptions {
+        min_banks: if boot { 1 } else { 2 },
+        ..AssemblyOptions::default()
+    };
+    let (final_addr, table_addr, header_addr) = match boot {
+        true => (0x3FFF, Some(0x3FE0), None),
+        false => (0x7FFF, Some(0x0060), Some(0x0000)),
+    };
+    let entry_path = Path::new("test.asm");
+    assemble_with_options(
+        entry_path,
+        final_addr,
+        table_addr,
+        header_addr,
+        &[],
+        &options,
+        &reader,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_disassemble_round_trip() {
+    let output = assemble_rom(
+        r#"
+        .header_start
+            .boot_anim "CICA"
+            .title "Test-Game"
+            .developer "Test-Dev"
+            .version 1
+            .mapper 1
+            .rom_size 2
+            .interrupt_mode 1
+            .region 3
+        .header_end
+        .interrupt_table
+            .word start, start, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+        .table_end
+        start:
+            LDI R1, 0x1234
+            LD R2, (0xC000)
+        .loop:
+            DEC R1
+            JRNZ .loop
+            CALL sub
+            JMP.far far
+            LD.b R3, (R2)+
+            ST (R1, -2), R2
+            BIT (0xC002), 3
+            ADD SP, -2
+            .byte 0xFD, 0xB8
+            .fill 20, 0xFF
+        sub:
+            RET
+        .bank 1
+        .org 0x4000
+        far:
+            ADDI R1, 0x10
+            JR far
+        "#,
+        false,
+    );
+
+    let mut symbols = output.symbol_file();
+    symbols.labels.push(cicasm::symbol_file::LabelEntry {
+        name: "counter".to_string(),
+        bank: 0,
+        address: 0xC000,
+    });
+
+    let options = DisassemblyOptions::default();
+    let source = disassemble(&output.rom, Some(&symbols), &options).unwrap();
+    assert!(source.contains(".define counter 0xC000\n"));
+    assert!(source.contains("    .word start                 ; 00:0060  80 00\n"));
+    assert!(source.contains("start:\n    LDI R1, 0x1234              ; 00:0080  02 34 12\n"));
+    assert!(source.contains("    LD R2, (counter)"));
+    assert!(source.contains(".loop:\n    DEC R1"));
+    assert!(source.contains("    JRNZ start.loop"));
+    assert!(source.contains("    CALL sub"));
+    assert!(source.contains("    .byte 0xFD"));
+    assert!(source.contains("    .fill 20, 0xFF"));
+    assert!(source.contains(".bank 1\n.org 0x4000\nfar:\n    ADDI R1, 0x0010"));
+    assert!(source.contains("    JR far"));
+
+    assert_eq!(assemble_rom(&source, false).rom, output.rom);
+
+    // without symbols every address is written as a number
+    let source = disassemble(&output.rom, None, &options).unwrap();
+    assert!(source.contains("    JRNZ -2"));
+    assert_eq!(assemble_rom(&source, false).rom, output.rom);
+}
+
+#[test]
+fn test_disassemble_arbitrary_bytes() {
+    let header = assemble_rom(
+        ".header_start\n.boot_anim \"CICA\"\n.header_end\n\
+         .interrupt_table\n.word 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0\n.table_end\n",
+        false,
+    )
+    .rom;
+
+    // any bytes, decoded as instructions or not, must assemble back into the same ROM
+    let mut seed: u32 = 0x1234_5678;
+    let mut random_rom = |len: usize| -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect()
+    };
+
+    let mut rom = random_rom(BANK_SIZE * 3);
+    rom[..0x60].copy_from_slice(&header[..0x60]);
+    cicasm::assembler::patch_rom_checksums(&mut rom, 0x0000).unwrap();
+    let source = disassemble(&rom, None, &DisassemblyOptions::default()).unwrap();
+    assert_eq!(assemble_rom(&source, false).rom, rom);
+
+    let rom = random_rom(BANK_SIZE);
+    let options = DisassemblyOptions { boot_rom: true };
+    let source = disassemble(&rom, None, &options).unwrap();
+    assert_eq!(assemble_rom(&source, true).rom, rom);
+
+    let err = disassemble(&rom[..0x100], None, &options).unwrap_err();
+    assert!(format!("{}", err).contains("A boot ROM must be exactly 16384 bytes, found 256 bytes"));
+}
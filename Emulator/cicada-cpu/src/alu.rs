@@ -0,0 +1,135 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Flag arithmetic shared by the word and byte forms of each instruction. `byte` selects 8-bit
+// operation, the operands are masked and the flags are taken from bit 7 instead of bit 15.
+
+use crate::cpu::{FLAG_C, FLAG_N, FLAG_V, FLAG_Z};
+
+fn mask(byte: bool) -> u32 {
+    if byte { 0xFF } else { 0xFFFF }
+}
+
+fn sign_bit(byte: bool) -> u32 {
+    if byte { 0x80 } else { 0x8000 }
+}
+
+pub(crate) fn set_flag(flags: &mut u16, flag: u16, value: bool) {
+    if value {
+        *flags |= flag;
+    } else {
+        *flags &= !flag;
+    }
+}
+
+/// Set Z and N from a result, leaving C and V alone.
+pub(crate) fn set_zn(flags: &mut u16, result: u16, byte: bool) -> u16 {
+    let result = result as u32 & mask(byte);
+    set_flag(flags, FLAG_Z, result == 0);
+    set_flag(flags, FLAG_N, result & sign_bit(byte) != 0);
+    result as u16
+}
+
+/// a + b + carry, setting Z, N, C and V.
+pub(crate) fn add(flags: &mut u16, a: u16, b: u16, carry: bool, byte: bool) -> u16 {
+    let a = a as u32 & mask(byte);
+    let b = b as u32 & mask(byte);
+    let sum = a + b + carry as u32;
+    let result = sum & mask(byte);
+
+    set_flag(flags, FLAG_C, sum > mask(byte));
+    set_flag(
+        flags,
+        FLAG_V,
+        (a ^ result) & (b ^ result) & sign_bit(byte) != 0,
+    );
+    set_zn(flags, result as u16, byte)
+}
+
+/// a - b - borrow, setting Z, N, V and C when the subtraction borrows.
+pub(crate) fn sub(flags: &mut u16, a: u16, b: u16, borrow: bool, byte: bool) -> u16 {
+    let a = a as u32 & mask(byte);
+    let b = b as u32 & mask(byte);
+    let result = a.wrapping_sub(b).wrapping_sub(borrow as u32) & mask(byte);
+
+    set_flag(flags, FLAG_C, b + borrow as u32 > a);
+    set_flag(flags, FLAG_V, (a ^ b) & (a ^ result) & sign_bit(byte) != 0);
+    set_zn(flags, result as u16, byte)
+}
+
+/// INC and DEC set Z, N and V but keep the carry.
+pub(crate) fn step(flags: &mut u16, value: u16, increment: bool) -> u16 {
+    let carry = *flags & FLAG_C;
+    let result = if increment {
+        add(flags, value, 1, false, false)
+    } else {
+        sub(flags, value, 1, false, false)
+    };
+    *flags = (*flags & !FLAG_C) | carry;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_flags() {
+        let mut flags = 0;
+        assert_eq!(add(&mut flags, 0xFFFF, 1, false, false), 0);
+        assert_eq!(flags, FLAG_Z | FLAG_C);
+
+        assert_eq!(add(&mut flags, 0x7FFF, 1, false, false), 0x8000);
+        assert_eq!(flags, FLAG_N | FLAG_V);
+
+        assert_eq!(add(&mut flags, 0x1234, 0x0001, true, false), 0x1236);
+        assert_eq!(flags, 0);
+    }
+
+    #[test]
+    fn test_sub_flags() {
+        let mut flags = 0;
+        assert_eq!(sub(&mut flags, 0, 1, false, false), 0xFFFF);
+        assert_eq!(flags, FLAG_N | FLAG_C);
+
+        assert_eq!(sub(&mut flags, 0x8000, 1, false, false), 0x7FFF);
+        assert_eq!(flags, FLAG_V);
+
+        assert_eq!(sub(&mut flags, 5, 4, true, false), 0);
+        assert_eq!(flags, FLAG_Z);
+    }
+
+    #[test]
+    fn test_byte_flags() {
+        let mut flags = 0;
+        assert_eq!(add(&mut flags, 0x12FF, 0x0001, false, true), 0x00);
+        assert_eq!(flags, FLAG_Z | FLAG_C);
+
+        assert_eq!(sub(&mut flags, 0x0080, 0x0001, false, true), 0x7F);
+        assert_eq!(flags, FLAG_V);
+    }
+
+    #[test]
+    fn test_step_keeps_carry() {
+        let mut flags = FLAG_C;
+        assert_eq!(step(&mut flags, 0xFFFF, true), 0);
+        assert_eq!(flags, FLAG_Z | FLAG_C);
+
+        let mut flags = 0;
+        assert_eq!(step(&mut flags, 0, false), 0xFFFF);
+        assert_eq!(flags, FLAG_N);
+    }
+}
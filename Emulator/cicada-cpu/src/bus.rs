@@ -0,0 +1,83 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use thiserror::Error;
+
+/// Address of the interrupt vector table in cartridge ROM (standard mode).
+pub const STANDARD_VECTOR_TABLE: u16 = 0x0060;
+
+/// A CPU fault, serviced through the interrupt vector table regardless of the interrupt enable
+/// state. A faulting instruction is abandoned and its own address is pushed as the return address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum Fault {
+    #[error("Bus error: 16-bit access at odd address 0x{addr:04X}")]
+    BusError { addr: u16 },
+
+    #[error("Illegal instruction at 0x{pc:04X}")]
+    IllegalInstruction { pc: u16 },
+
+    #[error("Protected memory: write to read-only address 0x{addr:04X}")]
+    ProtectedMemory { addr: u16 },
+
+    #[error("Stack overflow: SP 0x{sp:04X} is below the stack region")]
+    StackOverflow { sp: u16 },
+}
+
+impl Fault {
+    /// Offset of this fault's handler address from the base of the interrupt vector table.
+    pub fn vector_offset(&self) -> u16 {
+        match self {
+            Fault::BusError { .. } => 0x02,
+            Fault::IllegalInstruction { .. } => 0x04,
+            Fault::ProtectedMemory { .. } => 0x06,
+            Fault::StackOverflow { .. } => 0x08,
+        }
+    }
+}
+
+/// The CPU's view of memory. Every access the CPU makes, including instruction fetches, goes
+/// through this trait, and an access the memory map does not allow is refused with a `Fault`.
+///
+/// Words are little-endian. The provided word accessors are built from byte accesses and do not
+/// check alignment, a bus that enforces the memory map is expected to override them.
+pub trait Bus {
+    fn read_byte(&mut self, addr: u16) -> Result<u8, Fault>;
+
+    fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), Fault>;
+
+    fn read_word(&mut self, addr: u16) -> Result<u16, Fault> {
+        let lo = self.read_byte(addr)?;
+        let hi = self.read_byte(addr.wrapping_add(1))?;
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    fn write_word(&mut self, addr: u16, value: u16) -> Result<(), Fault> {
+        let [lo, hi] = value.to_le_bytes();
+        self.write_byte(addr, lo)?;
+        self.write_byte(addr.wrapping_add(1), hi)
+    }
+
+    /// Called with the lowered stack pointer before every push, so that a bus can refuse a push
+    /// that would leave the stack region.
+    fn check_stack(&mut self, _sp: u16) -> Result<(), Fault> {
+        Ok(())
+    }
+
+    /// Base address of the interrupt vector table the CPU currently reads handler addresses from.
+    fn vector_table(&self) -> u16 {
+        STANDARD_VECTOR_TABLE
+    }
+}
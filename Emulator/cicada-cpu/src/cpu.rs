@@ -0,0 +1,195 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::bus::{Bus, Fault};
+
+// flags register bits, bits 11-0 are unused and always read as 0
+pub const FLAG_Z: u16 = 1 << 15;
+pub const FLAG_N: u16 = 1 << 14;
+pub const FLAG_C: u16 = 1 << 13;
+pub const FLAG_V: u16 = 1 << 12;
+pub const FLAGS_MASK: u16 = FLAG_Z | FLAG_N | FLAG_C | FLAG_V;
+
+// register numbers with a fixed role
+pub const FP: usize = 6;
+pub const SP: usize = 7;
+
+pub const IE_ADDR: u16 = 0xF020;
+pub const IF_ADDR: u16 = 0xF021;
+pub const SYSCALL_TABLE: u16 = 0xE000;
+pub const HRAM_START: u16 = 0xFE00;
+
+/// Offset of the V-Blank handler in the interrupt vector table, the handler for interrupt bit
+/// `n` of `IE`/`IF` is at `INTERRUPT_VECTORS + n * 2`.
+pub const INTERRUPT_VECTORS: u16 = 0x0A;
+
+/// The Cicada-16 processor state.
+///
+/// Each call to `step` runs one instruction and returns the T-cycles it took, following the
+/// timing rules in `HardwareSpec/notes.md`: 4 T-cycles for every instruction byte fetched, plus
+/// 8 (4 in HRAM) for every 16-bit data access and 4 (2 in HRAM) for every 8-bit data access.
+/// Stack pushes and pops are 16-bit accesses, vector table lookups are free.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Cpu {
+    pub regs: [u16; 8], // R0-R7, R7 is the stack pointer
+    pub pc: u16,
+    pub f: u16,
+    pub ime: bool, // master interrupt switch, set by EI and RETI
+    pub halted: bool,
+    pub cycles: u64, // T-cycles run since the CPU was created
+    step_cycles: u32,
+}
+
+impl Cpu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clear every register and jump through the reset vector.
+    pub fn reset<B: Bus>(&mut self, bus: &mut B) -> Result<(), Fault> {
+        *self = Self::default();
+        self.pc = bus.read_word(bus.vector_table())?;
+        Ok(())
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.regs[SP]
+    }
+
+    pub fn flag(&self, flag: u16) -> bool {
+        self.f & flag != 0
+    }
+
+    /// Run one instruction, then service the highest priority pending interrupt if interrupts
+    /// are enabled. A halted CPU spends 4 T-cycles waiting for an interrupt to be flagged.
+    /// Returns the T-cycles taken.
+    pub fn step<B: Bus>(&mut self, bus: &mut B) -> u32 {
+        self.step_cycles = 0;
+
+        if self.halted {
+            self.step_cycles = 4;
+            if pending_interrupts(bus) != 0 {
+                self.halted = false;
+            }
+        } else {
+            let start = self.pc;
+            if let Err(fault) = self.execute(bus) {
+                self.pc = start;
+                self.enter_handler(bus, fault.vector_offset());
+            }
+        }
+
+        if self.ime {
+            let pending = pending_interrupts(bus);
+            if pending != 0 {
+                let bit = pending.trailing_zeros() as u16;
+                self.halted = false;
+                self.enter_handler(bus, INTERRUPT_VECTORS + bit * 2);
+            }
+        }
+
+        self.cycles += self.step_cycles as u64;
+        self.step_cycles
+    }
+
+    // Push PC and F and jump to the handler at `offset` in the vector table. A fault raised while
+    // pushing is ignored, the handler is entered regardless.
+    fn enter_handler<B: Bus>(&mut self, bus: &mut B, offset: u16) {
+        self.ime = false;
+        let _ = self.push(bus, self.pc);
+        let _ = self.push(bus, self.f);
+
+        let entry = bus.vector_table().wrapping_add(offset);
+        if let Ok(addr) = bus.read_word(entry) {
+            self.pc = addr;
+        }
+    }
+
+    pub(crate) fn fetch_byte<B: Bus>(&mut self, bus: &mut B) -> Result<u8, Fault> {
+        let value = bus.read_byte(self.pc)?;
+        self.pc = self.pc.wrapping_add(1);
+        self.step_cycles += 4;
+        Ok(value)
+    }
+
+    pub(crate) fn fetch_word<B: Bus>(&mut self, bus: &mut B) -> Result<u16, Fault> {
+        let lo = self.fetch_byte(bus)?;
+        let hi = self.fetch_byte(bus)?;
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    pub(crate) fn read_byte<B: Bus>(&mut self, bus: &mut B, addr: u16) -> Result<u8, Fault> {
+        self.step_cycles += access_cycles(addr, false);
+        bus.read_byte(addr)
+    }
+
+    pub(crate) fn write_byte<B: Bus>(
+        &mut self,
+        bus: &mut B,
+        addr: u16,
+        value: u8,
+    ) -> Result<(), Fault> {
+        self.step_cycles += access_cycles(addr, false);
+        bus.write_byte(addr, value)
+    }
+
+    pub(crate) fn read_word<B: Bus>(&mut self, bus: &mut B, addr: u16) -> Result<u16, Fault> {
+        self.step_cycles += access_cycles(addr, true);
+        bus.read_word(addr)
+    }
+
+    pub(crate) fn write_word<B: Bus>(
+        &mut self,
+        bus: &mut B,
+        addr: u16,
+        value: u16,
+    ) -> Result<(), Fault> {
+        self.step_cycles += access_cycles(addr, true);
+        bus.write_word(addr, value)
+    }
+
+    pub(crate) fn push<B: Bus>(&mut self, bus: &mut B, value: u16) -> Result<(), Fault> {
+        let sp = self.regs[SP].wrapping_sub(2);
+        bus.check_stack(sp)?;
+        self.write_word(bus, sp, value)?;
+        self.regs[SP] = sp;
+        Ok(())
+    }
+
+    pub(crate) fn pop<B: Bus>(&mut self, bus: &mut B) -> Result<u16, Fault> {
+        let sp = self.regs[SP];
+        let value = self.read_word(bus, sp)?;
+        self.regs[SP] = sp.wrapping_add(2);
+        Ok(value)
+    }
+}
+
+// interrupts that are both enabled in IE and flagged in IF
+fn pending_interrupts<B: Bus>(bus: &mut B) -> u8 {
+    let enabled = bus.read_byte(IE_ADDR).unwrap_or(0);
+    let flagged = bus.read_byte(IF_ADDR).unwrap_or(0);
+    enabled & flagged
+}
+
+// execution cost of one data access, HRAM is faster than every other region
+fn access_cycles(addr: u16, word: bool) -> u32 {
+    match (addr >= HRAM_START, word) {
+        (true, true) => 4,
+        (false, true) => 8,
+        (true, false) => 2,
+        (false, false) => 4,
+    }
+}
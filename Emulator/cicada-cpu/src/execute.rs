@@ -0,0 +1,392 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Instruction execution, one arm per opcode group of HardwareSpec/CPU_Opcodes.md.
+
+use crate::alu;
+use crate::bus::{Bus, Fault};
+use crate::cpu::{Cpu, FLAG_C, FLAG_N, FLAG_V, FLAG_Z, FLAGS_MASK, FP, SP, SYSCALL_TABLE};
+
+// ALU operation numbers, in the order the opcode groups list them
+const OP_ADD: u8 = 0;
+const OP_SUB: u8 = 1;
+const OP_AND: u8 = 2;
+const OP_OR: u8 = 3;
+const OP_XOR: u8 = 4;
+const OP_CMP: u8 = 5;
+const OP_ADC: u8 = 6;
+const OP_SBC: u8 = 7;
+
+impl Cpu {
+    pub(crate) fn execute<B: Bus>(&mut self, bus: &mut B) -> Result<(), Fault> {
+        let start = self.pc;
+        let opcode = self.fetch_byte(bus)?;
+        let r = (opcode & 0x07) as usize;
+
+        match opcode {
+            0x00 => {}
+            0x01..=0x08 => self.regs[(opcode - 0x01) as usize] = self.fetch_word(bus)?,
+            0x09..=0x0E => {
+                let r = self.fetch_reg(bus, start)?;
+                let imm = self.fetch_word(bus)?;
+                self.alu_to_reg(opcode - 0x09, r, imm);
+            }
+            0x0F => self.halted = true,
+            0x10..=0x17 => {
+                let (rd, rs) = self.fetch_reg_pair(bus, start)?;
+                self.alu_to_reg(opcode - 0x10, rd, self.regs[rs]);
+            }
+            0x18..=0x47 => self.alu_to_reg((opcode - 0x18) >> 3, 0, self.regs[r]),
+            0x48 => self.regs[0] = alu::sub(&mut self.f, 0, self.regs[0], false, false),
+            0x49 => self.regs[0] = alu::set_zn(&mut self.f, !self.regs[0], false),
+            0x4A => self.regs[0] = alu::set_zn(&mut self.f, self.regs[0].swap_bytes(), false),
+            0x4B => self.f = (self.f ^ FLAG_C) & !FLAG_N,
+            0x4C => self.f = (self.f | FLAG_C) & !FLAG_N,
+            0x4D => self.f &= !(FLAG_C | FLAG_N),
+            0x4E => {
+                let index = self.fetch_byte(bus)? as u16;
+                self.push(bus, self.pc)?;
+                self.push(bus, self.f)?;
+                self.pc = bus.read_word(SYSCALL_TABLE.wrapping_add(index * 2))?;
+            }
+            0x4F => {
+                self.push(bus, self.regs[FP])?;
+                self.regs[FP] = self.regs[SP];
+            }
+            0x50 => {
+                let saved_sp = self.regs[SP];
+                self.regs[SP] = self.regs[FP];
+                match self.pop(bus) {
+                    Ok(fp) => self.regs[FP] = fp,
+                    Err(fault) => {
+                        self.regs[SP] = saved_sp;
+                        return Err(fault);
+                    }
+                }
+            }
+
+            // control flow
+            0x51 => self.pc = self.fetch_word(bus)?,
+            0x52..=0x59 => self.pc = self.regs[(opcode - 0x52) as usize],
+            0x5A => {
+                let offset = self.fetch_byte(bus)?;
+                self.pc = relative(start, offset);
+            }
+            0x5B..=0x62 => {
+                let target = self.fetch_word(bus)?;
+                if self.condition(opcode - 0x5B) {
+                    self.pc = target;
+                }
+            }
+            0x63..=0x6A => {
+                let offset = self.fetch_byte(bus)?;
+                if self.condition(opcode - 0x63) {
+                    self.pc = relative(start, offset);
+                }
+            }
+            0x6B => {
+                let offset = self.fetch_byte(bus)?;
+                self.regs[5] = self.regs[5].wrapping_sub(1);
+                if self.regs[5] != 0 {
+                    self.pc = relative(start, offset);
+                }
+            }
+
+            // stack
+            0x6C => {
+                let offset = self.fetch_byte(bus)? as i8;
+                self.regs[SP] = self.regs[SP].wrapping_add_signed(offset as i16);
+            }
+            0x6D..=0x74 => self.push(bus, self.regs[(opcode - 0x6D) as usize])?,
+            0x75..=0x7C => self.regs[(opcode - 0x75) as usize] = self.pop(bus)?,
+            0x7D => {
+                let imm = self.fetch_word(bus)?;
+                self.push(bus, imm)?;
+            }
+            0x7E => self.push(bus, self.f)?,
+            0x7F => self.f = self.pop(bus)? & FLAGS_MASK,
+
+            0x80..=0xBF => self.regs[((opcode >> 3) & 0x07) as usize] = self.regs[r],
+            0xC0..=0xC7 => {
+                let imm = self.fetch_word(bus)?;
+                self.alu_to_reg(opcode - 0xC0, 0, imm);
+            }
+
+            // subroutines
+            0xC8 => {
+                let target = self.fetch_word(bus)?;
+                self.call(bus, target)?;
+            }
+            0xC9..=0xD0 => self.call(bus, self.regs[(opcode - 0xC9) as usize])?,
+            0xD1..=0xD8 => {
+                let target = self.fetch_word(bus)?;
+                if self.condition(opcode - 0xD1) {
+                    self.call(bus, target)?;
+                }
+            }
+
+            // absolute addressing
+            0xD9..=0xE0 => {
+                let addr = self.fetch_word(bus)?;
+                self.regs[(opcode - 0xD9) as usize] = self.read_byte(bus, addr)? as u16;
+            }
+            0xE1..=0xE8 => {
+                let addr = self.fetch_word(bus)?;
+                self.write_byte(bus, addr, self.regs[(opcode - 0xE1) as usize] as u8)?;
+            }
+            0xE9..=0xF0 => {
+                let addr = self.fetch_word(bus)?;
+                self.regs[(opcode - 0xE9) as usize] = self.read_word(bus, addr)?;
+            }
+            0xF1..=0xF8 => {
+                let addr = self.fetch_word(bus)?;
+                self.write_word(bus, addr, self.regs[(opcode - 0xF1) as usize])?;
+            }
+
+            0xF9 => self.pc = self.pop(bus)?,
+            0xFA => {
+                self.f = self.pop(bus)? & FLAGS_MASK;
+                self.pc = self.pop(bus)?;
+                self.ime = true;
+            }
+            0xFB => self.ime = true,
+            0xFC => self.ime = false,
+            0xFD => self.execute_fd(bus, start)?,
+            0xFE => self.execute_fe(bus)?,
+            0xFF => self.execute_ff(bus, start)?,
+        }
+
+        Ok(())
+    }
+
+    // bit, byte and shift operations
+    fn execute_fd<B: Bus>(&mut self, bus: &mut B, start: u16) -> Result<(), Fault> {
+        let sub = self.fetch_byte(bus)?;
+        let r = (sub & 0x07) as usize;
+        let bit = 1u8 << (sub & 0x07);
+
+        match sub {
+            0x00..=0x27 => self.regs[r] = self.shift(sub >> 3, self.regs[r]),
+            0x28..=0x57 => {
+                let result = self.alu((sub - 0x28) >> 3, self.regs[0], self.regs[r], true);
+                if let Some(result) = result {
+                    self.regs[0] = (self.regs[0] & 0xFF00) | result;
+                }
+            }
+            0x58..=0x6F => {
+                let r = self.fetch_reg(bus, start)?;
+                let value = self.regs[r] as u8;
+                if let Some(value) = self.bit_op(sub - 0x58, value, bit) {
+                    self.regs[r] = (self.regs[r] & 0xFF00) | value as u16;
+                }
+            }
+            0x70..=0x87 => {
+                let addr = self.fetch_word(bus)?;
+                self.bit_op_memory(bus, sub - 0x70, addr, bit)?;
+            }
+            0x88..=0x9F => {
+                let r = self.fetch_reg(bus, start)?;
+                self.bit_op_memory(bus, sub - 0x88, self.regs[r], bit)?;
+            }
+            0xA0..=0xA7 => self.regs[r] = self.fetch_byte(bus)? as u16,
+            0xA8..=0xAF => self.regs[r] = alu::step(&mut self.f, self.regs[r], false),
+            0xB0..=0xB7 => self.regs[r] = alu::step(&mut self.f, self.regs[r], true),
+            _ => return Err(Fault::IllegalInstruction { pc: start }),
+        }
+
+        Ok(())
+    }
+
+    // register indirect loads and stores
+    fn execute_fe<B: Bus>(&mut self, bus: &mut B) -> Result<(), Fault> {
+        let sub = self.fetch_byte(bus)?;
+        let rd = ((sub >> 3) & 0x07) as usize;
+        let rs = (sub & 0x07) as usize;
+
+        match sub >> 6 {
+            0 => self.regs[rd] = self.read_word(bus, self.regs[rs])?,
+            1 => self.write_word(bus, self.regs[rd], self.regs[rs])?,
+            2 => self.regs[rd] = self.read_byte(bus, self.regs[rs])? as u16,
+            _ => self.write_byte(bus, self.regs[rd], self.regs[rs] as u8)?,
+        }
+
+        Ok(())
+    }
+
+    // indexed, post-increment and pre-decrement addressing
+    fn execute_ff<B: Bus>(&mut self, bus: &mut B, start: u16) -> Result<(), Fault> {
+        let sub = self.fetch_byte(bus)?;
+        let rd = ((sub >> 3) & 0x07) as usize;
+        let rs = (sub & 0x07) as usize;
+
+        if sub < 0xC0 {
+            let offset = self.fetch_byte(bus)? as i8 as i16;
+            match sub >> 6 {
+                0 => {
+                    let addr = self.regs[rs].wrapping_add_signed(offset);
+                    self.regs[rd] = self.read_word(bus, addr)?;
+                }
+                1 => {
+                    let addr = self.regs[rd].wrapping_add_signed(offset);
+                    self.write_word(bus, addr, self.regs[rs])?;
+                }
+                _ => self.regs[rd] = self.regs[rs].wrapping_add_signed(offset),
+            }
+            return Ok(());
+        }
+
+        // C0-FF: the low 3 bits of the sub-opcode are rs, the third byte is rd. Loads use rs as
+        // the pointer, stores use rd
+        let rd = self.fetch_reg(bus, start)?;
+        let kind = (sub - 0xC0) >> 3;
+        let store = kind & 0x01 != 0;
+        let pre_decrement = kind & 0x02 != 0;
+        let byte = kind & 0x04 != 0;
+
+        let (pointer, data) = if store { (rd, rs) } else { (rs, rd) };
+        let size = if byte { 1 } else { 2 };
+        let addr = if pre_decrement {
+            self.regs[pointer].wrapping_sub(size)
+        } else {
+            self.regs[pointer]
+        };
+
+        // the pointer is only updated once the access has succeeded, so that a faulting
+        // instruction can be restarted
+        let loaded = match (store, byte) {
+            (false, false) => Some(self.read_word(bus, addr)?),
+            (false, true) => Some(self.read_byte(bus, addr)? as u16),
+            (true, false) => {
+                self.write_word(bus, addr, self.regs[data])?;
+                None
+            }
+            (true, true) => {
+                self.write_byte(bus, addr, self.regs[data] as u8)?;
+                None
+            }
+        };
+
+        self.regs[pointer] = if pre_decrement {
+            addr
+        } else {
+            addr.wrapping_add(size)
+        };
+        if let Some(value) = loaded {
+            self.regs[data] = value;
+        }
+
+        Ok(())
+    }
+
+    // register operand byte, anything above R7 is not an instruction
+    fn fetch_reg<B: Bus>(&mut self, bus: &mut B, start: u16) -> Result<usize, Fault> {
+        match self.fetch_byte(bus)? {
+            r @ 0..=7 => Ok(r as usize),
+            _ => Err(Fault::IllegalInstruction { pc: start }),
+        }
+    }
+
+    // b'00dddsss register pair operand byte
+    fn fetch_reg_pair<B: Bus>(&mut self, bus: &mut B, start: u16) -> Result<(usize, usize), Fault> {
+        match self.fetch_byte(bus)? {
+            pair @ 0..=0x3F => Ok(((pair >> 3) as usize, (pair & 0x07) as usize)),
+            _ => Err(Fault::IllegalInstruction { pc: start }),
+        }
+    }
+
+    fn call<B: Bus>(&mut self, bus: &mut B, target: u16) -> Result<(), Fault> {
+        self.push(bus, self.pc)?;
+        self.pc = target;
+        Ok(())
+    }
+
+    // condition codes in encoding order: V, NV, N, NN, C, NC, Z, NZ
+    fn condition(&self, cc: u8) -> bool {
+        let flag = [FLAG_V, FLAG_N, FLAG_C, FLAG_Z][(cc >> 1) as usize];
+        self.flag(flag) != (cc & 0x01 != 0)
+    }
+
+    fn alu_to_reg(&mut self, op: u8, r: usize, operand: u16) {
+        if let Some(result) = self.alu(op, self.regs[r], operand, false) {
+            self.regs[r] = result;
+        }
+    }
+
+    // returns None for CMP, which only sets the flags
+    fn alu(&mut self, op: u8, a: u16, b: u16, byte: bool) -> Option<u16> {
+        let carry = self.flag(FLAG_C);
+        let result = match op {
+            OP_ADD => alu::add(&mut self.f, a, b, false, byte),
+            OP_SUB => alu::sub(&mut self.f, a, b, false, byte),
+            OP_AND => alu::set_zn(&mut self.f, a & b, byte),
+            OP_OR => alu::set_zn(&mut self.f, a | b, byte),
+            OP_XOR => alu::set_zn(&mut self.f, a ^ b, byte),
+            OP_CMP => {
+                alu::sub(&mut self.f, a, b, false, byte);
+                return None;
+            }
+            OP_ADC => alu::add(&mut self.f, a, b, carry, byte),
+            OP_SBC => alu::sub(&mut self.f, a, b, carry, byte),
+            _ => unreachable!("ALU operation {} out of range", op),
+        };
+        Some(result)
+    }
+
+    // SRA, SHL, SHR, ROL, ROR. Set Z, N and C to the bit shifted out
+    fn shift(&mut self, kind: u8, value: u16) -> u16 {
+        let carry_in = self.flag(FLAG_C) as u16;
+        let (result, carry_out) = match kind {
+            0 => ((value as i16 >> 1) as u16, value & 0x0001 != 0),
+            1 => (value << 1, value & 0x8000 != 0),
+            2 => (value >> 1, value & 0x0001 != 0),
+            3 => ((value << 1) | carry_in, value & 0x8000 != 0),
+            _ => ((value >> 1) | (carry_in << 15), value & 0x0001 != 0),
+        };
+        alu::set_flag(&mut self.f, FLAG_C, carry_out);
+        alu::set_zn(&mut self.f, result, false)
+    }
+
+    // BIT, SET, RES on a byte. BIT sets Z if the bit is clear and returns None
+    fn bit_op(&mut self, kind: u8, value: u8, bit: u8) -> Option<u8> {
+        match kind >> 3 {
+            0 => {
+                alu::set_flag(&mut self.f, FLAG_Z, value & bit == 0);
+                None
+            }
+            1 => Some(value | bit),
+            _ => Some(value & !bit),
+        }
+    }
+
+    fn bit_op_memory<B: Bus>(
+        &mut self,
+        bus: &mut B,
+        kind: u8,
+        addr: u16,
+        bit: u8,
+    ) -> Result<(), Fault> {
+        let value = self.read_byte(bus, addr)?;
+        if let Some(value) = self.bit_op(kind, value, bit) {
+            self.write_byte(bus, addr, value)?;
+        }
+        Ok(())
+    }
+}
+
+// relative branch targets are measured from the start of the branch instruction
+fn relative(start: u16, offset: u8) -> u16 {
+    start.wrapping_add_signed(offset as i8 as i16)
+}
@@ -0,0 +1,600 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use cicada_cpu::cpu::{FLAG_C, FLAG_N, FLAG_V, FLAG_Z, IE_ADDR, IF_ADDR, SP};
+use cicada_cpu::{Bus, Cpu, Fault};
+use cicasm::assemble;
+use cicasm::disassembler::decode;
+use cicasm::file_reader::MockFileReader;
+use std::path::Path;
+
+const STACK_TOP: u16 = 0xD000;
+const MAX_STEPS: usize = 10_000;
+
+// 64 KiB of plain RAM
+struct FlatBus {
+    memory: Vec<u8>,
+}
+
+impl FlatBus {
+    fn new() -> Self {
+        Self {
+            memory: vec![0; 0x10000],
+        }
+    }
+
+    fn load(&mut self, addr: u16, bytes: &[u8]) {
+        let start = addr as usize;
+        self.memory[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn word(&self, addr: u16) -> u16 {
+        u16::from_le_bytes([self.memory[addr as usize], self.memory[addr as usize + 1]])
+    }
+}
+
+impl Bus for FlatBus {
+    fn read_byte(&mut self, addr: u16) -> Result<u8, Fault> {
+        Ok(self.memory[addr as usize])
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), Fault> {
+        self.memory[addr as usize] = value;
+        Ok(())
+    }
+}
+
+// assemble a headerless program at 0x0000 and load it into RAM, with SP at STACK_TOP
+fn load_program(source: &str) -> (Cpu, FlatBus) {
+    let mut reader = MockFileReader::default();
+    reader.add_file("test.asm", source);
+
+    let rom = assemble(Path::new("test.asm"), 0x3FFF, None, None, &[], &reader)
+        .unwrap()
+        .rom;
+
+    let mut bus = FlatBus::new();
+    bus.load(0x0000, &rom[..0x4000]);
+
+    let mut cpu = Cpu::new();
+    cpu.regs[SP] = STACK_TOP;
+    (cpu, bus)
+}
+
+// run a program until it halts
+fn run(source: &str) -> (Cpu, FlatBus) {
+    let (mut cpu, mut bus) = load_program(source);
+    for _ in 0..MAX_STEPS {
+        cpu.step(&mut bus);
+        if cpu.halted {
+            return (cpu, bus);
+        }
+    }
+    panic!("program did not halt, PC = 0x{:04X}", cpu.pc);
+}
+
+// the T-cycles taken by each instruction of a program, up to its HALT
+fn step_cycles(source: &str) -> Vec<u32> {
+    let (mut cpu, mut bus) = load_program(source);
+    let mut cycles = Vec::new();
+    while !cpu.halted {
+        cycles.push(cpu.step(&mut bus));
+        assert!(cycles.len() < MAX_STEPS, "program did not halt");
+    }
+    cycles.pop(); // HALT
+    cycles
+}
+
+#[test]
+fn test_loads_and_stores() {
+    let (cpu, bus) = run("
+        LDI R1, 0xC000
+        LDI R0, 0x1234
+        ST (0xC000), R0
+        LD R2, (0xC000)
+        ST.b (0xC002), R0
+        LD.b R3, (0xC000)
+        LDI.b R4, 0xAB
+        LD R5, R2
+        LDI R6, 0xBEEF
+        ST (R1, 4), R6
+        LD R6, (R1)
+        LEA R7, (R1, -2)
+        HALT
+    ");
+
+    assert_eq!(bus.word(0xC000), 0x1234);
+    assert_eq!(bus.memory[0xC002], 0x34);
+    assert_eq!(bus.word(0xC004), 0xBEEF);
+    assert_eq!(cpu.regs[2], 0x1234);
+    assert_eq!(cpu.regs[3], 0x0034);
+    assert_eq!(cpu.regs[4], 0x00AB);
+    assert_eq!(cpu.regs[5], 0x1234);
+    assert_eq!(cpu.regs[6], 0x1234);
+    assert_eq!(cpu.regs[7], 0xBFFE);
+}
+
+#[test]
+fn test_post_increment_and_pre_decrement() {
+    let (cpu, bus) = run("
+        LDI R1, 0xC000
+        LDI R0, 0x1111
+        ST (R1)+, R0
+        LDI R0, 0x2222
+        ST (R1)+, R0
+        ST.b (R1)+, R0
+        LDI R2, 0xC000
+        LD R3, (R2)+
+        LD R4, (R2)+
+        LD.b R5, (R2)+
+        LD.b R6, -(R2)
+        HALT
+    ");
+
+    assert_eq!(bus.word(0xC000), 0x1111);
+    assert_eq!(bus.word(0xC002), 0x2222);
+    assert_eq!(bus.memory[0xC004], 0x22);
+    assert_eq!(cpu.regs[1], 0xC005);
+    assert_eq!(cpu.regs[3], 0x1111);
+    assert_eq!(cpu.regs[4], 0x2222);
+    assert_eq!(cpu.regs[5], 0x0022);
+    assert_eq!(cpu.regs[2], 0xC004);
+    assert_eq!(cpu.regs[6], 0x0022);
+}
+
+#[test]
+fn test_arithmetic_flags() {
+    let (cpu, _) = run("
+        LDI R0, 0xFFFF
+        LDI R1, 1
+        ADD R1
+        HALT
+    ");
+    assert_eq!(cpu.regs[0], 0);
+    assert_eq!(cpu.f, FLAG_Z | FLAG_C);
+
+    let (cpu, _) = run("
+        LDI R2, 0x7FFF
+        ADDI R2, 1
+        HALT
+    ");
+    assert_eq!(cpu.regs[2], 0x8000);
+    assert_eq!(cpu.f, FLAG_N | FLAG_V);
+
+    // a 32 bit add, 0x0001FFFF + 0x00000001
+    let (cpu, _) = run("
+        LDI R0, 0xFFFF
+        LDI R1, 0x0001
+        LDI R2, 0x0001
+        LDI R3, 0x0000
+        ADD R0, R2
+        ADC R1, R3
+        HALT
+    ");
+    assert_eq!(cpu.regs[0], 0x0000);
+    assert_eq!(cpu.regs[1], 0x0002);
+
+    let (cpu, _) = run("
+        LDI R0, 5
+        CMPI 6
+        HALT
+    ");
+    assert_eq!(cpu.regs[0], 5);
+    assert_eq!(cpu.f, FLAG_N | FLAG_C);
+
+    let (cpu, _) = run("
+        LDI R0, 0x1200
+        NEG
+        LD R1, R0
+        NOT
+        LD R2, R0
+        SWAP
+        HALT
+    ");
+    assert_eq!(cpu.regs[1], 0xEE00);
+    assert_eq!(cpu.regs[2], 0x11FF);
+    assert_eq!(cpu.regs[0], 0xFF11);
+    assert_eq!(cpu.f, FLAG_N | FLAG_C); // SWAP keeps the carry from NEG
+
+    let (cpu, _) = run("
+        LDI R0, 0xF0F0
+        LDI R1, 0xFF00
+        AND R0, R1
+        XORI 0xF000
+        HALT
+    ");
+    assert_eq!(cpu.regs[0], 0x0000);
+    assert_eq!(cpu.f, FLAG_Z);
+
+    let (cpu, _) = run("
+        SCF
+        LDI R3, 0xFFFF
+        INC R3
+        LD R4, R3
+        DEC R4
+        HALT
+    ");
+    assert_eq!(cpu.regs[3], 0);
+    assert_eq!(cpu.regs[4], 0xFFFF);
+    assert_eq!(cpu.f, FLAG_N | FLAG_C); // INC and DEC keep the carry
+}
+
+#[test]
+fn test_byte_arithmetic() {
+    let (cpu, _) = run("
+        LDI R0, 0x12FF
+        LDI R1, 0x0001
+        ADD.b R1
+        HALT
+    ");
+    assert_eq!(cpu.regs[0], 0x1200);
+    assert_eq!(cpu.f, FLAG_Z | FLAG_C);
+
+    let (cpu, _) = run("
+        LDI R0, 0x3480
+        LDI R1, 0x0001
+        SUB.b R1
+        CMP.b R1
+        HALT
+    ");
+    assert_eq!(cpu.regs[0], 0x347F);
+    assert_eq!(cpu.f, 0);
+}
+
+#[test]
+fn test_shifts_and_bits() {
+    let (cpu, _) = run("
+        LDI R0, 0x8001
+        SHL R0
+        LDI R1, 0x8001
+        SHR R1
+        LDI R2, 0x8002
+        SRA R2
+        RCF
+        LDI R3, 0x8000
+        ROL R3
+        ROR R3
+        HALT
+    ");
+    assert_eq!(cpu.regs[0], 0x0002);
+    assert_eq!(cpu.regs[1], 0x4000);
+    assert_eq!(cpu.regs[2], 0xC001);
+    assert_eq!(cpu.regs[3], 0x8000);
+    assert_eq!(cpu.f, FLAG_N);
+
+    let (cpu, bus) = run("
+        LDI R0, 0x1200
+        SET R0, 3
+        RES R0, 3
+        SET R0, 7
+        BIT R0, 6
+        LDI R1, 0xC010
+        SET (R1), 1
+        SET (0xC011), 0
+        RES (0xC011), 0
+        SET (0xC011), 7
+        BIT (0xC011), 7
+        HALT
+    ");
+    assert_eq!(cpu.regs[0], 0x1280);
+    assert_eq!(bus.memory[0xC010], 0x02);
+    assert_eq!(bus.memory[0xC011], 0x80);
+    assert!(!cpu.flag(FLAG_Z));
+}
+
+#[test]
+fn test_branches() {
+    // sum 1 to 10 with DJNZ
+    let (cpu, _) = run("
+        LDI R0, 0
+        LDI R5, 10
+    loop:
+        ADD R5
+        DJNZ loop
+        HALT
+    ");
+    assert_eq!(cpu.regs[0], 55);
+    assert_eq!(cpu.regs[5], 0);
+
+    let (cpu, _) = run("
+        LDI R0, 3
+        CMPI 3
+        JRNZ fail
+        JZ taken
+    fail:
+        LDI R1, 0xDEAD
+        HALT
+    taken:
+        LDI R2, target
+        JMP (R2)
+        HALT
+    target:
+        LDI R1, 0x600D
+        JR done
+        HALT
+    done:
+        HALT
+    ");
+    assert_eq!(cpu.regs[1], 0x600D);
+}
+
+#[test]
+fn test_calls_and_stack() {
+    let (cpu, bus) = run("
+        LDI R0, 1
+        CALL double
+        LDI R1, double
+        CALL (R1)
+        SCF
+        CALLNC double
+        CALLC double
+        PUSH 0x1234
+        POP R4
+        HALT
+    double:
+        ENTER
+        PUSH R0
+        POP R2
+        ADD R0, R2
+        LEAVE
+        RET
+    ");
+    assert_eq!(cpu.regs[0], 8);
+    assert_eq!(cpu.regs[4], 0x1234);
+    assert_eq!(cpu.sp(), STACK_TOP);
+    assert_eq!(cpu.regs[6], 0);
+    assert_eq!(bus.word(STACK_TOP - 2), 0x1234);
+
+    let (cpu, _) = run("
+        SCF
+        PUSH F
+        RCF
+        POP F
+        LDI R1, 0x1234
+        PUSH R1
+        ADD SP, 4
+        ADD SP, -4
+        POP R2
+        HALT
+    ");
+    assert_eq!(cpu.f, FLAG_C);
+    assert_eq!(cpu.regs[2], 0x1234);
+    assert_eq!(cpu.sp(), STACK_TOP);
+}
+
+#[test]
+fn test_syscall() {
+    let (mut cpu, mut bus) = load_program(
+        "
+        LDI R0, 1
+        SCF
+        SYSCALL 0x02
+        HALT
+    .org 0x0200
+    routine:
+        LDI R0, 0x4242
+        RCF
+        RETI
+    ",
+    );
+    bus.load(0xE004, &[0x00, 0x02]);
+
+    while !cpu.halted {
+        cpu.step(&mut bus);
+    }
+    assert_eq!(cpu.regs[0], 0x4242);
+    assert!(cpu.flag(FLAG_C)); // restored by RETI
+    assert!(cpu.ime);
+    assert_eq!(cpu.sp(), STACK_TOP);
+}
+
+#[test]
+fn test_instruction_cycles() {
+    let cycles = step_cycles(
+        "
+        NOP
+        LDI R1, 0xC000
+        LDI R2, 0xFE00
+        LD R0, (R1)
+        LD R0, (R2)
+        LD.b R0, (R1)
+        ST.b (R2), R0
+        LD R0, (0xC000)
+        ST.b (0xFE00), R0
+        LD R3, (R1, 2)
+        LD R3, (R1)+
+        ST.b (R2)+, R0
+        PUSH R0
+        POP R0
+        PUSH 0x1234
+        POP R0
+        ADD R0, R1
+        ADDI R0, 1
+        ADDI 1
+        SET (0xC000), 1
+        BIT (0xFE00), 1
+        SET (R1), 1
+        CALL sub
+        CALLNZ sub
+        JMP next
+    next:
+        JR 2
+        HALT
+    sub:
+        RET
+    ",
+    );
+    assert_eq!(
+        cycles,
+        vec![
+            4,  // NOP
+            12, // LDI
+            12, // LDI
+            16, // LD word, WRAM
+            12, // LD word, HRAM
+            12, // LD.b, WRAM
+            10, // ST.b, HRAM
+            20, // LD absolute, WRAM
+            14, // ST.b absolute, HRAM
+            20, // LD indexed
+            20, // LD post-increment
+            14, // ST.b post-increment, HRAM
+            12, // PUSH
+            12, // POP
+            20, // PUSH immediate
+            12, // POP
+            8,  // ADD rd, rs
+            16, // ADDI r
+            12, // ADDI acc
+            24, // SET absolute, read and write
+            18, // BIT absolute, HRAM
+            20, // SET indirect, read and write
+            20, // CALL
+            12, // RET
+            12, // CALLNZ, not taken
+            12, // JMP
+            8,  // JR
+        ]
+    );
+}
+
+#[test]
+fn test_cycle_total() {
+    let (cpu, _) = run("
+        LDI R5, 3
+    loop:
+        DJNZ loop
+        HALT
+    ");
+    // LDI, three DJNZ and the HALT itself
+    assert_eq!(cpu.cycles, 12 + 3 * 8 + 4);
+}
+
+#[test]
+fn test_illegal_instruction_fault() {
+    let (cpu, bus) = run("
+        LDI R0, 7
+        .byte 0xFD, 0xB8
+        HALT
+    .org 0x0060
+        .word 0, 0, handler, 0, 0
+    .org 0x0100
+    handler:
+        LDI R1, 0xBAD
+        HALT
+    ");
+    assert_eq!(cpu.regs[1], 0x0BAD);
+    assert!(!cpu.ime);
+    // the return address is the faulting instruction
+    assert_eq!(bus.word(STACK_TOP - 2), 0x0003);
+    assert_eq!(cpu.sp(), STACK_TOP - 4);
+}
+
+#[test]
+fn test_interrupt_wakes_halt() {
+    let (mut cpu, mut bus) = load_program(
+        "
+        EI
+        HALT
+        LDI R2, 0x0002
+        HALT
+    .org 0x0060
+        .word 0, 0, 0, 0, 0, vblank
+    .org 0x0100
+    vblank:
+        LDI R1, 0x0001
+        LDI.b R3, 0
+        ST.b (0xF021), R3 ; plain RAM, there is no write-1-to-clear
+        RETI
+    ",
+    );
+    bus.memory[IE_ADDR as usize] = 0x01;
+
+    for _ in 0..10 {
+        cpu.step(&mut bus);
+    }
+    assert!(cpu.halted);
+    assert_eq!(cpu.pc, 0x0002);
+
+    // the V-Blank flag wakes the CPU, the handler runs and clears it, then the second HALT
+    bus.memory[IF_ADDR as usize] = 0x01;
+    for _ in 0..MAX_STEPS {
+        cpu.step(&mut bus);
+        if cpu.halted {
+            break;
+        }
+    }
+    assert_eq!(cpu.regs[1], 0x0001);
+    assert_eq!(cpu.regs[2], 0x0002);
+    assert!(cpu.ime);
+    assert_eq!(cpu.sp(), STACK_TOP);
+}
+
+#[test]
+fn test_masked_interrupt_wakes_without_servicing() {
+    let (mut cpu, mut bus) = load_program(
+        "
+        HALT
+        LDI R2, 0x0002
+        HALT
+    ",
+    );
+    bus.memory[IE_ADDR as usize] = 0x08;
+    bus.memory[IF_ADDR as usize] = 0x08;
+
+    cpu.step(&mut bus);
+    assert!(cpu.halted);
+    cpu.step(&mut bus);
+    assert!(!cpu.halted);
+    cpu.step(&mut bus);
+    cpu.step(&mut bus);
+    assert!(cpu.halted);
+    assert_eq!(cpu.regs[2], 0x0002);
+    assert_eq!(cpu.sp(), STACK_TOP);
+}
+
+#[test]
+fn test_executes_every_encoding() {
+    // every byte sequence the assembler can emit runs without an illegal instruction fault,
+    // and every sequence it cannot is refused
+    const HANDLER: u16 = 0x9000;
+    let operand_bytes = [0x00, 0x07, 0x08, 0x80];
+
+    for first in 0..=0xFFu8 {
+        for second in 0..=0xFFu8 {
+            for third in operand_bytes {
+                let bytes = [first, second, third, 0x00];
+
+                let mut bus = FlatBus::new();
+                bus.load(0x0100, &bytes);
+                bus.load(0x0064, &HANDLER.to_le_bytes());
+                let mut cpu = Cpu::new();
+                cpu.pc = 0x0100;
+                cpu.regs = [0xC000; 8];
+
+                cpu.step(&mut bus);
+                let faulted = cpu.pc == HANDLER;
+                match decode(&bytes) {
+                    Some((_, size)) => {
+                        assert!(!faulted, "{:02X?} faulted", bytes);
+                        assert!(cpu.cycles >= size as u64 * 4, "{:02X?}", bytes);
+                    }
+                    None => assert!(faulted, "{:02X?} did not fault", bytes),
+                }
+            }
+        }
+    }
+}
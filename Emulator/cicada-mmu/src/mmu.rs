@@ -0,0 +1,488 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::registers::{
+    BOOT_CTRL, DIV0, DIV3, IF, MPR_BANK, RAM_BANK, VRAM_BANK, WE_LATCH, WRAM_BANK,
+};
+use cicada_cpu::bus::STANDARD_VECTOR_TABLE;
+use cicada_cpu::{Bus, Fault};
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const CART_RAM_BANK_SIZE: usize = 0x1000;
+pub const VRAM_BANK_SIZE: usize = 0x2000;
+pub const VRAM_BANKS: usize = 4;
+pub const WRAM0_SIZE: usize = 0x2000;
+pub const WRAM1_BANK_SIZE: usize = 0x1000;
+pub const WRAM1_BANKS: usize = 6;
+pub const SYSLIB_SIZE: usize = 0x1000;
+
+/// Lowest address the stack pointer may be lowered to before a Stack Overflow fault.
+pub const STACK_BASE: u16 = 0xC000;
+
+// interrupt vector table locations, see HardwareSpec/Interrupts.md
+pub const BOOT_VECTOR_TABLE: u16 = 0x3FE0;
+pub const ENHANCED_VECTOR_TABLE: u16 = 0xBFE0;
+
+// cartridge header fields read by the MMU
+const HEADER_RAM_SIZE: usize = 0x0026;
+const HEADER_FEATURE_FLAGS: usize = 0x0028;
+const INTERRUPT_MODE_BIT: u8 = 0x80;
+
+const IO_START: u16 = 0xF000;
+const IO_SIZE: usize = 0xC0; // I/O, PPU and APU registers, F000-F0BF
+const UPPER_START: u16 = 0xF200;
+
+// offsets of the memories in the F200-FFFF block
+const CRAM: std::ops::Range<usize> = 0x000..0x200;
+const OAM: std::ops::Range<usize> = 0x200..0x400;
+const HRAM: std::ops::Range<usize> = 0xC00..0xE00;
+
+// where an address lands in the memory map
+enum Region {
+    BootRom(usize),
+    Rom(usize),
+    CartRam(Option<usize>),
+    Vram(usize),
+    Wram(Option<usize>),
+    SystemLibrary(usize),
+    Io(u16),
+    Reserved,
+    Upper(usize),
+}
+
+/// The memory map of `HardwareSpec/Memory_Map.md`, with bank switching, the boot-time overlay and
+/// the access rules the MMU enforces.
+///
+/// Unmapped addresses read as 0xFF and ignore writes. Writes to cartridge ROM, the Boot ROM and
+/// (after boot) the System Library raise a Protected Memory fault, 16-bit accesses to odd
+/// addresses raise a Bus Error and pushes below `STACK_BASE` raise a Stack Overflow.
+#[derive(Debug, Clone)]
+pub struct Mmu {
+    rom: Vec<u8>,              // cartridge ROM, padded to whole banks
+    boot_rom: Option<Vec<u8>>, // mapped at 0x0000 until BOOT_CTRL is written
+    cart_ram: Vec<u8>,
+    vram: Vec<u8>,
+    wram: Vec<u8>, // WRAM0 followed by the six WRAM1 banks
+    system_library: Vec<u8>,
+    io: [u8; IO_SIZE],
+    upper: Vec<u8>, // CRAM, OAM, DSP delay buffer, wave RAM and HRAM, F200-FFFF
+    enhanced_vectors: bool,
+}
+
+impl Mmu {
+    /// A console that has already finished booting, with the cartridge mapped in and the
+    /// interrupt mode taken from its header, as the Boot ROM would leave it.
+    pub fn new(cartridge: Vec<u8>) -> Self {
+        let mut mmu = Self::with_boot_rom(cartridge, Vec::new());
+        mmu.finish_boot();
+        mmu
+    }
+
+    /// A console at power-on, running `boot_rom` at 0x0000 with cartridge bank 0 at 0x4000.
+    pub fn with_boot_rom(cartridge: Vec<u8>, boot_rom: Vec<u8>) -> Self {
+        let mut rom = cartridge;
+        let banks = rom.len().div_ceil(ROM_BANK_SIZE).max(1);
+        rom.resize(banks * ROM_BANK_SIZE, 0xFF);
+
+        let cart_ram_size = cart_ram_size(rom[HEADER_RAM_SIZE]);
+
+        let mut mmu = Self {
+            rom,
+            boot_rom: Some(boot_rom),
+            cart_ram: vec![0; cart_ram_size],
+            vram: vec![0; VRAM_BANKS * VRAM_BANK_SIZE],
+            wram: vec![0; WRAM0_SIZE + WRAM1_BANKS * WRAM1_BANK_SIZE],
+            system_library: vec![0; SYSLIB_SIZE],
+            io: [0; IO_SIZE],
+            upper: vec![0; 0x10000 - UPPER_START as usize],
+            enhanced_vectors: false,
+        };
+        mmu.set_io(MPR_BANK, 1);
+        mmu
+    }
+
+    pub fn booting(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+
+    /// Number of 16 KiB banks in the cartridge ROM.
+    pub fn rom_banks(&self) -> usize {
+        self.rom.len() / ROM_BANK_SIZE
+    }
+
+    /// The ROM bank currently mapped at 0x4000-0x7FFF.
+    pub fn rom_bank(&self) -> usize {
+        match self.booting() {
+            true => 0,
+            false => self.io(MPR_BANK) as usize % self.rom_banks(),
+        }
+    }
+
+    /// Read an I/O, PPU or APU register (F000-F0BF) without any side effects.
+    pub fn io(&self, addr: u16) -> u8 {
+        self.io[(addr - IO_START) as usize]
+    }
+
+    /// Set an I/O, PPU or APU register (F000-F0BF) directly, bypassing the rules for CPU writes.
+    /// This is how the other peripherals update their read-only registers.
+    pub fn set_io(&mut self, addr: u16, value: u8) {
+        self.io[(addr - IO_START) as usize] = value;
+    }
+
+    /// Flag interrupt `bit` in IF.
+    pub fn request_interrupt(&mut self, bit: u8) {
+        let flags = self.io(IF) | (1 << bit);
+        self.set_io(IF, flags);
+    }
+
+    /// All 32 KiB of VRAM, every bank.
+    pub fn vram(&self) -> &[u8] {
+        &self.vram
+    }
+
+    pub fn cram(&self) -> &[u8] {
+        &self.upper[CRAM]
+    }
+
+    pub fn oam(&self) -> &[u8] {
+        &self.upper[OAM]
+    }
+
+    /// All 32 KiB of work RAM, WRAM0 followed by WRAM1 banks 1-6.
+    pub fn wram(&self) -> &[u8] {
+        &self.wram
+    }
+
+    pub fn hram(&self) -> &[u8] {
+        &self.upper[HRAM]
+    }
+
+    pub fn cart_ram(&self) -> &[u8] {
+        &self.cart_ram
+    }
+
+    // unmap the Boot ROM, protect the System Library and latch the game's interrupt mode
+    fn finish_boot(&mut self) {
+        self.boot_rom = None;
+        self.enhanced_vectors = self.rom[HEADER_FEATURE_FLAGS] & INTERRUPT_MODE_BIT != 0;
+    }
+
+    fn region(&self, addr: u16) -> Region {
+        let booting = self.booting();
+        let offset = |start: u16| (addr - start) as usize;
+
+        match addr {
+            0x0000..=0x3FFF if booting => Region::BootRom(addr as usize),
+            0x0000..=0x3FFF => Region::Rom(addr as usize),
+            0x4000..=0x7FFF => Region::Rom(self.rom_bank() * ROM_BANK_SIZE + offset(0x4000)),
+            0x8000..=0x8FFF => {
+                let banks = self.cart_ram.len() / CART_RAM_BANK_SIZE;
+                Region::CartRam(match booting || banks == 0 {
+                    true => None,
+                    false => {
+                        let bank = self.io(RAM_BANK) as usize % banks;
+                        Some(bank * CART_RAM_BANK_SIZE + offset(0x8000))
+                    }
+                })
+            }
+            0x9000..=0xAFFF => {
+                // the VRAM_BANK register is ignored during boot
+                let bank = match booting {
+                    true => 0,
+                    false => self.io(VRAM_BANK) as usize % VRAM_BANKS,
+                };
+                Region::Vram(bank * VRAM_BANK_SIZE + offset(0x9000))
+            }
+            0xB000..=0xCFFF => Region::Wram(Some(offset(0xB000))),
+            0xD000..=0xDFFF => Region::Wram(match booting {
+                true => None,
+                false => {
+                    let bank = self.io(WRAM_BANK) as usize % WRAM1_BANKS;
+                    Some(WRAM0_SIZE + bank * WRAM1_BANK_SIZE + offset(0xD000))
+                }
+            }),
+            0xE000..=0xEFFF => Region::SystemLibrary(offset(0xE000)),
+            0xF000..=0xF0BF => Region::Io(addr),
+            0xF0C0..=0xF1FF => Region::Reserved,
+            _ => Region::Upper(offset(UPPER_START)),
+        }
+    }
+
+    fn read_io(&self, addr: u16) -> u8 {
+        match addr {
+            BOOT_CTRL => 0xFF, // write-only
+            _ => self.io(addr),
+        }
+    }
+
+    fn write_io(&mut self, addr: u16, value: u8) {
+        match addr {
+            DIV0..=DIV3 => {} // the divider cannot be written
+            IF => {
+                // writing a 1 clears the flag
+                let flags = self.io(IF) & !value;
+                self.set_io(IF, flags);
+            }
+            BOOT_CTRL => {
+                if self.booting() && value & 0x01 != 0 {
+                    self.finish_boot();
+                }
+            }
+            _ => self.set_io(addr, value),
+        }
+    }
+}
+
+impl Bus for Mmu {
+    fn read_byte(&mut self, addr: u16) -> Result<u8, Fault> {
+        Ok(match self.region(addr) {
+            Region::BootRom(i) => self.boot_rom.as_ref().and_then(|rom| rom.get(i).copied()),
+            Region::Rom(i) => Some(self.rom[i]),
+            Region::CartRam(i) => i.map(|i| self.cart_ram[i]),
+            Region::Vram(i) => Some(self.vram[i]),
+            Region::Wram(i) => i.map(|i| self.wram[i]),
+            Region::SystemLibrary(i) => Some(self.system_library[i]),
+            Region::Io(addr) => Some(self.read_io(addr)),
+            Region::Reserved => None,
+            Region::Upper(i) => Some(self.upper[i]),
+        }
+        .unwrap_or(0xFF))
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), Fault> {
+        match self.region(addr) {
+            Region::BootRom(_) | Region::Rom(_) => return Err(Fault::ProtectedMemory { addr }),
+            Region::SystemLibrary(_) if !self.booting() => {
+                return Err(Fault::ProtectedMemory { addr });
+            }
+            Region::SystemLibrary(i) => self.system_library[i] = value,
+            // cartridge RAM ignores writes unless the write-enable latch is set
+            Region::CartRam(Some(i)) if self.io(WE_LATCH) != 0 => self.cart_ram[i] = value,
+            Region::Vram(i) => self.vram[i] = value,
+            Region::Wram(Some(i)) => self.wram[i] = value,
+            Region::Io(addr) => self.write_io(addr, value),
+            Region::Upper(i) => self.upper[i] = value,
+            Region::CartRam(_) | Region::Wram(None) | Region::Reserved => {}
+        }
+        Ok(())
+    }
+
+    fn read_word(&mut self, addr: u16) -> Result<u16, Fault> {
+        check_alignment(addr)?;
+        let lo = self.read_byte(addr)?;
+        let hi = self.read_byte(addr + 1)?;
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    fn write_word(&mut self, addr: u16, value: u16) -> Result<(), Fault> {
+        check_alignment(addr)?;
+        let [lo, hi] = value.to_le_bytes();
+        self.write_byte(addr, lo)?;
+        self.write_byte(addr + 1, hi)
+    }
+
+    fn check_stack(&mut self, sp: u16) -> Result<(), Fault> {
+        match sp < STACK_BASE {
+            true => Err(Fault::StackOverflow { sp }),
+            false => Ok(()),
+        }
+    }
+
+    fn vector_table(&self) -> u16 {
+        if self.booting() {
+            BOOT_VECTOR_TABLE
+        } else if self.enhanced_vectors {
+            ENHANCED_VECTOR_TABLE
+        } else {
+            STANDARD_VECTOR_TABLE
+        }
+    }
+}
+
+fn check_alignment(addr: u16) -> Result<(), Fault> {
+    match addr % 2 {
+        0 => Ok(()),
+        _ => Err(Fault::BusError { addr }),
+    }
+}
+
+/// Size in bytes of the save RAM described by the header's RAM Size byte. 0x00 is no RAM, every
+/// step above doubles it from a single 4 KiB bank, up to 256 banks.
+pub fn cart_ram_size(code: u8) -> usize {
+    match code {
+        0 => 0,
+        _ => CART_RAM_BANK_SIZE << (code - 1).min(8),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registers::IE;
+
+    // a cartridge whose every byte holds its bank number
+    fn cartridge(banks: usize, ram_size: u8, features: u8) -> Vec<u8> {
+        let mut rom: Vec<u8> = (0..banks * ROM_BANK_SIZE)
+            .map(|i| (i / ROM_BANK_SIZE) as u8)
+            .collect();
+        rom[HEADER_RAM_SIZE] = ram_size;
+        rom[HEADER_FEATURE_FLAGS] = features;
+        rom
+    }
+
+    #[test]
+    fn test_rom_banking() {
+        let mut mmu = Mmu::new(cartridge(4, 0, 0));
+        assert_eq!(mmu.read_byte(0x0100).unwrap(), 0);
+        assert_eq!(mmu.read_byte(0x4000).unwrap(), 1);
+
+        mmu.write_byte(MPR_BANK, 3).unwrap();
+        assert_eq!(mmu.read_byte(0x7FFF).unwrap(), 3);
+        assert_eq!(mmu.rom_bank(), 3);
+
+        // bank numbers past the end of the ROM wrap around
+        mmu.write_byte(MPR_BANK, 6).unwrap();
+        assert_eq!(mmu.read_byte(0x4000).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_boot_overlay() {
+        let mut boot_rom = vec![0xB0; 0x4000];
+        boot_rom[0x3FE0] = 0x34;
+        boot_rom[0x3FE1] = 0x12;
+        let mut mmu = Mmu::with_boot_rom(cartridge(4, 0, 0), boot_rom);
+
+        assert!(mmu.booting());
+        assert_eq!(mmu.vector_table(), BOOT_VECTOR_TABLE);
+        assert_eq!(mmu.read_word(0x3FE0).unwrap(), 0x1234);
+        assert_eq!(mmu.read_byte(0x0000).unwrap(), 0xB0);
+        // cartridge bank 0 is visible at 0x4000, whatever MPR_BANK holds
+        mmu.write_byte(MPR_BANK, 2).unwrap();
+        assert_eq!(mmu.read_byte(0x4000).unwrap(), 0);
+        // WRAM1 is unmapped and the System Library is writable
+        mmu.write_byte(0xD000, 0x55).unwrap();
+        assert_eq!(mmu.read_byte(0xD000).unwrap(), 0xFF);
+        mmu.write_byte(0xE000, 0x42).unwrap();
+
+        mmu.write_byte(BOOT_CTRL, 0x01).unwrap();
+        assert!(!mmu.booting());
+        assert_eq!(mmu.read_byte(0x0000).unwrap(), 0);
+        assert_eq!(mmu.read_byte(0x4000).unwrap(), 2);
+        assert_eq!(mmu.read_byte(0xE000).unwrap(), 0x42);
+        assert_eq!(
+            mmu.write_byte(0xE000, 0x00),
+            Err(Fault::ProtectedMemory { addr: 0xE000 })
+        );
+        assert_eq!(mmu.vector_table(), STANDARD_VECTOR_TABLE);
+    }
+
+    #[test]
+    fn test_enhanced_interrupt_mode() {
+        let mmu = Mmu::new(cartridge(2, 0, INTERRUPT_MODE_BIT));
+        assert_eq!(mmu.vector_table(), ENHANCED_VECTOR_TABLE);
+    }
+
+    #[test]
+    fn test_cart_ram() {
+        let mut mmu = Mmu::new(cartridge(2, 2, 0));
+        assert_eq!(mmu.cart_ram().len(), 2 * CART_RAM_BANK_SIZE);
+
+        // ignored until the write-enable latch is set
+        mmu.write_byte(0x8000, 0x11).unwrap();
+        assert_eq!(mmu.read_byte(0x8000).unwrap(), 0x00);
+
+        mmu.write_byte(WE_LATCH, 0x01).unwrap();
+        mmu.write_byte(0x8000, 0x11).unwrap();
+        mmu.write_byte(RAM_BANK, 1).unwrap();
+        mmu.write_byte(0x8000, 0x22).unwrap();
+        assert_eq!(mmu.read_byte(0x8000).unwrap(), 0x22);
+        mmu.write_byte(RAM_BANK, 0).unwrap();
+        assert_eq!(mmu.read_byte(0x8000).unwrap(), 0x11);
+        assert_eq!(mmu.cart_ram()[CART_RAM_BANK_SIZE], 0x22);
+
+        // no RAM on the cartridge
+        let mut mmu = Mmu::new(cartridge(2, 0, 0));
+        mmu.write_byte(WE_LATCH, 0x01).unwrap();
+        mmu.write_byte(0x8000, 0x11).unwrap();
+        assert_eq!(mmu.read_byte(0x8000).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn test_ram_banking() {
+        let mut mmu = Mmu::new(cartridge(2, 0, 0));
+
+        for bank in 0..VRAM_BANKS as u8 {
+            mmu.write_byte(VRAM_BANK, bank).unwrap();
+            mmu.write_byte(0x9000, bank + 0x10).unwrap();
+        }
+        for bank in 0..WRAM1_BANKS as u8 {
+            mmu.write_byte(WRAM_BANK, bank).unwrap();
+            mmu.write_byte(0xD000, bank + 0x20).unwrap();
+        }
+        mmu.write_byte(0xB000, 0x30).unwrap();
+
+        assert_eq!(mmu.vram()[2 * VRAM_BANK_SIZE], 0x12);
+        assert_eq!(mmu.wram()[0], 0x30);
+        assert_eq!(mmu.wram()[WRAM0_SIZE + 5 * WRAM1_BANK_SIZE], 0x25);
+        mmu.write_byte(WRAM_BANK, 1).unwrap();
+        assert_eq!(mmu.read_byte(0xD000).unwrap(), 0x21);
+    }
+
+    #[test]
+    fn test_faults() {
+        let mut mmu = Mmu::new(cartridge(2, 0, 0));
+
+        assert_eq!(
+            mmu.write_byte(0x1234, 0),
+            Err(Fault::ProtectedMemory { addr: 0x1234 })
+        );
+        assert_eq!(
+            mmu.write_word(0x4000, 0),
+            Err(Fault::ProtectedMemory { addr: 0x4000 })
+        );
+        assert_eq!(mmu.read_word(0xC001), Err(Fault::BusError { addr: 0xC001 }));
+        assert_eq!(
+            mmu.write_word(0xFE01, 0),
+            Err(Fault::BusError { addr: 0xFE01 })
+        );
+        assert_eq!(mmu.read_byte(0xC001), Ok(0));
+        assert_eq!(
+            mmu.check_stack(0xBFFE),
+            Err(Fault::StackOverflow { sp: 0xBFFE })
+        );
+        assert_eq!(mmu.check_stack(0xC000), Ok(()));
+    }
+
+    #[test]
+    fn test_io_registers() {
+        let mut mmu = Mmu::new(cartridge(2, 0, 0));
+
+        mmu.set_io(DIV0, 0x12);
+        mmu.write_byte(DIV0, 0x00).unwrap();
+        assert_eq!(mmu.read_byte(DIV0).unwrap(), 0x12);
+
+        mmu.write_byte(IE, 0x0F).unwrap();
+        mmu.request_interrupt(0);
+        mmu.request_interrupt(3);
+        mmu.write_byte(IF, 0x01).unwrap();
+        assert_eq!(mmu.read_byte(IF).unwrap(), 0x08);
+        assert_eq!(mmu.read_byte(IE).unwrap(), 0x0F);
+
+        assert_eq!(mmu.read_byte(0xF100).unwrap(), 0xFF);
+        mmu.write_byte(0xFFFE, 0x99).unwrap();
+        assert_eq!(mmu.hram()[0x1FE], 0x99);
+    }
+}
@@ -0,0 +1,60 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Addresses of the I/O registers, see HardwareSpec/IO_Peripherals.md.
+
+pub const DIV0: u16 = 0xF002;
+pub const DIV3: u16 = 0xF005;
+pub const JOYP: u16 = 0xF006;
+pub const TIMA0: u16 = 0xF007;
+pub const TMA0: u16 = 0xF008;
+pub const TAC0: u16 = 0xF009;
+pub const MPR_BANK: u16 = 0xF011;
+pub const RAM_BANK: u16 = 0xF012;
+pub const WE_LATCH: u16 = 0xF013;
+pub const VRAM_BANK: u16 = 0xF014;
+pub const WRAM_BANK: u16 = 0xF015;
+pub const IE: u16 = 0xF020;
+pub const IF: u16 = 0xF021;
+pub const BOOT_CTRL: u16 = 0xF022;
+pub const TIMA1: u16 = 0xF023;
+pub const TMA1: u16 = 0xF024;
+pub const TAC1: u16 = 0xF025;
+
+// PPU registers, see HardwareSpec/PPU_Architecture.md
+pub const LCDC: u16 = 0xF040;
+pub const STAT: u16 = 0xF041;
+pub const SCY0: u16 = 0xF042;
+pub const SCX0: u16 = 0xF043;
+pub const SCY1: u16 = 0xF044;
+pub const SCX1: u16 = 0xF045;
+pub const WINY: u16 = 0xF046;
+pub const WINX: u16 = 0xF047;
+pub const LY: u16 = 0xF048;
+pub const LYC: u16 = 0xF049;
+pub const BG_MODE: u16 = 0xF04A;
+pub const BG_TMB: u16 = 0xF04B;
+pub const WIN_TMB: u16 = 0xF04C;
+
+// interrupt bits of IE and IF
+pub const INT_VBLANK: u8 = 0;
+pub const INT_HBLANK: u8 = 1;
+pub const INT_LYC: u8 = 2;
+pub const INT_TIMER0: u8 = 3;
+pub const INT_TIMER1: u8 = 4;
+pub const INT_SERIAL: u8 = 5;
+pub const INT_LINK: u8 = 6;
+pub const INT_JOYPAD: u8 = 7;
@@ -0,0 +1,169 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use cicada_cpu::cpu::SP;
+use cicada_cpu::{Bus, Cpu};
+use cicada_mmu::Mmu;
+use cicasm::assemble;
+use cicasm::file_reader::MockFileReader;
+use std::path::Path;
+
+const MAX_STEPS: usize = 10_000;
+
+// every fault handler records its vector number in R5 and halts
+const HEADER: &str = r#"
+    .header_start
+        .boot_anim "CICA"
+        .title "MMU-Test"
+        .mapper 1
+        .rom_size 2
+        .ram_size 1
+        .interrupt_mode 0
+    .header_end
+    .interrupt_table
+        .word start, bus_error, illegal, protected, stack_overflow
+        .word 0, 0, 0, 0, 0, 0, 0, 0
+    .table_end
+    bus_error:
+        LDI R5, 1
+        HALT
+    illegal:
+        LDI R5, 2
+        HALT
+    protected:
+        LDI R5, 3
+        HALT
+    stack_overflow:
+        LDI R5, 4
+        HALT
+    start:
+        LDI R7, 0xD000
+"#;
+
+// assemble a cartridge whose code follows HEADER, and run it on a booted console until it halts
+fn run(source: &str) -> (Cpu, Mmu) {
+    let mut reader = MockFileReader::default();
+    reader.add_file("test.asm", &format!("{HEADER}{source}"));
+
+    let rom = assemble(
+        Path::new("test.asm"),
+        0x7FFF,
+        Some(0x0060),
+        Some(0x0000),
+        &[],
+        &reader,
+    )
+    .unwrap()
+    .rom;
+
+    let mut mmu = Mmu::new(rom);
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut mmu).unwrap();
+
+    for _ in 0..MAX_STEPS {
+        cpu.step(&mut mmu);
+        if cpu.halted {
+            return (cpu, mmu);
+        }
+    }
+    panic!("program did not halt, PC = 0x{:04X}", cpu.pc);
+}
+
+// the return address a fault handler was entered with
+fn fault_pc(cpu: &Cpu, mmu: &mut Mmu) -> u16 {
+    mmu.read_word(cpu.sp().wrapping_add(2)).unwrap()
+}
+
+#[test]
+fn test_bank_switching() {
+    let (cpu, mmu) = run("
+        LD R0, (0x4000)
+        LDI.b R1, 2
+        ST.b (0xF011), R1
+        LD R1, (0x4000)
+        LDI.b R2, 1
+        ST.b (0xF013), R2
+        LDI R2, 0xCAFE
+        ST (0x8000), R2
+        LDI.b R3, 3
+        ST.b (0xF015), R3
+        ST (0xD000), R1
+        HALT
+    .bank 1
+    .org 0x4000
+        .word 0x1111
+    .bank 2
+    .org 0x4000
+        .word 0x2222
+    ");
+
+    assert_eq!(cpu.regs[5], 0);
+    assert_eq!(cpu.regs[0], 0x1111);
+    assert_eq!(cpu.regs[1], 0x2222);
+    assert_eq!(mmu.cart_ram()[..2], [0xFE, 0xCA]);
+    assert_eq!(mmu.wram()[0x2000 + 3 * 0x1000], 0x22);
+}
+
+#[test]
+fn test_bus_error() {
+    let (cpu, mut mmu) = run("
+        LDI R1, 0xC001
+        JMP fault
+    .org 0x0200
+    fault:
+        LD R2, (R1)
+        HALT
+    ");
+
+    assert_eq!(cpu.regs[5], 1);
+    assert_eq!(fault_pc(&cpu, &mut mmu), 0x0200);
+}
+
+#[test]
+fn test_protected_memory() {
+    let (cpu, mut mmu) = run("
+        JMP fault
+    .org 0x0200
+    fault:
+        ST.b (0x1000), R0
+        HALT
+    ");
+
+    assert_eq!(cpu.regs[5], 3);
+    assert_eq!(fault_pc(&cpu, &mut mmu), 0x0200);
+    assert!(!cpu.ime);
+
+    let (cpu, _) = run("
+        LDI R0, 0x1234
+        ST (0xE000), R0
+        HALT
+    ");
+    assert_eq!(cpu.regs[5], 3);
+}
+
+#[test]
+fn test_stack_overflow() {
+    let (cpu, _) = run("
+        LDI R7, 0xC002
+        PUSH R0
+        PUSH R0
+        HALT
+    ");
+
+    // the handler is entered without a stack frame, the second push never happened
+    assert_eq!(cpu.regs[5], 4);
+    assert_eq!(cpu.regs[SP], 0xC000);
+}
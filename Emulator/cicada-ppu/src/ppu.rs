@@ -0,0 +1,265 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::render::{LCDC_ENABLE, render_line};
+use cicada_mmu::Mmu;
+use cicada_mmu::registers::{INT_HBLANK, INT_LYC, INT_VBLANK, LCDC, LY, LYC, STAT};
+
+pub const SCREEN_WIDTH: usize = 240;
+pub const SCREEN_HEIGHT: usize = 160;
+
+// frame timing in T-cycles, see HardwareSpec/PPU_Architecture.md section 10
+pub const LINE_CYCLES: u32 = 1300;
+pub const LINES: usize = 216;
+pub const FRAME_CYCLES: u32 = LINE_CYCLES * LINES as u32;
+const OAM_SCAN_END: u32 = 160;
+const DRAWING_END: u32 = OAM_SCAN_END + 960;
+
+// STAT bits
+const STAT_LYC_INT: u8 = 0x40;
+const STAT_HBLANK_INT: u8 = 0x08;
+const STAT_WRITABLE: u8 = 0x78;
+
+/// The PPU mode reported in bits 1-0 of STAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+/// The Picture Processing Unit of `HardwareSpec/PPU_Architecture.md`.
+///
+/// `tick` runs the scanline timing, keeps LY and STAT up to date and flags the V-Blank, H-Blank
+/// and LY=LYC interrupts. Each visible line is drawn into the frame buffer from VRAM, CRAM and
+/// OAM as it finishes Mode 3, so changes made during H-Blank show up on the next line.
+///
+/// While LCDC bit 7 is clear the screen is black, LY reads 0 and no interrupts are flagged, but
+/// the PPU keeps counting out frames so that the console keeps its frame pacing.
+#[derive(Debug, Clone)]
+pub struct Ppu {
+    framebuffer: Vec<u16>, // RGB555, row by row
+    line: usize,           // the scanline being drawn
+    dot: u32,              // T-cycles into the line
+    mode: Mode,
+    lyc_equal: bool,
+    frames: u64, // frames completed since power-on
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self {
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            line: 0,
+            dot: 0,
+            mode: Mode::OamScan,
+            lyc_equal: false,
+            frames: 0,
+        }
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last drawn image, `SCREEN_WIDTH * SCREEN_HEIGHT` RGB555 colors.
+    pub fn framebuffer(&self) -> &[u16] {
+        &self.framebuffer
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Advance the PPU by `cycles` T-cycles.
+    pub fn tick(&mut self, mmu: &mut Mmu, cycles: u32) {
+        let mut remaining = cycles;
+        while remaining > 0 {
+            let boundary = match self.mode {
+                Mode::OamScan => OAM_SCAN_END,
+                Mode::Drawing => DRAWING_END,
+                Mode::HBlank | Mode::VBlank => LINE_CYCLES,
+            };
+            let step = remaining.min(boundary - self.dot);
+            self.dot += step;
+            remaining -= step;
+
+            if self.dot == boundary {
+                self.next_mode(mmu);
+            }
+        }
+
+        self.update_registers(mmu);
+    }
+
+    fn next_mode(&mut self, mmu: &mut Mmu) {
+        let enabled = mmu.io(LCDC) & LCDC_ENABLE != 0;
+
+        match self.mode {
+            Mode::OamScan => self.mode = Mode::Drawing,
+            Mode::Drawing => {
+                let row = &mut self.framebuffer[self.line * SCREEN_WIDTH..][..SCREEN_WIDTH];
+                match enabled {
+                    true => render_line(mmu, self.line, row),
+                    false => row.fill(0),
+                }
+
+                self.mode = Mode::HBlank;
+                if enabled && mmu.io(STAT) & STAT_HBLANK_INT != 0 {
+                    mmu.request_interrupt(INT_HBLANK);
+                }
+            }
+            Mode::HBlank | Mode::VBlank => {
+                self.dot = 0;
+                self.line = (self.line + 1) % LINES;
+                if self.line == 0 {
+                    self.frames += 1;
+                }
+
+                self.mode = match self.line < SCREEN_HEIGHT {
+                    true => Mode::OamScan,
+                    false => Mode::VBlank,
+                };
+                if enabled && self.line == SCREEN_HEIGHT {
+                    mmu.request_interrupt(INT_VBLANK);
+                }
+            }
+        }
+    }
+
+    // refresh LY and the read-only bits of STAT, which the CPU may have written over
+    fn update_registers(&mut self, mmu: &mut Mmu) {
+        let enabled = mmu.io(LCDC) & LCDC_ENABLE != 0;
+        let (ly, mode) = match enabled {
+            true => (self.line as u8, self.mode),
+            false => (0, Mode::HBlank),
+        };
+        mmu.set_io(LY, ly);
+
+        let stat = mmu.io(STAT);
+        let equal = ly == mmu.io(LYC);
+        if enabled && equal && !self.lyc_equal && stat & STAT_LYC_INT != 0 {
+            mmu.request_interrupt(INT_LYC);
+        }
+        self.lyc_equal = equal;
+
+        mmu.set_io(
+            STAT,
+            (stat & STAT_WRITABLE) | (equal as u8) << 2 | mode as u8,
+        );
+    }
+}
+
+/// Expand an RGB555 color to 8 bits per channel.
+pub fn rgb888(color: u16) -> [u8; 3] {
+    let expand = |shift: u16| {
+        let value = ((color >> shift) & 0x1F) as u8;
+        (value << 3) | (value >> 2)
+    };
+    [expand(10), expand(5), expand(0)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cicada_mmu::registers::IF;
+
+    fn enabled_mmu() -> Mmu {
+        let mut mmu = Mmu::new(vec![0; 0x8000]);
+        mmu.set_io(LCDC, LCDC_ENABLE);
+        mmu
+    }
+
+    #[test]
+    fn test_line_timing() {
+        let mut mmu = enabled_mmu();
+        let mut ppu = Ppu::new();
+
+        ppu.tick(&mut mmu, OAM_SCAN_END - 1);
+        assert_eq!(mmu.io(STAT) & 0x03, Mode::OamScan as u8);
+        ppu.tick(&mut mmu, 1);
+        assert_eq!(mmu.io(STAT) & 0x03, Mode::Drawing as u8);
+        ppu.tick(&mut mmu, DRAWING_END - OAM_SCAN_END);
+        assert_eq!(mmu.io(STAT) & 0x03, Mode::HBlank as u8);
+        ppu.tick(&mut mmu, LINE_CYCLES - DRAWING_END);
+        assert_eq!(mmu.io(LY), 1);
+        assert_eq!(mmu.io(STAT) & 0x03, Mode::OamScan as u8);
+    }
+
+    #[test]
+    fn test_vblank() {
+        let mut mmu = enabled_mmu();
+        let mut ppu = Ppu::new();
+
+        ppu.tick(&mut mmu, LINE_CYCLES * SCREEN_HEIGHT as u32 - 1);
+        assert_eq!(mmu.io(IF), 0);
+        ppu.tick(&mut mmu, 1);
+        assert_eq!(mmu.io(LY), SCREEN_HEIGHT as u8);
+        assert_eq!(mmu.io(STAT) & 0x03, Mode::VBlank as u8);
+        assert_eq!(mmu.io(IF), 1 << INT_VBLANK);
+        assert_eq!(ppu.frames(), 0);
+
+        // the rest of the frame, in CPU sized steps
+        for _ in 0..(LINES - SCREEN_HEIGHT) as u32 * LINE_CYCLES / 4 {
+            ppu.tick(&mut mmu, 4);
+        }
+        assert_eq!(mmu.io(LY), 0);
+        assert_eq!(ppu.frames(), 1);
+    }
+
+    #[test]
+    fn test_stat_interrupts() {
+        let mut mmu = enabled_mmu();
+        let mut ppu = Ppu::new();
+
+        mmu.set_io(LYC, 2);
+        mmu.set_io(STAT, STAT_LYC_INT | STAT_HBLANK_INT | 0x03);
+        ppu.tick(&mut mmu, DRAWING_END);
+        assert_eq!(mmu.io(IF), 1 << INT_HBLANK);
+        // the CPU cannot write the read-only bits
+        assert_eq!(mmu.io(STAT), STAT_LYC_INT | STAT_HBLANK_INT);
+
+        ppu.tick(&mut mmu, 2 * LINE_CYCLES - DRAWING_END);
+        assert_eq!(mmu.io(IF), 1 << INT_HBLANK | 1 << INT_LYC);
+        assert_eq!(mmu.io(STAT) & 0x04, 0x04);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut mmu = Mmu::new(vec![0; 0x8000]);
+        let mut ppu = Ppu::new();
+
+        ppu.tick(&mut mmu, FRAME_CYCLES / 2);
+        assert_eq!(mmu.io(LY), 0);
+        assert_eq!(mmu.io(IF), 0);
+        ppu.tick(&mut mmu, FRAME_CYCLES / 2);
+        assert_eq!(ppu.frames(), 1);
+    }
+
+    #[test]
+    fn test_rgb888() {
+        assert_eq!(rgb888(0x7FFF), [0xFF, 0xFF, 0xFF]);
+        assert_eq!(rgb888(0x7C00), [0xFF, 0x00, 0x00]);
+        assert_eq!(rgb888(0x0200), [0x00, 0x84, 0x00]);
+    }
+}
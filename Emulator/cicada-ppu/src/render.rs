@@ -0,0 +1,386 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Draws one scanline from the tilemaps, tiles, sprites and palettes, following the layer order
+// and priority rules of HardwareSpec/PPU_Architecture.md section 6.
+
+use cicada_mmu::Mmu;
+use cicada_mmu::registers::{BG_MODE, BG_TMB, LCDC, SCX0, SCX1, SCY0, SCY1, WIN_TMB, WINX, WINY};
+
+// LCDC bits
+pub(crate) const LCDC_ENABLE: u8 = 0x80;
+const LCDC_SPRITES: u8 = 0x40;
+const LCDC_BG1: u8 = 0x20;
+const LCDC_BG0: u8 = 0x10;
+const LCDC_WINDOW: u8 = 0x01;
+
+const VRAM_MASK: usize = 0x7FFF;
+const TILE_BYTES: usize = 32;
+const TILEMAP_SLOT_BYTES: usize = 0x800;
+
+// tilemap entry bits
+const TILE_PRIORITY: u16 = 0x8000;
+const TILE_V_FLIP: u16 = 0x4000;
+const TILE_H_FLIP: u16 = 0x2000;
+const TILE_INDEX: u16 = 0x03FF;
+
+const SPRITES: usize = 64;
+const SPRITE_BYTES: usize = 8;
+const SPRITES_PER_LINE: usize = 16;
+
+// sprite attribute flag bits
+const SPRITE_PRIORITY: u8 = 0x80;
+const SPRITE_V_FLIP: u8 = 0x40;
+const SPRITE_H_FLIP: u8 = 0x20;
+const SPRITE_PALETTE: u8 = 0x0F;
+
+// one layer's pixel, before the palette lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pixel {
+    color: u8,
+    palette: u8,
+    priority: bool,
+}
+
+// a tilemap in VRAM, its size is in tiles
+struct Tilemap {
+    base: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Tilemap {
+    // a tilemap from its 2 KiB slot number and its BG_MODE size
+    fn new(slot: u8, size: u8) -> Self {
+        let (width, height) = match size & 0x03 {
+            0 => (32, 32),
+            1 => (64, 32),
+            2 => (32, 64),
+            _ => (64, 64),
+        };
+        Self {
+            base: (slot & 0x0F) as usize * TILEMAP_SLOT_BYTES,
+            width,
+            height,
+        }
+    }
+
+    // the pixel at (x, y) of the whole map, wrapping around its edges
+    fn pixel(&self, vram: &[u8], x: usize, y: usize) -> Pixel {
+        let column = (x / 8) % self.width;
+        let row = (y / 8) % self.height;
+        let addr = self.base + (row * self.width + column) * 2;
+        let entry = u16::from_le_bytes([vram[addr & VRAM_MASK], vram[(addr + 1) & VRAM_MASK]]);
+
+        let mut x = x % 8;
+        let mut y = y % 8;
+        if entry & TILE_H_FLIP != 0 {
+            x = 7 - x;
+        }
+        if entry & TILE_V_FLIP != 0 {
+            y = 7 - y;
+        }
+
+        Pixel {
+            color: tile_pixel(vram, (entry & TILE_INDEX) as usize, x, y),
+            palette: ((entry >> 10) & 0x07) as u8,
+            priority: entry & TILE_PRIORITY != 0,
+        }
+    }
+}
+
+struct Sprite {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    tile: usize,
+    flags: u8,
+}
+
+impl Sprite {
+    // the sprite at `index` in OAM, None if its shape is the reserved one
+    fn read(oam: &[u8], index: usize) -> Option<Self> {
+        let entry = &oam[index * SPRITE_BYTES..][..SPRITE_BYTES];
+        let size = (entry[2] & 0x03) as usize;
+        let (width, height) = match (entry[2] >> 2) & 0x03 {
+            0 => [(8, 8), (16, 16), (32, 32), (64, 64)][size],
+            1 => [(16, 8), (32, 8), (32, 16), (64, 32)][size],
+            2 => [(8, 16), (8, 32), (16, 32), (32, 64)][size],
+            _ => return None,
+        };
+
+        Some(Self {
+            y: entry[0] as usize,
+            x: entry[1] as usize,
+            width,
+            height,
+            tile: entry[3] as usize,
+            flags: entry[4],
+        })
+    }
+
+    fn on_line(&self, line: usize) -> bool {
+        (self.y..self.y + self.height).contains(&line)
+    }
+
+    // the sprite's pixel at screen position (x, line), None where it is transparent. The tiles
+    // of a large sprite follow each other in VRAM, row by row
+    fn pixel(&self, vram: &[u8], x: usize, line: usize) -> Option<Pixel> {
+        if !(self.x..self.x + self.width).contains(&x) {
+            return None;
+        }
+
+        let mut x = x - self.x;
+        let mut y = line - self.y;
+        if self.flags & SPRITE_H_FLIP != 0 {
+            x = self.width - 1 - x;
+        }
+        if self.flags & SPRITE_V_FLIP != 0 {
+            y = self.height - 1 - y;
+        }
+
+        let tile = self.tile + (y / 8) * (self.width / 8) + x / 8;
+        let color = tile_pixel(vram, tile, x % 8, y % 8);
+        (color != 0).then_some(Pixel {
+            color,
+            palette: self.flags & SPRITE_PALETTE,
+            priority: self.flags & SPRITE_PRIORITY != 0,
+        })
+    }
+}
+
+// the 4-bit color index of pixel (x, y) of a 4bpp planar tile, bit 7 of a plane's row byte is the
+// leftmost pixel
+fn tile_pixel(vram: &[u8], tile: usize, x: usize, y: usize) -> u8 {
+    let row = tile * TILE_BYTES + y;
+    (0..4).fold(0, |color, plane| {
+        let bits = vram[(row + plane * 8) & VRAM_MASK];
+        color | ((bits >> (7 - x)) & 1) << plane
+    })
+}
+
+// the RGB555 color of a palette entry in CRAM
+fn palette_color(cram: &[u8], pixel: Pixel) -> u16 {
+    let index = (pixel.palette as usize * 16 + pixel.color as usize) * 2;
+    u16::from_le_bytes([cram[index], cram[index + 1]]) & 0x7FFF
+}
+
+/// Draw scanline `line` into `row`, one RGB555 color per pixel.
+pub(crate) fn render_line(mmu: &Mmu, line: usize, row: &mut [u16]) {
+    let lcdc = mmu.io(LCDC);
+    let vram = mmu.vram();
+
+    let bg_mode = mmu.io(BG_MODE);
+    let bg_tmb = mmu.io(BG_TMB);
+    let bg0 = Tilemap::new(bg_tmb, bg_mode);
+    let bg1 = Tilemap::new(bg_tmb >> 4, bg_mode >> 2);
+    let window = Tilemap::new(mmu.io(WIN_TMB), 0);
+    let (scx0, scy0) = (mmu.io(SCX0) as usize, mmu.io(SCY0) as usize);
+    let (scx1, scy1) = (mmu.io(SCX1) as usize, mmu.io(SCY1) as usize);
+    let (winx, winy) = (mmu.io(WINX) as usize, mmu.io(WINY) as usize);
+
+    // the OAM scan, lower OAM indexes are drawn on top
+    let sprites: Vec<Sprite> = match lcdc & LCDC_SPRITES {
+        0 => Vec::new(),
+        _ => (0..SPRITES)
+            .filter_map(|index| Sprite::read(mmu.oam(), index))
+            .filter(|sprite| sprite.on_line(line))
+            .take(SPRITES_PER_LINE)
+            .collect(),
+    };
+
+    for (x, out) in row.iter_mut().enumerate() {
+        // BG0 is opaque, without it the backdrop is the first CRAM color
+        let mut pixel = match lcdc & LCDC_BG0 {
+            0 => Pixel {
+                color: 0,
+                palette: 0,
+                priority: false,
+            },
+            _ => bg0.pixel(vram, x + scx0, line + scy0),
+        };
+
+        if lcdc & LCDC_BG1 != 0 {
+            let bg1_pixel = bg1.pixel(vram, x + scx1, line + scy1);
+            if bg1_pixel.color != 0 {
+                pixel = bg1_pixel;
+            }
+        }
+
+        if lcdc & LCDC_WINDOW != 0 && x >= winx && line >= winy {
+            let window_pixel = window.pixel(vram, x - winx, line - winy);
+            if window_pixel.color != 0 {
+                pixel = window_pixel;
+            }
+        }
+
+        // a sprite without its priority flag goes behind high priority background tiles
+        let sprite_pixel = sprites
+            .iter()
+            .find_map(|sprite| sprite.pixel(vram, x, line));
+        if let Some(sprite_pixel) = sprite_pixel
+            && (sprite_pixel.priority || !pixel.priority)
+        {
+            pixel = sprite_pixel;
+        }
+
+        *out = palette_color(mmu.cram(), pixel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppu::SCREEN_WIDTH;
+    use cicada_cpu::Bus;
+    use cicada_mmu::registers::VRAM_BANK;
+
+    const RED: u16 = 0x7C00;
+    const GREEN: u16 = 0x03E0;
+    const BLUE: u16 = 0x001F;
+    const WHITE: u16 = 0x7FFF;
+
+    fn write_vram(mmu: &mut Mmu, addr: usize, bytes: &[u8]) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            let addr = addr + i;
+            mmu.write_byte(VRAM_BANK, (addr / 0x2000) as u8).unwrap();
+            mmu.write_byte(0x9000 + (addr % 0x2000) as u16, byte)
+                .unwrap();
+        }
+    }
+
+    fn write_color(mmu: &mut Mmu, palette: u16, color: u16, value: u16) {
+        mmu.write_word(0xF200 + (palette * 16 + color) * 2, value)
+            .unwrap();
+    }
+
+    // tile 1 is solid color 1, tile 2 has color 2 in its left column only
+    fn mmu() -> Mmu {
+        let mut mmu = Mmu::new(vec![0; 0x8000]);
+        write_vram(&mut mmu, TILE_BYTES, &[0xFF; 8]);
+        write_vram(&mut mmu, 2 * TILE_BYTES + 8, &[0x80; 8]);
+        write_color(&mut mmu, 0, 0, WHITE);
+        write_color(&mut mmu, 0, 1, RED);
+        write_color(&mut mmu, 1, 2, GREEN);
+        write_color(&mut mmu, 9, 1, BLUE);
+        mmu
+    }
+
+    fn render(mmu: &Mmu, line: usize) -> Vec<u16> {
+        let mut row = vec![0; SCREEN_WIDTH];
+        render_line(mmu, line, &mut row);
+        row
+    }
+
+    #[test]
+    fn test_tile_pixel() {
+        let mut vram = vec![0; 0x8000];
+        // pixel 2 of row 5 has color 0b1010
+        vram[TILE_BYTES + 8 + 5] = 0x20;
+        vram[TILE_BYTES + 24 + 5] = 0x20;
+        assert_eq!(tile_pixel(&vram, 1, 2, 5), 0b1010);
+        assert_eq!(tile_pixel(&vram, 1, 3, 5), 0);
+    }
+
+    #[test]
+    fn test_backgrounds() {
+        let mut mmu = mmu();
+        // BG0 at slot 1 is all tile 1, BG1 at slot 2 has tile 2 with palette 1 at column 1
+        mmu.set_io(BG_TMB, 0x21);
+        for i in 0..32 * 32 {
+            write_vram(&mut mmu, 0x800 + i * 2, &[0x01, 0x00]);
+        }
+        write_vram(&mut mmu, 0x1000 + 2, &[0x02, 0x04]);
+        mmu.set_io(LCDC, LCDC_ENABLE | LCDC_BG0 | LCDC_BG1);
+
+        let row = render(&mmu, 0);
+        assert_eq!(row[0], RED);
+        assert_eq!(row[8], GREEN);
+        assert_eq!(row[9], RED);
+
+        // scrolling BG1 moves the tile left, BG0 without tile 1 shows color 0
+        mmu.set_io(SCX1, 4);
+        mmu.set_io(LCDC, LCDC_ENABLE | LCDC_BG1);
+        let row = render(&mmu, 0);
+        assert_eq!(row[4], GREEN);
+        assert_eq!(row[8], WHITE);
+    }
+
+    #[test]
+    fn test_window() {
+        let mut mmu = mmu();
+        mmu.set_io(WIN_TMB, 0x03);
+        write_vram(&mut mmu, 0x1800, &[0x01, 0x00]);
+        mmu.set_io(WINX, 16);
+        mmu.set_io(WINY, 4);
+        mmu.set_io(LCDC, LCDC_ENABLE | LCDC_WINDOW);
+
+        assert_eq!(render(&mmu, 3)[16], WHITE);
+        let row = render(&mmu, 4);
+        assert_eq!(row[15], WHITE);
+        assert_eq!(row[16], RED);
+        assert_eq!(row[24], WHITE);
+    }
+
+    #[test]
+    fn test_sprites() {
+        let mut mmu = mmu();
+        // sprite 0: 16x8 at (8, 2) using tiles 1 and 2 with palette 9, flipped horizontally,
+        // sprite 1: 8x8 at (12, 2) using tile 1 with palette 0
+        let oam = [
+            [2, 8, 0x04, 1, 0x80 | SPRITE_H_FLIP | 9, 0, 0, 0],
+            [2, 12, 0x00, 1, 0x80, 0, 0, 0],
+        ];
+        for (i, entry) in oam.iter().enumerate() {
+            for (j, &byte) in entry.iter().enumerate() {
+                mmu.write_byte(0xF400 + (i * SPRITE_BYTES + j) as u16, byte)
+                    .unwrap();
+            }
+        }
+        mmu.set_io(LCDC, LCDC_ENABLE | LCDC_SPRITES);
+
+        assert_eq!(render(&mmu, 1)[16], WHITE);
+        let row = render(&mmu, 2);
+        // flipped, tile 2 comes first and its colored column is the last one
+        assert_eq!(row[8], WHITE);
+        assert_eq!(row[15], 0x0000);
+        assert_eq!(row[23], BLUE);
+        assert_eq!(row[24], WHITE);
+        // sprite 1 shows through sprite 0's transparent pixels, but is behind its opaque ones
+        assert_eq!(row[12], RED);
+        assert_eq!(row[16], BLUE);
+        assert_eq!(row[19], BLUE);
+    }
+
+    #[test]
+    fn test_sprite_priority() {
+        let mut mmu = mmu();
+        mmu.set_io(BG_TMB, 0x01);
+        write_vram(&mut mmu, 0x800, &[0x00, 0x80]); // tile 0 with priority
+        for (i, &byte) in [0u8, 0, 0x00, 1, 0x09].iter().enumerate() {
+            mmu.write_byte(0xF400 + i as u16, byte).unwrap();
+        }
+        mmu.set_io(LCDC, LCDC_ENABLE | LCDC_BG0 | LCDC_SPRITES);
+
+        let row = render(&mmu, 0);
+        assert_eq!(row[0], WHITE);
+
+        mmu.write_byte(0xF404, 0x89).unwrap();
+        let row = render(&mmu, 0);
+        assert_eq!(row[0], BLUE);
+    }
+}
@@ -0,0 +1,161 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use cicada_mmu::Mmu;
+use cicada_mmu::registers::{DIV0, INT_TIMER0, INT_TIMER1, TAC0, TAC1, TIMA0, TIMA1, TMA0, TMA1};
+
+// TAC0/TAC1 bits
+const TIMER_ENABLE: u8 = 0x20;
+const CLOCK_SELECT: u8 = 0x1F;
+
+// the registers of one programmable timer and the interrupt its overflow requests
+struct TimerRegisters {
+    tima: u16,
+    tma: u16,
+    tac: u16,
+    interrupt: u8,
+}
+
+const TIMERS: [TimerRegisters; 2] = [
+    TimerRegisters {
+        tima: TIMA0,
+        tma: TMA0,
+        tac: TAC0,
+        interrupt: INT_TIMER0,
+    },
+    TimerRegisters {
+        tima: TIMA1,
+        tma: TMA1,
+        tac: TAC1,
+        interrupt: INT_TIMER1,
+    },
+];
+
+/// The 32-bit divider and the two programmable timers of `HardwareSpec/IO_Peripherals.md`.
+///
+/// The divider counts T-cycles. A running timer increments TIMA on every falling edge of the
+/// divider bit selected by the CLK_SEL field of its TAC register, and on overflow reloads TIMA from
+/// TMA and flags its interrupt.
+#[derive(Debug, Clone, Default)]
+pub struct Timers {
+    div: u64, // T-cycles since power-on, DIV0-DIV3 hold the low 32 bits
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn div(&self) -> u32 {
+        self.div as u32
+    }
+
+    /// Advance the divider and timers by `cycles` T-cycles.
+    pub fn tick(&mut self, mmu: &mut Mmu, cycles: u32) {
+        let old = self.div;
+        self.div += cycles as u64;
+
+        for (i, byte) in self.div().to_le_bytes().into_iter().enumerate() {
+            mmu.set_io(DIV0 + i as u16, byte);
+        }
+
+        for timer in &TIMERS {
+            let tac = mmu.io(timer.tac);
+            if tac & TIMER_ENABLE == 0 {
+                continue;
+            }
+
+            // the selected bit falls once every time the bit above it changes
+            let shift = (tac & CLOCK_SELECT) as u32 + 1;
+            let edges = (self.div >> shift) - (old >> shift);
+
+            for _ in 0..edges {
+                match mmu.io(timer.tima).checked_add(1) {
+                    Some(tima) => mmu.set_io(timer.tima, tima),
+                    None => {
+                        let tma = mmu.io(timer.tma);
+                        mmu.set_io(timer.tima, tma);
+                        mmu.request_interrupt(timer.interrupt);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cicada_mmu::registers::{DIV3, IF};
+
+    fn mmu() -> Mmu {
+        Mmu::new(vec![0; 0x8000])
+    }
+
+    #[test]
+    fn test_divider() {
+        let mut mmu = mmu();
+        let mut timers = Timers::new();
+
+        timers.tick(&mut mmu, 0x1234);
+        assert_eq!(mmu.io(DIV0), 0x34);
+        assert_eq!(mmu.io(DIV0 + 1), 0x12);
+
+        // the divider is 32 bits wide and wraps
+        timers.div = 0xFFFF_FFFF;
+        timers.tick(&mut mmu, 2);
+        assert_eq!(timers.div(), 1);
+        assert_eq!(mmu.io(DIV3), 0x00);
+    }
+
+    #[test]
+    fn test_timer_rate() {
+        let mut mmu = mmu();
+        let mut timers = Timers::new();
+
+        // DIV bit 3 falls every 16 T-cycles
+        mmu.set_io(TAC0, TIMER_ENABLE | 3);
+        timers.tick(&mut mmu, 15);
+        assert_eq!(mmu.io(TIMA0), 0);
+        timers.tick(&mut mmu, 1);
+        assert_eq!(mmu.io(TIMA0), 1);
+        timers.tick(&mut mmu, 160);
+        assert_eq!(mmu.io(TIMA0), 11);
+
+        // a stopped timer keeps its count
+        mmu.set_io(TAC0, 3);
+        timers.tick(&mut mmu, 160);
+        assert_eq!(mmu.io(TIMA0), 11);
+    }
+
+    #[test]
+    fn test_overflow_reloads_and_interrupts() {
+        let mut mmu = mmu();
+        let mut timers = Timers::new();
+
+        mmu.set_io(TIMA1, 0xFE);
+        mmu.set_io(TMA1, 0xC0);
+        mmu.set_io(TAC1, TIMER_ENABLE);
+        timers.tick(&mut mmu, 2);
+        assert_eq!(mmu.io(TIMA1), 0xFF);
+        assert_eq!(mmu.io(IF), 0);
+
+        timers.tick(&mut mmu, 2);
+        assert_eq!(mmu.io(TIMA1), 0xC0);
+        assert_eq!(mmu.io(IF), 1 << INT_TIMER1);
+        assert_eq!(mmu.io(TIMA0), 0);
+    }
+}
@@ -0,0 +1,142 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use cicada_cpu::bus::STANDARD_VECTOR_TABLE;
+use cicada_cpu::cpu::SP;
+use cicada_cpu::{Bus, Cpu, Fault};
+use cicada_mmu::Mmu;
+use cicada_mmu::mmu::ENHANCED_VECTOR_TABLE;
+use cicada_ppu::Ppu;
+use cicada_timer::Timers;
+
+/// Writing a byte here stops the emulator and makes it the exit code of `cicemu`. The address is
+/// in the reserved block after the APU registers, so no hardware register is hidden by it.
+pub const TEST_RESULT_PORT: u16 = 0xF0C0;
+
+// the state the Boot ROM leaves the console in, see HardwareSpec/Boot_Process.md
+pub const ENTRY_POINT: u16 = 0x0080;
+pub const STACK_TOP: u16 = 0xD000;
+const VECTOR_TABLE_BYTES: u16 = 26;
+
+/// The memory the CPU sees, the MMU with the emulator's test result port in front of it.
+#[derive(Debug, Clone)]
+pub struct ConsoleBus {
+    pub mmu: Mmu,
+    test_result: Option<u8>,
+}
+
+impl Bus for ConsoleBus {
+    fn read_byte(&mut self, addr: u16) -> Result<u8, Fault> {
+        self.mmu.read_byte(addr)
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), Fault> {
+        match addr {
+            TEST_RESULT_PORT => {
+                self.test_result = Some(value);
+                Ok(())
+            }
+            _ => self.mmu.write_byte(addr, value),
+        }
+    }
+
+    fn read_word(&mut self, addr: u16) -> Result<u16, Fault> {
+        self.mmu.read_word(addr)
+    }
+
+    fn write_word(&mut self, addr: u16, value: u16) -> Result<(), Fault> {
+        match addr {
+            TEST_RESULT_PORT => self.write_byte(addr, value as u8),
+            _ => self.mmu.write_word(addr, value),
+        }
+    }
+
+    fn check_stack(&mut self, sp: u16) -> Result<(), Fault> {
+        self.mmu.check_stack(sp)
+    }
+
+    fn vector_table(&self) -> u16 {
+        self.mmu.vector_table()
+    }
+}
+
+/// A whole console: the CPU, memory, timers and PPU, run in lockstep one instruction at a time.
+#[derive(Debug, Clone)]
+pub struct Console {
+    pub cpu: Cpu,
+    pub bus: ConsoleBus,
+    pub timers: Timers,
+    pub ppu: Ppu,
+}
+
+impl Console {
+    /// A console that has just left the Boot ROM: the cartridge is mapped in, an enhanced mode
+    /// vector table has been copied to RAM and the CPU is at the entry point with interrupts
+    /// disabled.
+    pub fn new(cartridge: Vec<u8>) -> Self {
+        let mut mmu = Mmu::new(cartridge);
+        if mmu.vector_table() == ENHANCED_VECTOR_TABLE {
+            for i in 0..VECTOR_TABLE_BYTES {
+                let byte = mmu.read_byte(STANDARD_VECTOR_TABLE + i).unwrap_or(0xFF);
+                let _ = mmu.write_byte(ENHANCED_VECTOR_TABLE + i, byte);
+            }
+        }
+
+        let mut cpu = Cpu::new();
+        cpu.pc = ENTRY_POINT;
+        cpu.regs[SP] = STACK_TOP;
+
+        Self {
+            cpu,
+            bus: ConsoleBus {
+                mmu,
+                test_result: None,
+            },
+            timers: Timers::new(),
+            ppu: Ppu::new(),
+        }
+    }
+
+    pub fn mmu(&self) -> &Mmu {
+        &self.bus.mmu
+    }
+
+    /// The value the program wrote to `TEST_RESULT_PORT`, if it has.
+    pub fn test_result(&self) -> Option<u8> {
+        self.bus.test_result
+    }
+
+    /// Run one CPU instruction and the timers and PPU for as long as it took. Returns the
+    /// T-cycles taken.
+    pub fn step(&mut self) -> u32 {
+        let cycles = self.cpu.step(&mut self.bus);
+        self.timers.tick(&mut self.bus.mmu, cycles);
+        self.ppu.tick(&mut self.bus.mmu, cycles);
+        cycles
+    }
+
+    /// Run until `frames` frames have been completed since power-on or the program writes a
+    /// test result, which is returned.
+    pub fn run(&mut self, frames: u64) -> Option<u8> {
+        while self.ppu.frames() < frames {
+            self.step();
+            if self.test_result().is_some() {
+                break;
+            }
+        }
+        self.test_result()
+    }
+}
@@ -0,0 +1,157 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Writing the state of a console for inspection by test scripts: the CPU registers and RAM as
+// JSON and the frame buffer as a PNG or binary PPM image.
+
+use crate::console::Console;
+use cicada_ppu::{SCREEN_HEIGHT, SCREEN_WIDTH, rgb888};
+use serde::Serialize;
+use std::fmt::Write;
+
+const IO_REGISTERS: std::ops::Range<u16> = 0xF000..0xF0C0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CpuState {
+    pub registers: [u16; 8],
+    pub pc: u16,
+    pub f: u16,
+    pub ime: bool,
+    pub halted: bool,
+    pub cycles: u64,
+}
+
+/// The contents of each memory, as hex strings. Banked memories hold every bank in order.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MemoryState {
+    pub io: String, // F000-F0BF
+    pub wram: String,
+    pub hram: String,
+    pub vram: String,
+    pub cram: String,
+    pub oam: String,
+    pub cart_ram: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateDump {
+    pub frames: u64,
+    pub test_result: Option<u8>,
+    pub rom_bank: usize,
+    pub cpu: CpuState,
+    pub memory: MemoryState,
+}
+
+impl StateDump {
+    pub fn new(console: &Console) -> Self {
+        let cpu = &console.cpu;
+        let mmu = console.mmu();
+        let io: Vec<u8> = IO_REGISTERS.map(|addr| mmu.io(addr)).collect();
+
+        Self {
+            frames: console.ppu.frames(),
+            test_result: console.test_result(),
+            rom_bank: mmu.rom_bank(),
+            cpu: CpuState {
+                registers: cpu.regs,
+                pc: cpu.pc,
+                f: cpu.f,
+                ime: cpu.ime,
+                halted: cpu.halted,
+                cycles: cpu.cycles,
+            },
+            memory: MemoryState {
+                io: to_hex(&io),
+                wram: to_hex(mmu.wram()),
+                hram: to_hex(mmu.hram()),
+                vram: to_hex(mmu.vram()),
+                cram: to_hex(mmu.cram()),
+                oam: to_hex(mmu.oam()),
+                cart_ram: to_hex(mmu.cart_ram()),
+            },
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02X}", byte);
+        hex
+    })
+}
+
+// the frame buffer as 8-bit RGB triples
+fn rgb_pixels(framebuffer: &[u16]) -> Vec<u8> {
+    framebuffer
+        .iter()
+        .flat_map(|&color| rgb888(color))
+        .collect()
+}
+
+/// A frame buffer as a binary (P6) PPM image.
+pub fn to_ppm(framebuffer: &[u16]) -> Vec<u8> {
+    let mut image = format!("P6\n{} {}\n255\n", SCREEN_WIDTH, SCREEN_HEIGHT).into_bytes();
+    image.extend(rgb_pixels(framebuffer));
+    image
+}
+
+/// A frame buffer as a PNG image.
+pub fn to_png(framebuffer: &[u16]) -> Result<Vec<u8>, png::EncodingError> {
+    let mut image = Vec::new();
+    let mut encoder = png::Encoder::new(&mut image, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgb_pixels(framebuffer))?;
+    writer.finish()?;
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ppm() {
+        let mut framebuffer = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        framebuffer[1] = 0x7C00;
+
+        let image = to_ppm(&framebuffer);
+        let header = b"P6\n240 160\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + SCREEN_WIDTH * SCREEN_HEIGHT * 3);
+        assert_eq!(&image[header.len()..][..6], &[0, 0, 0, 0xFF, 0, 0]);
+    }
+
+    #[test]
+    fn test_png() {
+        let framebuffer = vec![0x7FFF; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+        let image = to_png(&framebuffer).unwrap();
+        let decoder = png::Decoder::new(std::io::Cursor::new(image));
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!((info.width, info.height), (240, 160));
+        assert!(pixels.iter().all(|&byte| byte == 0xFF));
+    }
+}
@@ -0,0 +1,138 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use anyhow::{Context, Result, bail};
+use cicemu::Console;
+use cicemu::console::TEST_RESULT_PORT;
+use cicemu::dump::{StateDump, to_png, to_ppm};
+use clap::Parser as clap_parser;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(clap_parser)]
+#[clap(version = "0.1.0", author = "Connor Nolan")]
+struct Opts {
+    /// Cartridge ROM image to run
+    input: PathBuf,
+
+    /// Run without a window. This is currently the only mode
+    #[clap(long)]
+    headless: bool,
+
+    /// Number of frames to run for, unless the program writes a test result first
+    #[clap(long, value_name = "COUNT", default_value_t = 600)]
+    frames: u64,
+
+    /// Write the final CPU registers and the contents of RAM to a JSON file
+    #[clap(long, value_name = "PATH")]
+    dump_state: Option<PathBuf>,
+
+    /// Write the final frame buffer to an image file, PNG or binary PPM depending on the
+    /// extension (.png or .ppm)
+    #[clap(long, value_name = "PATH")]
+    screenshot: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let opts: Opts = Opts::parse();
+
+    match run(opts) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("Error: {:#}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// run the ROM and return the exit code: the test result written by the program, or success if it
+// never wrote one
+fn run(opts: Opts) -> Result<ExitCode> {
+    if !opts.headless {
+        bail!("Only headless mode is available, run with --headless");
+    }
+
+    let screenshot = match &opts.screenshot {
+        Some(path) => Some((path, ImageFormat::from_path(path)?)),
+        None => None,
+    };
+
+    let rom = fs::read(&opts.input)
+        .with_context(|| format!("Failed to read ROM file {}", opts.input.display()))?;
+
+    let mut console = Console::new(rom);
+    let result = console.run(opts.frames);
+
+    if let Some(path) = &opts.dump_state {
+        fs::write(path, StateDump::new(&console).to_json()?)
+            .with_context(|| format!("Failed to write state dump {}", path.display()))?;
+    }
+
+    if let Some((path, format)) = screenshot {
+        fs::write(path, format.render(console.ppu.framebuffer())?)
+            .with_context(|| format!("Failed to write screenshot {}", path.display()))?;
+    }
+
+    match result {
+        Some(code) => {
+            println!(
+                "Test result 0x{:02X} after {} frames",
+                code,
+                console.ppu.frames()
+            );
+            Ok(ExitCode::from(code))
+        }
+        None => {
+            println!(
+                "Ran {} frames without a test result at 0x{:04X}",
+                console.ppu.frames(),
+                TEST_RESULT_PORT
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ImageFormat {
+    Png,
+    Ppm,
+}
+
+impl ImageFormat {
+    // the format of a screenshot file, from its extension
+    fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("png") => Ok(ImageFormat::Png),
+            Some("ppm") => Ok(ImageFormat::Ppm),
+            _ => bail!(
+                "Unknown screenshot format for {}, use a .png or .ppm file",
+                path.display()
+            ),
+        }
+    }
+
+    fn render(self, framebuffer: &[u16]) -> Result<Vec<u8>> {
+        Ok(match self {
+            ImageFormat::Png => to_png(framebuffer)?,
+            ImageFormat::Ppm => to_ppm(framebuffer),
+        })
+    }
+}
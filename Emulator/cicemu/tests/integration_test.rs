@@ -0,0 +1,200 @@
+/*
+Copyright 2025 Connor Nolan
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use cicasm::assemble;
+use cicasm::file_reader::MockFileReader;
+use cicemu::Console;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// assemble a cartridge, `source` follows the header and the vector table and starts at the
+// entry point
+fn cartridge(interrupt_mode: u8, source: &str) -> Vec<u8> {
+    let mut reader = MockFileReader::default();
+    reader.add_file(
+        "test.asm",
+        &format!(
+            r#"
+            .header_start
+                .boot_anim "CICA"
+                .title "EMU-Test"
+                .mapper 1
+                .rom_size 2
+                .interrupt_mode {interrupt_mode}
+            .header_end
+            .interrupt_table
+                .word 0, 0, 0, 0, 0, vblank, 0, 0, timer0, 0, 0, 0, 0
+            .table_end
+            .org 0x0080
+            {source}
+            vblank:
+                INC R1
+                LDI.b R0, 0x01
+                ST.b (0xF021), R0
+                RETI
+            timer0:
+                INC R2
+                LDI.b R0, 0x08
+                ST.b (0xF021), R0
+                RETI
+            "#
+        ),
+    );
+
+    assemble(
+        Path::new("test.asm"),
+        0x7FFF,
+        Some(0x0060),
+        Some(0x0000),
+        &[],
+        &reader,
+    )
+    .unwrap()
+    .rom
+}
+
+// a file in the temporary directory, unique to this test run
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cicemu-{}-{}", std::process::id(), name))
+}
+
+// run the cicemu binary on a cartridge
+fn run_cicemu(name: &str, rom: &[u8], args: &[&str]) -> std::process::Output {
+    let rom_path = temp_path(&format!("{}.bin", name));
+    fs::write(&rom_path, rom).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cicemu"))
+        .arg("--headless")
+        .arg(&rom_path)
+        .args(args)
+        .output()
+        .unwrap();
+
+    fs::remove_file(&rom_path).unwrap();
+    output
+}
+
+// count V-Blank interrupts in R1 and Timer 0 interrupts in R2, Timer 0 overflowing every 16
+// falling edges of DIV bit 9
+const INTERRUPT_COUNTER: &str = "
+    LDI.b R0, 0x09
+    ST.b (0xF020), R0
+    LDI.b R0, 0xF0
+    ST.b (0xF007), R0
+    ST.b (0xF008), R0
+    LDI.b R0, 0x29
+    ST.b (0xF009), R0
+    LDI.b R0, 0x80
+    ST.b (0xF040), R0
+    EI
+loop:
+    HALT
+    JR loop
+";
+
+#[test]
+fn test_interrupts() {
+    for mode in [0, 1] {
+        let mut console = Console::new(cartridge(mode, INTERRUPT_COUNTER));
+        assert_eq!(console.run(10), None);
+
+        assert_eq!(console.ppu.frames(), 10);
+        assert_eq!(console.cpu.regs[1], 10);
+        // 2,808,000 T-cycles are 2742 ticks of DIV bit 9, the first overflow is at the 16th
+        assert_eq!(console.cpu.regs[2], 171);
+    }
+}
+
+#[test]
+fn test_result_port() {
+    let rom = cartridge(
+        0,
+        "
+        LDI R3, 0x1234
+        LDI.b R0, 0x2A
+        ST.b (0xF0C0), R0
+    spin:
+        JR spin
+    ",
+    );
+
+    let mut console = Console::new(rom.clone());
+    assert_eq!(console.run(600), Some(0x2A));
+    assert_eq!(console.ppu.frames(), 0);
+
+    let state_path = temp_path("state.json");
+    let output = run_cicemu(
+        "result",
+        &rom,
+        &["--dump-state", state_path.to_str().unwrap()],
+    );
+    assert_eq!(output.status.code(), Some(0x2A));
+
+    let state: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&state_path).unwrap()).unwrap();
+    fs::remove_file(&state_path).unwrap();
+    assert_eq!(state["test_result"], 0x2A);
+    assert_eq!(state["cpu"]["registers"][3], 0x1234);
+    assert_eq!(state["cpu"]["registers"][7], 0xD000);
+    assert_eq!(state["memory"]["wram"].as_str().unwrap().len(), 0x8000 * 2);
+}
+
+#[test]
+fn test_screenshots() {
+    // fill the screen with BG0 tile 0, whose color 0 is set to red
+    let rom = cartridge(
+        0,
+        "
+        LDI R0, 0x7C00
+        ST (0xF200), R0
+        LDI.b R0, 0x90
+        ST.b (0xF040), R0
+    spin:
+        JR spin
+    ",
+    );
+
+    let ppm_path = temp_path("screen.ppm");
+    let png_path = temp_path("screen.png");
+    let output = run_cicemu(
+        "screen",
+        &rom,
+        &["--frames", "2", "--screenshot", ppm_path.to_str().unwrap()],
+    );
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Ran 2 frames"));
+    let output = run_cicemu(
+        "screen",
+        &rom,
+        &["--frames", "2", "--screenshot", png_path.to_str().unwrap()],
+    );
+    assert!(output.status.success());
+
+    let ppm = fs::read(&ppm_path).unwrap();
+    let png = fs::read(&png_path).unwrap();
+    fs::remove_file(&ppm_path).unwrap();
+    fs::remove_file(&png_path).unwrap();
+
+    let pixels = &ppm[b"P6\n240 160\n255\n".len()..];
+    assert_eq!(pixels.len(), 240 * 160 * 3);
+    assert!(pixels.chunks(3).all(|pixel| pixel == [0xFF, 0x00, 0x00]));
+    assert_eq!(&png[1..4], b"PNG");
+
+    // an unknown image format is refused before running
+    let output = run_cicemu("screen", &rom, &["--screenshot", "screen.bmp"]);
+    assert_eq!(output.status.code(), Some(1));
+}